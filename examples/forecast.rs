@@ -0,0 +1,63 @@
+//! Two weeks of volcano loot for one save, as plain text: `cargo run --example forecast`.
+//! tests/forecast_example.rs checks the output against fixtures/forecast_example.txt.
+
+use sdv_volcano::GameSettings;
+use sdv_volcano::date::{DayNumber, GameDate};
+use sdv_volcano::forecast::{Builder, DayState};
+use sdv_volcano::loot::Goodie;
+use sdv_volcano::render::{OutputFormat, Render, RenderOptions};
+use sdv_volcano::search::{self, Query};
+use std::fmt::Write;
+
+pub fn write_forecast(out: &mut String) -> sdv_volcano::Result<()> {
+    let settings = GameSettings {
+        has_caldera: true,
+        post_1_6_4: true,
+        max_luck_lvl: 3,
+        ..GameSettings::for_game_id(123456789)?
+    };
+    let start: GameDate = "summer 1, Y2".parse()?;
+    let forecast = Builder::new(settings.clone()).days(start, 14).generate()?;
+
+    *out += &forecast.try_render_to_string(&RenderOptions::with_format(OutputFormat::Plain))?;
+
+    // writing to a String can't fail
+    writeln!(out, "rare chests with unique items:").unwrap();
+    for day in &forecast {
+        for record in day.records(&DayState::default()) {
+            if let Goodie::RareChest(item) = &record.goodie
+                && item.kind().is_unique()
+            {
+                writeln!(
+                    out,
+                    "  {}, floor {}: {}",
+                    GameDate::from(DayNumber(day.days_played)),
+                    record.level,
+                    item
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    let query = Query::parse("dragontooth shiv, dragontooth cutlass", &settings)?;
+    match search::first_of(&[query], start, 112, &settings)? {
+        Some(hit) => writeln!(
+            out,
+            "first dragontooth shiv or cutlass: {}, floor {}: {}",
+            GameDate::from(DayNumber(hit.days_played)),
+            hit.level,
+            hit.goodie
+        ),
+        None => writeln!(out, "no dragontooth shiv or cutlass within a year"),
+    }
+    .unwrap();
+    Ok(())
+}
+
+fn main() -> sdv_volcano::Result<()> {
+    let mut out = String::new();
+    write_forecast(&mut out)?;
+    print!("{}", out);
+    Ok(())
+}
@@ -0,0 +1,73 @@
+//! How much faster RangeIndex::query is than going through every day of a 10 year index:
+//! `cargo run --release --example range_index`
+
+use sdv_volcano::GameSettings;
+use sdv_volcano::index::{HitFilter, IndexHit, RangeIndex};
+use sdv_volcano::loot::ChestItemKind;
+use std::ops::Range;
+use std::time::Instant;
+
+/// what query gives, by looking at every day of every year
+fn scan(index: &RangeIndex, range: Range<u32>, kinds: &[ChestItemKind]) -> Vec<IndexHit> {
+    let mut out = vec![];
+    for year in index.years() {
+        for &kind in kinds {
+            out.extend(year.days(kind).filter(|(day, _)| range.contains(day)).map(
+                |(days_played, guaranteed)| IndexHit {
+                    days_played,
+                    kind,
+                    guaranteed,
+                },
+            ));
+        }
+    }
+    out.sort();
+    out
+}
+
+fn main() -> sdv_volcano::Result<()> {
+    let settings = GameSettings {
+        has_caldera: true,
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let index = RangeIndex::build(4242, 1..11, &settings)?;
+    println!("built 10 years in {:?}", started.elapsed());
+
+    let kinds = [ChestItemKind::DragontoothShiv, ChestItemKind::GoldenCoconut];
+    let filter = HitFilter {
+        kinds: kinds.to_vec(),
+        ..Default::default()
+    };
+    // a season in every year
+    let ranges: Vec<Range<u32>> = (0..10).map(|y| y * 112 + 29..y * 112 + 57).collect();
+    let runs = 1000;
+
+    let started = Instant::now();
+    let mut queried = 0;
+    for _ in 0..runs {
+        for range in &ranges {
+            queried += index.query(range.clone(), &filter).len();
+        }
+    }
+    let query_time = started.elapsed();
+
+    let started = Instant::now();
+    let mut scanned = 0;
+    for _ in 0..runs {
+        for range in &ranges {
+            scanned += scan(&index, range.clone(), &kinds).len();
+        }
+    }
+    let scan_time = started.elapsed();
+
+    assert_eq!(queried, scanned);
+    println!(
+        "{} queries: {:?} with query, {:?} scanning, {:.1}x",
+        runs * ranges.len(),
+        query_time,
+        scan_time,
+        scan_time.as_secs_f64() / query_time.as_secs_f64()
+    );
+    Ok(())
+}
@@ -0,0 +1,20 @@
+//! Checks that chests classify the same as when fixtures/rarity.txt was made, e.g. on another
+//! platform: `cargo run --example rarity_fixture`. With `-- --write` it makes the fixture again,
+//! for generation changes that are meant to change it.
+
+use sdv_volcano::audit::{RARITY_FIXTURE_SEEDS, check_rarity_fixture, rarity_fixture};
+
+fn main() -> sdv_volcano::Result<()> {
+    if std::env::args().any(|arg| arg == "--write") {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/rarity.txt");
+        std::fs::write(path, rarity_fixture()?)?;
+        println!("wrote {}", path);
+        return Ok(());
+    }
+    check_rarity_fixture()?;
+    println!(
+        "all {} seeds classify like the fixture",
+        RARITY_FIXTURE_SEEDS
+    );
+    Ok(())
+}
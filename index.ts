@@ -9,7 +9,9 @@ function get_settings() {
     settings.legacy_rng = get_el("legacy_rng").checked;
     settings.post_1_6_4 = get_el("post_1_6_4").checked;
     settings.has_caldera = get_el("has_caldera").checked;
-    settings.cracked_golden_coconut = get_el("cracked_coconut").checked;
+    // the game has one flag for both chest tables
+    settings.golden_coconut_in_common = get_el("cracked_coconut").checked;
+    settings.golden_coconuts_in_rare = get_el("cracked_coconut").checked;
     settings.max_luck_lvl = Math.max(+get_el("max_luck_lvl").value, 0);
     settings.days_played = Math.max(+get_el("days_played").value, 1);
     settings.special_charm = get_el("special_charm").checked;
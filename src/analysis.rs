@@ -0,0 +1,216 @@
+//! Checks over whole forecasts
+
+use crate::date::{DayNumber, GameDate};
+use crate::forecast::{ChestId, DayForecast, DayState, GoodieRecord};
+use crate::loot::Goodie;
+use std::collections::BTreeMap;
+
+/// Different chests with the same seed, which always have the same loot. Mostly happens with
+/// legacy rng, where the floor seeds are sums of the day and level.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollisionGroup {
+    pub seed: i32,
+    /// every luck range of every chest with the seed, at least two different chests
+    pub records: Vec<GoodieRecord>,
+}
+
+impl CollisionGroup {
+    /// the first chest in the group that isn't `id`
+    pub fn other_than(&self, id: ChestId) -> Option<ChestId> {
+        self.records
+            .iter()
+            .filter_map(|r| r.chest)
+            .find(|&c| c != id)
+    }
+
+    /// e.g. "same roll as fall 3, Y1, floor 7", for the output next to `id`
+    pub fn note_for(&self, id: ChestId) -> Option<String> {
+        let other = self.other_than(id)?;
+        Some(format!(
+            "same roll as {}, floor {}",
+            GameDate::from(DayNumber(other.days_played)),
+            other.level
+        ))
+    }
+}
+
+/// Groups the chests of the forecast by seed, keeping the seeds that more than one chest has.
+/// Settings-dependent (Ambiguous) chests are skipped, their seeds aren't known for sure.
+pub fn find_collisions(forecast: &[DayForecast]) -> Vec<CollisionGroup> {
+    let mut by_seed: BTreeMap<i32, Vec<GoodieRecord>> = BTreeMap::new();
+    for day in forecast {
+        let mut floor: Option<(u8, f64, Vec<i32>)> = None;
+        for record in day.records(&DayState::default()) {
+            let Some(chest) = record.chest else {
+                continue;
+            };
+            if matches!(record.goodie, Goodie::Ambiguous(_)) {
+                continue;
+            }
+            if floor
+                .as_ref()
+                .is_none_or(|f| (f.0, f.1) != (record.level, record.min_luck))
+            {
+                let seeds = day.chest_seeds(record.level as usize, record.min_luck);
+                floor = Some((record.level, record.min_luck, seeds));
+            }
+            if let Some(&seed) = floor.as_ref().unwrap().2.get(chest.index as usize) {
+                by_seed.entry(seed).or_default().push(record);
+            }
+        }
+    }
+    by_seed
+        .into_iter()
+        .filter(|(_, records)| {
+            let first = records[0].chest;
+            records.iter().any(|r| r.chest != first)
+        })
+        .map(|(seed, records)| CollisionGroup { seed, records })
+        .collect()
+}
+
+/// the group with `id`'s entry for the luck range starting at `min_luck`
+pub fn collision_for(
+    groups: &[CollisionGroup],
+    id: ChestId,
+    min_luck: f64,
+) -> Option<&CollisionGroup> {
+    groups.iter().find(|g| {
+        g.records
+            .iter()
+            .any(|r| r.chest == Some(id) && r.min_luck == min_luck)
+    })
+}
+
+/// Loot in the volcano that forecasts don't list, see `known_unmodeled`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UnmodeledSource {
+    pub name: &'static str,
+    pub note: &'static str,
+}
+
+/// Where loot can come from in the volcano besides the forecast's chests and dragon teeth. The
+/// shop floor (level 5) has a fixed layout without any chest spawns, loot found there comes from
+/// one of these too.
+pub fn known_unmodeled() -> &'static [UnmodeledSource] {
+    &[
+        UnmodeledSource {
+            name: "Barrels and crates",
+            note: "breakable containers, rolled when broken",
+        },
+        UnmodeledSource {
+            name: "Monster drops",
+            note: "depend on the kills, not just the day",
+        },
+        UnmodeledSource {
+            name: "Ore and cinder shard nodes",
+            note: "rolled when mined",
+        },
+        UnmodeledSource {
+            name: "Lava fishing",
+            note: "including fishing treasure chests",
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameSettings;
+    use crate::render::{OutputFormat, Render, RenderOptions};
+
+    fn days(legacy_rng: bool, days: &[u32]) -> Vec<DayForecast> {
+        days.iter()
+            .map(|&days_played| {
+                DayForecast::generate(&GameSettings {
+                    seed: 12,
+                    legacy_rng,
+                    days_played,
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn legacy_collision() {
+        // with legacy rng the layout seed of floor 1 on day 5170 is the one of floor 2 on day 9:
+        // 5170 * 1 + 5152 * 1 = 9 * 2 + 5152 * 2
+        let forecast = days(true, &[9, 5170]);
+        let groups = find_collisions(&forecast);
+        assert_eq!(groups.len(), 1);
+        let group = &groups[0];
+        let chests: Vec<(u32, u8)> = group
+            .records
+            .iter()
+            .filter_map(|r| r.chest)
+            .map(|c| (c.days_played, c.level))
+            .collect();
+        assert!(chests.contains(&(9, 2)) && chests.contains(&(5170, 1)));
+        assert!(chests.iter().all(|c| [(9, 2), (5170, 1)].contains(c)));
+        let first = &group.records[0];
+        assert!(group.records.iter().all(|r| r.goodie == first.goodie));
+        let on_day_9 = first.chest.unwrap();
+        assert_eq!(on_day_9.days_played, 9);
+        let note = group.note_for(on_day_9).unwrap();
+        assert!(note.starts_with("same roll as ") && note.ends_with(", floor 1"));
+        let opts = RenderOptions {
+            collisions: Some(&groups),
+            ..RenderOptions::with_format(OutputFormat::Plain)
+        };
+        let text = forecast.try_render_to_string(&opts).unwrap();
+        assert!(text.contains(&note), "{}", text);
+    }
+
+    #[test]
+    fn no_collisions() {
+        // the hashed seeds of the same days don't collide
+        assert!(find_collisions(&days(false, &[9, 5170])).is_empty());
+        let month: Vec<u32> = (1..=28).collect();
+        assert!(find_collisions(&days(true, &month)).is_empty());
+    }
+
+    #[test]
+    fn unmodeled_footnote() {
+        let forecast = days(false, &[1, 2]);
+        for format in [
+            OutputFormat::Html,
+            OutputFormat::Plain,
+            OutputFormat::Markdown,
+            OutputFormat::Ansi,
+        ] {
+            let render = |unmodeled_footnote| {
+                let opts = RenderOptions {
+                    unmodeled_footnote,
+                    ..RenderOptions::with_format(format)
+                };
+                forecast.try_render_to_string(&opts).unwrap()
+            };
+            let with = render(true);
+            let without = render(false);
+            for source in known_unmodeled() {
+                assert!(with.contains(source.name), "{:?} {}", format, source.name);
+                assert!(
+                    !without.contains(source.name),
+                    "{:?} {}",
+                    format,
+                    source.name
+                );
+            }
+            assert!(with.starts_with(&without));
+        }
+    }
+
+    #[test]
+    fn no_chests_on_the_shop_floor() {
+        let settings = GameSettings::default();
+        for day in crate::forecast::range(&settings, 1, 112).unwrap() {
+            assert!(day.loot[5].iter().all(|(_, _, loot)| loot.is_empty()));
+            assert!(day.population_order(5, day.loot[5][0].0).is_empty());
+        }
+        assert!(matches!(
+            Goodie::generate_at(1, &settings, crate::loot::FloorLevel::SHOP, 1., 1.),
+            Err(crate::Error::NoChestOnFloor(5))
+        ));
+    }
+}
@@ -0,0 +1,199 @@
+//! Checks that a computation gives the same result every time, also when it runs on several
+//! threads at once. Nothing in the crate iterates hash maps for its output or sums floats in a
+//! different order per run, this is for catching that if it changes.
+
+use crate::loot::{FloorLevel, Goodie, RarityOutcome};
+use crate::luck::luck_range;
+use crate::{Error, GameSettings, Result, rng};
+use std::fmt::{Debug, Write};
+
+/// how much of each Debug output a Nondeterministic error keeps, from the first difference on
+const EXCERPT_CHARS: usize = 80;
+
+/// most threads determinism_check uses, whatever the machine has
+pub const MAX_CHECK_THREADS: usize = 16;
+
+/// Runs `f` `runs` times (at least once) on as many threads as the machine has (up to
+/// MAX_CHECK_THREADS) at the same time, and gives its result if every run got the same one.
+/// Otherwise Nondeterministic for the first run that's different from run 0. Needs threads, so
+/// not for wasm.
+pub fn determinism_check<T, F>(f: F, runs: usize) -> Result<T>
+where
+    T: PartialEq + Debug + Send,
+    F: Fn() -> T + Sync,
+{
+    let runs = runs.max(1);
+    let threads = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .clamp(1, MAX_CHECK_THREADS)
+        .min(runs);
+    // thread t does the runs t, t + threads, ...
+    let mut by_thread: Vec<std::vec::IntoIter<T>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                let f = &f;
+                scope.spawn(move || (t..runs).step_by(threads).map(|_| f()).collect::<Vec<T>>())
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| {
+                h.join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                    .into_iter()
+            })
+            .collect()
+    });
+    let mut results = (0..runs).map(|run| {
+        by_thread[run % threads]
+            .next()
+            .expect("every run has a result")
+    });
+    let first = results.next().expect("there's always a run");
+    for (i, other) in results.enumerate() {
+        if other != first {
+            let (expected, got) = (format!("{:?}", first), format!("{:?}", other));
+            let offset = expected
+                .char_indices()
+                .zip(got.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(expected.len().min(got.len()), |((i, _), _)| i);
+            let excerpt = |s: &str| {
+                s.get(offset..)
+                    .unwrap_or("")
+                    .chars()
+                    .take(EXCERPT_CHARS)
+                    .collect()
+            };
+            return Err(Error::Nondeterministic {
+                run: i + 1,
+                offset,
+                expected: excerpt(&expected),
+                got: excerpt(&got),
+            });
+        }
+    }
+    Ok(first)
+}
+
+/// chest seeds in the rarity fixture, from the sequence of seed 0
+pub const RARITY_FIXTURE_SEEDS: usize = 1000;
+
+/// rarity_fixture as it was when it was made, see examples/rarity_fixture.rs
+pub const RARITY_FIXTURE: &str = include_str!("../fixtures/rarity.txt");
+
+/// One line per chest seed: the seed, then per floor with chests `c` (common), `r` (rare) or the
+/// bits of the chance chest's minluck in hex. For the luck range of the special charm and 3 luck
+/// levels, so some of them are chance chests.
+pub fn rarity_fixture() -> Result<String> {
+    let luck = luck_range(&GameSettings {
+        special_charm: true,
+        max_luck_lvl: 3,
+        ..Default::default()
+    });
+    let mut seeds = rng::DotnetRng::new(0);
+    let mut out = String::new();
+    for _ in 0..RARITY_FIXTURE_SEEDS {
+        let seed = seeds.next();
+        write!(out, "{}", seed).unwrap();
+        for level in FloorLevel::ALL.into_iter().filter(|l| l.has_chests()) {
+            match Goodie::rarity_only(seed, level, luck)? {
+                RarityOutcome::Common => out += " c",
+                RarityOutcome::Rare => out += " r",
+                RarityOutcome::Chance { minluck } => {
+                    write!(out, " {:016x}", minluck.to_bits()).unwrap()
+                }
+            }
+        }
+        out += "\n";
+    }
+    Ok(out)
+}
+
+/// Compares rarity_fixture with RARITY_FIXTURE, for catching a platform (or a change) that
+/// classifies chests differently, see luck::past_threshold. FixtureMismatch for the first line
+/// that's different.
+pub fn check_rarity_fixture() -> Result<()> {
+    let got = rarity_fixture()?;
+    let mut expected = RARITY_FIXTURE.lines();
+    for (i, line) in got.lines().enumerate() {
+        let want = expected.next().unwrap_or("");
+        if line != want {
+            return Err(Error::FixtureMismatch {
+                line: i + 1,
+                expected: want.to_string(),
+                got: line.to_string(),
+            });
+        }
+    }
+    match expected.next() {
+        Some(extra) => Err(Error::FixtureMismatch {
+            line: RARITY_FIXTURE_SEEDS + 1,
+            expected: extra.to_string(),
+            got: String::new(),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::write_snapshot;
+    use crate::date::Season;
+    use crate::forecast;
+    use crate::loot::ChestItemKind;
+    use crate::stats::{LuckCounting, SeasonalCounts};
+
+    #[test]
+    fn forecasts_are_deterministic() {
+        let settings = GameSettings {
+            seed: 31337,
+            ..Default::default()
+        };
+        // the snapshot has every float's bits
+        let year = determinism_check(
+            || {
+                let mut out = vec![];
+                write_snapshot(&mut out, &forecast::range(&settings, 1, 112).unwrap()).unwrap();
+                out
+            },
+            10,
+        )
+        .unwrap();
+        assert!(!year.is_empty());
+        let counts = determinism_check(
+            || {
+                SeasonalCounts::generate(
+                    31337,
+                    1,
+                    &settings,
+                    &ChestItemKind::ALL,
+                    LuckCounting::Fractional,
+                )
+                .unwrap()
+            },
+            10,
+        )
+        .unwrap();
+        assert!(counts.season_total(Season::Spring) > 0.);
+    }
+
+    #[test]
+    fn rarity_fixture_matches() {
+        check_rarity_fixture().unwrap();
+        assert_eq!(RARITY_FIXTURE.lines().count(), RARITY_FIXTURE_SEEDS);
+    }
+
+    #[test]
+    fn nondeterminism_is_caught() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let calls = AtomicUsize::new(0);
+        // more runs than threads, so some threads do several
+        let runs = MAX_CHECK_THREADS * 2 + 1;
+        let result = determinism_check(|| calls.fetch_add(1, Ordering::SeqCst) == runs - 1, runs);
+        assert_eq!(calls.into_inner(), runs);
+        assert!(matches!(result, Err(Error::Nondeterministic { .. })));
+        assert_eq!(determinism_check(|| 7, 0).unwrap(), 7);
+    }
+}
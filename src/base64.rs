@@ -0,0 +1,87 @@
+//! Standard base64 with padding, for data URIs and report tokens
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// None if it isn't base64. The padding can be left out, whitespace around it is ignored.
+pub fn decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim().trim_end_matches('=');
+    let mut out = Vec::with_capacity(s.len() * 3 / 4);
+    let mut n = 0u32;
+    for (i, c) in s.bytes().enumerate() {
+        let x = ALPHABET.iter().position(|&a| a == c)? as u32;
+        n = n << 6 | x;
+        if i % 4 == 3 {
+            out.extend_from_slice(&n.to_be_bytes()[1..]);
+            n = 0;
+        }
+    }
+    match s.len() % 4 {
+        0 => {}
+        2 => out.push((n >> 4) as u8),
+        3 => out.extend_from_slice(&((n >> 2) as u16).to_be_bytes()),
+        _ => return None,
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_values() {
+        // from rfc 4648
+        for (data, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encode(data.as_bytes()), encoded);
+            assert_eq!(decode(encoded).unwrap(), data.as_bytes());
+            assert_eq!(
+                decode(encoded.trim_end_matches('=')).unwrap(),
+                data.as_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let data: Vec<u8> = (0..=255).collect();
+        for len in 0..data.len() {
+            assert_eq!(decode(&encode(&data[..len])).unwrap(), &data[..len]);
+        }
+        assert_eq!(decode(&format!(" {}\n", encode(b"ab"))).unwrap(), b"ab");
+    }
+
+    #[test]
+    fn rejects_other_text() {
+        for s in ["Zm9v!", "Z", "Zm9vY", "Zm 9v", "-_-_"] {
+            assert_eq!(decode(s), None, "{}", s);
+        }
+    }
+}
@@ -0,0 +1,909 @@
+//! Binary snapshots of precomputed forecasts, for hosting many seeds. A snapshot starts with an
+//! index of where each day is, so single days can be read without loading the whole file. The
+//! reader seeks to the day and reads just its bytes, no mapping needed for files this size.
+//! ChestCache keeps generated days in memory instead.
+//!
+//! Layout, all little endian:
+//! - `SDVV`, version byte, the save_hash of the settings (u64), number of days (u32), offset
+//!   (u64) and length (u32) of the range index section, both 0 without one
+//! - per day: days_played (u32), offset from the start of the file (u64), length (u32)
+//! - the days, see `encode_day`
+//! - the range index, see `RangeIndex::encode`
+//!
+//! Compressed snapshots (the `compression` feature) start with `SDVZ` instead and every day is a
+//! zstd frame of its own, so they can still be read a day at a time.
+
+use crate::calendar;
+use crate::compress::decompress;
+#[cfg(feature = "compression")]
+use crate::compress::{CompressionLevel, compress};
+use crate::date::{DayNumber, MAX_DAY_RANGE};
+use crate::forecast::DayForecast;
+use crate::index::RangeIndex;
+use crate::loot::{ChestItem, ChestItemKind, Enchants, Goodie, QuantityModifiers};
+use crate::partial::SettingsAssumption;
+use crate::{Error, GameSettings, ProbabilityRange, Result};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"SDVV";
+const COMPRESSED_MAGIC: &[u8; 4] = b"SDVZ";
+// 2: the settings hash. 3: the quantity multipliers in the settings. 4: the range index section
+const VERSION: u8 = 4;
+const HEADER_LEN: u64 = 29;
+const INDEX_ENTRY_LEN: u64 = 16;
+/// how deep Ambiguous goodies can nest. the crate only ever makes one level, this is so that a
+/// crafted file can't recurse the decoder into a stack overflow
+const MAX_NESTING: u32 = 8;
+
+fn corrupt(msg: &str) -> Error {
+    Error::CorruptSnapshot(msg.to_string())
+}
+
+/// `n` as the integer type of its length field
+pub(crate) fn len_field<T: TryFrom<usize>>(n: usize, what: &str) -> Result<T> {
+    T::try_from(n).map_err(|_| Error::TooLargeToEncode(format!("{} {}", n, what)))
+}
+
+/// Writes the snapshot for `days`. Reading picks days by days_played, so those should be unique,
+/// and all of them have to be of the same settings (MixedSnapshotSettings otherwise).
+pub fn write_snapshot(out: &mut impl Write, days: &[DayForecast]) -> Result<()> {
+    let blobs = days.iter().map(encode_day).collect::<Result<Vec<_>>>()?;
+    write_blobs(out, MAGIC, days, &blobs, None)
+}
+
+/// write_snapshot with the bitsets of `index` after the days, for SnapshotReader::range_index.
+/// IndexSettingsMismatch if the index is for other settings than the days.
+pub fn write_snapshot_with_index(
+    out: &mut impl Write,
+    days: &[DayForecast],
+    index: &RangeIndex,
+) -> Result<()> {
+    let blobs = days.iter().map(encode_day).collect::<Result<Vec<_>>>()?;
+    write_blobs(out, MAGIC, days, &blobs, Some(index))
+}
+
+#[cfg(feature = "compression")]
+fn compressed_blobs(days: &[DayForecast], level: CompressionLevel) -> Result<Vec<Vec<u8>>> {
+    days.iter()
+        .map(|day| Ok(compress(&encode_day(day)?, level)))
+        .collect()
+}
+
+/// write_snapshot with every day compressed
+#[cfg(feature = "compression")]
+pub fn write_snapshot_compressed(
+    out: &mut impl Write,
+    days: &[DayForecast],
+    level: CompressionLevel,
+) -> Result<()> {
+    write_blobs(
+        out,
+        COMPRESSED_MAGIC,
+        days,
+        &compressed_blobs(days, level)?,
+        None,
+    )
+}
+
+/// write_snapshot_with_index with every day compressed, the index isn't
+#[cfg(feature = "compression")]
+pub fn write_snapshot_compressed_with_index(
+    out: &mut impl Write,
+    days: &[DayForecast],
+    index: &RangeIndex,
+    level: CompressionLevel,
+) -> Result<()> {
+    let blobs = compressed_blobs(days, level)?;
+    write_blobs(out, COMPRESSED_MAGIC, days, &blobs, Some(index))
+}
+
+fn write_blobs(
+    out: &mut impl Write,
+    magic: &[u8; 4],
+    days: &[DayForecast],
+    blobs: &[Vec<u8>],
+    index: Option<&RangeIndex>,
+) -> Result<()> {
+    let hash = days.first().map_or(0, |day| day.settings.save_hash());
+    if let Some(day) = days.iter().find(|day| day.settings.save_hash() != hash) {
+        return Err(Error::MixedSnapshotSettings(day.days_played));
+    }
+    let mut section = vec![];
+    if let Some(index) = index {
+        let first = index.years().first();
+        if let Some(year) = first.filter(|y| !days.is_empty() && y.settings.save_hash() != hash) {
+            return Err(Error::IndexSettingsMismatch(year.year));
+        }
+        index.encode(&mut section)?;
+    }
+    let mut offset = HEADER_LEN + INDEX_ENTRY_LEN * days.len() as u64;
+    let section_offset = offset + blobs.iter().map(|b| b.len() as u64).sum::<u64>();
+    let mut header = Vec::new();
+    header.extend_from_slice(magic);
+    header.push(VERSION);
+    header.extend_from_slice(&hash.to_le_bytes());
+    header.extend_from_slice(&len_field::<u32>(days.len(), "days")?.to_le_bytes());
+    if section.is_empty() {
+        header.extend_from_slice(&[0; 12]);
+    } else {
+        header.extend_from_slice(&section_offset.to_le_bytes());
+        header.extend_from_slice(&len_field::<u32>(section.len(), "bytes of index")?.to_le_bytes());
+    }
+    for (day, blob) in days.iter().zip(blobs) {
+        header.extend_from_slice(&day.days_played.to_le_bytes());
+        header.extend_from_slice(&offset.to_le_bytes());
+        header.extend_from_slice(&len_field::<u32>(blob.len(), "bytes in a day")?.to_le_bytes());
+        offset += blob.len() as u64;
+    }
+    out.write_all(&header)?;
+    for blob in blobs {
+        out.write_all(blob)?;
+    }
+    out.write_all(&section)?;
+    Ok(())
+}
+
+/// Reads single days out of a snapshot file, compressed or not
+pub struct SnapshotReader {
+    file: File,
+    compressed: bool,
+    save_hash: u64,
+    /// (days_played, offset, length), checked against the file length
+    index: Vec<(u32, u64, u32)>,
+    /// (offset, length) of the range index section, also checked
+    range_index: Option<(u64, u32)>,
+}
+
+impl SnapshotReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut header = [0; HEADER_LEN as usize];
+        file.read_exact(&mut header)
+            .map_err(|_| corrupt("file too short for the header"))?;
+        let compressed = match &header[..4] {
+            m if m == MAGIC => false,
+            m if m == COMPRESSED_MAGIC => true,
+            _ => return Err(corrupt("not a snapshot file")),
+        };
+        if header[4] != VERSION {
+            return Err(corrupt("unsupported snapshot version"));
+        }
+        let save_hash = u64::from_le_bytes(header[5..13].try_into().unwrap());
+        let count = u32::from_le_bytes(header[13..17].try_into().unwrap()) as u64;
+        let section_offset = u64::from_le_bytes(header[17..25].try_into().unwrap());
+        let section_len = u32::from_le_bytes(header[25..29].try_into().unwrap());
+        let data_start = HEADER_LEN + INDEX_ENTRY_LEN * count;
+        if data_start > file_len {
+            return Err(corrupt("index goes past the end of the file"));
+        }
+        let in_file = |offset: u64, len: u32| {
+            offset >= data_start
+                && offset
+                    .checked_add(len as u64)
+                    .is_some_and(|end| end <= file_len)
+        };
+        let range_index = match (section_offset, section_len) {
+            (0, 0) => None,
+            (offset, len) if in_file(offset, len) => Some((offset, len)),
+            _ => return Err(corrupt("range index outside of the file")),
+        };
+        let mut raw = vec![0; (INDEX_ENTRY_LEN * count) as usize];
+        file.read_exact(&mut raw)?;
+        let index = raw
+            .chunks_exact(INDEX_ENTRY_LEN as usize)
+            .map(|entry| {
+                let day = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+                let offset = u64::from_le_bytes(entry[4..12].try_into().unwrap());
+                let len = u32::from_le_bytes(entry[12..16].try_into().unwrap());
+                if in_file(offset, len) {
+                    Ok((day, offset, len))
+                } else {
+                    Err(corrupt("index entry outside of the file"))
+                }
+            })
+            .collect::<Result<_>>()?;
+        Ok(SnapshotReader {
+            file,
+            compressed,
+            save_hash,
+            index,
+            range_index,
+        })
+    }
+
+    /// GameSettings::save_hash of the settings the days are for, to check a snapshot is the one
+    /// for a save before serving from it
+    pub fn save_hash(&self) -> u64 {
+        self.save_hash
+    }
+
+    /// days_played of every day in the snapshot, in file order
+    pub fn days(&self) -> impl Iterator<Item = u32> + '_ {
+        self.index.iter().map(|x| x.0)
+    }
+
+    /// The range index written with the days, None if the snapshot doesn't have one. Reads the
+    /// whole section.
+    pub fn range_index(&mut self) -> Result<Option<RangeIndex>> {
+        let Some((offset, len)) = self.range_index else {
+            return Ok(None);
+        };
+        let mut section = vec![0; len as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut section)?;
+        RangeIndex::decode(&section).map(Some)
+    }
+
+    /// None if the snapshot doesn't have the day
+    pub fn read_day(&mut self, days_played: u32) -> Result<Option<DayForecast>> {
+        let Some(&(_, offset, len)) = self.index.iter().find(|x| x.0 == days_played) else {
+            return Ok(None);
+        };
+        let mut blob = vec![0; len as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut blob)?;
+        if self.compressed {
+            blob = decompress(&blob, offset)?;
+        }
+        let mut decoder = Decoder(&blob);
+        let day = decoder.day()?;
+        if !decoder.0.is_empty() {
+            return Err(corrupt("trailing bytes after a day"));
+        }
+        Ok(Some(day))
+    }
+}
+
+/// Days generated before, by GameSettings::behavior_hash, so asking for the same day again
+/// doesn't generate it again. Holds at most `capacity` days and drops the oldest one first.
+pub struct ChestCache {
+    capacity: usize,
+    days: HashMap<u64, DayForecast>,
+    /// the hashes in `days`, oldest first
+    order: VecDeque<u64>,
+}
+
+impl ChestCache {
+    pub fn new(capacity: usize) -> Self {
+        ChestCache {
+            capacity,
+            days: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.days.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.days.is_empty()
+    }
+
+    /// DayForecast::generate, or the copy from last time
+    pub fn day(&mut self, settings: &GameSettings) -> DayForecast {
+        let hash = settings.behavior_hash();
+        if let Some(day) = self.days.get(&hash) {
+            // a collision would need equal settings bytes under FNV, check them anyways
+            let (mut a, mut b) = (vec![], vec![]);
+            encode_settings(&mut a, &day.settings);
+            encode_settings(&mut b, settings);
+            if a == b {
+                return day.clone();
+            }
+        }
+        let day = DayForecast::generate(settings);
+        if self.capacity == 0 {
+            return day;
+        }
+        if self.days.insert(hash, day.clone()).is_none() {
+            self.order.push_back(hash);
+        }
+        while self.order.len() > self.capacity {
+            let oldest = self.order.pop_front().unwrap();
+            self.days.remove(&oldest);
+        }
+        day
+    }
+
+    /// forecast::range through the cache, it fails the same way
+    pub fn range(
+        &mut self,
+        settings: &GameSettings,
+        start_day: impl Into<DayNumber>,
+        days: u32,
+    ) -> Result<Vec<DayForecast>> {
+        let days = start_day.into().checked_span(days, MAX_DAY_RANGE)?;
+        Ok(days
+            .map(|days_played| {
+                self.day(&GameSettings {
+                    days_played,
+                    ..*settings
+                })
+            })
+            .collect())
+    }
+}
+
+// encoding
+
+/// seed, flags, days_played, max_luck_lvl, then the shard and coconut multipliers (u8 each, they're
+/// at most 10). also used by report tokens, year indexes and behavior_hash.
+pub(crate) fn encode_settings(out: &mut Vec<u8>, s: &GameSettings) {
+    out.extend_from_slice(&s.seed.to_le_bytes());
+    let flags = [
+        s.legacy_rng,
+        s.has_caldera,
+        s.post_1_6_4,
+        s.golden_coconut_in_common(),
+        s.special_charm,
+        s.golden_coconuts_in_rare(),
+        s.has_ostrich_incubator,
+    ];
+    out.push(flags.iter().rev().fold(0, |acc, &f| acc << 1 | f as u8));
+    out.extend_from_slice(&s.days_played.to_le_bytes());
+    out.extend_from_slice(&s.max_luck_lvl.to_le_bytes());
+    out.push(s.quantities.shards() as u8);
+    out.push(s.quantities.coconuts() as u8);
+}
+
+fn encode_day(day: &DayForecast) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    encode_settings(
+        &mut out,
+        &GameSettings {
+            days_played: day.days_played,
+            ..day.settings
+        },
+    );
+    for floor in &day.layouts {
+        encode_ranges(&mut out, floor, |out, layout| {
+            out.extend_from_slice(&layout.to_le_bytes());
+            Ok(())
+        })?;
+    }
+    for floor in &day.loot {
+        encode_ranges(&mut out, floor, |out, loot| {
+            out.extend_from_slice(
+                &len_field::<u16>(loot.len(), "goodies on a floor")?.to_le_bytes(),
+            );
+            for goodie in loot {
+                encode_goodie(out, goodie)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(out)
+}
+
+fn encode_ranges<T>(
+    out: &mut Vec<u8>,
+    ranges: &ProbabilityRange<T>,
+    mut encode: impl FnMut(&mut Vec<u8>, &T) -> Result<()>,
+) -> Result<()> {
+    out.extend_from_slice(&len_field::<u16>(ranges.len(), "luck ranges")?.to_le_bytes());
+    for (minl, maxl, x) in ranges {
+        out.extend_from_slice(&minl.to_le_bytes());
+        out.extend_from_slice(&maxl.to_le_bytes());
+        encode(out, x)?;
+    }
+    Ok(())
+}
+
+/// the enchants go in as their compact string, so the raw rolls are lost
+fn encode_item(out: &mut Vec<u8>, item: &ChestItem) -> Result<()> {
+    let kind = ChestItemKind::ALL.iter().position(|&k| k == item.kind());
+    out.push(kind.unwrap() as u8);
+    let enchants = item
+        .enchants()
+        .map(|e| e.to_compact_string())
+        .unwrap_or_default();
+    out.push(len_field(enchants.len(), "bytes of enchantments")?);
+    out.extend_from_slice(enchants.as_bytes());
+    Ok(())
+}
+
+fn encode_flag(x: Option<bool>) -> u8 {
+    match x {
+        None => 0,
+        Some(false) => 1,
+        Some(true) => 2,
+    }
+}
+
+/// Fails for what the decoder wouldn't read back: too many alternatives, or Ambiguous nested
+/// deeper than MAX_NESTING
+pub(crate) fn encode_goodie(out: &mut Vec<u8>, goodie: &Goodie) -> Result<()> {
+    encode_goodie_at(out, goodie, 0)
+}
+
+fn encode_goodie_at(out: &mut Vec<u8>, goodie: &Goodie, depth: u32) -> Result<()> {
+    match goodie {
+        Goodie::DragonTooth => out.push(0),
+        Goodie::CommonChest(c) => {
+            out.push(1);
+            encode_item(out, c)?;
+        }
+        Goodie::RareChest(c) => {
+            out.push(2);
+            encode_item(out, c)?;
+        }
+        Goodie::ChanceChest {
+            minluck,
+            common,
+            rare,
+        } => {
+            out.push(3);
+            out.extend_from_slice(&minluck.to_le_bytes());
+            encode_item(out, common)?;
+            encode_item(out, rare)?;
+        }
+        Goodie::Ambiguous(alts) => {
+            if depth >= MAX_NESTING {
+                return Err(Error::TooLargeToEncode(format!(
+                    "ambiguous goodies nested more than {} deep",
+                    MAX_NESTING
+                )));
+            }
+            out.push(4);
+            out.extend_from_slice(&len_field::<u16>(alts.len(), "alternatives")?.to_le_bytes());
+            for (a, g) in alts {
+                out.push(encode_flag(a.legacy_rng));
+                out.push(encode_flag(a.has_caldera));
+                out.push(encode_flag(a.post_1_6_4));
+                out.push(encode_flag(a.cracked_golden_coconut));
+                encode_goodie_at(out, g, depth + 1)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// decoding. everything is bounds checked, a bad file gives CorruptSnapshot
+
+pub(crate) struct Decoder<'a>(pub(crate) &'a [u8]);
+
+impl Decoder<'_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.0.len() < N {
+            return Err(corrupt("data ends early"));
+        }
+        let (x, rest) = self.0.split_at(N);
+        self.0 = rest;
+        Ok(x.try_into().unwrap())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn u128(&mut self) -> Result<u128> {
+        Ok(u128::from_le_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn settings(&mut self) -> Result<GameSettings> {
+        let seed = i32::from_le_bytes(self.bytes()?);
+        let flags = self.u8()?;
+        let days_played = self.u32()?;
+        let max_luck_lvl = self.u32()?;
+        let quantities = QuantityModifiers::new(self.u8()? as u32, self.u8()? as u32)
+            .map_err(|_| corrupt("bad quantity multipliers"))?;
+        Ok(GameSettings {
+            seed,
+            legacy_rng: flags & 1 != 0,
+            has_caldera: flags & 2 != 0,
+            post_1_6_4: flags & 4 != 0,
+            golden_coconut_in_common: flags & 8 != 0,
+            special_charm: flags & 16 != 0,
+            golden_coconuts_in_rare: flags & 32 != 0,
+            has_ostrich_incubator: flags & 64 != 0,
+            days_played,
+            max_luck_lvl,
+            quantities,
+            ..Default::default()
+        })
+    }
+
+    fn day(&mut self) -> Result<DayForecast> {
+        let settings = self.settings()?;
+        let days_played = settings.days_played;
+        let mut layouts: [ProbabilityRange<u32>; 10] = Default::default();
+        for floor in &mut layouts {
+            *floor = self.ranges(|d| d.u32())?;
+        }
+        let mut loot: [ProbabilityRange<Vec<Goodie>>; 10] = Default::default();
+        for floor in &mut loot {
+            *floor = self.ranges(|d| {
+                let n = d.u16()?;
+                (0..n).map(|_| d.goodie()).collect()
+            })?;
+        }
+        Ok(DayForecast {
+            days_played,
+            settings,
+            layouts,
+            loot,
+            calendar_events: calendar::events_on(DayNumber(days_played)),
+        })
+    }
+
+    fn ranges<T>(
+        &mut self,
+        mut decode: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<ProbabilityRange<T>> {
+        let n = self.u16()?;
+        (0..n)
+            .map(|_| Ok((self.f64()?, self.f64()?, decode(self)?)))
+            .collect()
+    }
+
+    fn item(&mut self) -> Result<ChestItem> {
+        let kind = *ChestItemKind::ALL
+            .get(self.u8()? as usize)
+            .ok_or_else(|| corrupt("unknown item"))?;
+        let len = self.u8()? as usize;
+        if self.0.len() < len {
+            return Err(corrupt("data ends early"));
+        }
+        let (enchants, rest) = self.0.split_at(len);
+        self.0 = rest;
+        let enchants = std::str::from_utf8(enchants).map_err(|_| corrupt("bad enchantments"))?;
+        Ok(ChestItem::with_enchants(
+            kind,
+            Enchants::from_compact_string(enchants)?,
+        ))
+    }
+
+    fn flag(&mut self) -> Result<Option<bool>> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(false)),
+            2 => Ok(Some(true)),
+            _ => Err(corrupt("bad settings flag")),
+        }
+    }
+
+    /// at most MAX_NESTING levels of Ambiguous, deeper is CorruptSnapshot
+    pub(crate) fn goodie(&mut self) -> Result<Goodie> {
+        self.goodie_at(0)
+    }
+
+    fn goodie_at(&mut self, depth: u32) -> Result<Goodie> {
+        Ok(match self.u8()? {
+            0 => Goodie::DragonTooth,
+            1 => Goodie::CommonChest(self.item()?),
+            2 => Goodie::RareChest(self.item()?),
+            3 => Goodie::ChanceChest {
+                minluck: self.f64()?,
+                common: self.item()?,
+                rare: self.item()?,
+            },
+            4 => {
+                if depth >= MAX_NESTING {
+                    return Err(corrupt("ambiguous goodies nested too deep"));
+                }
+                let n = self.u16()?;
+                let alts = (0..n)
+                    .map(|_| {
+                        let assumption = SettingsAssumption {
+                            legacy_rng: self.flag()?,
+                            has_caldera: self.flag()?,
+                            post_1_6_4: self.flag()?,
+                            cracked_golden_coconut: self.flag()?,
+                        };
+                        Ok((assumption, self.goodie_at(depth + 1)?))
+                    })
+                    .collect::<Result<_>>()?;
+                Goodie::Ambiguous(alts)
+            }
+            _ => return Err(corrupt("unknown goodie")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecast;
+    use crate::index::HitFilter;
+    use std::path::PathBuf;
+
+    /// a file in the temp dir that's removed again when the test is done
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let name = format!("sdv-volcano-{}-{}", std::process::id(), name);
+            TempFile(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn days() -> Vec<DayForecast> {
+        let settings = GameSettings {
+            seed: 12345,
+            has_caldera: true,
+            quantities: QuantityModifiers::new(3, 2).unwrap(),
+            ..Default::default()
+        };
+        forecast::range(&settings, 3, 20).unwrap()
+    }
+
+    fn assert_same(a: &DayForecast, b: &DayForecast) {
+        assert_eq!(a.days_played, b.days_played);
+        assert_eq!(a.layouts, b.layouts);
+        assert_eq!(a.loot, b.loot);
+        assert_eq!(a.calendar_events, b.calendar_events);
+        assert_eq!(a.settings.behavior_hash(), b.settings.behavior_hash());
+        assert_eq!(a.settings.quantities, b.settings.quantities);
+    }
+
+    fn ambiguous(depth: u32) -> Goodie {
+        (0..depth).fold(Goodie::DragonTooth, |g, _| {
+            Goodie::Ambiguous(vec![(SettingsAssumption::default(), g)])
+        })
+    }
+
+    fn write(name: &str, days: &[DayForecast]) -> TempFile {
+        let file = TempFile::new(name);
+        let mut out = vec![];
+        write_snapshot(&mut out, days).unwrap();
+        std::fs::write(&file.0, out).unwrap();
+        file
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut days = days();
+        days[0].loot[1] = vec![(0., 1., vec![ambiguous(MAX_NESTING)])];
+        let file = write("roundtrip", &days);
+        let mut reader = SnapshotReader::open(&file.0).unwrap();
+        assert!(reader.days().eq(days.iter().map(|d| d.days_played)));
+        for day in &days {
+            assert_same(&reader.read_day(day.days_played).unwrap().unwrap(), day);
+        }
+        assert!(reader.read_day(1000).unwrap().is_none());
+    }
+
+    #[test]
+    fn settings_roundtrip() {
+        let settings = GameSettings {
+            seed: -5,
+            legacy_rng: true,
+            special_charm: true,
+            days_played: 99,
+            max_luck_lvl: 2,
+            quantities: QuantityModifiers::new(10, 4).unwrap(),
+            has_ostrich_incubator: true,
+            ..Default::default()
+        };
+        let mut out = vec![];
+        encode_settings(&mut out, &settings);
+        let decoded = Decoder(&out).settings().unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", settings));
+        // a multiplier of 0
+        *out.last_mut().unwrap() = 0;
+        assert!(matches!(
+            Decoder(&out).settings(),
+            Err(Error::CorruptSnapshot(_))
+        ));
+    }
+
+    #[test]
+    fn reads_out_of_order() {
+        let days = days();
+        let file = write("out_of_order", &days);
+        let mut reader = SnapshotReader::open(&file.0).unwrap();
+        for i in [7, 19, 0, 7, 12, 1] {
+            assert_same(
+                &reader.read_day(days[i].days_played).unwrap().unwrap(),
+                &days[i],
+            );
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let days = days();
+        let mut full = vec![];
+        write_snapshot(&mut full, &days).unwrap();
+        let file = TempFile::new("truncated");
+        // header, index, and the middle of the last day
+        for len in [5, HEADER_LEN as usize + 3, full.len() - 1] {
+            std::fs::write(&file.0, &full[..len]).unwrap();
+            let result = SnapshotReader::open(&file.0)
+                .and_then(|mut reader| reader.read_day(days.last().unwrap().days_played));
+            assert!(
+                matches!(result, Err(Error::CorruptSnapshot(_))),
+                "{}: {:?}",
+                len,
+                result
+            );
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_roundtrip() {
+        let days = days();
+        let file = TempFile::new("compressed");
+        let mut out = vec![];
+        write_snapshot_compressed(&mut out, &days, CompressionLevel::default()).unwrap();
+        let mut plain = vec![];
+        write_snapshot(&mut plain, &days).unwrap();
+        assert!(out.len() < plain.len());
+        std::fs::write(&file.0, &out).unwrap();
+        let mut reader = SnapshotReader::open(&file.0).unwrap();
+        for day in &days {
+            assert_same(&reader.read_day(day.days_played).unwrap().unwrap(), day);
+        }
+        // truncated: the index goes past the end
+        std::fs::write(&file.0, &out[..out.len() - 2]).unwrap();
+        assert!(matches!(
+            SnapshotReader::open(&file.0),
+            Err(Error::CorruptSnapshot(_))
+        ));
+        // a broken frame, with where it is
+        let entry = HEADER_LEN as usize + INDEX_ENTRY_LEN as usize * 4;
+        let offset = u64::from_le_bytes(out[entry + 4..entry + 12].try_into().unwrap());
+        out[offset as usize..offset as usize + 4].fill(0);
+        std::fs::write(&file.0, &out).unwrap();
+        let result = SnapshotReader::open(&file.0)
+            .unwrap()
+            .read_day(days[4].days_played);
+        assert!(
+            matches!(result, Err(Error::CorruptFrame { offset: o, .. }) if o == offset),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let mut out = vec![];
+        assert!(matches!(
+            encode_goodie(&mut out, &ambiguous(MAX_NESTING + 1)),
+            Err(Error::TooLargeToEncode(_))
+        ));
+        // a crafted goodie far deeper than the stack could take
+        let mut data = vec![];
+        for _ in 0..1_000_000 {
+            data.extend_from_slice(&[4, 1, 0, 0, 0, 0, 0]);
+        }
+        data.push(0);
+        assert!(matches!(
+            Decoder(&data).goodie(),
+            Err(Error::CorruptSnapshot(_))
+        ));
+    }
+
+    #[test]
+    fn lengths_are_checked() {
+        let alts = vec![(SettingsAssumption::default(), Goodie::DragonTooth); 1 << 16];
+        assert!(matches!(
+            encode_goodie(&mut vec![], &Goodie::Ambiguous(alts)),
+            Err(Error::TooLargeToEncode(_))
+        ));
+        let mut day = days().remove(0);
+        day.loot[0] = vec![(0., 1., vec![Goodie::DragonTooth; 1 << 16])];
+        assert!(matches!(
+            write_snapshot(&mut vec![], &[day]),
+            Err(Error::TooLargeToEncode(_))
+        ));
+    }
+
+    #[test]
+    fn snapshot_has_the_save_hash() {
+        let days = days();
+        let file = write("save_hash", &days);
+        let reader = SnapshotReader::open(&file.0).unwrap();
+        assert_eq!(reader.save_hash(), days[0].settings.save_hash());
+        let mut mixed = days.clone();
+        mixed.push(DayForecast::generate(&GameSettings {
+            seed: 1,
+            days_played: 50,
+            ..Default::default()
+        }));
+        assert!(matches!(
+            write_snapshot(&mut vec![], &mixed),
+            Err(Error::MixedSnapshotSettings(50))
+        ));
+    }
+
+    #[test]
+    fn range_index_section() {
+        let days = days();
+        let settings = &days[0].settings;
+        let index = RangeIndex::build(settings.seed, 1..3, settings).unwrap();
+        let file = TempFile::new("range_index");
+        let mut out = vec![];
+        write_snapshot_with_index(&mut out, &days, &index).unwrap();
+        std::fs::write(&file.0, &out).unwrap();
+        let mut reader = SnapshotReader::open(&file.0).unwrap();
+        let read = reader.range_index().unwrap().unwrap();
+        let filter = HitFilter::default();
+        assert_eq!(read.query(0..400, &filter), index.query(0..400, &filter));
+        assert_eq!(read.years().len(), 2);
+        for day in &days {
+            assert_same(&reader.read_day(day.days_played).unwrap().unwrap(), day);
+        }
+        // the days are where they'd be without it
+        let mut plain = vec![];
+        write_snapshot(&mut plain, &days).unwrap();
+        assert_eq!(
+            &out[HEADER_LEN as usize..plain.len()],
+            &plain[HEADER_LEN as usize..]
+        );
+        let plain_file = write("no_range_index", &days);
+        assert!(
+            SnapshotReader::open(&plain_file.0)
+                .unwrap()
+                .range_index()
+                .unwrap()
+                .is_none()
+        );
+
+        // the section has to be in the file
+        std::fs::write(&file.0, &out[..out.len() - 1]).unwrap();
+        assert!(matches!(
+            SnapshotReader::open(&file.0),
+            Err(Error::CorruptSnapshot(_))
+        ));
+        let other = RangeIndex::build(settings.seed + 2, 1..2, settings).unwrap();
+        assert!(matches!(
+            write_snapshot_with_index(&mut vec![], &days, &other),
+            Err(Error::IndexSettingsMismatch(1))
+        ));
+    }
+
+    #[test]
+    fn chest_cache() {
+        let settings = &days()[0].settings;
+        let mut cache = ChestCache::new(10);
+        assert!(cache.is_empty());
+        let expected = forecast::range(settings, 5, 8).unwrap();
+        for _ in 0..2 {
+            let days = cache.range(settings, 5, 8).unwrap();
+            for (a, b) in days.iter().zip(&expected) {
+                assert_same(a, b);
+            }
+            assert_eq!(cache.len(), 8);
+        }
+        // other settings for the same days are other entries
+        let coconut = settings.clone().with_cracked_golden_coconut(true);
+        let day = cache.day(&GameSettings {
+            days_played: 5,
+            ..coconut
+        });
+        assert_same(&day, &forecast::range(&coconut, 5, 1).unwrap()[0]);
+        assert_eq!(cache.len(), 9);
+        cache.range(settings, 20, 5).unwrap();
+        assert_eq!(cache.len(), 10);
+        assert!(ChestCache::new(0).range(settings, 1, 3).unwrap().len() == 3);
+    }
+}
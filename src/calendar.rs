@@ -0,0 +1,95 @@
+//! The vanilla festivals, for planning volcano trips around them. Festivals that take up the day
+//! (or its evening) are time restricted: going to them leaves little or no time for the volcano.
+//! The markets and fishing events can be visited any time of the day.
+
+use crate::date::{DayNumber, GameDate, Season};
+
+/// A festival on one day, events that last several days have an entry per day
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CalendarEvent {
+    pub season: Season,
+    pub day: u32,
+    pub name: &'static str,
+    /// the festival takes the day or the evening, like the Egg Festival or the Luau
+    pub time_restricted: bool,
+}
+
+const fn event(season: Season, day: u32, name: &'static str, restricted: bool) -> CalendarEvent {
+    CalendarEvent {
+        season,
+        day,
+        name,
+        time_restricted: restricted,
+    }
+}
+
+/// in calendar order
+pub const FESTIVALS: [CalendarEvent; 18] = {
+    use Season::*;
+    [
+        event(Spring, 13, "Egg Festival", true),
+        event(Spring, 15, "Desert Festival", false),
+        event(Spring, 16, "Desert Festival", false),
+        event(Spring, 17, "Desert Festival", false),
+        event(Spring, 24, "Flower Dance", true),
+        event(Summer, 11, "Luau", true),
+        event(Summer, 20, "Trout Derby", false),
+        event(Summer, 21, "Trout Derby", false),
+        event(Summer, 28, "Dance of the Moonlight Jellies", true),
+        event(Fall, 16, "Stardew Valley Fair", true),
+        event(Fall, 27, "Spirit's Eve", true),
+        event(Winter, 8, "Festival of Ice", true),
+        event(Winter, 12, "SquidFest", false),
+        event(Winter, 13, "SquidFest", false),
+        event(Winter, 15, "Night Market", false),
+        event(Winter, 16, "Night Market", false),
+        event(Winter, 17, "Night Market", false),
+        event(Winter, 25, "Feast of the Winter Star", true),
+    ]
+};
+
+// in order, so a day's events are next to each other
+const _: () = {
+    let mut i = 1;
+    while i < FESTIVALS.len() {
+        let (a, b) = (&FESTIVALS[i - 1], &FESTIVALS[i]);
+        assert!((a.season as u32) * 28 + a.day <= (b.season as u32) * 28 + b.day);
+        assert!(b.day >= 1 && b.day <= 28);
+        i += 1;
+    }
+};
+
+/// The festivals on `day`, the same every year
+pub fn events_on(day: DayNumber) -> Vec<CalendarEvent> {
+    let date = GameDate::from(day);
+    FESTIVALS
+        .iter()
+        .filter(|e| e.season == date.season && e.day == date.day)
+        .copied()
+        .collect()
+}
+
+/// whether a festival takes up (part of) `day`
+pub fn is_restricted(day: DayNumber) -> bool {
+    events_on(day).iter().any(|e| e.time_restricted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn egg_festival() {
+        for year in 0..3 {
+            let day = DayNumber(year * 112 + 13);
+            let events = events_on(day);
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].name, "Egg Festival");
+            assert!(is_restricted(day));
+        }
+        assert!(events_on(DayNumber(12)).is_empty());
+        // the desert festival leaves time for the volcano
+        assert_eq!(events_on(DayNumber(15))[0].name, "Desert Festival");
+        assert!(!is_restricted(DayNumber(15)));
+    }
+}
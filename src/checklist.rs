@@ -0,0 +1,163 @@
+//! Everything the chests can give with some settings, as a checklist for completionists. Items
+//! the player already has are struck through.
+
+use crate::GameSettings;
+use crate::loot::{ChestItemKind, LootTables, Rarity};
+use crate::render::{OutputFormat, Render, RenderOptions};
+use crate::save::Owned;
+use std::fmt::Write;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checklist {
+    /// from LootTables::obtainable
+    pub common: Vec<ChestItemKind>,
+    pub rare: Vec<ChestItemKind>,
+    /// None to list everything as not checked off
+    pub owned: Option<Owned>,
+}
+
+impl Checklist {
+    /// the items of the vanilla tables
+    pub fn new(settings: &GameSettings) -> Self {
+        Self::for_tables(LootTables::vanilla(), settings)
+    }
+
+    pub fn for_tables(tables: &LootTables, settings: &GameSettings) -> Self {
+        Checklist {
+            common: tables.obtainable(Rarity::Common, settings),
+            rare: tables.obtainable(Rarity::Rare, settings),
+            owned: None,
+        }
+    }
+
+    pub fn with_owned(self, owned: impl IntoIterator<Item = ChestItemKind>) -> Self {
+        Checklist {
+            owned: Some(owned.into_iter().collect()),
+            ..self
+        }
+    }
+
+    pub fn is_owned(&self, kind: ChestItemKind) -> bool {
+        self.owned.as_ref().is_some_and(|o| o.contains(&kind))
+    }
+}
+
+/// "Cinder Shard (3)", the two shard stacks are separate entries
+fn entry_name(kind: ChestItemKind) -> String {
+    match kind.quantity() {
+        1 => kind.name().to_string(),
+        n => format!("{} ({})", kind.name(), n),
+    }
+}
+
+impl Render for Checklist {
+    /// a list per table. only html gets its own markup, the other formats get markdown task
+    /// lists.
+    fn render(&self, out: &mut dyn Write, opts: &RenderOptions) -> std::fmt::Result {
+        for (title, kinds) in [("Common chests", &self.common), ("Rare chests", &self.rare)] {
+            if opts.format == OutputFormat::Html {
+                writeln!(out, "<h3>{}</h3>", title)?;
+                writeln!(out, "<ul class=checklist>")?;
+                for &kind in kinds {
+                    write!(out, "<li>")?;
+                    opts.icons.icon(out, kind.meta().icon_key)?;
+                    if self.is_owned(kind) {
+                        writeln!(out, " <s>{}</s></li>", entry_name(kind))?;
+                    } else {
+                        writeln!(out, " {}</li>", entry_name(kind))?;
+                    }
+                }
+                writeln!(out, "</ul>")?;
+            } else {
+                writeln!(out, "### {}", title)?;
+                for &kind in kinds {
+                    if self.is_owned(kind) {
+                        writeln!(out, "- [x] ~~{}~~", entry_name(kind))?;
+                    } else {
+                        writeln!(out, "- [ ] {}", entry_name(kind))?;
+                    }
+                }
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coconuts_after_cracking() {
+        use ChestItemKind::{GoldenCoconut, GoldenCoconuts};
+        let uncracked = GameSettings::default();
+        let cracked = uncracked.clone().with_cracked_golden_coconut(true);
+        let all = ChestItemKind::all_obtainable(&uncracked);
+        assert!(!all.contains(&GoldenCoconut) && !all.contains(&GoldenCoconuts));
+        let all = ChestItemKind::all_obtainable(&cracked);
+        assert!(all.contains(&GoldenCoconut) && all.contains(&GoldenCoconuts));
+        for settings in [&uncracked, &cracked] {
+            let checklist = Checklist::new(settings);
+            let mut both = [checklist.common.clone(), checklist.rare.clone()].concat();
+            both.sort();
+            both.dedup();
+            assert_eq!(both, ChestItemKind::all_obtainable(settings));
+        }
+        // only the common table's slot
+        let common_only = GameSettings {
+            golden_coconut_in_common: true,
+            ..uncracked
+        };
+        let checklist = Checklist::new(&common_only);
+        assert!(checklist.common.contains(&GoldenCoconut));
+        assert!(!checklist.rare.contains(&GoldenCoconuts));
+    }
+
+    #[test]
+    fn other_tables() {
+        let tables: LootTables = "[[common]]\nitems = [\"ostrich_egg\"]\n\
+             [[common]]\nitems = [\"coconut\"]\nrequires = \"golden_coconut\"\n\
+             [[rare]]\nitems = [\"taro\", \"phoenix_ring\"]\n"
+            .parse()
+            .unwrap();
+        let settings = GameSettings::default();
+        let checklist = Checklist::for_tables(&tables, &settings);
+        assert_eq!(checklist.common, [ChestItemKind::OstrichEgg]);
+        assert_eq!(
+            checklist.rare,
+            [ChestItemKind::TaroTuber, ChestItemKind::PhoenixRing]
+        );
+        let cracked = settings.with_cracked_golden_coconut(true);
+        let checklist = Checklist::for_tables(&tables, &cracked);
+        assert_eq!(
+            checklist.common,
+            [ChestItemKind::GoldenCoconut, ChestItemKind::OstrichEgg]
+        );
+    }
+
+    #[test]
+    fn owned_items_are_struck_through() {
+        let checklist = Checklist::new(&GameSettings::default());
+        let render = |checklist: &Checklist, format| {
+            checklist
+                .try_render_to_string(&RenderOptions::with_format(format))
+                .unwrap()
+        };
+        let markdown = render(&checklist, OutputFormat::Markdown);
+        assert!(markdown.starts_with("### Common chests\n- [ ] Cinder Shard (3)\n"));
+        assert!(!markdown.contains("[x]"));
+        assert!(!render(&checklist, OutputFormat::Html).contains("<s>"));
+
+        let owned = checklist.with_owned([ChestItemKind::PhoenixRing, ChestItemKind::OstrichEgg]);
+        let markdown = render(&owned, OutputFormat::Markdown);
+        assert!(markdown.contains("- [x] ~~Phoenix Ring~~\n"));
+        assert!(markdown.contains("- [x] ~~Ostrich Egg~~\n"));
+        assert_eq!(markdown.matches("[x]").count(), 2);
+        assert!(markdown.contains("- [ ] Hot Java Ring\n"));
+        let html = render(&owned, OutputFormat::Html);
+        assert!(html.contains("<ul class=checklist>"));
+        assert!(html.contains(" <s>Phoenix Ring</s></li>"));
+        assert_eq!(html.matches("<s>").count(), 2);
+    }
+}
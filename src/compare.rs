@@ -0,0 +1,157 @@
+use crate::date::{DayNumber, GameDate};
+use crate::forecast;
+use crate::render::{OutputFormat, Render, RenderOptions};
+use crate::stats::{self, LuckCounting, ValueModel};
+use crate::{Error, GameSettings, Result};
+use std::fmt::Write;
+
+/// Volcano highlights of one seed over the compared days
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeedSummary {
+    pub game_id: u64,
+    pub total_value: f64,
+    pub rare_uniques: u32,
+    /// chest exclusive items, weighted by how much of the luck range gets them
+    pub exclusive_items: f64,
+    /// first day with a dragontooth weapon whose enchant score is at least the threshold
+    pub first_good_dragontooth: Option<DayNumber>,
+}
+
+/// Side by side summaries of several seeds, best total value first
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeedComparison {
+    pub start_day: DayNumber,
+    pub days: u32,
+    pub seeds: Vec<SeedSummary>,
+}
+
+/// Compares the first `days` days of each game seed (starting at settings.days_played).
+/// UnsupportedGameId for ids the forecasts can't take, see GameSettings::for_game_id.
+pub fn seeds(
+    seeds: &[u64],
+    days: u32,
+    settings: &GameSettings,
+    model: &ValueModel,
+    dragontooth_threshold: f64,
+) -> Result<SeedComparison> {
+    let start_day = DayNumber(settings.days_played.max(1));
+    let mut summaries: Vec<SeedSummary> = seeds
+        .iter()
+        .map(|&game_id| {
+            let seed = i32::try_from(game_id).map_err(|_| Error::UnsupportedGameId(game_id))?;
+            let forecast = forecast::range(&GameSettings { seed, ..*settings }, start_day, days)?;
+            let first_good_dragontooth = forecast
+                .iter()
+                .find(|day| {
+                    day.loot.iter().flatten().flat_map(|x| &x.2).any(|g| {
+                        g.as_chest().is_some_and(|(_, c)| {
+                            c.kind().is_dragontooth_weapon()
+                                && c.enchants().is_some_and(|e| {
+                                    e.score(&model.enchant_weights) >= dragontooth_threshold
+                                })
+                        })
+                    })
+                })
+                .map(|day| DayNumber(day.days_played));
+            Ok(SeedSummary {
+                game_id,
+                total_value: forecast.iter().map(|d| model.expected_value(d)).sum(),
+                rare_uniques: forecast.iter().map(stats::rare_uniques).sum(),
+                exclusive_items: forecast
+                    .iter()
+                    .map(|d| stats::exclusive_items(d, LuckCounting::Fractional))
+                    .sum(),
+                first_good_dragontooth,
+            })
+        })
+        .collect::<Result<_>>()?;
+    // stable sort, so equal seeds stay in input order
+    summaries.sort_by(|a, b| b.total_value.total_cmp(&a.total_value));
+    Ok(SeedComparison {
+        start_day,
+        days,
+        seeds: summaries,
+    })
+}
+
+impl Render for SeedComparison {
+    /// table with one column per seed. only html and markdown have tables, the other formats
+    /// get the markdown one.
+    fn render(&self, out: &mut dyn Write, opts: &RenderOptions) -> std::fmt::Result {
+        let cells =
+            |f: &dyn Fn(&SeedSummary) -> String| self.seeds.iter().map(f).collect::<Vec<_>>();
+        let nf = &opts.number_format;
+        let rows = [
+            ("seed", cells(&|s| s.game_id.to_string())),
+            ("total value", cells(&|s| nf.number(s.total_value, 1))),
+            ("rare uniques", cells(&|s| s.rare_uniques.to_string())),
+            (
+                "chest exclusive items",
+                cells(&|s| nf.number(s.exclusive_items, 1)),
+            ),
+            (
+                "first good dragontooth weapon",
+                cells(&|s| match s.first_good_dragontooth {
+                    Some(day) => GameDate::from(day).to_string(),
+                    None => "-".to_string(),
+                }),
+            ),
+        ];
+        if opts.format == OutputFormat::Html {
+            writeln!(out, "<table class=seed-comparison>")?;
+            for (i, (name, row)) in rows.iter().enumerate() {
+                let tag = if i == 0 { "th" } else { "td" };
+                write!(out, "<tr><th>{}</th>", name)?;
+                for cell in row {
+                    write!(out, "<{tag}>{}</{tag}>", cell)?;
+                }
+                writeln!(out, "</tr>")?;
+            }
+            writeln!(out, "</table>")
+        } else {
+            for (i, (name, row)) in rows.iter().enumerate() {
+                write!(out, "| {} |", name)?;
+                for cell in row {
+                    write!(out, " {} |", cell)?;
+                }
+                writeln!(out)?;
+                if i == 0 {
+                    write!(out, "|---|")?;
+                    for _ in &self.seeds {
+                        write!(out, "---|")?;
+                    }
+                    writeln!(out)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordered_by_total_value() {
+        let model = ValueModel::default();
+        let comparison = seeds(&[1, 6, 12], 7, &GameSettings::default(), &model, 0.).unwrap();
+        let order: Vec<u64> = comparison.seeds.iter().map(|s| s.game_id).collect();
+        assert_eq!(order, [12, 1, 6]);
+        assert!(
+            comparison
+                .seeds
+                .windows(2)
+                .all(|w| w[0].total_value > w[1].total_value)
+        );
+        // 2 and 3 have the same volcano, so they keep their order
+        let tied = seeds(&[3, 2], 7, &GameSettings::default(), &model, 0.).unwrap();
+        assert_eq!(tied.seeds[0].total_value, tied.seeds[1].total_value);
+        assert_eq!(tied.seeds[0].game_id, 3);
+
+        assert!(matches!(
+            seeds(&[1, u64::MAX], 7, &GameSettings::default(), &model, 0.),
+            Err(Error::UnsupportedGameId(u64::MAX))
+        ));
+    }
+}
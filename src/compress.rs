@@ -0,0 +1,114 @@
+//! Optional zstd compression of snapshot and checkpoint files, with the `compression` feature.
+//! Compressed data is told apart by its magic, so uncompressed files keep working either way.
+
+use crate::{Error, Result};
+
+/// what every zstd frame starts with
+const ZSTD_MAGIC: &[u8; 4] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// most a frame may decompress to, 256 MiB. way more than any day or checkpoint, it's there so a
+/// crafted frame can't blow up into all of the memory.
+const MAX_DECOMPRESSED: u64 = 1 << 28;
+
+/// zstd level, from 1 (fastest) to 22 (smallest)
+#[cfg(feature = "compression")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CompressionLevel(pub i32);
+
+#[cfg(feature = "compression")]
+impl CompressionLevel {
+    pub const FASTEST: Self = CompressionLevel(1);
+    pub const SMALLEST: Self = CompressionLevel(22);
+}
+
+#[cfg(feature = "compression")]
+impl Default for CompressionLevel {
+    /// zstd's default
+    fn default() -> Self {
+        CompressionLevel(3)
+    }
+}
+
+pub(crate) fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(ZSTD_MAGIC)
+}
+
+#[cfg(feature = "compression")]
+pub(crate) fn compress(data: &[u8], level: CompressionLevel) -> Vec<u8> {
+    zstd::encode_all(data, level.0).expect("compressing in memory doesn't fail")
+}
+
+/// `offset` is where the frame starts in its file, for the error. More than MAX_DECOMPRESSED
+/// bytes of output is a CorruptFrame too.
+pub(crate) fn decompress(frame: &[u8], offset: u64) -> Result<Vec<u8>> {
+    decompress_capped(frame, offset, MAX_DECOMPRESSED)
+}
+
+fn decompress_capped(frame: &[u8], offset: u64, cap: u64) -> Result<Vec<u8>> {
+    #[cfg(feature = "compression")]
+    {
+        use std::io::Read;
+        let corrupt = |msg: String| Error::CorruptFrame { offset, msg };
+        let decoder = zstd::Decoder::with_buffer(frame).map_err(|e| corrupt(e.to_string()))?;
+        let mut out = Vec::new();
+        // one byte past the cap, to tell hitting it apart from ending right at it
+        decoder
+            .take(cap + 1)
+            .read_to_end(&mut out)
+            .map_err(|e| corrupt(e.to_string()))?;
+        if out.len() as u64 > cap {
+            return Err(corrupt(format!("decompresses to more than {} bytes", cap)));
+        }
+        Ok(out)
+    }
+    #[cfg(not(feature = "compression"))]
+    {
+        let _ = (frame, offset, cap);
+        Err(Error::CompressionUnavailable)
+    }
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let data: Vec<u8> = (0..10_000u32)
+            .flat_map(|i| (i % 97).to_le_bytes())
+            .collect();
+        for level in [CompressionLevel::FASTEST, CompressionLevel::default()] {
+            let frame = compress(&data, level);
+            assert!(is_compressed(&frame));
+            assert!(frame.len() < data.len());
+            assert_eq!(decompress(&frame, 0).unwrap(), data);
+        }
+        assert!(!is_compressed(&data));
+    }
+
+    #[test]
+    fn truncated() {
+        let frame = compress(&[7; 5000], CompressionLevel::default());
+        let result = decompress(&frame[..frame.len() - 3], 1234);
+        assert!(
+            matches!(result, Err(Error::CorruptFrame { offset: 1234, .. })),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn output_is_capped() {
+        let frame = compress(&[0; 100_000], CompressionLevel::default());
+        assert_eq!(
+            decompress_capped(&frame, 0, 100_000).unwrap().len(),
+            100_000
+        );
+        let result = decompress_capped(&frame, 5, 99_999);
+        assert!(
+            matches!(&result, Err(Error::CorruptFrame { offset: 5, msg }) if msg.contains("more than")),
+            "{:?}",
+            result
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use crate::{Error, Result};
+use std::fmt::Display;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Day number as used by the game for `days_played`, starting at 1 for spring 1 of year 1
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DayNumber(pub u32);
+
+/// Longest range of days anything here generates, 100 years. Everything that takes a start day
+/// and a number of days fails with RangeTooLarge for longer ranges (and for ranges past u32::MAX),
+/// and with DayOutOfRange for day 0.
+pub const MAX_DAY_RANGE: u32 = 100 * 112;
+
+/// last year whose days all fit in a u32
+const MAX_YEAR: u32 = u32::MAX / 112;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Season {
+    Spring,
+    Summer,
+    Fall,
+    Winter,
+}
+
+/// In-game calendar date. `day` is 1-28 and `year` starts at 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GameDate {
+    pub year: u32,
+    pub season: Season,
+    pub day: u32,
+}
+
+impl Season {
+    pub const ALL: [Season; 4] = [Season::Spring, Season::Summer, Season::Fall, Season::Winter];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Season::Spring => "spring",
+            Season::Summer => "summer",
+            Season::Fall => "fall",
+            Season::Winter => "winter",
+        }
+    }
+}
+
+impl FromStr for Season {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "spring" => Ok(Season::Spring),
+            "summer" => Ok(Season::Summer),
+            "fall" | "autumn" => Ok(Season::Fall),
+            "winter" => Ok(Season::Winter),
+            _ => Err(Error::UnknownSeason(s.to_string())),
+        }
+    }
+}
+
+impl GameDate {
+    pub fn new(year: u32, season: Season, day: u32) -> Result<Self> {
+        if !(1..=28).contains(&day) {
+            return Err(Error::DayOutOfRange(day));
+        }
+        if year == 0 {
+            return Err(Error::InvalidDate("year 0".to_string()));
+        }
+        if year > MAX_YEAR {
+            return Err(Error::InvalidDate(format!("year {} is too far out", year)));
+        }
+        Ok(GameDate { year, season, day })
+    }
+}
+
+impl DayNumber {
+    pub fn checked_add(self, days: u32) -> Option<Self> {
+        self.0.checked_add(days).map(DayNumber)
+    }
+
+    /// days_played of the `days` days from this one, cut off at u32::MAX. For ranges that are
+    /// already bounded, checked_span for anything that comes from users.
+    pub fn span(self, days: u32) -> Range<u32> {
+        self.0..self.0.saturating_add(days)
+    }
+
+    /// days_played of the `days` days from this one: DayOutOfRange for day 0, RangeTooLarge for
+    /// more than `max` days (which is capped at MAX_DAY_RANGE) or days past u32::MAX
+    pub fn checked_span(self, days: u32, max: u32) -> Result<Range<u32>> {
+        if self.0 == 0 {
+            return Err(Error::DayOutOfRange(0));
+        }
+        let max = max.min(MAX_DAY_RANGE).min(u32::MAX - self.0);
+        if days > max {
+            return Err(Error::RangeTooLarge {
+                start: self.0,
+                days,
+                max,
+            });
+        }
+        Ok(self.0..self.0 + days)
+    }
+}
+
+impl From<u32> for DayNumber {
+    fn from(days_played: u32) -> Self {
+        DayNumber(days_played)
+    }
+}
+
+impl From<GameDate> for DayNumber {
+    fn from(date: GameDate) -> Self {
+        // saturating for dates built without new
+        let year_start = date.year.saturating_sub(1).saturating_mul(112);
+        DayNumber(year_start.saturating_add(date.season as u32 * 28 + date.day))
+    }
+}
+
+impl From<DayNumber> for GameDate {
+    fn from(day: DayNumber) -> Self {
+        // day 0 isn't a day, it gets spring 1 instead of underflowing
+        let index = day.0.saturating_sub(1);
+        let total_seasons = index / 28;
+        GameDate {
+            year: total_seasons / 4 + 1,
+            season: Season::ALL[(total_seasons % 4) as usize],
+            day: index % 28 + 1,
+        }
+    }
+}
+
+impl Display for GameDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}, Y{}", self.season.name(), self.day, self.year)
+    }
+}
+
+/// Accepts "fall 5, Y2" (the Display format), "y2 fall 5", "fall 5, year 2" and "2-3-5"
+/// (year-season-day, seasons numbered from 1). The year can be left out and defaults to 1.
+impl FromStr for GameDate {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidDate(s.to_string());
+        let parse_num = |x: &str| x.parse::<u32>().map_err(|_| invalid());
+
+        let parts: Vec<&str> = s.trim().split('-').collect();
+        if parts.len() == 3 {
+            let year = parse_num(parts[0].trim())?;
+            let season = parse_num(parts[1].trim())?;
+            let day = parse_num(parts[2].trim())?;
+            if !(1..=4).contains(&season) {
+                return Err(Error::UnknownSeason(parts[1].trim().to_string()));
+            }
+            return GameDate::new(year, Season::ALL[season as usize - 1], day);
+        }
+
+        let lower = s.to_ascii_lowercase().replace(',', " ");
+        let mut tokens = lower.split_whitespace();
+        let (mut year, mut season, mut day) = (None, None, None);
+        while let Some(tok) = tokens.next() {
+            if tok == "year" || tok == "y" {
+                year = Some(parse_num(tokens.next().ok_or_else(invalid)?)?);
+            } else if let Some(num) = tok.strip_prefix('y')
+                && !num.is_empty()
+                && num.bytes().all(|c| c.is_ascii_digit())
+            {
+                year = Some(parse_num(num)?);
+            } else if tok.bytes().all(|c| c.is_ascii_digit()) {
+                if day.is_some() {
+                    return Err(invalid());
+                }
+                day = Some(parse_num(tok)?);
+            } else {
+                if season.is_some() {
+                    return Err(invalid());
+                }
+                season = Some(tok.parse::<Season>()?);
+            }
+        }
+        match (season, day) {
+            (Some(season), Some(day)) => GameDate::new(year.unwrap_or(1), season, day),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl FromStr for DayNumber {
+    type Err = Error;
+    /// either a plain days_played number or anything accepted by GameDate
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().parse::<u32>() {
+            Ok(0) => Err(Error::DayOutOfRange(0)),
+            Ok(n) => Ok(DayNumber(n)),
+            Err(_) => Ok(s.parse::<GameDate>()?.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_formats() {
+        let fall_5_y2 = GameDate::new(2, Season::Fall, 5).unwrap();
+        for s in [
+            "fall 5, Y2",
+            "y2 fall 5",
+            "Y2 Fall 5",
+            "fall 5, year 2",
+            "year 2 fall 5",
+            "5 autumn y2",
+            "2-3-5",
+            " 2 - 3 - 5 ",
+        ] {
+            assert_eq!(s.parse::<GameDate>().unwrap(), fall_5_y2, "{}", s);
+        }
+        assert_eq!(
+            "summer 1".parse::<GameDate>().unwrap(),
+            GameDate::new(1, Season::Summer, 1).unwrap()
+        );
+        assert_eq!(DayNumber::from(fall_5_y2), DayNumber(112 + 2 * 28 + 5));
+        assert_eq!("173".parse::<DayNumber>().unwrap(), DayNumber(173));
+        assert_eq!("fall 5, y2".parse::<DayNumber>().unwrap(), DayNumber(173));
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        for days_played in [1, 28, 29, 112, 113, 173, 1000, 11200] {
+            let date = GameDate::from(DayNumber(days_played));
+            assert_eq!(date.to_string().parse::<GameDate>().unwrap(), date);
+            assert_eq!(DayNumber::from(date), DayNumber(days_played));
+        }
+        assert_eq!(GameDate::from(DayNumber(173)).to_string(), "fall 5, Y2");
+    }
+
+    #[test]
+    fn dates_as_days() {
+        let settings = crate::GameSettings::default();
+        let date: GameDate = "fall 5, y2".parse().unwrap();
+        let by_date = crate::forecast::range(&settings, date, 2).unwrap();
+        let by_number = crate::forecast::range(&settings, 173, 2).unwrap();
+        assert_eq!(by_date[0].days_played, 173);
+        assert_eq!(
+            by_date.iter().map(|d| &d.loot).collect::<Vec<_>>(),
+            by_number.iter().map(|d| &d.loot).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn invalid_dates() {
+        assert!(matches!(
+            "winter 29".parse::<GameDate>(),
+            Err(Error::DayOutOfRange(29))
+        ));
+        assert!(matches!(
+            "winter 0".parse::<GameDate>(),
+            Err(Error::DayOutOfRange(0))
+        ));
+        assert!(matches!(
+            "2-5-1".parse::<GameDate>(),
+            Err(Error::UnknownSeason(s)) if s == "5"
+        ));
+        assert!(matches!(
+            "smmer 3".parse::<GameDate>(),
+            Err(Error::UnknownSeason(s)) if s == "smmer"
+        ));
+        assert!(matches!(
+            "y0 spring 1".parse::<GameDate>(),
+            Err(Error::InvalidDate(_))
+        ));
+        assert!(matches!(
+            "spring 1 2".parse::<GameDate>(),
+            Err(Error::InvalidDate(_))
+        ));
+        assert!(matches!(
+            "0".parse::<DayNumber>(),
+            Err(Error::DayOutOfRange(0))
+        ));
+    }
+
+    #[test]
+    fn checked_spans() {
+        let too_large = |r: Result<Range<u32>>| matches!(r, Err(Error::RangeTooLarge { .. }));
+        assert!(matches!(
+            DayNumber(0).checked_span(1, MAX_DAY_RANGE),
+            Err(Error::DayOutOfRange(0))
+        ));
+        assert!(matches!(
+            DayNumber(0).checked_span(0, MAX_DAY_RANGE),
+            Err(Error::DayOutOfRange(0))
+        ));
+        assert_eq!(
+            DayNumber(1)
+                .checked_span(MAX_DAY_RANGE, MAX_DAY_RANGE)
+                .unwrap(),
+            1..MAX_DAY_RANGE + 1
+        );
+        assert!(matches!(
+            DayNumber(1).checked_span(MAX_DAY_RANGE + 1, MAX_DAY_RANGE),
+            Err(Error::RangeTooLarge {
+                start: 1,
+                days,
+                max: MAX_DAY_RANGE,
+            }) if days == MAX_DAY_RANGE + 1
+        ));
+        // a higher max is still capped
+        assert!(too_large(
+            DayNumber(1).checked_span(MAX_DAY_RANGE + 1, u32::MAX)
+        ));
+        assert!(too_large(DayNumber(1).checked_span(u32::MAX, u32::MAX)));
+        assert_eq!(DayNumber(5).checked_span(10, 10).unwrap(), 5..15);
+        assert!(too_large(DayNumber(5).checked_span(11, 10)));
+        assert_eq!(DayNumber(5).checked_span(0, 10).unwrap(), 5..5);
+
+        // the last days a u32 counts
+        assert_eq!(
+            DayNumber(u32::MAX - 1)
+                .checked_span(1, MAX_DAY_RANGE)
+                .unwrap(),
+            u32::MAX - 1..u32::MAX
+        );
+        assert!(matches!(
+            DayNumber(u32::MAX).checked_span(1, MAX_DAY_RANGE),
+            Err(Error::RangeTooLarge { max: 0, .. })
+        ));
+        assert!(too_large(
+            DayNumber(u32::MAX - 5).checked_span(6, MAX_DAY_RANGE)
+        ));
+        assert_eq!(DayNumber(u32::MAX - 3).span(10), u32::MAX - 3..u32::MAX);
+    }
+}
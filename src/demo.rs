@@ -0,0 +1,105 @@
+//! A fixed forecast for showing something before a seed is entered, and as a fixture with a bit
+//! of everything. It's generated like any other forecast, so it follows generation changes.
+//!
+//! The 14 days have dragon teeth, common and rare chests, enchanted weapons and Ambiguous goodies
+//! (the coconut flag is left unknown). Forecasts never have chance chests, their luck ranges are
+//! split at every chest's cutoff, so chance_chest gives one on its own.
+
+use crate::GameSettings;
+use crate::forecast::DayForecast;
+use crate::loot::{FloorLevel, Goodie};
+use crate::luck::luck_range;
+use crate::partial::{Flag, PartialSettings};
+use crate::plan::{PlannedGoodie, SeedPlan};
+use std::sync::OnceLock;
+
+pub const DEMO_SEED: i32 = 6;
+pub const DEMO_START_DAY: u32 = 1;
+pub const DEMO_DAYS: u32 = 14;
+
+/// the demo's settings, defaults with the seed and an unknown coconut flag
+pub fn settings() -> PartialSettings {
+    PartialSettings {
+        cracked_golden_coconut: Flag::Unknown,
+        ..PartialSettings::new(GameSettings {
+            seed: DEMO_SEED,
+            days_played: DEMO_START_DAY,
+            ..Default::default()
+        })
+    }
+}
+
+/// The demo days, generated once
+pub fn forecast() -> Vec<DayForecast> {
+    static DAYS: OnceLock<Vec<DayForecast>> = OnceLock::new();
+    DAYS.get_or_init(|| {
+        settings()
+            .range(DEMO_START_DAY, DEMO_DAYS)
+            .expect("the demo days are a valid range")
+    })
+    .clone()
+}
+
+/// The first chest of the demo days whose rarity depends on the luck, as a chance chest over the
+/// whole luck range (with the coconut not cracked). None if none of them does, the demo days are
+/// picked so there is one.
+pub fn chance_chest() -> Option<Goodie> {
+    let settings = settings().settings;
+    let (min_luck, max_luck) = luck_range(&settings);
+    let plan = SeedPlan::new(&settings, DEMO_START_DAY, DEMO_DAYS)
+        .expect("the demo days are a valid range");
+    for day in &plan.days {
+        let settings = GameSettings {
+            days_played: day.days_played,
+            ..settings
+        };
+        for (_, _, floors) in &day.floors {
+            for (level, floor) in FloorLevel::ALL.into_iter().zip(floors) {
+                for goodie in &floor.goodies {
+                    let PlannedGoodie::Chest(seed) = *goodie else {
+                        continue;
+                    };
+                    let goodie = Goodie::generate_at(seed, &settings, level, min_luck, max_luck)
+                        .expect("planned chests are on chest floors");
+                    if matches!(goodie, Goodie::ChanceChest { .. }) {
+                        return Some(goodie);
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn has_a_chance_chest() {
+        let Some(Goodie::ChanceChest { minluck, .. }) = chance_chest() else {
+            panic!("no chance chest in the demo days");
+        };
+        let (min_luck, max_luck) = luck_range(&settings().settings);
+        assert!(min_luck < minluck && minluck <= max_luck);
+    }
+
+    #[test]
+    fn has_a_bit_of_everything() {
+        let days = forecast();
+        assert_eq!(days.len(), DEMO_DAYS as usize);
+        let goodies: Vec<&Goodie> = days
+            .iter()
+            .flat_map(|day| &day.loot)
+            .flat_map(|floor| floor.iter().flat_map(|(_, _, goodies)| goodies))
+            .collect();
+        assert!(goodies.iter().any(|g| matches!(g, Goodie::DragonTooth)));
+        assert!(goodies.iter().any(|g| matches!(g, Goodie::CommonChest(_))));
+        assert!(goodies.iter().any(|g| matches!(g, Goodie::RareChest(_))));
+        assert!(
+            goodies
+                .iter()
+                .any(|g| matches!(g, Goodie::Ambiguous { .. }))
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use crate::forecast::DayForecast;
+use crate::loot::{ChestItemKind, Enchant, FloorLevel, Goodie, LootTables, Rarity};
+use crate::luck::luck_range;
+use crate::{Error, GameSettings, Result};
+use std::fmt::Display;
+
+/// Something the player found in their game, used to figure out settings they don't know
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    pub days_played: u32,
+    pub level: i32,
+    /// CommonChest or RareChest with the observed item, or DragonTooth
+    pub goodie: Goodie,
+}
+
+/// How the game derives seeds: the "use legacy randomization" save option, or the default
+/// hashed seeds of 1.6
+#[derive(Clone, Debug, PartialEq)]
+pub enum RandomizationMode {
+    Legacy,
+    Hashed,
+    /// pick whichever of the two can produce all the observations
+    Auto(Vec<Observation>),
+}
+
+impl Observation {
+    /// whether the observation is possible under these settings (for any luck)
+    pub fn matches(&self, settings: &GameSettings) -> bool {
+        let day = DayForecast::generate(&GameSettings {
+            days_played: self.days_played,
+            ..*settings
+        });
+        let Some(floor) = usize::try_from(self.level)
+            .ok()
+            .and_then(|l| day.loot.get(l))
+        else {
+            return false;
+        };
+        floor.iter().any(|(_, _, loot)| loot.contains(&self.goodie))
+    }
+}
+
+/// Why an observation can't happen with some settings, whatever the seed. `index` is the
+/// observation's position in what was validated.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObservationIssue {
+    /// a chest on a level without chests, or a level that isn't a floor at all
+    NoChests { index: usize, level: i32 },
+    /// the table of that rarity never gives the item with these settings, e.g. golden coconuts
+    /// before the first one is cracked
+    NotInTable {
+        index: usize,
+        kind: ChestItemKind,
+        rarity: Rarity,
+    },
+    /// a rare chest on a floor where even the settings' highest luck can't make one rare
+    RareImpossible { index: usize, level: i32 },
+    /// an enchant level the game never rolls
+    InvalidEnchant {
+        index: usize,
+        enchant: Enchant,
+        level: i32,
+    },
+    /// the goodie isn't something a player finds, e.g. a ChanceChest
+    NotObservable { index: usize },
+}
+
+impl Display for ObservationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObservationIssue::NoChests { index, level } => {
+                write!(f, "observation {}: level {} has no chests", index, level)
+            }
+            ObservationIssue::NotInTable {
+                index,
+                kind,
+                rarity,
+            } => write!(
+                f,
+                "observation {}: {:?} chests can't give {} with these settings",
+                index,
+                rarity,
+                kind.name()
+            ),
+            ObservationIssue::RareImpossible { index, level } => write!(
+                f,
+                "observation {}: chests on level {} can't be rare with this luck",
+                index, level
+            ),
+            ObservationIssue::InvalidEnchant {
+                index,
+                enchant,
+                level,
+            } => write!(
+                f,
+                "observation {}: {} {} isn't an enchant level the game rolls",
+                index,
+                enchant.name(),
+                level
+            ),
+            ObservationIssue::NotObservable { index } => {
+                write!(f, "observation {}: not a goodie a player finds", index)
+            }
+        }
+    }
+}
+
+/// What makes any of the observations impossible with `settings` for every seed: the floor, the
+/// loot tables (with the settings' gates) and the enchant levels. Empty if they're all possible,
+/// which doesn't mean some seed actually gives them.
+pub fn validate_observations(
+    observations: &[Observation],
+    settings: &GameSettings,
+) -> Vec<ObservationIssue> {
+    let tables = LootTables::vanilla();
+    let max_luck = luck_range(settings).1;
+    let mut issues = vec![];
+    for (index, obs) in observations.iter().enumerate() {
+        let level = obs.level;
+        let floor = FloorLevel::try_from(level).ok();
+        if obs.goodie == Goodie::DragonTooth {
+            if floor.is_none() {
+                issues.push(ObservationIssue::NoChests { index, level });
+            }
+            continue;
+        }
+        let Some((rarity, item)) = obs.goodie.as_chest() else {
+            issues.push(ObservationIssue::NotObservable { index });
+            continue;
+        };
+        match floor {
+            Some(floor) if floor.has_chests() => {
+                if rarity == Rarity::Rare && !floor.rare_possible(max_luck) {
+                    issues.push(ObservationIssue::RareImpossible { index, level });
+                }
+            }
+            _ => issues.push(ObservationIssue::NoChests { index, level }),
+        }
+        if !tables.obtainable(rarity, settings).contains(&item.kind()) {
+            issues.push(ObservationIssue::NotInTable {
+                index,
+                kind: item.kind(),
+                rarity,
+            });
+        }
+        let enchants = item.enchants().map_or(&[][..], |e| e.as_slice());
+        for &(enchant, lvl) in enchants {
+            if !enchant.valid_level(lvl) {
+                issues.push(ObservationIssue::InvalidEnchant {
+                    index,
+                    enchant,
+                    level: lvl,
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// All modes (out of Legacy and Hashed) that can produce all the observations. The observations
+/// are validated first, InconsistentObservations if some can't happen with any seed. Empty if
+/// they could, but neither mode gives them for this game.
+pub fn matching_modes(
+    observations: &[Observation],
+    game_id: i32,
+    settings: &GameSettings,
+) -> Result<Vec<RandomizationMode>> {
+    let issues = validate_observations(observations, settings);
+    if !issues.is_empty() {
+        return Err(Error::InconsistentObservations(issues));
+    }
+    Ok([
+        (RandomizationMode::Legacy, true),
+        (RandomizationMode::Hashed, false),
+    ]
+    .into_iter()
+    .filter(|&(_, legacy_rng)| {
+        let settings = GameSettings {
+            seed: game_id,
+            legacy_rng,
+            ..*settings
+        };
+        observations.iter().all(|obs| obs.matches(&settings))
+    })
+    .map(|(mode, _)| mode)
+    .collect())
+}
+
+/// The randomization mode that produces the observations, or None if both or neither of them do.
+/// Use [`matching_modes`] to tell those two cases apart.
+pub fn randomization_mode(
+    observations: &[Observation],
+    game_id: i32,
+    settings: &GameSettings,
+) -> Result<Option<RandomizationMode>> {
+    let mut modes = matching_modes(observations, game_id, settings)?;
+    Ok(if modes.len() == 1 { modes.pop() } else { None })
+}
+
+impl RandomizationMode {
+    /// value for GameSettings::legacy_rng
+    pub fn resolve(&self, settings: &GameSettings) -> Result<bool> {
+        match self {
+            RandomizationMode::Legacy => Ok(true),
+            RandomizationMode::Hashed => Ok(false),
+            RandomizationMode::Auto(observations) => {
+                let modes = matching_modes(observations, settings.seed, settings)?;
+                match modes.as_slice() {
+                    [mode] => mode.resolve(settings),
+                    [] => Err(Error::ObservationMismatch),
+                    _ => Err(Error::AmbiguousObservation),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loot::ChestItem;
+
+    /// a chest of the legacy game `seed` that the hashed one doesn't have
+    fn legacy_only(seed: i32) -> Observation {
+        let settings = GameSettings {
+            seed,
+            legacy_rng: true,
+            ..Default::default()
+        };
+        for days_played in 1..100 {
+            let day = DayForecast::generate(&GameSettings {
+                days_played,
+                ..settings
+            });
+            for (level, floor) in day.loot.iter().enumerate() {
+                for goodie in floor.iter().flat_map(|(_, _, loot)| loot) {
+                    let obs = Observation {
+                        days_played,
+                        level: level as i32,
+                        goodie: goodie.clone(),
+                    };
+                    let hashed = GameSettings {
+                        legacy_rng: false,
+                        ..settings
+                    };
+                    if goodie.as_chest().is_some() && !obs.matches(&hashed) {
+                        return obs;
+                    }
+                }
+            }
+        }
+        panic!("no chest only the legacy game {} has", seed);
+    }
+
+    #[test]
+    fn detects_legacy() {
+        let settings = GameSettings::default();
+        let obs = legacy_only(3);
+        assert_eq!(
+            matching_modes(std::slice::from_ref(&obs), 3, &settings).unwrap(),
+            [RandomizationMode::Legacy]
+        );
+        assert_eq!(
+            randomization_mode(std::slice::from_ref(&obs), 3, &settings).unwrap(),
+            Some(RandomizationMode::Legacy)
+        );
+        let settings = GameSettings {
+            seed: 3,
+            ..settings
+        };
+        assert!(
+            RandomizationMode::Auto(vec![obs])
+                .resolve(&settings)
+                .unwrap()
+        );
+        // no observations, anything goes
+        assert!(matches!(
+            RandomizationMode::Auto(vec![]).resolve(&settings),
+            Err(Error::AmbiguousObservation)
+        ));
+    }
+
+    #[test]
+    fn issues_have_the_observation_index() {
+        let settings = GameSettings {
+            golden_coconut_in_common: false,
+            ..Default::default()
+        };
+        let observations = [
+            legacy_only(3),
+            Observation {
+                days_played: 1,
+                level: 1,
+                goodie: Goodie::CommonChest(ChestItem::GoldenCoconut),
+            },
+            Observation {
+                days_played: 1,
+                level: 0,
+                goodie: Goodie::CommonChest(ChestItem::TaroTuber),
+            },
+        ];
+        assert_eq!(
+            validate_observations(&observations, &settings),
+            [
+                ObservationIssue::NotInTable {
+                    index: 1,
+                    kind: ChestItemKind::GoldenCoconut,
+                    rarity: Rarity::Common,
+                },
+                ObservationIssue::NoChests { index: 2, level: 0 },
+            ]
+        );
+        let Err(Error::InconsistentObservations(issues)) =
+            matching_modes(&observations, 3, &settings)
+        else {
+            panic!("the observations are inconsistent");
+        };
+        assert_eq!(issues, validate_observations(&observations, &settings));
+        assert!(matches!(
+            RandomizationMode::Auto(observations.to_vec()).resolve(&settings),
+            Err(Error::InconsistentObservations(_))
+        ));
+    }
+
+    #[test]
+    fn ambiguous_observation() {
+        let settings = GameSettings {
+            seed: 3,
+            ..Default::default()
+        };
+        // the same item on the same floor of the same day in both
+        let both = |days_played| {
+            let [legacy, hashed] = [true, false].map(|legacy_rng| {
+                DayForecast::generate(&GameSettings {
+                    legacy_rng,
+                    days_played,
+                    ..settings
+                })
+            });
+            for level in 0..10 {
+                for goodie in legacy.loot[level].iter().flat_map(|(_, _, loot)| loot) {
+                    let in_hashed = hashed.loot[level]
+                        .iter()
+                        .any(|(_, _, l)| l.contains(goodie));
+                    if goodie.as_chest().is_some() && in_hashed {
+                        return Some(Observation {
+                            days_played,
+                            level: level as i32,
+                            goodie: goodie.clone(),
+                        });
+                    }
+                }
+            }
+            None
+        };
+        let obs = (1..200).find_map(both).expect("a chest both modes have");
+        let observations = std::slice::from_ref(&obs);
+        assert_eq!(
+            matching_modes(observations, 3, &settings).unwrap(),
+            [RandomizationMode::Legacy, RandomizationMode::Hashed]
+        );
+        assert_eq!(
+            randomization_mode(observations, 3, &settings).unwrap(),
+            None
+        );
+        assert!(matches!(
+            RandomizationMode::Auto(vec![obs]).resolve(&settings),
+            Err(Error::AmbiguousObservation)
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::detect::ObservationIssue;
+use crate::forecast::DayForecast;
+use crate::loot::{ChestItemKind, Enchant, Rarity};
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Icons referenced by the rendered output that weren't found in the icon directory
+    MissingIcons(Vec<String>),
+    /// Icon that isn't a png that can be read, with its name
+    InvalidIcon(String, String),
+    Io(std::io::Error),
+    /// Date string that couldn't be parsed
+    InvalidDate(String),
+    UnknownSeason(String),
+    /// Day of the month outside of 1-28
+    DayOutOfRange(u32),
+    UnknownEnchantCode(String),
+    UnknownItemCode(String),
+    /// Item name that doesn't match exactly one item, with the closest ones first
+    UnknownItemName {
+        name: String,
+        suggestions: Vec<ChestItemKind>,
+    },
+    /// Enchant level that the game can never roll
+    EnchantLevelOutOfRange(Enchant, i32),
+    /// The observation can't happen with any of the settings that were tried
+    ObservationMismatch,
+    /// Observations that can't happen with the settings at all, whatever the seed
+    InconsistentObservations(Vec<ObservationIssue>),
+    /// The observation happens with several of the settings that were tried
+    AmbiguousObservation,
+    /// Luck range with NaN, min above max, or nothing but infinity in it
+    InvalidLuck {
+        min: f64,
+        max: f64,
+    },
+    /// Every slot of the loot table is disallowed by the settings
+    EmptyLootTable(Rarity),
+    /// Chest generation on a level that never has chests
+    NoChestOnFloor(i32),
+    /// Level that isn't a volcano floor, see FloorLevel
+    InvalidLevel(i32),
+    /// Syntax error in a loot table file, with the line number
+    InvalidLootTable(usize, String),
+    /// Game id too large for the seed calculations
+    UnsupportedGameId(u64),
+    /// None of this many seeds meets the showcase criteria
+    NoShowcaseSeed(usize),
+    /// Search didn't find enough hits in the days it looks at, with the number it found
+    SearchCapReached(usize),
+    /// Text that isn't json, with where it goes wrong
+    InvalidJson(String),
+    /// Snapshot or checkpoint file that's truncated, has a bad index or can't be decoded
+    CorruptSnapshot(String),
+    /// Snapshot of days with different settings, with the first day that's not like the others
+    MixedSnapshotSettings(u32),
+    /// Something with more entries (or deeper nesting) than its field in a snapshot, checkpoint
+    /// or report token can hold, with what it was
+    TooLargeToEncode(String),
+    /// Game version that the generation isn't modeled for
+    UnsupportedGameVersion(String),
+    /// Output would be longer than the limit, `emitted` counts the write that went over
+    OutputTooLarge {
+        limit: usize,
+        emitted: usize,
+    },
+    /// Range of more days than the limit, or that goes past the last day a u32 can count. `max`
+    /// is the most days that would have been allowed from `start`.
+    RangeTooLarge {
+        start: u32,
+        days: u32,
+        max: u32,
+    },
+    /// Chest seed components pointing at a chest the floor doesn't have
+    NoSuchChest {
+        level: i32,
+        index: i32,
+    },
+    /// Search checkpoint made for a different query
+    CheckpointQueryMismatch,
+    /// Search checkpoint made with different settings
+    CheckpointSettingsMismatch,
+    /// Year index with other settings than the rest of a RangeIndex, or a year that's there twice
+    IndexSettingsMismatch(u32),
+    /// Flat goodie rows that don't make a goodie
+    InvalidFlatGoodie(String),
+    /// zstd data that doesn't decompress, `offset` is where it starts in the file
+    CorruptFrame {
+        offset: u64,
+        msg: String,
+    },
+    /// Compressed file, but the crate was built without the compression feature
+    CompressionUnavailable,
+    /// Report token that isn't base64 or can't be decoded
+    InvalidReport(String),
+    /// Report token from a newer (or unknown) version of the format
+    UnsupportedReportVersion(u8),
+    /// More combinations of unknown settings than an enumeration goes through
+    TooManyAssumptions {
+        count: usize,
+        max: usize,
+    },
+    /// More luck sources than plan::guaranteed_by tries the combinations of
+    TooManyLuckSources {
+        count: usize,
+        max: usize,
+    },
+    /// Quantity multiplier outside of 1-10, see QuantityModifiers
+    InvalidQuantityMultiplier(u32),
+    /// A run of audit::determinism_check with a different result than the first. The Debug
+    /// outputs differ from `offset` on, the excerpts start there.
+    Nondeterministic {
+        run: usize,
+        offset: usize,
+        expected: String,
+        got: String,
+    },
+    /// A line of generated output that's different from the fixture it's checked against
+    FixtureMismatch {
+        line: usize,
+        expected: String,
+        got: String,
+    },
+    /// Generation stopped by its cancel flag, with the days that were done
+    Cancelled {
+        partial: Vec<DayForecast>,
+    },
+    /// Save file that isn't xml, or doesn't look like a save
+    InvalidSave(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::MissingIcons(icons) => write!(f, "missing icons: {}", icons.join(", ")),
+            Error::InvalidIcon(name, msg) => write!(f, "icon {}: {}", name, msg),
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::InvalidDate(s) => write!(f, "invalid date: {:?}", s),
+            Error::UnknownSeason(s) => write!(f, "unknown season: {:?}", s),
+            Error::DayOutOfRange(d) => write!(f, "day {} is not between 1 and 28", d),
+            Error::UnknownEnchantCode(s) => write!(f, "unknown enchant code: {:?}", s),
+            Error::UnknownItemCode(s) => write!(f, "unknown item code: {:?}", s),
+            Error::UnknownItemName { name, suggestions } => {
+                write!(f, "unknown item name: {:?}", name)?;
+                let names: Vec<String> = suggestions
+                    .iter()
+                    .map(|k| match k.quantity() {
+                        1 => k.name().to_string(),
+                        n => format!("{} ({})", k.name(), n),
+                    })
+                    .collect();
+                match names.len() {
+                    0 => Ok(()),
+                    1 => write!(f, ", did you mean {}?", names[0]),
+                    _ => write!(f, ", did you mean one of: {}", names.join(", ")),
+                }
+            }
+            Error::EnchantLevelOutOfRange(e, lvl) => {
+                write!(f, "invalid level {} for enchant {:?}", lvl, e)
+            }
+            Error::ObservationMismatch => write!(f, "observation doesn't match any settings"),
+            Error::InconsistentObservations(issues) => {
+                write!(f, "impossible observations: ")?;
+                for (i, issue) in issues.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", issue)?;
+                }
+                Ok(())
+            }
+            Error::AmbiguousObservation => write!(f, "observation matches multiple settings"),
+            Error::InvalidLuck { min, max } => write!(f, "invalid luck range {} to {}", min, max),
+            Error::EmptyLootTable(r) => write!(f, "{:?} loot table has no rollable slots", r),
+            Error::NoChestOnFloor(level) => write!(f, "level {} doesn't have chests", level),
+            Error::InvalidLevel(level) => write!(f, "level {} isn't a volcano floor (0-9)", level),
+            Error::InvalidLootTable(line, msg) => write!(f, "loot tables line {}: {}", line, msg),
+            Error::NoShowcaseSeed(tried) => {
+                write!(f, "none of {} seeds meets the criteria", tried)
+            }
+            Error::UnsupportedGameId(id) => write!(f, "game id {} is too large", id),
+            Error::SearchCapReached(found) => {
+                write!(f, "only found {} within the search limit", found)
+            }
+            Error::InvalidJson(msg) => write!(f, "invalid json: {}", msg),
+            Error::CorruptSnapshot(msg) => write!(f, "corrupt snapshot: {}", msg),
+            Error::MixedSnapshotSettings(day) => {
+                write!(
+                    f,
+                    "day {} has other settings than the rest of the snapshot",
+                    day
+                )
+            }
+            Error::TooLargeToEncode(what) => write!(f, "too large to encode: {}", what),
+            Error::UnsupportedGameVersion(v) => write!(f, "unsupported game version: {:?}", v),
+            Error::OutputTooLarge { limit, emitted } => {
+                write!(f, "output over the limit of {} bytes ({})", limit, emitted)
+            }
+            Error::RangeTooLarge { start, days, max } => write!(
+                f,
+                "{} days from day {} requested, at most {} allowed",
+                days, start, max
+            ),
+            Error::NoSuchChest { level, index } => {
+                write!(f, "level {} has no chest number {}", level, index)
+            }
+            Error::CheckpointQueryMismatch => write!(f, "checkpoint is for a different query"),
+            Error::IndexSettingsMismatch(year) => {
+                write!(f, "year index of year {} doesn't go with the others", year)
+            }
+            Error::CheckpointSettingsMismatch => {
+                write!(f, "checkpoint is for different settings")
+            }
+            Error::InvalidFlatGoodie(msg) => write!(f, "invalid goodie rows: {}", msg),
+            Error::CorruptFrame { offset, msg } => {
+                write!(f, "corrupt compressed data at byte {}: {}", offset, msg)
+            }
+            Error::CompressionUnavailable => {
+                write!(f, "compressed file, built without the compression feature")
+            }
+            Error::InvalidReport(msg) => write!(f, "invalid report token: {}", msg),
+            Error::UnsupportedReportVersion(v) => {
+                write!(f, "report token version {} is not supported", v)
+            }
+            Error::TooManyLuckSources { count, max } => {
+                write!(f, "{} luck sources, at most {} allowed", count, max)
+            }
+            Error::TooManyAssumptions { count, max } => {
+                write!(
+                    f,
+                    "{} settings combinations, at most {} allowed",
+                    count, max
+                )
+            }
+            Error::InvalidQuantityMultiplier(m) => {
+                write!(f, "quantity multiplier {} isn't between 1 and 10", m)
+            }
+            Error::Nondeterministic {
+                run,
+                offset,
+                expected,
+                got,
+            } => write!(
+                f,
+                "run {} differs from the first at {}: {:?} instead of {:?}",
+                run, offset, got, expected
+            ),
+            Error::FixtureMismatch {
+                line,
+                expected,
+                got,
+            } => write!(
+                f,
+                "fixture line {}: {:?} instead of {:?}",
+                line, got, expected
+            ),
+            Error::Cancelled { partial } => {
+                write!(f, "cancelled after {} days", partial.len())
+            }
+            Error::InvalidSave(msg) => write!(f, "invalid save file: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,169 @@
+//! Example outcomes of a chest whose rarity depends on the luck, for showing "this could be" items
+//! without the first one looking like the prediction. The examples are drawn from the chest's
+//! actual outcomes by probability, with a seed from the caller so the same page always shows the
+//! same ones.
+
+use crate::date::DayNumber;
+use crate::loot::{ChestItem, Decisions, FloorLevel, Rarity};
+use crate::luck::LuckModel;
+use crate::seeds::SeedSpec;
+use crate::{GameSettings, Result, rng};
+
+/// An item the chest can give and the chance it does
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outcome {
+    pub item: ChestItem,
+    pub probability: f64,
+}
+
+/// Everything the chest with `chest_seed` on `level` can give, with probabilities from
+/// `luck` on the settings' day. That's the common and the rare item, outcomes with no chance are
+/// left out. Seed components pick the layout with the lowest luck of `luck`.
+pub fn chest_outcomes_at(
+    chest_seed: impl Into<SeedSpec>,
+    settings: &GameSettings,
+    level: FloorLevel,
+    luck: &dyn LuckModel,
+) -> Result<Vec<Outcome>> {
+    let luck = luck.distribution(DayNumber(settings.days_played));
+    let chest_seed = chest_seed.into().resolve(settings, luck.min())?;
+    let rare = luck.probability(
+        Decisions::for_chest_at(chest_seed, settings, level)?.rare_luck(),
+        f64::INFINITY,
+    );
+    let mut out: Vec<Outcome> = vec![];
+    for (rarity, probability) in [(Rarity::Common, 1. - rare), (Rarity::Rare, rare)] {
+        if probability <= 0. {
+            continue;
+        }
+        let item = ChestItem::generate(rarity, chest_seed, settings);
+        match out.iter_mut().find(|o| o.item == item) {
+            Some(o) => o.probability += probability,
+            None => out.push(Outcome { item, probability }),
+        }
+    }
+    Ok(out)
+}
+
+#[deprecated(note = "use chest_outcomes_at, it takes a FloorLevel")]
+pub fn chest_outcomes(
+    chest_seed: impl Into<SeedSpec>,
+    settings: &GameSettings,
+    level: i32,
+    luck: &dyn LuckModel,
+) -> Result<Vec<Outcome>> {
+    chest_outcomes_at(chest_seed, settings, level.try_into()?, luck)
+}
+
+/// `n` outcomes of the chest drawn by probability, with repeats. The same `presentation_seed`
+/// gives the same examples, the loot itself isn't affected by it.
+pub fn sample_outcomes_at(
+    chest_seed: impl Into<SeedSpec>,
+    settings: &GameSettings,
+    level: FloorLevel,
+    luck: &dyn LuckModel,
+    n: usize,
+    presentation_seed: i32,
+) -> Result<Vec<Outcome>> {
+    let outcomes = chest_outcomes_at(chest_seed, settings, level, luck)?;
+    let total: f64 = outcomes.iter().map(|o| o.probability).sum();
+    let mut rng = rng::DotnetRng::new(presentation_seed);
+    Ok((0..n)
+        .filter_map(|_| {
+            let mut roll = rng.next_f64() * total;
+            let picked = outcomes.iter().find(|o| {
+                roll -= o.probability;
+                roll < 0.
+            });
+            // rounding can leave a tiny bit of roll for the end
+            picked.or(outcomes.last()).cloned()
+        })
+        .collect())
+}
+
+#[deprecated(note = "use sample_outcomes_at, it takes a FloorLevel")]
+pub fn sample_outcomes(
+    chest_seed: impl Into<SeedSpec>,
+    settings: &GameSettings,
+    level: i32,
+    luck: &dyn LuckModel,
+    n: usize,
+    presentation_seed: i32,
+) -> Result<Vec<Outcome>> {
+    sample_outcomes_at(
+        chest_seed,
+        settings,
+        level.try_into()?,
+        luck,
+        n,
+        presentation_seed,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loot::Goodie;
+    use crate::luck::{LuckDistribution, luck_range};
+
+    /// a floor 1 chest that's rare for part of the luck range
+    fn chance_chest(settings: &GameSettings, luck: &LuckDistribution) -> i32 {
+        let (lo, hi) = luck.boost_range(DayNumber(1));
+        (0..)
+            .find(|&seed| {
+                let goodie =
+                    Goodie::generate_at(seed, settings, FloorLevel::ALL[1], lo, hi).unwrap();
+                matches!(goodie, Goodie::ChanceChest { common, rare, .. } if common != rare)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn samples_follow_the_probabilities() {
+        let settings = GameSettings::default();
+        let (lo, hi) = luck_range(&settings);
+        let luck = LuckDistribution::Uniform(lo, hi);
+        let seed = chance_chest(&settings, &luck);
+        let outcomes = chest_outcomes_at(seed, &settings, FloorLevel::ALL[1], &luck).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        let total: f64 = outcomes.iter().map(|o| o.probability).sum();
+        assert!((total - 1.).abs() < 1e-9);
+
+        let sample = |n, presentation_seed| {
+            sample_outcomes_at(
+                seed,
+                &settings,
+                FloorLevel::ALL[1],
+                &luck,
+                n,
+                presentation_seed,
+            )
+            .unwrap()
+        };
+        assert_eq!(sample(3, 42), sample(3, 42));
+        assert_eq!(sample(3, 42)[..], sample(10, 42)[..3]);
+        let draws = sample(10_000, 7);
+        for outcome in &outcomes {
+            let share = draws.iter().filter(|o| o.item == outcome.item).count() as f64 / 1e4;
+            assert!(
+                (share - outcome.probability).abs() < 0.02,
+                "{:?}: {} vs {}",
+                outcome.item,
+                share,
+                outcome.probability
+            );
+        }
+        assert!(draws.iter().all(|d| outcomes.contains(d)));
+    }
+
+    #[test]
+    fn fixed_rarity_has_one_outcome() {
+        let settings = GameSettings::default();
+        let luck = LuckDistribution::Fixed(1.);
+        let outcomes = chest_outcomes_at(1234, &settings, FloorLevel::ALL[2], &luck).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].probability, 1.);
+        let draws = sample_outcomes_at(1234, &settings, FloorLevel::ALL[2], &luck, 5, 1).unwrap();
+        assert_eq!(draws, vec![outcomes[0].clone(); 5]);
+    }
+}
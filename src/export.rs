@@ -0,0 +1,556 @@
+use crate::date::{DayNumber, GameDate};
+use crate::flat::{self, FlatGoodie};
+use crate::forecast::DayForecast;
+use crate::limit::LimitedWriter;
+use crate::loot::{Goodie, Rarity};
+use crate::stats::ValueModel;
+use crate::{Error, Result, base64, display_luck, html, json};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+// subset of the stylesheet in index.html that's relevant for the loot overview
+const STANDALONE_CSS: &str = "body { font-family: sans-serif; font-size: 16px; }
+.day ul { list-style: none; padding-left: 30px; margin: 1px; }
+.day > div { border-top: 1px solid black; }
+h3 { margin-bottom: 0.5em; }";
+
+const ICON_PREFIX: &str = "src=\"icons/";
+const ICON_SUFFIX: &str = ".png\"";
+
+/// Renders the forecast into a single html document that doesn't depend on any other files, with
+/// all icons read from `icon_dir` and embedded as data URIs.
+pub fn to_standalone_html(forecast: &[DayForecast], icon_dir: &Path) -> Result<String> {
+    let body = html::render_forecast(forecast);
+
+    // first pass: find and load all icons that are used
+    let mut icons = BTreeMap::new();
+    let mut missing = vec![];
+    for (start, _) in body.match_indices(ICON_PREFIX) {
+        let rest = &body[start + ICON_PREFIX.len()..];
+        let Some(end) = rest.find(ICON_SUFFIX) else {
+            continue;
+        };
+        let name = &rest[..end];
+        if icons.contains_key(name) || missing.iter().any(|x| x == name) {
+            continue;
+        }
+        match std::fs::read(icon_dir.join(format!("{}.png", name))) {
+            Ok(data) => {
+                icons.insert(
+                    name,
+                    format!("src=\"data:image/png;base64,{}\"", base64::encode(&data)),
+                );
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => missing.push(name.to_string()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(Error::MissingIcons(missing));
+    }
+
+    // second pass: replace the references
+    let mut out = String::from("<!doctype html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n");
+    out += "<title>SDV volcano forecast</title>\n<style>\n";
+    out += STANDALONE_CSS;
+    out += "\n</style>\n</head>\n<body>\n";
+    let mut rest = body.as_str();
+    while let Some(start) = rest.find(ICON_PREFIX) {
+        let after = &rest[start + ICON_PREFIX.len()..];
+        let Some(end) = after.find(ICON_SUFFIX) else {
+            break;
+        };
+        out += &rest[..start];
+        out += &icons[&after[..end]];
+        rest = &after[end + ICON_SUFFIX.len()..];
+    }
+    out += rest;
+    out += "</body>\n</html>\n";
+    Ok(out)
+}
+
+/// `json` in a form that's byte for byte the same for the same data: keys sorted, no
+/// whitespace, numbers written one way. For consumers that compare the json as strings, e.g. for
+/// caching. InvalidJson if it doesn't parse.
+pub fn to_canonical_json(json: &str) -> Result<String> {
+    let value = json::parse(json).map_err(Error::InvalidJson)?;
+    let mut out = String::with_capacity(json.len());
+    json::write_canonical(&mut out, &value);
+    Ok(out)
+}
+
+fn predictor_item(
+    out: &mut dyn Write,
+    chest: Option<&str>,
+    name: &str,
+    quantity: u32,
+    enchants: &[String],
+) -> std::fmt::Result {
+    let chest = chest.map(json::quote).unwrap_or_else(|| "null".to_string());
+    let enchants: Vec<String> = enchants.iter().map(|x| json::quote(x)).collect();
+    write!(
+        out,
+        "{{\"name\":{},\"quantity\":{},\"chest\":{},\"enchantments\":[{}]}}",
+        json::quote(name),
+        quantity,
+        chest,
+        enchants.join(",")
+    )
+}
+
+/// Json in the loose structure the Stardew Predictor community uses for island loot:
+/// `{"days": {"<days_played>": {"date": ..., "floors": {"<level>": [{"luck": [min, max],
+/// "items": [{"name", "quantity", "chest", "enchantments"}]}]}}}}`. Names are the English wiki
+/// names without quantities, enchantments are formatted like "+2 Speed". Luck is shown the same
+/// way as in the html overview.
+pub fn to_predictor_json(forecast: &[DayForecast]) -> String {
+    let mut out = String::new();
+    write_predictor_json(&mut out, forecast).unwrap();
+    out
+}
+
+/// to_predictor_json, but fails with OutputTooLarge instead of going over `max_bytes`
+pub fn to_predictor_json_limited(forecast: &[DayForecast], max_bytes: usize) -> Result<String> {
+    let mut out = LimitedWriter::new(String::new(), max_bytes);
+    let written = write_predictor_json(&mut out, forecast);
+    out.check()?;
+    written.expect("writing to a string doesn't fail");
+    Ok(out.into_inner())
+}
+
+fn write_predictor_json(out: &mut dyn Write, forecast: &[DayForecast]) -> std::fmt::Result {
+    out.write_str("{\"days\":{")?;
+    for (i, day) in forecast.iter().enumerate() {
+        if i != 0 {
+            out.write_str(",")?;
+        }
+        write!(
+            out,
+            "\"{}\":{{\"date\":{},\"floors\":{{",
+            day.days_played,
+            json::quote(&html::day_name(day.days_played))
+        )?;
+        let mut first_floor = true;
+        for (level, floor) in day.loot.iter().enumerate() {
+            if floor.iter().all(|x| x.2.is_empty()) {
+                continue;
+            }
+            if !first_floor {
+                out.write_str(",")?;
+            }
+            first_floor = false;
+            write!(out, "\"{}\":[", level)?;
+            for (j, (minluck, maxluck, loot)) in floor.iter().enumerate() {
+                if j != 0 {
+                    out.write_str(",")?;
+                }
+                write!(
+                    out,
+                    "{{\"luck\":[{},{}],\"items\":[",
+                    json::number(display_luck(*minluck)),
+                    json::number(display_luck(*maxluck))
+                )?;
+                let teeth = loot.iter().filter(|x| **x == Goodie::DragonTooth).count();
+                let mut first_item = true;
+                if teeth > 0 {
+                    predictor_item(out, None, "Dragon Tooth", teeth as u32, &[])?;
+                    first_item = false;
+                }
+                for goodie in loot {
+                    let (chest, item) = match goodie {
+                        Goodie::CommonChest(c) => ("common", c),
+                        Goodie::RareChest(c) => ("rare", c),
+                        _ => continue,
+                    };
+                    if !first_item {
+                        out.write_str(",")?;
+                    }
+                    first_item = false;
+                    let enchants: Vec<String> = item
+                        .enchants()
+                        .map(|e| e.as_slice())
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|&(e, lvl)| {
+                            if lvl == 0 {
+                                e.name().to_string()
+                            } else {
+                                format!("{:+} {}", lvl, e.name())
+                            }
+                        })
+                        .collect();
+                    let kind = item.kind();
+                    let quantity = item.quantity(&day.settings.quantities);
+                    predictor_item(out, Some(chest), kind.name(), quantity, &enchants)?;
+                }
+                out.write_str("]}")?;
+            }
+            out.write_str("]")?;
+        }
+        out.write_str("}}")?;
+    }
+    out.write_str("}}")
+}
+
+/// (name, whether json quotes it)
+const FLAT_COLUMNS: [(&str, bool); 15] = [
+    ("day", false),
+    ("floor", false),
+    ("chest_index", false),
+    ("group_id", false),
+    ("rarity", true),
+    ("item", true),
+    ("quantity", false),
+    ("enchant1", true),
+    ("enchant1_level", false),
+    ("enchant2", true),
+    ("enchant2_level", false),
+    ("min_luck", false),
+    ("max_luck", false),
+    ("minluck", false),
+    ("rare_probability", false),
+];
+
+/// the columns of a row as strings, None for empty ones. luck stays luckMult.
+fn flat_values(row: &FlatGoodie) -> [Option<String>; 15] {
+    let num = |x: f64| Some(json::number(x));
+    [
+        Some(row.day.to_string()),
+        Some(row.floor.to_string()),
+        row.chest_index.map(|i| i.to_string()),
+        row.group_id.map(|g| g.to_string()),
+        row.rarity.map(|r| match r {
+            Rarity::Common => "common".to_string(),
+            Rarity::Rare => "rare".to_string(),
+        }),
+        Some(
+            row.item_kind
+                .map_or("Dragon Tooth", |k| k.name())
+                .to_string(),
+        ),
+        Some(row.quantity.to_string()),
+        row.enchant1_kind.map(|e| e.name().to_string()),
+        row.enchant1_kind.map(|_| row.enchant1_level.to_string()),
+        row.enchant2_kind.map(|e| e.name().to_string()),
+        row.enchant2_kind.map(|_| row.enchant2_level.to_string()),
+        num(row.min_luck),
+        num(row.max_luck),
+        row.minluck.and_then(num),
+        num(row.rare_probability),
+    ]
+}
+
+/// One line per flat::FlatGoodie row with a header, empty cells for missing values. None of the
+/// names have commas or quotes, so nothing is quoted.
+pub fn to_csv(forecast: &[DayForecast]) -> String {
+    let names: Vec<&str> = FLAT_COLUMNS.iter().map(|c| c.0).collect();
+    let mut out = names.join(",");
+    out.push('\n');
+    for row in flat::flatten(forecast) {
+        let values = flat_values(&row).map(Option::unwrap_or_default);
+        out += &values.join(",");
+        out.push('\n');
+    }
+    out
+}
+
+/// One json object per flat::FlatGoodie row and line, with the same keys as the csv columns and
+/// null for missing values
+pub fn to_jsonl(forecast: &[DayForecast]) -> String {
+    let mut out = String::new();
+    for row in flat::flatten(forecast) {
+        let fields: Vec<String> = FLAT_COLUMNS
+            .iter()
+            .zip(flat_values(&row))
+            .map(|(&(key, quoted), value)| {
+                let value = match value {
+                    None => "null".to_string(),
+                    Some(v) if quoted => json::quote(&v),
+                    Some(v) => v,
+                };
+                format!("{}:{}", json::quote(key), value)
+            })
+            .collect();
+        writeln!(out, "{{{}}}", fields.join(",")).unwrap();
+    }
+    out
+}
+
+/// ValueModel::expected_value of every day, in the forecast's order, for charts
+pub fn value_series(forecast: &[DayForecast], model: &ValueModel) -> Vec<(DayNumber, f64)> {
+    forecast
+        .iter()
+        .map(|day| (DayNumber(day.days_played), model.expected_value(day)))
+        .collect()
+}
+
+/// The series as an svg line chart of `width` by `height` pixels, with a dot per day that links
+/// to `#day-<days_played>` (see RenderOptions::day_anchors). Values go from 0 at the bottom to the
+/// highest one at the top, an all zero series is a line along the bottom.
+pub fn value_sparkline(series: &[(DayNumber, f64)], width: u32, height: u32) -> String {
+    const R: f64 = 2.;
+    let (w, h) = (width as f64, height as f64);
+    // keeps the dots inside, unless the chart is smaller than them
+    let (mx, my) = (R.min(w / 2.), R.min(h / 2.));
+    let value = |v: f64| if v.is_finite() { v.max(0.) } else { 0. };
+    let max = series.iter().map(|p| value(p.1)).fold(0., f64::max);
+    let points: Vec<(f64, f64)> = series
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let x = match series.len() {
+                1 => w / 2.,
+                n => mx + (w - 2. * mx) * i as f64 / (n - 1) as f64,
+            };
+            let scaled = if max > 0. { value(p.1) / max } else { 0. };
+            (x, h - my - (h - 2. * my) * scaled)
+        })
+        .collect();
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" class=\"sparkline\" width=\"{}\" height=\"{}\" \
+         viewBox=\"0 0 {} {}\">",
+        width, height, width, height
+    );
+    let line: Vec<String> = points
+        .iter()
+        .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+        .collect();
+    write!(
+        out,
+        "<polyline fill=\"none\" stroke=\"currentColor\" points=\"{}\"/>",
+        line.join(" ")
+    )
+    .unwrap();
+    for ((day, v), (x, y)) in series.iter().zip(&points) {
+        write!(
+            out,
+            "<a href=\"#day-{}\"><circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{}\"><title>{}: {:.1}</title></circle></a>",
+            day.0,
+            x,
+            y,
+            R,
+            GameDate::from(*day),
+            v
+        )
+        .unwrap();
+    }
+    out += "</svg>";
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::Value;
+    use crate::{GameSettings, forecast};
+
+    /// `value` as json with the keys of every object in reverse and some whitespace
+    fn shuffled(value: &Value) -> String {
+        match value {
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(shuffled).collect();
+                format!("[ {} ]", items.join(", "))
+            }
+            Value::Object(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .rev()
+                    .map(|(k, v)| format!("{}: {}", json::quote(k), shuffled(v)))
+                    .collect();
+                format!("{{ {} }}", fields.join(", "))
+            }
+            Value::Number(x) => format!("{:e}", x),
+            other => {
+                let mut out = String::new();
+                json::write_canonical(&mut out, other);
+                out
+            }
+        }
+    }
+
+    #[test]
+    fn canonical_json_is_stable() {
+        let settings = GameSettings {
+            seed: 2024,
+            ..Default::default()
+        };
+        let json = to_predictor_json(&forecast::range(&settings, 1, 10).unwrap());
+        let first = to_canonical_json(&json).unwrap();
+        let again = to_canonical_json(&to_predictor_json(
+            &forecast::range(&settings, 1, 10).unwrap(),
+        ));
+        assert_eq!(again.unwrap(), first);
+        let reordered = shuffled(&json::parse(&json).unwrap());
+        assert_ne!(reordered, json);
+        assert_eq!(to_canonical_json(&reordered).unwrap(), first);
+        assert!(matches!(
+            to_canonical_json("[1e999]"),
+            Err(Error::InvalidJson(_))
+        ));
+    }
+
+    fn month(seed: i32) -> Vec<DayForecast> {
+        let settings = GameSettings {
+            seed,
+            ..Default::default()
+        };
+        forecast::range(&settings, 1, 28).unwrap()
+    }
+
+    #[test]
+    fn standalone_html_embeds_the_icons() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let forecast = month(1);
+        let html = to_standalone_html(&forecast, &dir.join("fixtures/icons")).unwrap();
+        assert!(html.starts_with("<!doctype html>") && html.ends_with("</html>\n"));
+        let placeholder = std::fs::read(dir.join("fixtures/icons/rare_chest.png")).unwrap();
+        let uri = format!(
+            "src=\"data:image/png;base64,{}\"",
+            base64::encode(&placeholder)
+        );
+        let count = html.matches("<img ").count();
+        assert!(count > 0);
+        assert_eq!(html.matches(&uri).count(), count);
+        assert!(!html.contains(ICON_PREFIX));
+        assert_eq!(
+            html.matches("src=").count(),
+            html.matches("src=\"data:").count()
+        );
+        // with the real icons a month is still small enough to send around
+        let real = to_standalone_html(&forecast, &dir.join("icons")).unwrap();
+        assert!(real.len() < 300_000, "{} bytes", real.len());
+    }
+
+    #[test]
+    fn standalone_html_lists_missing_icons() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/no_icons");
+        let Err(Error::MissingIcons(missing)) = to_standalone_html(&month(1), &dir) else {
+            panic!("icons found in an empty directory");
+        };
+        // each one once
+        let mut sorted = missing.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), missing.len());
+        for icon in ["common_chest", "rare_chest", "dragon_tooth"] {
+            assert!(missing.iter().any(|m| m == icon), "{}", icon);
+        }
+    }
+
+    #[test]
+    fn value_sparkline_of_a_month() {
+        let forecast = month(7);
+        let model = ValueModel::default();
+        let series = value_series(&forecast, &model);
+        assert_eq!(series.len(), 28);
+        for ((day, value), forecast) in series.iter().zip(&forecast) {
+            assert_eq!(day.0, forecast.days_played);
+            assert_eq!(*value, model.expected_value(forecast));
+        }
+        assert!(series.iter().any(|p| p.1 > 0.));
+        let svg = value_sparkline(&series, 280, 40);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+        assert!(svg.contains("viewBox=\"0 0 280 40\""));
+        assert_eq!(svg.matches("<circle").count(), 28);
+        assert!(svg.contains("<a href=\"#day-28\">"));
+        let points = svg.split("points=\"").nth(1).unwrap();
+        let points: Vec<&str> = points[..points.find('"').unwrap()].split(' ').collect();
+        assert_eq!(points.len(), 28);
+        for point in points {
+            let (x, y) = point.split_once(',').unwrap();
+            let (x, y): (f64, f64) = (x.parse().unwrap(), y.parse().unwrap());
+            assert!(
+                (0. ..=280.).contains(&x) && (0. ..=40.).contains(&y),
+                "{}",
+                point
+            );
+        }
+        assert!(!svg.contains("NaN") && !svg.contains("inf"));
+    }
+
+    #[test]
+    fn all_zero_sparkline() {
+        let series: Vec<_> = (1..=5).map(|d| (DayNumber(d), 0.)).collect();
+        let svg = value_sparkline(&series, 100, 20);
+        assert!(!svg.contains("NaN"));
+        // all along the bottom
+        assert_eq!(svg.matches(",18.0").count(), 5);
+        assert_eq!(svg.matches("cy=\"18.0\"").count(), 5);
+        let one = value_sparkline(&[(DayNumber(3), f64::NAN)], 100, 20);
+        assert!(one.contains("points=\"50.0,18.0\""));
+        assert!(!value_sparkline(&[], 100, 20).contains("<circle"));
+    }
+
+    #[test]
+    fn limited_json() {
+        let forecast = month(3);
+        let full = to_predictor_json(&forecast);
+        assert_eq!(
+            to_predictor_json_limited(&forecast, full.len()).unwrap(),
+            full
+        );
+        let limited = to_predictor_json_limited(&forecast, full.len() - 1);
+        assert!(matches!(
+            limited,
+            Err(Error::OutputTooLarge { limit, emitted }) if limit == full.len() - 1 && emitted > limit
+        ));
+        assert!(matches!(
+            to_predictor_json_limited(&forecast, 0),
+            Err(Error::OutputTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn predictor_names_and_quantities() {
+        use crate::loot::{ChestItem, Enchant, Enchants};
+        let settings = GameSettings {
+            days_played: 2,
+            ..Default::default()
+        };
+        let cutlass = ChestItem::DragontoothCutlass(Enchants::from_levels(vec![
+            (Enchant::Attack, 3),
+            (Enchant::SlimeSlayer, 0),
+        ]));
+        let mut loot: [Vec<(f64, f64, Vec<Goodie>)>; 10] = Default::default();
+        loot[4] = vec![(
+            1.,
+            1.05,
+            vec![
+                Goodie::DragonTooth,
+                Goodie::CommonChest(ChestItem::CinderShards3),
+                Goodie::DragonTooth,
+                Goodie::RareChest(ChestItem::CinderShards10),
+                Goodie::RareChest(cutlass),
+            ],
+        )];
+        let day = DayForecast {
+            days_played: 2,
+            settings,
+            layouts: Default::default(),
+            loot,
+            calendar_events: vec![],
+        };
+        let item = |name: &str, quantity, chest: &str, enchants: &str| {
+            format!(
+                "{{\"name\":\"{}\",\"quantity\":{},\"chest\":{},\"enchantments\":[{}]}}",
+                name, quantity, chest, enchants
+            )
+        };
+        let expected = format!(
+            "{{\"days\":{{\"2\":{{\"date\":\"{}\",\"floors\":{{\"4\":[{{\"luck\":[0,{}],\"items\":[{},{},{},{}]}}]}}}}}}}}",
+            html::day_name(2),
+            json::number(display_luck(1.05)),
+            item("Dragon Tooth", 2, "null", ""),
+            item("Cinder Shard", 3, "\"common\"", ""),
+            item("Cinder Shard", 10, "\"rare\"", ""),
+            item(
+                "Dragontooth Cutlass",
+                1,
+                "\"rare\"",
+                "\"+3 Attack\",\"Slime Slayer\""
+            ),
+        );
+        assert_eq!(to_predictor_json(&[day]), expected);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::loot::Goodie;
+use std::ops::BitOr;
+
+/// Set of goodie categories, for only showing some of the loot
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GoodieFilter {
+    flags: u8,
+    /// count chance chests as RARE when their rare probability is above this
+    chance_rare_threshold: Option<f64>,
+}
+
+impl GoodieFilter {
+    pub const NONE: Self = Self::from_flags(0);
+    pub const COMMON: Self = Self::from_flags(1);
+    pub const RARE: Self = Self::from_flags(2);
+    pub const CHANCE: Self = Self::from_flags(4);
+    pub const TEETH: Self = Self::from_flags(8);
+    /// barrels, crates etc. none of these are generated yet
+    pub const CONTAINERS: Self = Self::from_flags(16);
+    pub const ALL: Self = Self::from_flags(31);
+
+    const fn from_flags(flags: u8) -> Self {
+        GoodieFilter {
+            flags,
+            chance_rare_threshold: None,
+        }
+    }
+
+    /// Number for storing the categories, e.g. in telemetry: one bit per category, the bits of
+    /// the constants above. Bits are never renumbered or reused. The chance_as_rare_above
+    /// threshold isn't part of it.
+    pub const fn stable_id(self) -> u16 {
+        self.flags as u16
+    }
+
+    /// the categories of a stable_id, None if it has bits that aren't categories
+    pub const fn from_stable_id(id: u16) -> Option<Self> {
+        if id & !(Self::ALL.flags as u16) != 0 {
+            return None;
+        }
+        Some(Self::from_flags(id as u8))
+    }
+
+    pub fn contains(self, other: GoodieFilter) -> bool {
+        self.flags & other.flags == other.flags
+    }
+
+    pub fn chance_as_rare_above(self, threshold: f64) -> Self {
+        GoodieFilter {
+            chance_rare_threshold: Some(threshold),
+            ..self
+        }
+    }
+
+    /// whether the goodie passes, for a player whose luck is somewhere in [min_luck, max_luck]
+    pub fn matches(self, goodie: &Goodie, min_luck: f64, max_luck: f64) -> bool {
+        match goodie {
+            Goodie::DragonTooth => self.contains(Self::TEETH),
+            Goodie::CommonChest(_) => self.contains(Self::COMMON),
+            Goodie::RareChest(_) => self.contains(Self::RARE),
+            Goodie::ChanceChest { .. } => {
+                self.contains(Self::CHANCE)
+                    || (self.contains(Self::RARE)
+                        && self.chance_rare_threshold.is_some_and(|threshold| {
+                            goodie.rare_probability(min_luck, max_luck) > threshold
+                        }))
+            }
+            Goodie::Ambiguous(alts) => alts
+                .iter()
+                .any(|(_, g)| self.matches(g, min_luck, max_luck)),
+        }
+    }
+}
+
+// the stable ids of the categories
+const _: () = {
+    assert!(GoodieFilter::COMMON.stable_id() == 1);
+    assert!(GoodieFilter::RARE.stable_id() == 2);
+    assert!(GoodieFilter::CHANCE.stable_id() == 4);
+    assert!(GoodieFilter::TEETH.stable_id() == 8);
+    assert!(GoodieFilter::CONTAINERS.stable_id() == 16);
+    assert!(GoodieFilter::from_stable_id(32).is_none());
+};
+
+impl Default for GoodieFilter {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl BitOr for GoodieFilter {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        GoodieFilter {
+            flags: self.flags | rhs.flags,
+            chance_rare_threshold: self.chance_rare_threshold.or(rhs.chance_rare_threshold),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loot::ChestItem;
+    use crate::partial::SettingsAssumption;
+
+    #[test]
+    fn categories() {
+        let chance = Goodie::ChanceChest {
+            minluck: 0.,
+            common: ChestItem::TaroTuber,
+            rare: ChestItem::PhoenixRing,
+        };
+        let goodies = [
+            (Goodie::DragonTooth, GoodieFilter::TEETH),
+            (
+                Goodie::CommonChest(ChestItem::TaroTuber),
+                GoodieFilter::COMMON,
+            ),
+            (
+                Goodie::RareChest(ChestItem::PhoenixRing),
+                GoodieFilter::RARE,
+            ),
+            (chance.clone(), GoodieFilter::CHANCE),
+        ];
+        for (goodie, category) in &goodies {
+            for (_, other) in &goodies {
+                assert_eq!(
+                    other.matches(goodie, -1., 1.),
+                    other == category,
+                    "{:?} {:?}",
+                    goodie,
+                    other
+                );
+            }
+            assert!(GoodieFilter::ALL.matches(goodie, -1., 1.));
+            assert!(!GoodieFilter::NONE.matches(goodie, -1., 1.));
+        }
+        let both = GoodieFilter::COMMON | GoodieFilter::TEETH;
+        assert!(both.contains(GoodieFilter::TEETH) && !both.contains(GoodieFilter::RARE));
+        assert!(GoodieFilter::ALL.contains(both));
+        // rare for half of [-1, 1]
+        let rare = GoodieFilter::RARE.chance_as_rare_above(0.4);
+        assert!(rare.matches(&chance, -1., 1.));
+        assert!(
+            !GoodieFilter::RARE
+                .chance_as_rare_above(0.6)
+                .matches(&chance, -1., 1.)
+        );
+        assert!(
+            !GoodieFilter::COMMON
+                .chance_as_rare_above(0.4)
+                .matches(&chance, -1., 1.)
+        );
+        // the threshold survives combining
+        assert!((GoodieFilter::TEETH | rare).matches(&chance, -1., 1.));
+    }
+
+    #[test]
+    fn ambiguous_goodies_match_any_alternative() {
+        let ambiguous = Goodie::Ambiguous(vec![
+            (
+                SettingsAssumption::default(),
+                Goodie::CommonChest(ChestItem::TaroTuber),
+            ),
+            (SettingsAssumption::default(), Goodie::DragonTooth),
+        ]);
+        assert!(GoodieFilter::COMMON.matches(&ambiguous, 0., 0.));
+        assert!(GoodieFilter::TEETH.matches(&ambiguous, 0., 0.));
+        assert!(!GoodieFilter::RARE.matches(&ambiguous, 0., 0.));
+    }
+
+    #[test]
+    fn pinned_stable_ids() {
+        let categories = [
+            (GoodieFilter::COMMON, 1),
+            (GoodieFilter::RARE, 2),
+            (GoodieFilter::CHANCE, 4),
+            (GoodieFilter::TEETH, 8),
+            (GoodieFilter::CONTAINERS, 16),
+            (GoodieFilter::ALL, 31),
+        ];
+        for (filter, id) in categories {
+            assert_eq!(filter.stable_id(), id);
+            assert_eq!(GoodieFilter::from_stable_id(id), Some(filter));
+        }
+        for id in 0..32 {
+            assert_eq!(GoodieFilter::from_stable_id(id).unwrap().stable_id(), id);
+        }
+        for id in [32, 33, 255, u16::MAX] {
+            assert!(GoodieFilter::from_stable_id(id).is_none());
+        }
+        // the threshold isn't part of it
+        let threshold = GoodieFilter::RARE.chance_as_rare_above(0.5);
+        assert_eq!(threshold.stable_id(), 2);
+    }
+}
@@ -0,0 +1,301 @@
+//! Goodie records as flat rows of plain fields, for csv, jsonl and dataframes. Every exporter of
+//! rows goes through this so they all flatten the same way.
+//!
+//! Going back to records loses: already_opened (false), the decisions (None), the raw enchant
+//! rolls, and the settings assumptions of Ambiguous goodies, which can't be turned back at all.
+
+use crate::forecast::{ChestId, DayForecast, DayState, GoodieRecord};
+use crate::loot::{ChestItem, ChestItemKind, Enchant, Enchants, Goodie, Rarity};
+use crate::{Error, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlatGoodie {
+    pub day: u32,
+    pub floor: u8,
+    /// ChestId::index, None for dragon teeth
+    pub chest_index: Option<u8>,
+    /// the rows of one chance chest (or ambiguous goodie) share this, None for the others
+    pub group_id: Option<u32>,
+    /// None for dragon teeth
+    pub rarity: Option<Rarity>,
+    /// None for dragon teeth
+    pub item_kind: Option<ChestItemKind>,
+    pub quantity: u32,
+    pub enchant1_kind: Option<Enchant>,
+    pub enchant1_level: i32,
+    pub enchant2_kind: Option<Enchant>,
+    pub enchant2_level: i32,
+    pub min_luck: f64,
+    pub max_luck: f64,
+    /// the luckMult above which a chance chest is rare, None for everything else
+    pub minluck: Option<f64>,
+    /// chance of the chest being rare with the luck uniform in [min_luck, max_luck]
+    pub rare_probability: f64,
+}
+
+impl FlatGoodie {
+    /// The rows for one record of day `days_played`: one, or two for chance chests (common
+    /// first). Ambiguous goodies get a row per alternative that isn't ambiguous itself.
+    pub fn rows(days_played: u32, record: &GoodieRecord, group_id: u32) -> Vec<FlatGoodie> {
+        let base = FlatGoodie {
+            day: days_played,
+            floor: record.level,
+            chest_index: record.chest.map(|c| c.index),
+            group_id: None,
+            rarity: None,
+            item_kind: None,
+            quantity: 1,
+            enchant1_kind: None,
+            enchant1_level: 0,
+            enchant2_kind: None,
+            enchant2_level: 0,
+            min_luck: record.min_luck,
+            max_luck: record.max_luck,
+            minluck: None,
+            rare_probability: record
+                .goodie
+                .rare_probability(record.min_luck, record.max_luck),
+        };
+        let item = |rarity, c: &ChestItem, group_id| {
+            let enchants = c.enchants().map_or(&[][..], |e| e.as_slice());
+            let enchant = |i: usize| enchants.get(i).copied();
+            FlatGoodie {
+                group_id,
+                rarity: Some(rarity),
+                item_kind: Some(c.kind()),
+                quantity: c.kind().quantity(),
+                enchant1_kind: enchant(0).map(|e| e.0),
+                enchant1_level: enchant(0).map_or(0, |e| e.1),
+                enchant2_kind: enchant(1).map(|e| e.0),
+                enchant2_level: enchant(1).map_or(0, |e| e.1),
+                ..base.clone()
+            }
+        };
+        match &record.goodie {
+            Goodie::DragonTooth => vec![base],
+            Goodie::CommonChest(c) => vec![item(Rarity::Common, c, None)],
+            Goodie::RareChest(c) => vec![item(Rarity::Rare, c, None)],
+            Goodie::ChanceChest {
+                minluck,
+                common,
+                rare,
+            } => [(Rarity::Common, common), (Rarity::Rare, rare)]
+                .into_iter()
+                .map(|(rarity, c)| FlatGoodie {
+                    minluck: Some(*minluck),
+                    ..item(rarity, c, Some(group_id))
+                })
+                .collect(),
+            Goodie::Ambiguous(alts) => alts
+                .iter()
+                .filter_map(|(_, g)| match g {
+                    Goodie::DragonTooth => Some(FlatGoodie {
+                        group_id: Some(group_id),
+                        ..base.clone()
+                    }),
+                    Goodie::CommonChest(c) => Some(item(Rarity::Common, c, Some(group_id))),
+                    Goodie::RareChest(c) => Some(item(Rarity::Rare, c, Some(group_id))),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    fn item(&self) -> Result<ChestItem> {
+        let invalid = |msg: &str| Error::InvalidFlatGoodie(msg.to_string());
+        let kind = self
+            .item_kind
+            .ok_or_else(|| invalid("chest row without an item"))?;
+        let levels = [
+            (self.enchant1_kind, self.enchant1_level),
+            (self.enchant2_kind, self.enchant2_level),
+        ]
+        .into_iter()
+        .filter_map(|(e, lvl)| Some((e?, lvl)))
+        .collect();
+        Ok(ChestItem::with_enchants(
+            kind,
+            Enchants::from_levels(levels),
+        ))
+    }
+}
+
+/// Rows for every record of the forecast, in order. Group ids count up from 0.
+pub fn flatten(forecast: &[DayForecast]) -> Vec<FlatGoodie> {
+    let mut group_id = 0;
+    let mut out = vec![];
+    for day in forecast {
+        for record in day.records(&DayState::default()) {
+            let rows = FlatGoodie::rows(day.days_played, &record, group_id);
+            if rows.iter().any(|r| r.group_id.is_some()) {
+                group_id += 1;
+            }
+            out.extend(rows);
+        }
+    }
+    out
+}
+
+/// The records back from `flatten`'s rows, like DayForecast::records of every day. Fails for
+/// rows that don't fit together, like a group that isn't exactly a common and a rare row (which
+/// is what Ambiguous goodies give).
+pub fn unflatten(rows: &[FlatGoodie]) -> Result<Vec<GoodieRecord>> {
+    let invalid = |msg: &str| Error::InvalidFlatGoodie(msg.to_string());
+    let mut out = vec![];
+    let mut i = 0;
+    while i < rows.len() {
+        let row = &rows[i];
+        let goodie = match (row.group_id, row.rarity) {
+            (None, None) => Goodie::DragonTooth,
+            (None, Some(Rarity::Common)) => Goodie::CommonChest(row.item()?),
+            (None, Some(Rarity::Rare)) => Goodie::RareChest(row.item()?),
+            (Some(group), _) => {
+                let rare = rows
+                    .get(i + 1)
+                    .filter(|r| r.group_id == Some(group))
+                    .ok_or_else(|| invalid("chance chest without its rare row"))?;
+                let (Some(Rarity::Common), Some(Rarity::Rare), Some(minluck)) =
+                    (row.rarity, rare.rarity, row.minluck)
+                else {
+                    return Err(invalid("group isn't a common and a rare row"));
+                };
+                if rows.get(i + 2).is_some_and(|r| r.group_id == Some(group)) {
+                    return Err(invalid("group with more than two rows"));
+                }
+                i += 1;
+                Goodie::ChanceChest {
+                    minluck,
+                    common: row.item()?,
+                    rare: rare.item()?,
+                }
+            }
+        };
+        out.push(GoodieRecord {
+            chest: row.chest_index.map(|index| ChestId {
+                days_played: row.day,
+                level: row.floor,
+                index,
+            }),
+            level: row.floor,
+            min_luck: row.min_luck,
+            max_luck: row.max_luck,
+            goodie,
+            already_opened: false,
+            decisions: None,
+        });
+        i += 1;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameSettings;
+    use crate::partial::SettingsAssumption;
+
+    fn record(level: u8, index: Option<u8>, goodie: Goodie) -> GoodieRecord {
+        GoodieRecord {
+            chest: index.map(|index| ChestId {
+                days_played: 3,
+                level,
+                index,
+            }),
+            level,
+            min_luck: 0.95,
+            max_luck: 1.05,
+            goodie,
+            already_opened: false,
+            decisions: None,
+        }
+    }
+
+    #[test]
+    fn every_variant_round_trips() {
+        let club = ChestItem::DragontoothClub(Enchants::from_levels(vec![
+            (Enchant::Weight, -3),
+            (Enchant::CritPower, 50),
+        ]));
+        let records = [
+            record(1, None, Goodie::DragonTooth),
+            record(1, Some(0), Goodie::CommonChest(ChestItem::TaroTuber)),
+            record(2, Some(0), Goodie::RareChest(club.clone())),
+            record(
+                4,
+                Some(1),
+                Goodie::ChanceChest {
+                    minluck: 1.01,
+                    common: ChestItem::CinderShards3,
+                    rare: club,
+                },
+            ),
+            record(
+                6,
+                Some(0),
+                Goodie::ChanceChest {
+                    minluck: 0.99,
+                    common: ChestItem::PineappleSeeds,
+                    rare: ChestItem::HotJavaRing,
+                },
+            ),
+        ];
+        let rows: Vec<FlatGoodie> = records
+            .iter()
+            .zip(0..)
+            .flat_map(|(r, group)| FlatGoodie::rows(3, r, group))
+            .collect();
+        assert_eq!(rows.len(), 7);
+        assert_eq!(unflatten(&rows).unwrap(), records);
+        // the chance chests' rows are pairs, common first
+        let groups: Vec<Option<u32>> = rows.iter().map(|r| r.group_id).collect();
+        assert_eq!(
+            groups,
+            [None, None, None, Some(3), Some(3), Some(4), Some(4)]
+        );
+        assert_eq!(rows[3].rarity, Some(Rarity::Common));
+        assert_eq!(rows[4].rarity, Some(Rarity::Rare));
+        assert_eq!(rows[4].enchant2_kind, Some(Enchant::CritPower));
+        assert_eq!(rows[4].enchant2_level, 50);
+        assert!(rows[3..5].iter().all(|r| r.minluck == Some(1.01)));
+        assert_eq!(rows[1].quantity, 8);
+        assert_eq!(rows[0].item_kind, None);
+    }
+
+    #[test]
+    fn forecast_round_trips() {
+        let settings = GameSettings {
+            seed: 166,
+            ..Default::default()
+        };
+        let forecast = crate::forecast::range(&settings, 1, 28).unwrap();
+        let rows = flatten(&forecast);
+        let records: Vec<GoodieRecord> = forecast
+            .iter()
+            .flat_map(|day| day.records(&DayState::default()))
+            .collect();
+        assert_eq!(unflatten(&rows).unwrap(), records);
+        // group ids count up
+        let mut ids: Vec<u32> = rows.iter().filter_map(|r| r.group_id).collect();
+        ids.dedup();
+        assert!(ids.iter().zip(0..).all(|(&id, i)| id == i));
+    }
+
+    #[test]
+    fn ambiguous_goodies_dont_go_back() {
+        let alts = vec![
+            (
+                SettingsAssumption::default(),
+                Goodie::CommonChest(ChestItem::TaroTuber),
+            ),
+            (
+                SettingsAssumption::default(),
+                Goodie::CommonChest(ChestItem::GoldenCoconut),
+            ),
+        ];
+        let rows = FlatGoodie::rows(3, &record(1, Some(0), Goodie::Ambiguous(alts)), 9);
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|r| r.group_id == Some(9)));
+        assert!(matches!(unflatten(&rows), Err(Error::InvalidFlatGoodie(_))));
+        assert!(unflatten(&rows[..1]).is_err());
+    }
+}
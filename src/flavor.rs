@@ -0,0 +1,370 @@
+//! Hint texts for the best item of a day, the way a journal scrap would put it. Only flavor: the
+//! template comes from the day and a seed, so refreshing the page keeps the same text.
+
+use crate::forecast::DayForecast;
+use crate::loot::{ChestItem, ChestItemKind, Goodie, Rarity};
+use crate::render::Locale;
+use crate::rng;
+use crate::stats::ValueModel;
+
+/// What kind of item a hint talks about
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HintCategory {
+    Weapon,
+    Ring,
+    /// the boots and the hat
+    Clothing,
+    Resource,
+}
+
+impl HintCategory {
+    pub const ALL: [HintCategory; 4] = [
+        HintCategory::Weapon,
+        HintCategory::Ring,
+        HintCategory::Clothing,
+        HintCategory::Resource,
+    ];
+
+    pub const fn of(kind: ChestItemKind) -> Self {
+        use ChestItemKind::*;
+        match kind {
+            DwarfSword | DwarfHammer | DwarfDagger | DragontoothCutlass | DragontoothClub
+            | DragontoothShiv => HintCategory::Weapon,
+            ProtectionRing | SoulSapperRing | PhoenixRing | HotJavaRing => HintCategory::Ring,
+            MermaidBoots | DragonscaleBoots | DeluxePirateHat => HintCategory::Clothing,
+            CinderShards3 | GoldenCoconut | TaroTuber | PineappleSeeds | CinderShards10
+            | GoldenCoconuts | OstrichEgg => HintCategory::Resource,
+        }
+    }
+}
+
+/// `{floor}` is replaced with the floor's name from floor_names
+struct HintTemplate {
+    category: HintCategory,
+    rarity: Rarity,
+    text: &'static str,
+}
+
+const fn template(category: HintCategory, rarity: Rarity, text: &'static str) -> HintTemplate {
+    HintTemplate {
+        category,
+        rarity,
+        text,
+    }
+}
+
+const EN_TEMPLATES: &[HintTemplate] = {
+    use HintCategory::*;
+    use Rarity::*;
+    &[
+        template(
+            Weapon,
+            Common,
+            "Steel glints somewhere on the {floor} floor…",
+        ),
+        template(
+            Weapon,
+            Common,
+            "A dwarf's blade lies forgotten on the {floor} floor…",
+        ),
+        template(
+            Weapon,
+            Rare,
+            "A rare prize stirs beneath the {floor} floor…",
+        ),
+        template(
+            Weapon,
+            Rare,
+            "Something with teeth waits on the {floor} floor…",
+        ),
+        template(Ring, Common, "A small band gleams on the {floor} floor…"),
+        template(Ring, Common, "Someone lost a ring on the {floor} floor…"),
+        template(
+            Ring,
+            Rare,
+            "Fire-forged jewelry rests on the {floor} floor…",
+        ),
+        template(
+            Ring,
+            Rare,
+            "A ring hums with old magic on the {floor} floor…",
+        ),
+        template(
+            Clothing,
+            Common,
+            "Something to wear waits on the {floor} floor…",
+        ),
+        template(
+            Clothing,
+            Common,
+            "A pair of boots was left on the {floor} floor…",
+        ),
+        template(Clothing, Rare, "Fine scales shimmer on the {floor} floor…"),
+        template(
+            Clothing,
+            Rare,
+            "Something dashing is tucked away on the {floor} floor…",
+        ),
+        template(
+            Resource,
+            Common,
+            "Supplies are stashed on the {floor} floor…",
+        ),
+        template(
+            Resource,
+            Common,
+            "The {floor} floor smells faintly of the island…",
+        ),
+        template(Resource, Rare, "A heavy chest sits on the {floor} floor…"),
+        template(
+            Resource,
+            Rare,
+            "Something warm and precious rests on the {floor} floor…",
+        ),
+    ]
+};
+
+const DE_TEMPLATES: &[HintTemplate] = {
+    use HintCategory::*;
+    use Rarity::*;
+    &[
+        template(
+            Weapon,
+            Common,
+            "Irgendwo auf der {floor} Ebene blitzt Stahl…",
+        ),
+        template(
+            Weapon,
+            Common,
+            "Auf der {floor} Ebene liegt eine vergessene Zwergenklinge…",
+        ),
+        template(
+            Weapon,
+            Rare,
+            "Unter der {floor} Ebene regt sich ein seltener Schatz…",
+        ),
+        template(
+            Weapon,
+            Rare,
+            "Auf der {floor} Ebene wartet etwas mit Zähnen…",
+        ),
+        template(
+            Ring,
+            Common,
+            "Auf der {floor} Ebene glänzt ein kleiner Reif…",
+        ),
+        template(
+            Ring,
+            Common,
+            "Jemand hat auf der {floor} Ebene einen Ring verloren…",
+        ),
+        template(
+            Ring,
+            Rare,
+            "Im Feuer geschmiedeter Schmuck ruht auf der {floor} Ebene…",
+        ),
+        template(
+            Ring,
+            Rare,
+            "Auf der {floor} Ebene summt ein Ring vor alter Magie…",
+        ),
+        template(
+            Clothing,
+            Common,
+            "Auf der {floor} Ebene wartet etwas zum Anziehen…",
+        ),
+        template(
+            Clothing,
+            Common,
+            "Auf der {floor} Ebene hat jemand ein Paar Stiefel vergessen…",
+        ),
+        template(
+            Clothing,
+            Rare,
+            "Auf der {floor} Ebene schimmern feine Schuppen…",
+        ),
+        template(
+            Clothing,
+            Rare,
+            "Auf der {floor} Ebene ist etwas Schneidiges versteckt…",
+        ),
+        template(
+            Resource,
+            Common,
+            "Auf der {floor} Ebene sind Vorräte versteckt…",
+        ),
+        template(
+            Resource,
+            Common,
+            "Auf der {floor} Ebene riecht es ein wenig nach der Insel…",
+        ),
+        template(
+            Resource,
+            Rare,
+            "Auf der {floor} Ebene steht eine schwere Truhe…",
+        ),
+        template(
+            Resource,
+            Rare,
+            "Etwas Warmes und Kostbares ruht auf der {floor} Ebene…",
+        ),
+    ]
+};
+
+/// indexed by level, the ones without chests are never used
+const EN_FLOOR_NAMES: [&str; 10] = [
+    "entrance", "first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth",
+    "ninth",
+];
+
+/// for "auf der {floor} Ebene"
+const DE_FLOOR_NAMES: [&str; 10] = [
+    "Eingangs", "ersten", "zweiten", "dritten", "vierten", "fünften", "sechsten", "siebten",
+    "achten", "neunten",
+];
+
+fn templates(locale: Locale) -> &'static [HintTemplate] {
+    match locale {
+        Locale::En => EN_TEMPLATES,
+        Locale::De => DE_TEMPLATES,
+    }
+}
+
+fn floor_names(locale: Locale) -> &'static [&'static str; 10] {
+    match locale {
+        Locale::En => &EN_FLOOR_NAMES,
+        Locale::De => &DE_FLOOR_NAMES,
+    }
+}
+
+// every category has a template of both rarities. new locales need their table checked here too.
+const _: () = {
+    const fn complete(table: &[HintTemplate]) -> bool {
+        let mut c = 0;
+        while c < HintCategory::ALL.len() {
+            let mut has = [false; 2];
+            let mut i = 0;
+            while i < table.len() {
+                if table[i].category as usize == HintCategory::ALL[c] as usize {
+                    has[table[i].rarity as usize] = true;
+                }
+                i += 1;
+            }
+            if !has[0] || !has[1] {
+                return false;
+            }
+            c += 1;
+        }
+        true
+    }
+    assert!(complete(EN_TEMPLATES));
+    assert!(complete(DE_TEMPLATES));
+};
+
+/// Hint about the most valuable item of the day (by the default ValueModel), None for days
+/// without chests. Chance chests count as both of their items. The same day and `flavor_seed`
+/// always give the same text.
+pub fn hint_for(day: &DayForecast, locale: Locale, flavor_seed: i32) -> Option<String> {
+    let values = ValueModel::default();
+    let mut best: Option<(f64, usize, Rarity, &ChestItem)> = None;
+    for (level, floor) in day.loot.iter().enumerate() {
+        for (_, _, loot) in floor {
+            let mut items = vec![];
+            for goodie in loot {
+                chest_items(goodie, &mut items);
+            }
+            for (rarity, item) in items {
+                let value = values.item_value_for(item, &day.settings);
+                if best.is_none_or(|b| value > b.0) {
+                    best = Some((value, level, rarity, item));
+                }
+            }
+        }
+    }
+    let (_, level, rarity, item) = best?;
+    let category = HintCategory::of(item.kind());
+    let options: Vec<&HintTemplate> = templates(locale)
+        .iter()
+        .filter(|t| t.category == category && t.rarity == rarity)
+        .collect();
+    let seed = rng::stardew_seed_mix(false, &[day.days_played as f64, flavor_seed as f64]);
+    let picked = options[rng::DotnetRng::new(seed).next_range(options.len() as i32) as usize];
+    Some(picked.text.replace("{floor}", floor_names(locale)[level]))
+}
+
+/// (the table it's from, item) of everything the goodie could be
+fn chest_items<'a>(goodie: &'a Goodie, out: &mut Vec<(Rarity, &'a ChestItem)>) {
+    match goodie {
+        Goodie::DragonTooth => {}
+        Goodie::CommonChest(c) => out.push((Rarity::Common, c)),
+        Goodie::RareChest(c) => out.push((Rarity::Rare, c)),
+        Goodie::ChanceChest { common, rare, .. } => {
+            out.push((Rarity::Common, common));
+            out.push((Rarity::Rare, rare));
+        }
+        Goodie::Ambiguous(alts) => {
+            for (_, g) in alts {
+                chest_items(g, out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameSettings, forecast};
+
+    #[test]
+    fn every_locale_has_every_template() {
+        for locale in [Locale::En, Locale::De] {
+            for category in HintCategory::ALL {
+                for rarity in [Rarity::Common, Rarity::Rare] {
+                    let options: Vec<_> = templates(locale)
+                        .iter()
+                        .filter(|t| t.category == category && t.rarity == rarity)
+                        .collect();
+                    assert!(
+                        !options.is_empty(),
+                        "{:?} {:?} {:?}",
+                        locale,
+                        category,
+                        rarity
+                    );
+                    assert!(options.iter().all(|t| t.text.contains("{floor}")));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hints_are_seeded() {
+        let settings = GameSettings {
+            seed: 4321,
+            ..Default::default()
+        };
+        let month = forecast::range(&settings, 1, 28).unwrap();
+        let hints: Vec<String> = month
+            .iter()
+            .map(|day| hint_for(day, Locale::En, 7).unwrap())
+            .collect();
+        for (day, hint) in month.iter().zip(&hints) {
+            assert_eq!(&hint_for(day, Locale::En, 7).unwrap(), hint);
+            assert!(!hint.contains("{floor}"));
+            let german = hint_for(day, Locale::De, 7).unwrap();
+            assert!(german.contains(" Ebene"), "{}", german);
+        }
+        let mut distinct = hints.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert!(distinct.len() > 5, "{:?}", distinct);
+        let reseeded = month
+            .iter()
+            .filter(|day| hint_for(day, Locale::En, 7) != hint_for(day, Locale::En, 8));
+        assert!(reseeded.count() > 0);
+
+        let mut empty = month[0].clone();
+        empty.loot = Default::default();
+        assert_eq!(hint_for(&empty, Locale::En, 7), None);
+    }
+}
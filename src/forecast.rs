@@ -0,0 +1,1200 @@
+use crate::calendar::{self, CalendarEvent};
+use crate::date::DayNumber;
+use crate::detect::RandomizationMode;
+use crate::filter::GoodieFilter;
+use crate::loot::{Decisions, FloorLevel, Goodie};
+use crate::luck::{LuckModel, luck_range};
+use crate::partial::{PartialSettings, SettingsAssumption};
+use crate::plan::SeedPlan;
+use crate::seeds::SeedSpec;
+use crate::stats::DayScoreModel;
+use crate::{
+    DungeonFloorState, Error, GameSettings, GameVersion, ProbabilityRange, Result,
+    compute_volcano_layouts, do_dungeon, do_dungeon_with,
+};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Possible layouts and loot of every floor on one day
+#[derive(Clone, Debug)]
+pub struct DayForecast {
+    pub days_played: u32,
+    /// what the forecast was generated with
+    pub settings: GameSettings,
+    pub layouts: [ProbabilityRange<u32>; 10],
+    pub loot: [ProbabilityRange<Vec<Goodie>>; 10],
+    /// the festivals on the day, from calendar::events_on
+    pub calendar_events: Vec<CalendarEvent>,
+}
+
+/// Identifies a chest by its position in the day: the `index`th chest generated on `level`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChestId {
+    pub days_played: u32,
+    pub level: u8,
+    pub index: u8,
+}
+
+/// A chest tile of a floor layout
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChestSpawn {
+    /// ChestId::index
+    pub index: u8,
+    /// top left tile of the set piece the chest is in
+    pub piece: (i32, i32),
+    /// width and height of that set piece
+    pub piece_size: i32,
+    pub seed: i32,
+}
+
+/// What the player already did today. In vanilla every chest has its own seed, so opening chests
+/// never changes the others and this is only used to annotate the output.
+#[derive(Clone, Debug, Default)]
+pub struct DayState {
+    pub opened: HashSet<ChestId>,
+    /// For mods that reseed chests after one is opened: gets the id and seed of every chest that
+    /// comes after an opened one (by level, then index), and returns the seed to use instead.
+    pub reseed: Option<fn(ChestId, i32) -> i32>,
+}
+
+/// One goodie of the forecast, with where it is
+#[derive(Clone, Debug, PartialEq)]
+pub struct GoodieRecord {
+    /// None for dragon teeth
+    pub chest: Option<ChestId>,
+    pub level: u8,
+    pub min_luck: f64,
+    pub max_luck: f64,
+    pub goodie: Goodie,
+    pub already_opened: bool,
+    /// the rolls behind a chest, only filled in by `records_with_decisions`
+    pub decisions: Option<Decisions>,
+}
+
+impl DayForecast {
+    /// forecast for settings.days_played, with the chest seeds changed by `state.reseed`
+    pub fn generate_with_state(settings: &GameSettings, state: &DayState) -> Self {
+        let Some(reseed) = state.reseed else {
+            return Self::generate(settings);
+        };
+        let first_opened = state
+            .opened
+            .iter()
+            .filter(|id| id.days_played == settings.days_played)
+            .min()
+            .copied();
+        let hook = |level: usize, index: usize, seed: i32| {
+            let id = ChestId {
+                days_played: settings.days_played,
+                level: level as u8,
+                index: index as u8,
+            };
+            match first_opened {
+                Some(opened) if id > opened => reseed(id, seed),
+                _ => seed,
+            }
+        };
+        let (layouts, loot) = do_dungeon_with(settings, &hook);
+        DayForecast {
+            days_played: settings.days_played,
+            settings: settings.clone(),
+            layouts,
+            loot,
+            calendar_events: calendar::events_on(DayNumber(settings.days_played)),
+        }
+    }
+
+    /// every goodie as a record, marking the chests in `state.opened`
+    pub fn records(&self, state: &DayState) -> Vec<GoodieRecord> {
+        let mut out = vec![];
+        for (level, floor) in self.loot.iter().enumerate() {
+            for (minl, maxl, loot) in floor {
+                let mut index = 0;
+                for goodie in loot {
+                    let chest = match goodie {
+                        Goodie::DragonTooth => None,
+                        Goodie::Ambiguous(alts)
+                            if alts.iter().all(|(_, g)| *g == Goodie::DragonTooth) =>
+                        {
+                            None
+                        }
+                        _ => {
+                            index += 1;
+                            Some(ChestId {
+                                days_played: self.days_played,
+                                level: level as u8,
+                                index: index - 1,
+                            })
+                        }
+                    };
+                    out.push(GoodieRecord {
+                        chest,
+                        level: level as u8,
+                        min_luck: *minl,
+                        max_luck: *maxl,
+                        goodie: goodie.clone(),
+                        already_opened: chest.is_some_and(|c| state.opened.contains(&c)),
+                        decisions: None,
+                    });
+                }
+            }
+        }
+        out
+    }
+
+    /// like records, with the decisions of each chest
+    pub fn records_with_decisions(&self, state: &DayState) -> Vec<GoodieRecord> {
+        let mut out = self.records(state);
+        let mut floor: Option<(u8, f64, Vec<Decisions>)> = None;
+        for record in &mut out {
+            let Some(chest) = record.chest else {
+                continue;
+            };
+            if floor
+                .as_ref()
+                .is_none_or(|f| (f.0, f.1) != (record.level, record.min_luck))
+            {
+                let decisions = self.chest_decisions(record.level as usize, record.min_luck);
+                floor = Some((record.level, record.min_luck, decisions));
+            }
+            let decisions = &floor.as_ref().unwrap().2;
+            record.decisions = decisions.get(chest.index as usize).copied();
+        }
+        out
+    }
+
+    /// The rolls behind each chest on `level` with the luck range starting at `min_luck`, in the
+    /// same order as in `loot`. These use the unmodified chest seeds, so they don't know about
+    /// `DayState::reseed`.
+    pub fn chest_decisions(&self, level: usize, min_luck: f64) -> Vec<Decisions> {
+        self.chest_seeds(level, min_luck)
+            .into_iter()
+            .filter_map(|seed| {
+                Decisions::for_chest_at(seed, &self.settings, FloorLevel::ALL[level]).ok()
+            })
+            .collect()
+    }
+
+    /// The seeds of the chests on `level` with the luck range starting at `min_luck`, like
+    /// chest_decisions. Floors without chests still have seeds for their chest tiles.
+    pub fn chest_seeds(&self, level: usize, min_luck: f64) -> Vec<i32> {
+        self.population_order(level, min_luck)
+            .into_iter()
+            .map(|spawn| spawn.seed)
+            .collect()
+    }
+
+    /// The chests on `level` in the order the game populates them, which is the order of `loot`
+    /// and of ChestId::index. Set pieces go by column from the left, each column from the top,
+    /// and the chests of a piece in the order of its features. Every chest takes the next value
+    /// of the floor's rng for its seed, so the order can't be wrong without the seeds being wrong.
+    pub fn population_order(&self, level: usize, min_luck: f64) -> Vec<ChestSpawn> {
+        let Some(&(_, _, layout)) = self.layouts[level]
+            .iter()
+            .find(|(minl, maxl, _)| (*minl..=*maxl).contains(&min_luck))
+        else {
+            return vec![];
+        };
+        let seeds = RefCell::new(vec![]);
+        let mut floor =
+            DungeonFloorState::new(&self.settings, level as i32, layout, min_luck, min_luck);
+        floor.load_map_with(&|_, seed| {
+            seeds.borrow_mut().push(seed);
+            seed
+        });
+        seeds
+            .into_inner()
+            .into_iter()
+            .zip(&floor.chest_pieces)
+            .enumerate()
+            .map(|(index, (seed, &piece))| {
+                let (x, y, size) = floor.set_pieces[piece];
+                ChestSpawn {
+                    index: index as u8,
+                    piece: (x, y),
+                    piece_size: size,
+                    seed,
+                }
+            })
+            .collect()
+    }
+
+    /// Only the parts of the forecast for this exact luckMult, see `luck::luck_multiplier`. Floors
+    /// where it's outside the forecast's luck ranges end up empty.
+    pub fn at_luck(&self, luck: f64) -> DayForecast {
+        fn narrow<T: Clone>(ranges: &ProbabilityRange<T>, luck: f64) -> ProbabilityRange<T> {
+            ranges
+                .iter()
+                .filter(|(minl, maxl, _)| *minl <= luck && luck <= *maxl)
+                .take(1)
+                .map(|(_, _, x)| (luck, luck, x.clone()))
+                .collect()
+        }
+        DayForecast {
+            days_played: self.days_played,
+            settings: self.settings.clone(),
+            layouts: std::array::from_fn(|i| narrow(&self.layouts[i], luck)),
+            loot: std::array::from_fn(|i| narrow(&self.loot[i], luck)),
+            calendar_events: self.calendar_events.clone(),
+        }
+    }
+
+    /// (level, minluck, maxluck, goodie) for every goodie that passes the filter
+    pub fn iter_filtered(
+        &self,
+        filter: GoodieFilter,
+    ) -> impl Iterator<Item = (usize, f64, f64, &Goodie)> {
+        self.loot
+            .iter()
+            .enumerate()
+            .flat_map(move |(level, floor)| {
+                floor.iter().flat_map(move |(minl, maxl, loot)| {
+                    loot.iter()
+                        .filter(move |g| filter.matches(g, *minl, *maxl))
+                        .map(move |g| (level, *minl, *maxl, g))
+                })
+            })
+    }
+
+    /// forecast for the day given by `settings.days_played`
+    pub fn generate(settings: &GameSettings) -> Self {
+        let (layouts, loot) = do_dungeon(settings);
+        DayForecast {
+            days_played: settings.days_played,
+            settings: settings.clone(),
+            layouts,
+            loot,
+            calendar_events: calendar::events_on(DayNumber(settings.days_played)),
+        }
+    }
+}
+
+/// The chest with this seed on `level` over the settings' luck range, split at its cutoff like the
+/// loot of a forecast. Components can point at another chest in each layout, so they're laid out
+/// with their game id and day for every layout the luck gives, neighbouring ranges with the same
+/// chest are merged.
+pub fn chest_forecast(
+    chest_seed: impl Into<SeedSpec>,
+    settings: &GameSettings,
+    level: FloorLevel,
+) -> Result<ProbabilityRange<Goodie>> {
+    let chest_seed = chest_seed.into();
+    let ranges = match chest_seed {
+        SeedSpec::Raw(_) => vec![luck_range(settings)],
+        SeedSpec::Components {
+            game_id,
+            days_played,
+            ..
+        } => compute_volcano_layouts(&GameSettings {
+            seed: GameSettings::for_game_id(game_id)?.seed,
+            days_played,
+            ..*settings
+        })
+        .into_iter()
+        .map(|(minluck, maxluck, _)| (minluck, maxluck))
+        .collect(),
+    };
+    let mut out: ProbabilityRange<Goodie> = vec![];
+    let mut push = |minluck: f64, maxluck: f64, goodie: Goodie| match out.last_mut() {
+        Some(last) if last.2 == goodie && last.1.next_up() == minluck => last.1 = maxluck,
+        _ => out.push((minluck, maxluck, goodie)),
+    };
+    for (minluck, maxluck) in ranges {
+        match Goodie::generate_at(chest_seed, settings, level, minluck, maxluck)? {
+            Goodie::ChanceChest {
+                minluck: chestluck,
+                common,
+                rare,
+            } => {
+                push(minluck, chestluck, Goodie::CommonChest(common));
+                push(chestluck.next_up(), maxluck, Goodie::RareChest(rare));
+            }
+            goodie => push(minluck, maxluck, goodie),
+        }
+    }
+    Ok(out)
+}
+
+/// Forecasts for `days` consecutive days, starting at `start_day`. Fails for ranges that
+/// checked_span with date::MAX_DAY_RANGE doesn't take, Builder::max_days for a lower limit.
+pub fn range(
+    settings: &GameSettings,
+    start_day: impl Into<DayNumber>,
+    days: u32,
+) -> Result<Vec<DayForecast>> {
+    Ok(SeedPlan::new(settings, start_day, days)?.materialize(settings, luck_range(settings)))
+}
+
+/// Like range, but each day covers the luck `model` gives for it instead of the settings' range
+pub fn range_with_luck(
+    settings: &GameSettings,
+    start_day: impl Into<DayNumber>,
+    days: u32,
+    model: &dyn LuckModel,
+) -> Result<Vec<DayForecast>> {
+    Ok(SeedPlan::for_luck(settings, start_day, days, model)?
+        .materialize(settings, (f64::NEG_INFINITY, f64::INFINITY)))
+}
+
+/// most combinations of unknown settings enumerate_day goes through
+pub const MAX_ENUMERATED_ASSUMPTIONS: usize = 16;
+
+/// Every different forecast of day `day` with the flags of `settings` that are unknown, with the
+/// assumptions that give it. Assumptions with the same layouts and loot are merged, a flag that
+/// doesn't matter for that forecast goes back to None. A set that no single assumption describes
+/// gets an entry per part. The forecast's settings are those of the first assumption of it.
+pub fn enumerate_day(
+    game_seed: i32,
+    day: impl Into<DayNumber>,
+    settings: &PartialSettings,
+) -> Result<Vec<(SettingsAssumption, DayForecast)>> {
+    let settings = PartialSettings {
+        settings: GameSettings {
+            seed: game_seed,
+            days_played: day.into().0,
+            ..settings.settings
+        },
+        ..*settings
+    };
+    let assumptions = settings.assumptions();
+    if assumptions.len() > MAX_ENUMERATED_ASSUMPTIONS {
+        return Err(Error::TooManyAssumptions {
+            count: assumptions.len(),
+            max: MAX_ENUMERATED_ASSUMPTIONS,
+        });
+    }
+    // the raw enchant rolls and the settings are left out, Enchants compares only the levels
+    let mut groups: Vec<(Vec<SettingsAssumption>, DayForecast)> = vec![];
+    for (assumption, settings) in assumptions {
+        let day = DayForecast::generate(&settings);
+        match groups
+            .iter_mut()
+            .find(|(_, d)| d.layouts == day.layouts && d.loot == day.loot)
+        {
+            Some((group, _)) => group.push(assumption),
+            None => groups.push((vec![assumption], day)),
+        }
+    }
+    Ok(groups
+        .into_iter()
+        .flat_map(|(group, day)| {
+            merge_assumptions(group)
+                .into_iter()
+                .map(move |a| (a, day.clone()))
+        })
+        .collect())
+}
+
+/// merges pairs that only differ in one known flag until none are left
+fn merge_assumptions(mut group: Vec<SettingsAssumption>) -> Vec<SettingsAssumption> {
+    let flags = |a: &SettingsAssumption| {
+        [
+            a.legacy_rng,
+            a.has_caldera,
+            a.post_1_6_4,
+            a.cracked_golden_coconut,
+        ]
+    };
+    'merge: loop {
+        for i in 0..group.len() {
+            for j in i + 1..group.len() {
+                let (a, b) = (flags(&group[i]), flags(&group[j]));
+                let differing: Vec<usize> = (0..a.len()).filter(|&k| a[k] != b[k]).collect();
+                let [k] = differing[..] else {
+                    continue;
+                };
+                if a[k].is_none() || b[k].is_none() {
+                    continue;
+                }
+                let merged = &mut group[i];
+                match k {
+                    0 => merged.legacy_rng = None,
+                    1 => merged.has_caldera = None,
+                    2 => merged.post_1_6_4 = None,
+                    _ => merged.cracked_golden_coconut = None,
+                }
+                group.remove(j);
+                continue 'merge;
+            }
+        }
+        return group;
+    }
+}
+
+/// The `n` days with the best score, best first. Ties go to the earlier day.
+pub fn best_days<'a>(
+    days: &'a [DayForecast],
+    n: usize,
+    model: &DayScoreModel,
+) -> Vec<&'a DayForecast> {
+    let mut scored: Vec<(f64, &DayForecast)> = days.iter().map(|d| (d.score(model), d)).collect();
+    scored.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then(a.1.days_played.cmp(&b.1.days_played))
+    });
+    scored.into_iter().take(n).map(|(_, d)| d).collect()
+}
+
+/// default for Builder::max_days, ten years
+pub const DEFAULT_MAX_DAYS: u32 = 10 * 112;
+
+/// Forecast for a range of days, with settings that need to be resolved first
+pub struct Builder {
+    settings: GameSettings,
+    start_day: DayNumber,
+    days: u32,
+    randomization: Option<RandomizationMode>,
+    version: Option<GameVersion>,
+    max_days: u32,
+    luck: Option<Box<dyn LuckModel>>,
+    skip_festival_days: bool,
+}
+
+impl Builder {
+    pub fn new(settings: GameSettings) -> Self {
+        Builder {
+            start_day: DayNumber(settings.days_played.max(1)),
+            settings,
+            days: 1,
+            randomization: None,
+            version: None,
+            max_days: DEFAULT_MAX_DAYS,
+            luck: None,
+            skip_festival_days: false,
+        }
+    }
+
+    pub fn days(mut self, start_day: impl Into<DayNumber>, days: u32) -> Self {
+        self.start_day = start_day.into();
+        self.days = days;
+        self
+    }
+
+    /// overrides settings.legacy_rng
+    pub fn randomization(mut self, mode: RandomizationMode) -> Self {
+        self.randomization = Some(mode);
+        self
+    }
+
+    /// overrides the version flags of the settings, generate fails for unknown versions
+    pub fn version(mut self, version: GameVersion) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// generate fails with RangeTooLarge for longer ranges, at most date::MAX_DAY_RANGE
+    pub fn max_days(mut self, max_days: u32) -> Self {
+        self.max_days = max_days;
+        self
+    }
+
+    /// the luck of each day from `model`, instead of the whole range of the settings
+    pub fn luck_model(mut self, model: Box<dyn LuckModel>) -> Self {
+        self.luck = Some(model);
+        self
+    }
+
+    /// leave out the days with a time restricted festival (calendar::is_restricted), the other
+    /// days are still generated with the same seeds
+    pub fn skip_festival_days(mut self, skip: bool) -> Self {
+        self.skip_festival_days = skip;
+        self
+    }
+
+    pub fn generate(self) -> Result<Vec<DayForecast>> {
+        let settings = self.resolve()?;
+        let mut out = self.range(&settings, self.start_day, self.days)?;
+        out.retain(|day| !self.skips(day.days_played));
+        Ok(out)
+    }
+
+    /// Like generate, a day at a time so it can run off a UI thread. `cancel` is checked before
+    /// each day, and `progress` gets the fraction of days done after each. Cancelling gives
+    /// Error::Cancelled with the days done so far.
+    pub fn generate_with(
+        self,
+        cancel: &AtomicBool,
+        mut progress: impl FnMut(f32),
+    ) -> Result<Vec<DayForecast>> {
+        let settings = self.resolve()?;
+        let mut out = Vec::with_capacity(self.days as usize);
+        for (i, day) in self.start_day.span(self.days).enumerate() {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(Error::Cancelled { partial: out });
+            }
+            if !self.skips(day) {
+                out.extend(self.range(&settings, day, 1)?);
+            }
+            progress((i + 1) as f32 / self.days as f32);
+        }
+        Ok(out)
+    }
+
+    fn skips(&self, days_played: u32) -> bool {
+        self.skip_festival_days && calendar::is_restricted(DayNumber(days_played))
+    }
+
+    /// the settings to generate with, after the overrides
+    fn resolve(&self) -> Result<GameSettings> {
+        self.start_day.checked_span(self.days, self.max_days)?;
+        let mut settings = self.settings.clone();
+        if let Some(version) = &self.version {
+            settings = settings.with_version(version)?;
+        }
+        if let Some(mode) = &self.randomization {
+            settings.legacy_rng = mode.resolve(&settings)?;
+        }
+        Ok(settings)
+    }
+
+    fn range(
+        &self,
+        settings: &GameSettings,
+        start_day: impl Into<DayNumber>,
+        days: u32,
+    ) -> Result<Vec<DayForecast>> {
+        match &self.luck {
+            Some(model) => range_with_luck(settings, start_day, days, model.as_ref()),
+            None => range(settings, start_day, days),
+        }
+    }
+}
+
+/// Superset of the chests on one day, from every rng value that could end up as a chest seed
+/// without knowing the floor layouts. Only use this when the exact forecast can't be trusted.
+#[derive(Clone, Debug)]
+pub struct ApproxForecast {
+    pub days_played: u32,
+    /// possible chests per level (may be a ChanceChest)
+    pub levels: [Vec<Goodie>; 10],
+}
+
+/// Approximate forecast that doesn't depend on floor layouts. All set pieces on a floor take
+/// their rng values from the same stream after the floor tiles are generated, so the chests of
+/// the real layout are among the first `max_chests_per_level` values of that stream, as long as
+/// that's large enough (every set piece uses at least 2 values).
+pub fn exhaustive_day(
+    game_seed: i32,
+    day: impl Into<DayNumber>,
+    settings: &GameSettings,
+    max_chests_per_level: usize,
+) -> ApproxForecast {
+    let settings = GameSettings {
+        seed: game_seed,
+        days_played: day.into().0,
+        ..*settings
+    };
+    let (min_luck, max_luck) = luck_range(&settings);
+    let levels = std::array::from_fn(|level| {
+        // the layout doesn't change the rng stream up to here
+        let mut floor = DungeonFloorState::new(&settings, level as i32, 0, min_luck, max_luck);
+        floor.load_map_tiles();
+        floor
+            .candidate_chest_seeds(max_chests_per_level)
+            .into_iter()
+            .filter_map(|seed| {
+                Goodie::generate_at(seed, &settings, FloorLevel::ALL[level], min_luck, max_luck)
+                    .ok()
+            })
+            .collect()
+    });
+    ApproxForecast {
+        days_played: settings.days_played,
+        levels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::ChestCache;
+    use crate::date::MAX_DAY_RANGE;
+    use crate::loot::{ChestItem, ChestItemKind};
+    use crate::luck::{LuckDistribution, VanillaLuck};
+    use crate::search::{self, Query};
+    use crate::{plan, stats, stream_adapter};
+
+    #[test]
+    fn chests_are_populated_by_column() {
+        let settings = GameSettings {
+            seed: 12,
+            ..Default::default()
+        };
+        let mut multi_chest_floors = 0;
+        for day in range(&settings, 1, 28).unwrap() {
+            for level in 1..10 {
+                for (minl, maxl, loot) in &day.loot[level] {
+                    let order = day.population_order(level, *minl);
+                    let pieces: Vec<(i32, i32)> = order.iter().map(|s| s.piece).collect();
+                    assert!(pieces.is_sorted(), "{:?}", pieces);
+                    // every chest's loot is the one of its seed, in the same order
+                    let chests: Vec<&Goodie> =
+                        loot.iter().filter(|g| **g != Goodie::DragonTooth).collect();
+                    assert_eq!(chests.len(), order.len());
+                    let floor = FloorLevel::try_from(level as i32).unwrap();
+                    for (spawn, goodie) in order.iter().zip(chests) {
+                        let generated =
+                            Goodie::generate_at(spawn.seed, &settings, floor, *minl, *maxl)
+                                .unwrap();
+                        assert_eq!(&generated, goodie);
+                    }
+                    multi_chest_floors += (order.len() > 1) as u32;
+                }
+            }
+        }
+        assert!(multi_chest_floors > 0);
+    }
+
+    #[test]
+    fn column_order_against_row_order() {
+        // floor 3 of day 11 has a chest in the piece at column 32, row 39 and one at column 39,
+        // row 9. going by rows first would swap the two.
+        let day = DayForecast::generate(&GameSettings {
+            seed: 12,
+            days_played: 11,
+            ..Default::default()
+        });
+        let (min_luck, _, loot) = &day.loot[3][0];
+        let order = day.population_order(3, *min_luck);
+        let pieces: Vec<(i32, i32)> = order.iter().map(|s| s.piece).collect();
+        assert_eq!(pieces, [(32, 39), (39, 9)]);
+        assert_eq!(
+            loot[..],
+            [
+                Goodie::CommonChest(ChestItem::SoulSapperRing),
+                Goodie::CommonChest(ChestItem::CinderShards3)
+            ]
+        );
+        assert_eq!(order.iter().map(|s| s.index).collect::<Vec<_>>(), [0, 1]);
+    }
+
+    #[test]
+    fn decisions_reproduce_the_rarity() {
+        let mut seen = [0; 2];
+        for seed in [1, 12, 2024] {
+            let settings = GameSettings {
+                seed,
+                ..Default::default()
+            };
+            for day in range(&settings, 1, 28).unwrap() {
+                for record in day.records_with_decisions(&DayState::default()) {
+                    let Some(d) = record.decisions else {
+                        assert!(record.chest.is_none());
+                        continue;
+                    };
+                    // what the tooltip compares: the roll against the threshold plus the boost
+                    let rare_at = |luck: f64| d.rarity_roll < d.rare_threshold + luck - 1.;
+                    let (min, max) = (record.min_luck, record.max_luck);
+                    // the ends can be right at the threshold, where the rounding of the two
+                    // sums differs
+                    let mid = (min + max) / 2.;
+                    match &record.goodie {
+                        Goodie::RareChest(_) => {
+                            assert!(rare_at(mid) && d.is_rare(min));
+                            seen[0] += 1;
+                        }
+                        Goodie::CommonChest(_) => {
+                            assert!(!rare_at(mid) && !d.is_rare(max));
+                            seen[1] += 1;
+                        }
+                        // range splits chance chests by luck
+                        other => panic!("{:?}", other),
+                    }
+                }
+            }
+        }
+        assert!(seen.iter().all(|&n| n > 0), "{:?}", seen);
+    }
+
+    #[test]
+    fn opened_chests_only_annotate() {
+        for (seed, days_played) in [(12, 2), (12, 4), (2024, 9)] {
+            let settings = GameSettings {
+                seed,
+                days_played,
+                ..Default::default()
+            };
+            let day = DayForecast::generate(&settings);
+            let fresh = day.records(&DayState::default());
+            let chests: Vec<ChestId> = fresh.iter().filter_map(|r| r.chest).collect();
+            assert!(chests.len() > 1);
+            let state = DayState {
+                opened: chests.iter().copied().step_by(2).collect(),
+                reseed: None,
+            };
+            // without a reseed hook the forecast is the same
+            let with_state = DayForecast::generate_with_state(&settings, &state);
+            assert_eq!(format!("{:?}", with_state), format!("{:?}", day));
+            let opened = with_state.records(&state);
+            assert_eq!(opened.len(), fresh.len());
+            for (opened, fresh) in opened.iter().zip(&fresh) {
+                let expected = opened.chest.is_some_and(|c| state.opened.contains(&c));
+                assert_eq!(opened.already_opened, expected);
+                assert!(!fresh.already_opened);
+                let annotated = GoodieRecord {
+                    already_opened: false,
+                    ..opened.clone()
+                };
+                assert_eq!(&annotated, fresh);
+            }
+        }
+    }
+
+    #[test]
+    fn reseeding_changes_the_later_chests() {
+        let settings = GameSettings {
+            seed: 12,
+            days_played: 9,
+            ..Default::default()
+        };
+        let day = DayForecast::generate(&settings);
+        let chests: Vec<ChestId> = day
+            .records(&DayState::default())
+            .iter()
+            .filter_map(|r| r.chest)
+            .collect();
+        let first = chests[0];
+        let state = DayState {
+            opened: [first].into(),
+            reseed: Some(|_, seed| seed.wrapping_add(1)),
+        };
+        let reseeded = DayForecast::generate_with_state(&settings, &state);
+        // up to the opened chest the floors stay the same
+        for level in 0..=first.level as usize {
+            let goodies = |f: &DayForecast| format!("{:?}", f.loot[level][0].2.first());
+            assert_eq!(goodies(&reseeded), goodies(&day));
+        }
+        assert_ne!(format!("{:?}", reseeded.loot), format!("{:?}", day.loot));
+    }
+
+    #[test]
+    fn exact_chests_are_in_the_exhaustive_day() {
+        let mut chests = 0;
+        for seed in [1, 12, 2024] {
+            let settings = GameSettings {
+                seed,
+                ..Default::default()
+            };
+            for day in range(&settings, 1, 20).unwrap() {
+                let approx = exhaustive_day(seed, day.days_played, &settings, 40);
+                assert_eq!(approx.days_played, day.days_played);
+                for (level, loot) in day.loot.iter().enumerate() {
+                    let possible = &approx.levels[level];
+                    for goodie in loot.iter().flat_map(|(_, _, goodies)| goodies) {
+                        // the exact forecast splits chance chests by luck
+                        let found = possible.iter().any(|p| match (goodie, p) {
+                            (Goodie::CommonChest(c), Goodie::ChanceChest { common, .. }) => {
+                                c == common
+                            }
+                            (Goodie::RareChest(c), Goodie::ChanceChest { rare, .. }) => c == rare,
+                            _ => p == goodie,
+                        });
+                        match goodie {
+                            Goodie::DragonTooth => {}
+                            _ => {
+                                assert!(
+                                    found,
+                                    "seed {} day {} level {}: {}",
+                                    seed, day.days_played, level, goodie
+                                );
+                                chests += 1;
+                            }
+                        }
+                    }
+                }
+                // the chests-per-level cap keeps the superset small
+                assert!(approx.levels.iter().all(|l| l.len() <= 40));
+            }
+        }
+        assert!(chests > 50);
+    }
+
+    #[test]
+    fn day_ranges_are_checked() {
+        let settings = GameSettings::default();
+        let luck = VanillaLuck::new(&settings);
+        let any_chest = Query {
+            kinds: crate::loot::ChestItemKind::ALL.to_vec(),
+            min_enchants: None,
+            warnings: vec![],
+        };
+        type Days<'a> = Box<dyn Fn(u32, u32) -> Result<()> + 'a>;
+        let ranges: Vec<(&str, Days)> = vec![
+            (
+                "range",
+                Box::new(|start, days| range(&settings, start, days).map(drop)),
+            ),
+            (
+                "range_with_luck",
+                Box::new(|start, days| range_with_luck(&settings, start, days, &luck).map(drop)),
+            ),
+            (
+                "SeedPlan::new",
+                Box::new(|start, days| SeedPlan::new(&settings, start, days).map(drop)),
+            ),
+            (
+                "PartialSettings::range",
+                Box::new(|start, days| {
+                    PartialSettings::new(settings.clone())
+                        .range(start, days)
+                        .map(drop)
+                }),
+            ),
+            (
+                "ChestCache::range",
+                Box::new(|start, days| ChestCache::new(4).range(&settings, start, days).map(drop)),
+            ),
+            (
+                "Builder",
+                Box::new(|start, days| {
+                    let builder = Builder::new(settings.clone()).days(start, days);
+                    builder.max_days(u32::MAX).generate().map(drop)
+                }),
+            ),
+            (
+                "first_of",
+                Box::new(|start, days| {
+                    search::first_of(std::slice::from_ref(&any_chest), start, days, &settings)
+                        .map(drop)
+                }),
+            ),
+            (
+                "all_firsts",
+                Box::new(|start, days| {
+                    search::all_firsts(std::slice::from_ref(&any_chest), start, days, &settings)
+                        .map(drop)
+                }),
+            ),
+            (
+                "search_with_checkpoints",
+                Box::new(|start, days| {
+                    search::search_with_checkpoints(&any_chest, start, days, &settings, 1, |_| {})
+                        .map(drop)
+                }),
+            ),
+            (
+                "prob_at_least_one",
+                Box::new(|start, days| {
+                    let kind = crate::loot::ChestItemKind::PhoenixRing;
+                    stats::prob_at_least_one(1, start, days, kind, &settings, &luck).map(drop)
+                }),
+            ),
+            (
+                "stream_adapter::days",
+                Box::new(|start, days| {
+                    stream_adapter::days(settings.clone(), start, days).map(drop)
+                }),
+            ),
+        ];
+        for (name, f) in &ranges {
+            assert!(
+                matches!(f(0, 1), Err(Error::DayOutOfRange(0))),
+                "{} from day 0",
+                name
+            );
+            for (start, days) in [
+                (1, u32::MAX),
+                (1, MAX_DAY_RANGE + 1),
+                (u32::MAX, 1),
+                (u32::MAX - 2, 3),
+            ] {
+                assert!(
+                    matches!(f(start, days), Err(Error::RangeTooLarge { .. })),
+                    "{} with {} days from {}",
+                    name,
+                    days,
+                    start
+                );
+            }
+            f(u32::MAX - 2, 2).unwrap();
+        }
+        // all of the longest range is fine, these stop at the first day
+        search::first_of(
+            std::slice::from_ref(&any_chest),
+            1,
+            MAX_DAY_RANGE,
+            &settings,
+        )
+        .unwrap();
+        let mut days = stream_adapter::days(settings.clone(), 1, MAX_DAY_RANGE).unwrap();
+        assert_eq!(days.next().unwrap().days_played, 1);
+
+        let kind = crate::loot::ChestItemKind::PhoenixRing;
+        for from in [0, u32::MAX] {
+            assert!(search::next_occurrences(1, from, kind, 1, &settings).is_err());
+            assert!(plan::guaranteed_by(kind, from, &settings, &[]).is_err());
+        }
+    }
+
+    #[test]
+    fn free_functions_have_a_hard_cap() {
+        // no Builder to set a limit, so they stop at the most any range can have
+        let settings = GameSettings::default();
+        let luck = VanillaLuck::new(&settings);
+        let too_large = |r: Result<Vec<DayForecast>>| {
+            matches!(
+                r,
+                Err(Error::RangeTooLarge {
+                    start: 1,
+                    days,
+                    max: MAX_DAY_RANGE,
+                }) if days == MAX_DAY_RANGE + 1
+            )
+        };
+        assert!(too_large(range(&settings, 1, MAX_DAY_RANGE + 1)));
+        assert!(too_large(range_with_luck(
+            &settings,
+            1,
+            MAX_DAY_RANGE + 1,
+            &luck
+        )));
+        // the longest range itself is in date's checked_span tests, generating it takes minutes
+    }
+
+    #[test]
+    fn default_max_days() {
+        let settings = GameSettings::default();
+        assert!(matches!(
+            Builder::new(settings.clone())
+                .days(1, DEFAULT_MAX_DAYS + 1)
+                .generate(),
+            Err(Error::RangeTooLarge {
+                max: DEFAULT_MAX_DAYS,
+                ..
+            })
+        ));
+        assert!(matches!(
+            Builder::new(settings.clone())
+                .days(1, 11)
+                .max_days(10)
+                .generate(),
+            Err(Error::RangeTooLarge { max: 10, .. })
+        ));
+        assert_eq!(
+            Builder::new(settings)
+                .days(1, 10)
+                .max_days(10)
+                .generate()
+                .unwrap()
+                .len(),
+            10
+        );
+    }
+
+    #[test]
+    fn festival_days_are_skipped() {
+        let settings = GameSettings::for_game_id(12345).unwrap();
+        let all = Builder::new(settings.clone())
+            .days(1, 28)
+            .generate()
+            .unwrap();
+        assert_eq!(all.len(), 28);
+        assert_eq!(all[12].days_played, 13);
+        assert_eq!(all[12].calendar_events[0].name, "Egg Festival");
+
+        let skipped = Builder::new(settings.clone())
+            .days(1, 28)
+            .skip_festival_days(true)
+            .generate()
+            .unwrap();
+        let days: Vec<u32> = skipped.iter().map(|day| day.days_played).collect();
+        // the egg festival and the flower dance
+        let expected: Vec<u32> = (1..=28).filter(|&d| d != 13 && d != 24).collect();
+        assert_eq!(days, expected);
+        for day in &skipped {
+            let same = &all[day.days_played as usize - 1];
+            assert_eq!(format!("{:?}", day), format!("{:?}", same));
+        }
+
+        let cancel = AtomicBool::new(false);
+        let with = Builder::new(settings)
+            .days(1, 28)
+            .skip_festival_days(true)
+            .generate_with(&cancel, |_| {})
+            .unwrap();
+        let with_days: Vec<u32> = with.iter().map(|day| day.days_played).collect();
+        assert_eq!(with_days, expected);
+    }
+
+    /// a mod that always gives the same boost
+    struct FixedBoost(f64);
+
+    impl LuckModel for FixedBoost {
+        fn boost_range(&self, _day: DayNumber) -> (f64, f64) {
+            (1. + self.0, 1. + self.0)
+        }
+
+        fn resolve(&self, _day: DayNumber) -> Option<f64> {
+            Some(1. + self.0)
+        }
+    }
+
+    #[test]
+    fn custom_luck_model_resolves_chance_chests() {
+        let settings = GameSettings {
+            seed: 31,
+            ..Default::default()
+        };
+        let unresolved = range(&settings, 1, 28).unwrap();
+        let (lo, hi) = luck_range(&settings);
+        let resolved_at = |luck: f64| {
+            let resolved = range_with_luck(&settings, 1, 28, &FixedBoost(luck - 1.)).unwrap();
+            for (day, exact) in unresolved.iter().zip(&resolved) {
+                for level in 0..10 {
+                    let [(minl, maxl, _)] = exact.loot[level][..] else {
+                        panic!("{:?}", exact.loot[level]);
+                    };
+                    assert_eq!((minl, maxl), (luck, luck));
+                }
+                assert_eq!(exact.loot, day.at_luck(luck).loot);
+            }
+            resolved
+        };
+        let (unlucky, lucky) = (resolved_at(lo), resolved_at(hi));
+        resolved_at((lo + hi) / 2.);
+        // the boost decides the rarity of some chests
+        let mut upgraded = 0;
+        for (unlucky, lucky) in unlucky.iter().zip(&lucky) {
+            for level in 0..10 {
+                let (a, b) = (&unlucky.loot[level][0].2, &lucky.loot[level][0].2);
+                if a.len() != b.len() {
+                    continue;
+                }
+                upgraded += a
+                    .iter()
+                    .zip(b)
+                    .filter(|pair| matches!(pair, (Goodie::CommonChest(_), Goodie::RareChest(_))))
+                    .count();
+            }
+        }
+        assert!(upgraded > 0);
+    }
+
+    #[test]
+    fn boxed_luck_model() {
+        let settings = GameSettings {
+            seed: 31,
+            ..Default::default()
+        };
+        let model: Box<dyn LuckModel> = Box::new(FixedBoost(0.05));
+        assert_eq!(
+            model.distribution(DayNumber(3)),
+            LuckDistribution::Fixed(1.05)
+        );
+        let by_builder = Builder::new(settings.clone())
+            .days(1, 10)
+            .luck_model(Box::new(FixedBoost(0.05)))
+            .generate()
+            .unwrap();
+        let by_range = range_with_luck(&settings, 1, 10, model.as_ref()).unwrap();
+        assert_eq!(by_builder.len(), by_range.len());
+        for (a, b) in by_builder.iter().zip(&by_range) {
+            assert_eq!((a.days_played, &a.layouts), (b.days_played, &b.layouts));
+            assert_eq!(a.loot, b.loot);
+        }
+        // a resolved day either has the item or doesn't
+        for day in &by_builder {
+            for kind in ChestItemKind::ALL {
+                let p = stats::day_probability(day, kind, model.as_ref());
+                assert!(p == 0. || p == 1., "{}", p);
+            }
+        }
+    }
+
+    #[test]
+    fn cancelled_at_a_third() {
+        let settings = GameSettings {
+            seed: 99,
+            ..Default::default()
+        };
+        let batch = range(&settings, 5, 20).unwrap();
+        let cancel = AtomicBool::new(false);
+        let mut reported = vec![];
+        let result =
+            Builder::new(settings.clone())
+                .days(5, 20)
+                .generate_with(&cancel, |progress| {
+                    reported.push(progress);
+                    if progress >= 0.3 {
+                        cancel.store(true, Ordering::Relaxed);
+                    }
+                });
+        let Err(Error::Cancelled { partial }) = result else {
+            panic!("not cancelled");
+        };
+        // 6 of the 20 days are 30%, the 7th is never started
+        assert_eq!(reported, [0.05, 0.1, 0.15, 0.2, 0.25, 0.3]);
+        let days: Vec<u32> = partial.iter().map(|day| day.days_played).collect();
+        assert_eq!(days, (5..11).collect::<Vec<u32>>());
+        for (day, same) in partial.iter().zip(&batch) {
+            assert_eq!(format!("{:?}", day), format!("{:?}", same));
+        }
+
+        let cancel = AtomicBool::new(false);
+        let mut last = 0.;
+        let all = Builder::new(settings)
+            .days(5, 20)
+            .generate_with(&cancel, |progress| last = progress)
+            .unwrap();
+        assert_eq!(last, 1.);
+        assert_eq!(format!("{:?}", all), format!("{:?}", batch));
+    }
+
+    #[test]
+    fn enumerated_assumptions() {
+        use crate::partial::Flag;
+        let settings = GameSettings {
+            seed: 555,
+            ..Default::default()
+        };
+        let unknown_rng = PartialSettings {
+            legacy_rng: Flag::Unknown,
+            ..PartialSettings::new(settings.clone())
+        };
+        let days = enumerate_day(555, 12, &unknown_rng).unwrap();
+        let assumptions: Vec<Option<bool>> = days.iter().map(|(a, _)| a.legacy_rng).collect();
+        assert_eq!(assumptions, [Some(false), Some(true)]);
+        for (assumption, day) in &days {
+            assert_eq!(day.settings.legacy_rng, assumption.legacy_rng.unwrap());
+            assert_eq!(day.days_played, 12);
+        }
+
+        // the coconut only matters on some days
+        let unknown_coconut = PartialSettings {
+            cracked_golden_coconut: Flag::Unknown,
+            ..PartialSettings::new(settings.clone())
+        };
+        let (mut matters, mut doesnt) = (0, 0);
+        for days_played in 1..60 {
+            let days = enumerate_day(555, days_played, &unknown_coconut).unwrap();
+            let known = [false, true].map(|cracked| {
+                DayForecast::generate(
+                    &GameSettings {
+                        days_played,
+                        ..settings.clone()
+                    }
+                    .with_cracked_golden_coconut(cracked),
+                )
+            });
+            if known[0].loot == known[1].loot {
+                doesnt += 1;
+                let [(assumption, day)] = &days[..] else {
+                    panic!("{:?}", days);
+                };
+                assert_eq!(*assumption, SettingsAssumption::default());
+                assert_eq!(day.loot, known[0].loot);
+            } else {
+                matters += 1;
+                assert_eq!(days.len(), 2);
+                for ((assumption, day), (cracked, known)) in
+                    days.iter().zip([false, true].iter().zip(&known))
+                {
+                    assert_eq!(assumption.cracked_golden_coconut, Some(*cracked));
+                    assert_eq!(day.loot, known.loot);
+                }
+            }
+        }
+        assert!(matters > 0 && doesnt > 0);
+    }
+}
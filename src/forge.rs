@@ -0,0 +1,90 @@
+//! What rerolling a weapon's innate enchantments at the forge (dragon tooth + cinder shards)
+//! will give.
+//!
+//! The forge seeds every reroll with Utility.CreateRandom from a few game values, one of them a
+//! counter that goes up with each enchanting. Which values those are depends on the game version,
+//! so they're passed in as they are instead of being guessed here.
+
+use crate::loot::{Enchants, enchant_item};
+use crate::weapons::WeaponKind;
+use crate::{GameSettings, rng};
+
+/// The values the game mixes into the seed of a reroll, at most 5 of them
+#[derive(Clone, Debug, PartialEq)]
+pub struct RerollSeed {
+    /// in the order the game passes them
+    pub components: Vec<f64>,
+    /// index of the counter in `components`, it goes up by one every reroll
+    pub counter: usize,
+}
+
+/// Enchantments from the next `count` rerolls, starting with the seed as given
+pub fn reroll_sequence(
+    seed: &RerollSeed,
+    weapon: WeaponKind,
+    count: usize,
+    settings: &GameSettings,
+) -> Vec<Enchants> {
+    let params = weapon.params();
+    reroll_sequence_for_stats(seed, params.level, params.speed, count, settings)
+}
+
+/// reroll_sequence for any weapon, given its item level (MeleeWeapon.getItemLevel) and speed.
+/// Weapons above level 10 never roll defense, and speed 4 or more can only roll speed 1.
+pub fn reroll_sequence_for_stats(
+    seed: &RerollSeed,
+    level: i32,
+    speed: i32,
+    count: usize,
+    settings: &GameSettings,
+) -> Vec<Enchants> {
+    let mut components = seed.components.clone();
+    (0..count)
+        .map(|_| {
+            let mut rng =
+                rng::DotnetRng::new(rng::stardew_seed_mix(settings.legacy_rng, &components));
+            if let Some(counter) = components.get_mut(seed.counter) {
+                *counter += 1.;
+            }
+            enchant_item(&mut rng, level, speed, true)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compact(rolls: &[Enchants]) -> Vec<String> {
+        rolls.iter().map(|e| e.to_compact_string()).collect()
+    }
+
+    #[test]
+    fn three_rerolls() {
+        let seed = RerollSeed {
+            components: vec![12345., 7., 3.],
+            counter: 2,
+        };
+        let settings = GameSettings::default();
+        let weapon = WeaponKind::DragontoothCutlass;
+        let rolls = reroll_sequence(&seed, weapon, 3, &settings);
+        assert_eq!(compact(&rolls), ["w-1.cp25", "cp75", "ss"]);
+        let legacy = GameSettings {
+            legacy_rng: true,
+            ..settings
+        };
+        let legacy_rolls = reroll_sequence(&seed, weapon, 3, &legacy);
+        assert_eq!(compact(&legacy_rolls), ["w-4.ss", "cp75", "a2"]);
+        // waiting a reroll gives the rest of the sequence
+        let later = RerollSeed {
+            components: vec![12345., 7., 4.],
+            counter: 2,
+        };
+        assert_eq!(reroll_sequence(&later, weapon, 2, &settings), rolls[1..]);
+        let params = weapon.params();
+        assert_eq!(
+            reroll_sequence_for_stats(&seed, params.level, params.speed, 3, &settings),
+            rolls
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! What chests gave on days that are already over, e.g. to settle who got the phoenix ring. The
+//! loot only depends on the settings, so it's the normal forecast, but with the settings the save
+//! had on that day rather than the current ones.
+
+use crate::date::DayNumber;
+use crate::forecast::DayForecast;
+use crate::{GameSettings, GameVersion, Result};
+
+/// Something about the save that changed at some point
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingsChange {
+    CrackedGoldenCoconut,
+    /// updated to this version
+    Version(GameVersion),
+    SpecialCharm,
+    Caldera,
+    MaxLuckLevel(u32),
+    /// got the ostrich incubator
+    OstrichIncubator,
+}
+
+impl SettingsChange {
+    fn apply(&self, settings: GameSettings) -> Result<GameSettings> {
+        Ok(match self {
+            SettingsChange::CrackedGoldenCoconut => settings.with_cracked_golden_coconut(true),
+            SettingsChange::Version(version) => settings.with_version(version)?,
+            SettingsChange::SpecialCharm => GameSettings {
+                special_charm: true,
+                ..settings
+            },
+            SettingsChange::Caldera => GameSettings {
+                has_caldera: true,
+                ..settings
+            },
+            SettingsChange::MaxLuckLevel(lvl) => GameSettings {
+                max_luck_lvl: *lvl,
+                ..settings
+            },
+            SettingsChange::OstrichIncubator => GameSettings {
+                has_ostrich_incubator: true,
+                ..settings
+            },
+        })
+    }
+}
+
+/// The settings of a save over time: how it started, and the days things changed on
+#[derive(Clone, Debug)]
+pub struct SettingsTimeline {
+    pub initial: GameSettings,
+    /// (days_played, change), sorted by day. changes on the same day stay in the order they were
+    /// added.
+    changes: Vec<(u32, SettingsChange)>,
+}
+
+impl SettingsTimeline {
+    pub fn new(initial: GameSettings) -> Self {
+        SettingsTimeline {
+            initial,
+            changes: vec![],
+        }
+    }
+
+    /// Adds a change that applies from `day` on, including the chests of that day. Something that
+    /// happened after the day's chests were opened should go on the next day.
+    pub fn change(mut self, day: impl Into<DayNumber>, change: SettingsChange) -> Self {
+        let day = day.into().0;
+        let at = self.changes.partition_point(|(d, _)| *d <= day);
+        self.changes.insert(at, (day, change));
+        self
+    }
+
+    pub fn changes(&self) -> &[(u32, SettingsChange)] {
+        &self.changes
+    }
+
+    /// The settings for `day`, with days_played set to it. Fails for a version change to an
+    /// Unknown version.
+    pub fn settings_on(&self, day: impl Into<DayNumber>) -> Result<GameSettings> {
+        let day = day.into().0;
+        let mut settings = GameSettings {
+            days_played: day,
+            ..self.initial
+        };
+        for (_, change) in self.changes.iter().take_while(|(d, _)| *d <= day) {
+            settings = change.apply(settings)?;
+        }
+        Ok(settings)
+    }
+}
+
+/// The forecast for a past day of the save with `game_id`, with the settings the timeline has for
+/// that day. The seed of the timeline is replaced by the game id.
+pub fn reconstruct(
+    game_id: u64,
+    past_day: impl Into<DayNumber>,
+    timeline: &SettingsTimeline,
+) -> Result<DayForecast> {
+    let settings = GameSettings {
+        seed: GameSettings::for_game_id(game_id)?.seed,
+        ..timeline.settings_on(past_day)?
+    };
+    Ok(DayForecast::generate(&settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use crate::loot::ChestItemKind;
+    use crate::search::day_hits;
+
+    const GAME_ID: u64 = 123456789;
+
+    fn coconuts(day: &DayForecast) -> usize {
+        day_hits(day, ChestItemKind::GoldenCoconut).len()
+            + day_hits(day, ChestItemKind::GoldenCoconuts).len()
+    }
+
+    #[test]
+    fn before_and_after_the_coconut() {
+        let timeline = |cracked_on: u32| {
+            SettingsTimeline::new(GameSettings::default())
+                .change(cracked_on, SettingsChange::CrackedGoldenCoconut)
+        };
+        // the first day a cracked coconut changes the chests
+        let day = (1..=112)
+            .find(|&day| coconuts(&reconstruct(GAME_ID, day, &timeline(1)).unwrap()) > 0)
+            .expect("a coconut in the first year");
+        let after = reconstruct(GAME_ID, day, &timeline(day)).unwrap();
+        assert!(coconuts(&after) > 0);
+        assert!(after.settings.golden_coconut_in_common());
+        // cracked the next day, so that day still had the chests without coconuts
+        let before = reconstruct(GAME_ID, day, &timeline(day + 1)).unwrap();
+        assert_eq!(coconuts(&before), 0);
+        assert!(!before.settings.golden_coconuts_in_rare());
+        let uncracked = DayForecast::generate(&GameSettings {
+            seed: GAME_ID as i32,
+            days_played: day,
+            ..Default::default()
+        });
+        assert_eq!(
+            format!("{:?}", before.loot),
+            format!("{:?}", uncracked.loot)
+        );
+    }
+
+    #[test]
+    fn changes_apply_in_order() {
+        let timeline = SettingsTimeline::new(GameSettings::default())
+            .change(20, SettingsChange::MaxLuckLevel(2))
+            .change(10, SettingsChange::MaxLuckLevel(1))
+            .change(20, SettingsChange::MaxLuckLevel(3))
+            .change(30, SettingsChange::Version(GameVersion::V1_6_4))
+            .change(
+                40,
+                SettingsChange::Version(GameVersion::Unknown("1.7".into())),
+            );
+        let days: Vec<u32> = timeline.changes().iter().map(|(d, _)| *d).collect();
+        assert_eq!(days, [10, 20, 20, 30, 40]);
+        let luck = |day: u32| timeline.settings_on(day).unwrap().max_luck_lvl;
+        assert_eq!([luck(9), luck(10), luck(19), luck(20)], [0, 1, 1, 3]);
+        assert!(!timeline.settings_on(29).unwrap().post_1_6_4);
+        assert!(timeline.settings_on(30).unwrap().post_1_6_4);
+        assert_eq!(timeline.settings_on(35).unwrap().days_played, 35);
+        assert!(matches!(
+            reconstruct(GAME_ID, 40, &timeline),
+            Err(Error::UnsupportedGameVersion(_))
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::date::{DayNumber, GameDate};
+use crate::forecast::DayForecast;
+use crate::render::{NumberFormat, Render, RenderOptions};
+use crate::{ProbabilityRange, display_luck, format_icon, is_monster_floor, is_mushroom_floor};
+use std::fmt::Write;
+
+pub fn day_name(days_played: u32) -> String {
+    GameDate::from(DayNumber(days_played)).to_string()
+}
+
+fn format_layout(level: usize, layout: u32) -> String {
+    let displayname = if is_mushroom_floor(layout) {
+        format!("{} {}", format_icon("magma_cap"), layout)
+    } else if is_monster_floor(layout) {
+        format!("{} {}", format_icon("monster_floor"), layout)
+    } else {
+        layout.to_string()
+    };
+    format!(
+        "<button data-lvl=\"{}\" data-layout=\"{}\" class=\"layout-btn\">{}</button>",
+        level, layout, displayname
+    )
+}
+
+/// Table of the possible layouts for each floor, with buttons for the map preview
+pub fn render_layouts(layouts: &[ProbabilityRange<u32>; 10]) -> String {
+    render_layouts_with(layouts, &NumberFormat::default())
+}
+
+pub fn render_layouts_with(layouts: &[ProbabilityRange<u32>; 10], nf: &NumberFormat) -> String {
+    let layouts_disp = String::from_iter(layouts.iter().enumerate().map(|(lvl, this_layouts)| {
+        let mut out = String::from("<td>");
+        if this_layouts.len() == 1 {
+            out += &format_layout(lvl, this_layouts[0].2);
+        } else {
+            let formatted: Vec<_> = this_layouts
+                .iter()
+                .map(|&(a, b, c)| {
+                    format!(
+                        "<span title=\"luck {} to {}\">{}</span>",
+                        nf.luck(display_luck(a)),
+                        nf.luck(display_luck(b)),
+                        format_layout(lvl, c)
+                    )
+                })
+                .collect();
+            out += &formatted.join(" / ");
+        }
+        out += "</td>";
+        out
+    }));
+    let mut layouts_full = String::from("<table><tr>");
+    for i in 0..10 {
+        write!(layouts_full, "<td>{}</td>", i).unwrap();
+    }
+    layouts_full += "</tr><tr>";
+    layouts_full += &layouts_disp;
+    layouts_full += "</tr></table>";
+    layouts_full
+}
+
+/// Loot overview of all floors, skipping floors that never have anything
+pub fn render_loot(day: &DayForecast) -> String {
+    day.try_render_to_string(&RenderOptions::default())
+        .expect("the default options have no output limit")
+}
+
+/// Loot overview for several days, one section per day
+pub fn render_forecast(forecast: &[DayForecast]) -> String {
+    forecast
+        .try_render_to_string(&RenderOptions::default())
+        .expect("the default options have no output limit")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts_with_a_number_format() {
+        let mut layouts: [ProbabilityRange<u32>; 10] = std::array::from_fn(|_| vec![(0.9, 1.1, 2)]);
+        layouts[3] = vec![(0.9, 1.02, 12), (1.02, 1.1, 40)];
+        let html = render_layouts(&layouts);
+        assert!(html.starts_with("<table><tr><td>0</td><td>1</td>"));
+        assert_eq!(html.matches("class=\"layout-btn\"").count(), 11);
+        assert_eq!(html.matches("<span title=").count(), 2);
+        let title = format!(
+            "<span title=\"luck {:.4} to {:.4}\"><button data-lvl=\"3\" data-layout=\"12\"",
+            display_luck(0.9),
+            display_luck(1.02)
+        );
+        assert!(html.contains(&title), "{}", html);
+        let german = NumberFormat {
+            decimal_sep: ',',
+            luck_decimals: 2,
+            ..Default::default()
+        };
+        let html = render_layouts_with(&layouts, &german);
+        let title = format!(
+            "<span title=\"luck {} to {}\">",
+            german.luck(display_luck(1.02)),
+            german.luck(display_luck(1.1))
+        );
+        assert!(title.contains(','));
+        assert!(html.contains(&title), "{}", html);
+    }
+}
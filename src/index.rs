@@ -0,0 +1,525 @@
+//! Which days of a year have each item, precomputed as bitsets so "when's the next one" and
+//! heatmap counts don't have to generate the days again. Built from the seed plan with only the
+//! items rolled, no enchantments. RangeIndex puts several years together for "what's there
+//! between these days" queries.
+
+use crate::cache::{Decoder, encode_settings, len_field};
+use crate::date::{DayNumber, GameDate, Season};
+use crate::loot::{ChestItemKind, FloorLevel, LootTables, Rarity, rare_luck};
+use crate::luck::{ThresholdSplit, luck_range, threshold_split};
+use crate::plan::{FloorPlan, PlannedGoodie, SeedPlan};
+use crate::{Error, GameSettings, ProbabilityRange, Result};
+use std::ops::Range;
+
+const INDEX_MAGIC: &[u8; 4] = b"SDVI";
+// 2: the quantity multipliers in the settings
+const INDEX_VERSION: u8 = 2;
+const DAYS_PER_YEAR: u32 = 4 * 28;
+
+/// Per item kind, the days of one year with it. Bit `i` is the `i`th day of the year.
+#[derive(Clone, Debug)]
+pub struct YearIndex {
+    /// the settings it was built with, days_played is the first day of the year
+    pub settings: GameSettings,
+    pub year: u32,
+    /// by ChestItemKind::ALL. days where some floor has the item at every luck
+    guaranteed: Vec<u128>,
+    /// days where it takes the right luck, the same days as search hits that aren't guaranteed
+    /// (and no guaranteed ones)
+    chance: Vec<u128>,
+}
+
+/// which kinds a floor has at every luck and at some luck, as bits by ChestItemKind::ALL
+fn floor_kinds(
+    plan: &ProbabilityRange<[FloorPlan; 10]>,
+    level: FloorLevel,
+    settings: &GameSettings,
+    luck: (f64, f64),
+) -> Result<(u32, u32)> {
+    let tables = LootTables::vanilla();
+    let bit = |kind: ChestItemKind| 1 << kind as u32;
+    let (mut everywhere, mut somewhere) = (!0u32, 0u32);
+    for (minl, maxl, floors) in plan {
+        let (minl, maxl) = (minl.max(luck.0), maxl.min(luck.1));
+        if minl > maxl {
+            continue;
+        }
+        // kinds in the whole range, and per kind the highest luck with it as a common and the
+        // lowest luck with it as a rare, like the chance chests get split up
+        let mut whole = 0;
+        let mut common_up_to = [f64::NEG_INFINITY; ChestItemKind::ALL.len()];
+        let mut rare_from = [f64::INFINITY; ChestItemKind::ALL.len()];
+        for goodie in &floors[level.get() as usize].goodies {
+            let PlannedGoodie::Chest(seed) = *goodie else {
+                continue;
+            };
+            let r = rare_luck(seed, level);
+            let kind = |rarity| tables.generate_kind(rarity, seed, settings);
+            match threshold_split(r, (minl, maxl)) {
+                ThresholdSplit::Past => whole |= bit(kind(Rarity::Rare)?),
+                ThresholdSplit::NotPast => whole |= bit(kind(Rarity::Common)?),
+                ThresholdSplit::Within => {
+                    let (common, rare) = (kind(Rarity::Common)?, kind(Rarity::Rare)?);
+                    let c = &mut common_up_to[common as usize];
+                    *c = c.max(r);
+                    let c = &mut rare_from[rare as usize];
+                    *c = c.min(r);
+                    somewhere |= bit(common) | bit(rare);
+                }
+            }
+        }
+        let covered = ChestItemKind::ALL
+            .iter()
+            .filter(|&&k| rare_from[k as usize] <= common_up_to[k as usize])
+            .fold(whole, |acc, &k| acc | bit(k));
+        everywhere &= covered;
+        somewhere |= whole;
+    }
+    Ok((everywhere & somewhere, somewhere))
+}
+
+impl YearIndex {
+    /// Indexes `year` of `game_seed`, for the luck range of `settings`
+    pub fn build(game_seed: i32, year: u32, settings: &GameSettings) -> Result<Self> {
+        let start = DayNumber::from(GameDate::new(year, Season::Spring, 1)?);
+        let settings = GameSettings {
+            seed: game_seed,
+            days_played: start.0,
+            ..settings.clone()
+        };
+        let luck = luck_range(&settings);
+        let plan = SeedPlan::new(&settings, start, DAYS_PER_YEAR)?;
+        let mut guaranteed = vec![0u128; ChestItemKind::ALL.len()];
+        let mut chance = vec![0u128; ChestItemKind::ALL.len()];
+        for (i, day) in plan.days.iter().enumerate() {
+            let day_settings = GameSettings {
+                days_played: day.days_played,
+                ..settings
+            };
+            let (mut everywhere, mut somewhere) = (0, 0);
+            for level in FloorLevel::ALL.into_iter().filter(|l| l.has_chests()) {
+                let (e, s) = floor_kinds(&day.floors, level, &day_settings, luck)?;
+                everywhere |= e;
+                somewhere |= s;
+            }
+            for kind in ChestItemKind::ALL {
+                let k = kind as usize;
+                if everywhere & 1 << k != 0 {
+                    guaranteed[k] |= 1 << i;
+                } else if somewhere & 1 << k != 0 {
+                    chance[k] |= 1 << i;
+                }
+            }
+        }
+        Ok(YearIndex {
+            settings,
+            year,
+            guaranteed,
+            chance,
+        })
+    }
+
+    /// days_played of the first day of the year
+    pub fn start_day(&self) -> u32 {
+        self.settings.days_played
+    }
+
+    /// days_played of every day of the year with `kind`, with whether it's guaranteed
+    pub fn days(&self, kind: ChestItemKind) -> impl Iterator<Item = (u32, bool)> + '_ {
+        let (guaranteed, chance) = (self.guaranteed[kind as usize], self.chance[kind as usize]);
+        (0..DAYS_PER_YEAR)
+            .filter(move |i| (guaranteed | chance) & 1 << i != 0)
+            .map(move |i| (self.start_day() + i, guaranteed & 1 << i != 0))
+    }
+
+    /// The first day from `day` on (`day` included) in this year with `kind`, with whether it's
+    /// guaranteed. None if the rest of the year doesn't have it.
+    pub fn first_after(
+        &self,
+        kind: ChestItemKind,
+        day: impl Into<DayNumber>,
+    ) -> Option<(u32, bool)> {
+        let day = day.into().0;
+        self.days(kind).find(|&(d, _)| d >= day)
+    }
+
+    /// (guaranteed, chance) days with `kind` in `range` of days_played, the part outside of the
+    /// year counts as nothing
+    pub fn count_in(&self, kind: ChestItemKind, range: Range<u32>) -> (u32, u32) {
+        let start = self.start_day();
+        let from = range.start.clamp(start, start + DAYS_PER_YEAR) - start;
+        let to = range.end.clamp(start, start + DAYS_PER_YEAR) - start;
+        if from >= to {
+            return (0, 0);
+        }
+        let mask = (!0u128 >> (128 - to)) & (!0u128 << from);
+        (
+            (self.guaranteed[kind as usize] & mask).count_ones(),
+            (self.chance[kind as usize] & mask).count_ones(),
+        )
+    }
+
+    /// `SDVI`, version byte, the settings as in snapshots, the year (u32), the number of kinds
+    /// (u8) and per kind the guaranteed and chance bitsets (u128). All little endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = INDEX_MAGIC.to_vec();
+        out.push(INDEX_VERSION);
+        encode_settings(&mut out, &self.settings);
+        out.extend_from_slice(&self.year.to_le_bytes());
+        out.push(self.guaranteed.len() as u8);
+        for (g, c) in self.guaranteed.iter().zip(&self.chance) {
+            out.extend_from_slice(&g.to_le_bytes());
+            out.extend_from_slice(&c.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let corrupt = |msg: &str| Error::CorruptSnapshot(msg.to_string());
+        let rest = data
+            .strip_prefix(INDEX_MAGIC)
+            .ok_or_else(|| corrupt("not a year index"))?;
+        let mut d = Decoder(rest);
+        if d.u8()? != INDEX_VERSION {
+            return Err(corrupt("unsupported year index version"));
+        }
+        let settings = d.settings()?;
+        let year = d.u32()?;
+        if d.u8()? as usize != ChestItemKind::ALL.len() {
+            return Err(corrupt("wrong number of item kinds"));
+        }
+        let mut guaranteed = vec![];
+        let mut chance = vec![];
+        for _ in ChestItemKind::ALL {
+            let (g, c) = (d.u128()?, d.u128()?);
+            if (g | c) >> DAYS_PER_YEAR != 0 || g & c != 0 {
+                return Err(corrupt("bad day bits"));
+            }
+            guaranteed.push(g);
+            chance.push(c);
+        }
+        if !d.0.is_empty() {
+            return Err(corrupt("trailing bytes after the index"));
+        }
+        Ok(YearIndex {
+            settings,
+            year,
+            guaranteed,
+            chance,
+        })
+    }
+}
+
+/// One day with an item in a RangeIndex
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IndexHit {
+    pub days_played: u32,
+    pub kind: ChestItemKind,
+    /// false if it takes the right luck
+    pub guaranteed: bool,
+}
+
+/// Which hits RangeIndex::query gives
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HitFilter {
+    /// empty for all of them
+    pub kinds: Vec<ChestItemKind>,
+    /// only kinds in this chest table (with the index's settings)
+    pub rarity: Option<Rarity>,
+    pub guaranteed_only: bool,
+}
+
+/// Several YearIndexes of the same seed and settings, with the days of each kind sorted for range
+/// queries: a query binary searches the start of the range per kind and then only goes through
+/// the hits, instead of every day of every year.
+#[derive(Clone, Debug)]
+pub struct RangeIndex {
+    /// by first day
+    years: Vec<YearIndex>,
+    /// by ChestItemKind::ALL, the days with the kind and whether they're guaranteed, by day
+    days: Vec<Vec<(u32, bool)>>,
+}
+
+impl RangeIndex {
+    /// IndexSettingsMismatch for a year with other settings (or seed) than the first one or
+    /// that's there twice. The years don't have to be consecutive.
+    pub fn new(mut years: Vec<YearIndex>) -> Result<Self> {
+        years.sort_by_key(|y| y.year);
+        if let Some(first) = years.first() {
+            let hash = first.settings.save_hash();
+            for (i, y) in years.iter().enumerate() {
+                if y.settings.save_hash() != hash || (i > 0 && years[i - 1].year == y.year) {
+                    return Err(Error::IndexSettingsMismatch(y.year));
+                }
+            }
+        }
+        let days = ChestItemKind::ALL
+            .iter()
+            .map(|&kind| years.iter().flat_map(|y| y.days(kind)).collect())
+            .collect();
+        Ok(RangeIndex { years, days })
+    }
+
+    /// YearIndex::build for each of `years`
+    pub fn build(game_seed: i32, years: Range<u32>, settings: &GameSettings) -> Result<Self> {
+        let years = years
+            .map(|year| YearIndex::build(game_seed, year, settings))
+            .collect::<Result<_>>()?;
+        Self::new(years)
+    }
+
+    pub fn years(&self) -> &[YearIndex] {
+        &self.years
+    }
+
+    /// The hits in `range` of days_played that pass `filter`, by day and then by kind. Days
+    /// outside of the indexed years have nothing.
+    pub fn query(&self, range: Range<u32>, filter: &HitFilter) -> Vec<IndexHit> {
+        let Some(first) = self.years.first() else {
+            return vec![];
+        };
+        let in_table = filter
+            .rarity
+            .map(|rarity| LootTables::vanilla().obtainable(rarity, &first.settings));
+        let mut out = vec![];
+        for kind in ChestItemKind::ALL {
+            if !filter.kinds.is_empty() && !filter.kinds.contains(&kind) {
+                continue;
+            }
+            if in_table
+                .as_ref()
+                .is_some_and(|kinds| !kinds.contains(&kind))
+            {
+                continue;
+            }
+            let days = &self.days[kind as usize];
+            let from = days.partition_point(|&(d, _)| d < range.start);
+            out.extend(
+                days[from..]
+                    .iter()
+                    .take_while(|&&(d, _)| d < range.end)
+                    .filter(|&&(_, guaranteed)| guaranteed || !filter.guaranteed_only)
+                    .map(|&(days_played, guaranteed)| IndexHit {
+                        days_played,
+                        kind,
+                        guaranteed,
+                    }),
+            );
+        }
+        out.sort();
+        out
+    }
+
+    /// GameSettings::save_hash of the years, None without any
+    pub fn save_hash(&self) -> Option<u64> {
+        self.years.first().map(|y| y.settings.save_hash())
+    }
+
+    /// The section of a snapshot with the index (see cache::write_snapshot_with_index): the
+    /// number of years (u32) and per year the length (u32) and bytes of YearIndex::to_bytes.
+    /// Only the bitsets are stored, the sorted days are made again when it's read.
+    pub(crate) fn encode(&self, out: &mut Vec<u8>) -> Result<()> {
+        out.extend_from_slice(&len_field::<u32>(self.years.len(), "years")?.to_le_bytes());
+        for year in &self.years {
+            let bytes = year.to_bytes();
+            out.extend_from_slice(&len_field::<u32>(bytes.len(), "bytes in a year")?.to_le_bytes());
+            out.extend_from_slice(&bytes);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn decode(data: &[u8]) -> Result<Self> {
+        let corrupt = |msg: &str| Error::CorruptSnapshot(msg.to_string());
+        let mut d = Decoder(data);
+        let mut years = vec![];
+        for _ in 0..d.u32()? {
+            let len = d.u32()? as usize;
+            if d.0.len() < len {
+                return Err(corrupt("data ends early"));
+            }
+            let (year, rest) = d.0.split_at(len);
+            years.push(YearIndex::from_bytes(year)?);
+            d.0 = rest;
+        }
+        if !d.0.is_empty() {
+            return Err(corrupt("trailing bytes after the index"));
+        }
+        Self::new(years)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecast::DayForecast;
+    use crate::rng::DotnetRng;
+    use crate::search::day_hits;
+
+    fn settings() -> GameSettings {
+        GameSettings {
+            seed: 4242,
+            has_caldera: true,
+            ..Default::default()
+        }
+    }
+
+    /// RangeIndex::query going through every day of every year
+    fn scan(index: &RangeIndex, range: Range<u32>, filter: &HitFilter) -> Vec<IndexHit> {
+        let mut out: Vec<IndexHit> = index
+            .years()
+            .iter()
+            .flat_map(|year| {
+                ChestItemKind::ALL.into_iter().flat_map(move |kind| {
+                    year.days(kind)
+                        .map(move |(days_played, guaranteed)| IndexHit {
+                            days_played,
+                            kind,
+                            guaranteed,
+                        })
+                })
+            })
+            .filter(|hit| range.contains(&hit.days_played))
+            .filter(|hit| filter.kinds.is_empty() || filter.kinds.contains(&hit.kind))
+            .filter(|hit| {
+                filter.rarity.is_none_or(|rarity| {
+                    LootTables::vanilla()
+                        .obtainable(rarity, &index.years()[0].settings)
+                        .contains(&hit.kind)
+                })
+            })
+            .filter(|hit| hit.guaranteed || !filter.guaranteed_only)
+            .collect();
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn year_index_matches_the_forecast() {
+        let year = YearIndex::build(4242, 1, &settings()).unwrap();
+        for days_played in 1..=DAYS_PER_YEAR {
+            let day = DayForecast::generate(&GameSettings {
+                days_played,
+                ..year.settings
+            });
+            for kind in ChestItemKind::ALL {
+                let hits = day_hits(&day, kind);
+                let expected = match hits.iter().any(|h| h.guaranteed) {
+                    true => Some(true),
+                    false if !hits.is_empty() => Some(false),
+                    false => None,
+                };
+                let indexed = year.days(kind).find(|&(d, _)| d == days_played);
+                assert_eq!(
+                    indexed.map(|d| d.1),
+                    expected,
+                    "day {} {:?}",
+                    days_played,
+                    kind
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn queries_match_a_scan() {
+        let index = RangeIndex::build(4242, 1..4, &settings()).unwrap();
+        let mut rng = DotnetRng::new(7);
+        for i in 0..200 {
+            let start = rng.next_range(4 * DAYS_PER_YEAR as i32) as u32;
+            let range = start..start + rng.next_range(150) as u32;
+            let kinds = (0..rng.next_range(4))
+                .map(|_| {
+                    ChestItemKind::ALL[rng.next_range(ChestItemKind::ALL.len() as i32) as usize]
+                })
+                .collect();
+            let filter = HitFilter {
+                kinds,
+                rarity: [None, Some(Rarity::Common), Some(Rarity::Rare)][i % 3],
+                guaranteed_only: i % 4 == 0,
+            };
+            assert_eq!(
+                index.query(range.clone(), &filter),
+                scan(&index, range.clone(), &filter),
+                "{:?} {:?}",
+                range,
+                filter
+            );
+        }
+    }
+
+    #[test]
+    fn years_have_to_match() {
+        let settings = settings();
+        let year = |seed, year| YearIndex::build(seed, year, &settings).unwrap();
+        assert!(matches!(
+            RangeIndex::new(vec![year(4242, 2), year(4242, 1), year(4242, 2)]),
+            Err(Error::IndexSettingsMismatch(2))
+        ));
+        assert!(matches!(
+            RangeIndex::new(vec![year(4242, 1), year(4244, 2)]),
+            Err(Error::IndexSettingsMismatch(2))
+        ));
+        let index = RangeIndex::new(vec![year(4242, 3), year(4242, 1)]).unwrap();
+        let years: Vec<u32> = index.years().iter().map(|y| y.year).collect();
+        assert_eq!(years, [1, 3]);
+        // the gap has nothing
+        let second = DAYS_PER_YEAR + 1..2 * DAYS_PER_YEAR + 1;
+        assert!(index.query(second, &HitFilter::default()).is_empty());
+    }
+
+    #[test]
+    fn encoded_roundtrip() {
+        let index = RangeIndex::build(4242, 1..3, &settings()).unwrap();
+        let mut out = vec![];
+        index.encode(&mut out).unwrap();
+        let decoded = RangeIndex::decode(&out).unwrap();
+        let all = HitFilter::default();
+        assert_eq!(decoded.query(0..1000, &all), index.query(0..1000, &all));
+        assert!(matches!(
+            RangeIndex::decode(&out[..out.len() - 1]),
+            Err(Error::CorruptSnapshot(_))
+        ));
+    }
+
+    #[test]
+    fn year_index_queries() {
+        let year = YearIndex::build(4242, 2, &settings()).unwrap();
+        let start = year.start_day();
+        assert_eq!(start, DAYS_PER_YEAR + 1);
+        for kind in ChestItemKind::ALL {
+            let days: Vec<(u32, bool)> = year.days(kind).collect();
+            // first_after is the next of the days from anywhere in the year
+            for day in start..start + DAYS_PER_YEAR {
+                assert_eq!(
+                    year.first_after(kind, day),
+                    days.iter().copied().find(|&(d, _)| d >= day)
+                );
+            }
+            let guaranteed = days.iter().filter(|d| d.1).count() as u32;
+            assert_eq!(
+                year.count_in(kind, 0..10_000),
+                (guaranteed, days.len() as u32 - guaranteed)
+            );
+            assert_eq!(year.count_in(kind, 0..start), (0, 0));
+        }
+    }
+
+    #[test]
+    fn year_index_roundtrip() {
+        let year = YearIndex::build(4242, 1, &settings()).unwrap();
+        let bytes = year.to_bytes();
+        let decoded = YearIndex::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.year, 1);
+        assert_eq!(
+            decoded.settings.behavior_hash(),
+            year.settings.behavior_hash()
+        );
+        for kind in ChestItemKind::ALL {
+            assert!(decoded.days(kind).eq(year.days(kind)));
+        }
+        assert!(matches!(
+            YearIndex::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::CorruptSnapshot(_))
+        ));
+    }
+}
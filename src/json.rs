@@ -0,0 +1,309 @@
+//! Just enough json writing for the exporters, so we don't need serde in the wasm build
+
+use std::fmt::Write;
+
+pub fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// json has no NaN / infinity, those become null
+pub fn number(x: f64) -> String {
+    if x.is_finite() {
+        x.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Parsed json, for re-writing it canonically and for checking what the exporters write in the
+/// tests. Objects keep their keys in order and numbers are f64, like in javascript.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+/// how deep arrays and objects can nest, so that parsing can't run out of stack
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    rest: &'a str,
+    /// arrays and objects the parser is in
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        self.rest = self.rest.trim_start_matches([' ', '\t', '\n', '\r']);
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(format!("expected {} at {:?}", token, self.excerpt()))
+        }
+    }
+
+    fn excerpt(&self) -> String {
+        self.rest.chars().take(20).collect()
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_ws();
+        let Some(c) = self.rest.chars().next() else {
+            return Err("unexpected end".to_string());
+        };
+        match c {
+            '{' => self.nested(Self::object),
+            '[' => self.nested(Self::array),
+            '"' => Ok(Value::String(self.string()?)),
+            _ if self.eat("null") => Ok(Value::Null),
+            _ if self.eat("true") => Ok(Value::Bool(true)),
+            _ if self.eat("false") => Ok(Value::Bool(false)),
+            _ => {
+                let end = self
+                    .rest
+                    .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+                    .unwrap_or(self.rest.len());
+                let x = self.rest[..end]
+                    .parse::<f64>()
+                    .ok()
+                    // 1e999 parses as infinity, which json has no way to write
+                    .filter(|x| x.is_finite())
+                    .ok_or_else(|| format!("invalid value at {:?}", self.excerpt()))?;
+                self.rest = &self.rest[end..];
+                Ok(Value::Number(x))
+            }
+        }
+    }
+
+    /// `parse` one array or object deeper, at most MAX_DEPTH
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Value, String>) -> Result<Value, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(format!("nested more than {} deep", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    /// at the `{`
+    fn object(&mut self) -> Result<Value, String> {
+        self.rest = &self.rest[1..];
+        let mut fields = vec![];
+        if self.eat("}") {
+            return Ok(Value::Object(fields));
+        }
+        loop {
+            self.skip_ws();
+            let key = self.string()?;
+            self.expect(":")?;
+            fields.push((key, self.value()?));
+            if self.eat("}") {
+                return Ok(Value::Object(fields));
+            }
+            self.expect(",")?;
+        }
+    }
+
+    /// at the `[`
+    fn array(&mut self) -> Result<Value, String> {
+        self.rest = &self.rest[1..];
+        let mut items = vec![];
+        if self.eat("]") {
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            if self.eat("]") {
+                return Ok(Value::Array(items));
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let Some(mut rest) = self.rest.strip_prefix('"') else {
+            return Err(format!("expected a string at {:?}", self.excerpt()));
+        };
+        let mut out = String::new();
+        loop {
+            let mut chars = rest.chars();
+            let c = chars.next().ok_or("unterminated string")?;
+            rest = chars.as_str();
+            match c {
+                '"' => break,
+                '\\' => {
+                    let mut chars = rest.chars();
+                    let escaped = chars.next().ok_or("unterminated string")?;
+                    rest = chars.as_str();
+                    out.push(match escaped {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'u' => {
+                            let (code, unit) = unicode_escape(rest)?;
+                            rest = unit;
+                            code
+                        }
+                        c @ ('"' | '\\' | '/') => c,
+                        c => return Err(format!("invalid escape \\{}", c)),
+                    });
+                }
+                c => out.push(c),
+            }
+        }
+        self.rest = rest;
+        Ok(out)
+    }
+}
+
+/// the char of a \u escape (after the u), with what's after it. surrogate pairs are two escapes.
+fn unicode_escape(rest: &str) -> Result<(char, &str), String> {
+    let hex = |s: &str| {
+        s.get(..4)
+            .and_then(|h| u32::from_str_radix(h, 16).ok())
+            .ok_or_else(|| "invalid \\u escape".to_string())
+    };
+    let high = hex(rest)?;
+    let rest = &rest[4..];
+    if (0xd800..0xdc00).contains(&high) {
+        let low = rest.strip_prefix("\\u").map(hex).transpose()?;
+        if let Some(low @ 0xdc00..0xe000) = low {
+            let code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+            return Ok((char::from_u32(code).unwrap(), &rest[6..]));
+        }
+    }
+    Ok((char::from_u32(high).unwrap_or('\u{fffd}'), rest))
+}
+
+pub fn parse(s: &str) -> Result<Value, String> {
+    let mut parser = Parser { rest: s, depth: 0 };
+    let value = parser.value()?;
+    parser.skip_ws();
+    if !parser.rest.is_empty() {
+        return Err(format!("trailing {:?}", parser.excerpt()));
+    }
+    Ok(value)
+}
+
+/// `value` with the keys of every object sorted (by their bytes) and no whitespace. Numbers are
+/// the shortest text that reads back as the same f64, without exponents, and -0 is 0.
+pub fn write_canonical(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => *out += "null",
+        Value::Bool(b) => *out += if *b { "true" } else { "false" },
+        Value::Number(x) if *x == 0. => *out += "0",
+        Value::Number(x) => *out += &number(*x),
+        Value::String(s) => *out += &quote(s),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                write_canonical(out, item);
+            }
+            out.push(']');
+        }
+        Value::Object(fields) => {
+            let mut fields: Vec<&(String, Value)> = fields.iter().collect();
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            out.push('{');
+            for (i, (key, item)) in fields.into_iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                *out += &quote(key);
+                out.push(':');
+                write_canonical(out, item);
+            }
+            out.push('}');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(s: &str) -> String {
+        let mut out = String::new();
+        write_canonical(&mut out, &parse(s).unwrap());
+        out
+    }
+
+    #[test]
+    fn canonical_form() {
+        assert_eq!(
+            canonical(r#" { "b": [1.50, -0, 1e3], "a": {"y": null, "x": true} } "#),
+            r#"{"a":{"x":true,"y":null},"b":[1.5,0,1000]}"#
+        );
+        assert_eq!(
+            canonical(r#""\"\\\/\né🦀""#),
+            quote("\"\\/\n\u{e9}\u{1f980}")
+        );
+    }
+
+    #[test]
+    fn depth_is_limited() {
+        let nested = |n: usize| "[".repeat(n) + &"]".repeat(n);
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+        // deep enough to overflow the stack without the limit
+        assert!(parse(&nested(1_000_000)).is_err());
+        assert!(parse(&"{\"a\":".repeat(1_000_000)).is_err());
+    }
+
+    #[test]
+    fn rejects_non_finite_numbers() {
+        for s in ["1e999", "-1e999", "[1, 1e400]", "inf", "NaN"] {
+            assert!(parse(s).is_err(), "{}", s);
+        }
+        assert_eq!(parse("1e308").unwrap(), Value::Number(1e308));
+    }
+
+    #[test]
+    fn rejects_unknown_escapes() {
+        for s in [r#""\a""#, r#""\x41""#, r#""\'""#, r#""\u12""#] {
+            assert!(parse(s).is_err(), "{}", s);
+        }
+        assert_eq!(
+            parse(r#""\b\f\r\t""#).unwrap(),
+            Value::String("\u{8}\u{c}\r\t".to_string())
+        );
+    }
+}
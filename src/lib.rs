@@ -1,11 +1,58 @@
-use std::fmt::Write;
+use std::fmt::Display;
 use std::ops::{Index, IndexMut};
 use wasm_bindgen::prelude::*;
 use web_sys::{CanvasRenderingContext2d, HtmlImageElement};
 
-mod loot;
+pub mod analysis;
+pub mod audit;
+mod base64;
+pub mod cache;
+pub mod calendar;
+pub mod checklist;
+pub mod compare;
+pub mod compress;
+pub mod date;
+pub mod demo;
+pub mod detect;
+mod error;
+pub mod examples;
+pub mod export;
+pub mod filter;
+pub mod flat;
+pub mod flavor;
+pub mod forecast;
+pub mod forge;
+pub mod history;
+pub mod html;
+pub mod index;
+mod json;
+pub mod limit;
+pub mod loot;
+pub mod luck;
 mod map_data;
+pub mod meta;
+pub mod notify;
+pub mod partial;
+pub mod plan;
+pub mod render;
+pub mod report;
 mod rng;
+pub mod save;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod search;
+pub mod seeds;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod showcase;
+#[cfg(feature = "image")]
+pub mod sprites;
+pub mod stats;
+pub mod stream_adapter;
+pub mod weapons;
+mod xml;
+
+pub use error::{Error, Result};
 
 fn format_icon(name: &str) -> String {
     format!("<img src=\"icons/{}.png\" class=icon>", name)
@@ -25,16 +72,29 @@ pub enum MapTile {
 }
 
 #[wasm_bindgen]
-#[derive(Copy, Clone, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct GameSettings {
     pub seed: i32,
     pub legacy_rng: bool,
     pub has_caldera: bool,
     pub post_1_6_4: bool,
+    /// whether the common table can give a single golden coconut
+    pub golden_coconut_in_common: bool,
+    /// whether the rare table can give 3 golden coconuts
+    pub golden_coconuts_in_rare: bool,
+    /// both of the above, use with_cracked_golden_coconut instead
+    #[deprecated(note = "set golden_coconut_in_common and golden_coconuts_in_rare")]
+    #[wasm_bindgen(skip)]
     pub cracked_golden_coconut: bool,
     pub special_charm: bool,
     pub days_played: u32,
     pub max_luck_lvl: u32,
+    /// counts of the stacks chests give, they don't change the generation
+    #[wasm_bindgen(skip)]
+    pub quantities: loot::QuantityModifiers,
+    /// whether the player can hatch ostrich eggs (the incubator from the field office), only
+    /// changes what the eggs are worth
+    pub has_ostrich_incubator: bool,
 }
 #[wasm_bindgen]
 impl GameSettings {
@@ -42,6 +102,140 @@ impl GameSettings {
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// sets the flags that depend on the version, e.g. "1.6.8". Versions that aren't modeled
+    /// here throw instead of being treated as the newest one.
+    pub fn set_version(&mut self, version: &str) -> std::result::Result<(), JsError> {
+        *self = self.clone().with_version(&GameVersion::from(version))?;
+        Ok(())
+    }
+}
+
+/// Game versions with different volcano generation. Versions this crate doesn't know about (yet)
+/// are kept as Unknown, so stored settings still load.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GameVersion {
+    /// 1.6.0 to 1.6.3
+    V1_6,
+    /// 1.6.4 and later 1.6 versions
+    V1_6_4,
+    Unknown(String),
+}
+
+impl From<&str> for GameVersion {
+    fn from(s: &str) -> Self {
+        let s = s.trim();
+        let patch = match s.strip_prefix("1.6") {
+            Some("") => Some(0),
+            Some(rest) => rest.strip_prefix('.').and_then(|p| p.parse::<u32>().ok()),
+            None => None,
+        };
+        match patch {
+            Some(0..=3) => GameVersion::V1_6,
+            Some(_) => GameVersion::V1_6_4,
+            None => GameVersion::Unknown(s.to_string()),
+        }
+    }
+}
+
+impl Display for GameVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameVersion::V1_6 => write!(f, "1.6"),
+            GameVersion::V1_6_4 => write!(f, "1.6.4"),
+            GameVersion::Unknown(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl GameSettings {
+    /// Default settings for the save with this game id, the number after the farm name in the
+    /// save folder name
+    pub fn for_game_id(game_id: u64) -> Result<Self> {
+        // the game uses the full 64 bit id, seeds here are i32 like in the web ui
+        let seed = i32::try_from(game_id).map_err(|_| Error::UnsupportedGameId(game_id))?;
+        Ok(GameSettings {
+            seed,
+            ..Default::default()
+        })
+    }
+
+    /// Fails for Unknown versions rather than guessing
+    pub fn with_version(self, version: &GameVersion) -> Result<Self> {
+        let post_1_6_4 = match version {
+            GameVersion::V1_6 => false,
+            GameVersion::V1_6_4 => true,
+            GameVersion::Unknown(s) => return Err(Error::UnsupportedGameVersion(s.clone())),
+        };
+        Ok(GameSettings { post_1_6_4, ..self })
+    }
+
+    /// The coconut gates from the save's flag. Both tables check
+    /// Game1.netWorldState.Value.GoldenCoconutCracked (1.6.x), there's no separate flag for the
+    /// single coconut vs the stack of 3.
+    #[allow(deprecated)]
+    pub fn with_cracked_golden_coconut(self, cracked: bool) -> Self {
+        GameSettings {
+            golden_coconut_in_common: cracked,
+            golden_coconuts_in_rare: cracked,
+            cracked_golden_coconut: false,
+            ..self
+        }
+    }
+
+    /// the version the flags are for, with_version the other way around
+    pub fn version(&self) -> GameVersion {
+        if self.post_1_6_4 {
+            GameVersion::V1_6_4
+        } else {
+            GameVersion::V1_6
+        }
+    }
+
+    /// Hash of all of the settings, e.g. for cache keys. Fields that don't change the generation
+    /// (the quantities, the incubator) are in it too, they change what the forecast is worth.
+    /// It's FNV-1a over the fields as snapshots store them, so it's the same across runs and
+    /// platforms with the same crate version, but may change between versions.
+    pub fn behavior_hash(&self) -> u64 {
+        let mut bytes = vec![];
+        cache::encode_settings(&mut bytes, self);
+        fnv1a(&bytes)
+    }
+
+    /// behavior_hash with days_played 0, the same for every day of a save. Snapshots, checkpoints
+    /// and year indexes use it to tell whether they're for the same settings.
+    pub fn save_hash(&self) -> u64 {
+        GameSettings {
+            days_played: 0,
+            ..*self
+        }
+        .behavior_hash()
+    }
+
+    // the deprecated cracked_golden_coconut still opens both slots
+
+    /// whether the common table can give a single golden coconut (slot 1)
+    #[allow(deprecated)]
+    pub fn golden_coconut_in_common(&self) -> bool {
+        self.golden_coconut_in_common || self.cracked_golden_coconut
+    }
+    /// whether the rare table can give 3 golden coconuts (slot 3)
+    #[allow(deprecated)]
+    pub fn golden_coconuts_in_rare(&self) -> bool {
+        self.golden_coconuts_in_rare || self.cracked_golden_coconut
+    }
+}
+
+/// 64 bit FNV-1a, for hashes that have to stay the same across runs
+pub(crate) const fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash = (hash ^ bytes[i] as u64).wrapping_mul(0x100000001b3);
+        i += 1;
+    }
+    hash
 }
 
 #[derive(Clone)]
@@ -79,38 +273,46 @@ impl Tilemap {
     }
 }
 
-struct DungeonFloorState {
+/// The seed the floor's layout is generated from. days_played times the level is done in u64 like
+/// the game does (it's a double there), it goes past u32::MAX after about 400 million days.
+fn layout_seed(settings: &GameSettings, level: i32) -> i32 {
+    let lvl_mod = if settings.post_1_6_4 {
+        level + 1
+    } else {
+        level
+    };
+    rng::stardew_seed_mix(
+        settings.legacy_rng,
+        &[
+            (settings.days_played as u64 * lvl_mod as u64) as f64,
+            (level * 5152) as f64,
+            (settings.seed / 2) as f64,
+        ],
+    )
+}
+
+struct DungeonFloorState<'a> {
     rng: rng::DotnetRng,
     map: Tilemap,
     set_pieces: Vec<(i32, i32, i32)>,
-    settings: GameSettings,
+    /// index into set_pieces for each chest, in population order
+    chest_pieces: Vec<usize>,
+    settings: &'a GameSettings,
     level: i32,
     layout_id: u32,
     min_luck: f64,
     max_luck: f64,
 }
 
-impl DungeonFloorState {
+impl<'a> DungeonFloorState<'a> {
     fn new(
-        settings: GameSettings,
+        settings: &'a GameSettings,
         level: i32,
         layout_id: u32,
         min_luck: f64,
         max_luck: f64,
     ) -> Self {
-        let lvl_mod = if settings.post_1_6_4 {
-            level + 1
-        } else {
-            level
-        };
-        let gen_seed = rng::stardew_seed_mix(
-            settings.legacy_rng,
-            &[
-                (settings.days_played * lvl_mod as u32) as f64,
-                (level * 5152) as f64,
-                (settings.seed / 2) as f64,
-            ],
-        );
+        let gen_seed = layout_seed(settings, level);
         let mut gen_random = rng::DotnetRng::new(rng::stardew_seed_mix(
             settings.legacy_rng,
             &[gen_seed as f64],
@@ -124,6 +326,7 @@ impl DungeonFloorState {
             rng: gen_random,
             map: Tilemap::load(layout_id, flip_x),
             set_pieces: vec![],
+            chest_pieces: vec![],
             level,
             layout_id,
             settings,
@@ -133,14 +336,27 @@ impl DungeonFloorState {
     }
 
     fn load_map(&mut self) -> Vec<Goodie> {
+        self.load_map_with(&|_, seed| seed)
+    }
+
+    /// `chest_seed_hook` gets (index of the chest on this floor, seed) and returns the seed to use
+    fn load_map_with(&mut self, chest_seed_hook: &dyn Fn(usize, i32) -> i32) -> Vec<Goodie> {
         self.load_map_tiles();
-        self.load_set_pieces()
+        self.load_set_pieces(chest_seed_hook)
     }
 
     fn get_tiles(&mut self) -> Tilemap {
         self.map.clone()
     }
 
+    /// the next `count` values the floor rng would use as chest seeds, if the set pieces were
+    /// laid out so that a chest takes each of them. call after load_map_tiles.
+    fn candidate_chest_seeds(&mut self, count: usize) -> Vec<i32> {
+        (0..count)
+            .map(|_| rng::stardew_seed_mix(self.settings.legacy_rng, &[self.rng.next() as f64]))
+            .collect()
+    }
+
     fn load_map_tiles(&mut self) {
         // floor tile type generation (we don't care about the result, but need RNG to sync)
         for _x in 0..64 {
@@ -153,7 +369,26 @@ impl DungeonFloorState {
         }
     }
 
-    fn load_set_pieces(&mut self) -> Vec<Goodie> {
+    fn load_set_pieces(&mut self, chest_seed_hook: &dyn Fn(usize, i32) -> i32) -> Vec<Goodie> {
+        self.plan_set_pieces(chest_seed_hook)
+            .iter()
+            .filter_map(|g| {
+                let level = FloorLevel::ALL[self.level as usize];
+                g.generate_at(self.settings, level, self.min_luck, self.max_luck)
+            })
+            .collect()
+    }
+
+    /// load_map_with, without generating the chests
+    fn plan_map_with(&mut self, chest_seed_hook: &dyn Fn(usize, i32) -> i32) -> Vec<PlannedGoodie> {
+        self.load_map_tiles();
+        self.plan_set_pieces(chest_seed_hook)
+    }
+
+    fn plan_set_pieces(
+        &mut self,
+        chest_seed_hook: &dyn Fn(usize, i32) -> i32,
+    ) -> Vec<PlannedGoodie> {
         let mut buggy = false;
         for x in 0_i32..64 {
             for y in 0_i32..64 {
@@ -197,8 +432,13 @@ impl DungeonFloorState {
             }
         }
         let mut goodies = vec![];
+        let mut num_chests = 0;
 
-        for (x, y, set_size) in self.set_pieces.iter().cloned() {
+        // chests are populated piece by piece in the order they were found above (x outer, y
+        // inner like the game's loop), then in the order of the piece's features. each takes the
+        // next value of the floor rng, so a different order wouldn't just swap two chests, it
+        // would change which pieces get picked and every seed after it.
+        for (piece, (x, y, set_size)) in self.set_pieces.iter().cloned().enumerate() {
             let (num_rows, num_cols) = map_data::get_piece_sizes(set_size);
             let selected_col = self.rng.next_range(num_cols);
             let selected_row = self.rng.next_range(num_rows);
@@ -219,23 +459,24 @@ impl DungeonFloorState {
                     }
                     map_data::SetPieceFeature::Tooth => {
                         if self.rng.next_f64() < 0.5 {
-                            goodies.push(Goodie::DragonTooth);
+                            goodies.push(PlannedGoodie::DragonTooth);
                         }
                     }
                     map_data::SetPieceFeature::Chest => {
+                        // unlike dragon teeth, chest tiles always spawn a chest: there's no spawn
+                        // roll, only the seed. a missing chest means a wrong layout or settings.
                         // TODO: does not go through seedmix in 1.5
                         // (though, legacy seedmix with 1 arg is mostly identity anyways...)
-                        let chest_seed = rng::stardew_seed_mix(
-                            self.settings.legacy_rng,
-                            &[self.rng.next() as f64],
+                        let chest_seed = chest_seed_hook(
+                            num_chests,
+                            rng::stardew_seed_mix(
+                                self.settings.legacy_rng,
+                                &[self.rng.next() as f64],
+                            ),
                         );
-                        goodies.push(Goodie::generate(
-                            chest_seed,
-                            self.settings,
-                            self.level,
-                            self.min_luck,
-                            self.max_luck,
-                        ));
+                        num_chests += 1;
+                        self.chest_pieces.push(piece);
+                        goodies.push(PlannedGoodie::Chest(chest_seed));
                     }
                 }
             }
@@ -244,27 +485,23 @@ impl DungeonFloorState {
     }
 }
 
-fn compute_volcano_layouts(settings: GameSettings) -> Vec<(f64, f64, [u32; 10])> {
+fn compute_volcano_layouts(settings: &GameSettings) -> Vec<(f64, f64, [u32; 10])> {
+    compute_volcano_layouts_in(settings, luck::luck_range(settings))
+}
+
+/// like compute_volcano_layouts, for luckMult within `luck` instead of the settings' range
+fn compute_volcano_layouts_in(
+    settings: &GameSettings,
+    luck: (f64, f64),
+) -> Vec<(f64, f64, [u32; 10])> {
     fn compute_inner(
-        settings: GameSettings,
+        settings: &GameSettings,
         prev: &[u32],
         minluck: f64,
         maxluck: f64,
     ) -> Vec<(f64, f64, [u32; 10])> {
         let level = prev.len();
-        let lvl_mod = if settings.post_1_6_4 {
-            level + 1
-        } else {
-            level
-        };
-        let gen_seed = rng::stardew_seed_mix(
-            settings.legacy_rng,
-            &[
-                (settings.days_played * lvl_mod as u32) as f64,
-                (level * 5152) as f64,
-                (settings.seed / 2) as f64,
-            ],
-        );
+        let gen_seed = layout_seed(settings, level as i32);
         let mut lvlbuf = prev.to_vec();
         if level == 0 {
             lvlbuf.push(0);
@@ -287,31 +524,36 @@ fn compute_volcano_layouts(settings: GameSettings) -> Vec<(f64, f64, [u32; 10])>
         if level > 1 {
             let special_rng = layout_random.next_f64();
             let special_possible = prev.iter().all(|&x| x < 32);
+            // the game adds them if rng < luckMult * 0.5, the same as rng / 0.5 < luckMult since
+            // halving and doubling are exact
+            let midpoint = special_rng / 0.5;
             if special_possible {
-                if special_rng < minluck * 0.5 {
+                match luck::threshold_split(midpoint, (minluck, maxluck)) {
                     // even with the worst possible luck, we still add the special floors
-                    valid_layouts.extend(32..38);
-                } else if !(special_rng < maxluck * 0.5) {
+                    luck::ThresholdSplit::Past => valid_layouts.extend(32..38),
                     // even with best luck, we do not add the special floors
-                } else {
-                    // bifurcate!
-                    let midpoint = special_rng / 0.5;
-                    assert!(minluck < midpoint && midpoint < maxluck);
-                    // in there: rng = maxluck * 0.5, so we go to the !< case
-                    let mut res1 = compute_inner(settings, prev, minluck, midpoint);
-                    // in there: rng ~= minluck * 0.5, but minluck is slightly increased,
-                    // so rng < minluck * 0.5
-                    let res2 = compute_inner(settings, prev, midpoint.next_up(), maxluck);
-
-                    res1.extend(res2);
-                    return res1;
+                    luck::ThresholdSplit::NotPast => {}
+                    luck::ThresholdSplit::Within => {
+                        // bifurcate!
+                        assert!(minluck < midpoint && midpoint < maxluck);
+                        // in there: rng = maxluck * 0.5, so we go to the !< case
+                        let mut res1 = compute_inner(settings, prev, minluck, midpoint);
+                        // in there: rng ~= minluck * 0.5, but minluck is slightly increased,
+                        // so rng < minluck * 0.5
+                        let res2 = compute_inner(settings, prev, midpoint.next_up(), maxluck);
+
+                        res1.extend(res2);
+                        return res1;
+                    }
                 }
             }
         }
-        if level > 0 && settings.post_1_6_4 && settings.has_caldera {
-            if layout_random.next_f64() < 0.75 {
-                valid_layouts.extend(38..58);
-            }
+        if level > 0
+            && settings.post_1_6_4
+            && settings.has_caldera
+            && layout_random.next_f64() < 0.75
+        {
+            valid_layouts.extend(38..58);
         }
         let prev_level = prev[level - 1];
         if let Some(i) = valid_layouts.iter().position(|&x| x == prev_level) {
@@ -320,22 +562,9 @@ fn compute_volcano_layouts(settings: GameSettings) -> Vec<(f64, f64, [u32; 10])>
         let the_layout =
             valid_layouts[layout_random.next_range(valid_layouts.len() as i32) as usize];
         lvlbuf.push(the_layout);
-        return compute_inner(settings, &lvlbuf, minluck, maxluck);
-    }
-    // these values are *technically* not exact due to rounding (special charm especially)
-    // but we only show them with 4 significant digits anyways
-    let mut minluck = -0.1;
-    let mut base_maxluck = 0.1;
-    if settings.special_charm {
-        minluck += 0.025_f32 as f64;
-        base_maxluck += 0.025_f32 as f64;
+        compute_inner(settings, &lvlbuf, minluck, maxluck)
     }
-    return compute_inner(
-        settings,
-        &[],
-        1. + minluck / 2.,
-        1. + base_maxluck / 2. + 0.035 * (settings.max_luck_lvl as f64),
-    );
+    compute_inner(settings, &[], luck.0, luck.1)
 }
 
 #[allow(unused_macros)]
@@ -354,20 +583,76 @@ macro_rules! console_log {
 }
 
 // (minluck, maxluck, item)
-type ProbabilityRange<T> = Vec<(f64, f64, T)>;
+pub type ProbabilityRange<T> = Vec<(f64, f64, T)>;
 
-use loot::Goodie;
+use loot::{FloorLevel, Goodie};
+use plan::{FloorPlan, PlannedGoodie};
 
 fn do_dungeon(
-    settings: GameSettings,
+    settings: &GameSettings,
+) -> (
+    [ProbabilityRange<u32>; 10],
+    [ProbabilityRange<Vec<Goodie>>; 10],
+) {
+    do_dungeon_with(settings, &|_, _, seed| seed)
+}
+
+/// `chest_seed_hook` gets (level, index of the chest on that level, seed) and returns the seed to
+/// use for the chest
+fn do_dungeon_with(
+    settings: &GameSettings,
+    chest_seed_hook: &dyn Fn(usize, usize, i32) -> i32,
+) -> (
+    [ProbabilityRange<u32>; 10],
+    [ProbabilityRange<Vec<Goodie>>; 10],
+) {
+    materialize_dungeon(
+        &plan_dungeon(settings, luck::luck_range(settings), chest_seed_hook),
+        settings,
+        luck::luck_range(settings),
+    )
+}
+
+/// the floors of each luck range (within `luck`) with different layouts, without generating any
+/// chests
+fn plan_dungeon(
+    settings: &GameSettings,
+    luck: (f64, f64),
+    chest_seed_hook: &dyn Fn(usize, usize, i32) -> i32,
+) -> ProbabilityRange<[FloorPlan; 10]> {
+    compute_volcano_layouts_in(settings, luck)
+        .into_iter()
+        .map(|(minluck, maxluck, lvls)| {
+            let floors = std::array::from_fn(|i| {
+                let mut h = DungeonFloorState::new(settings, i as i32, lvls[i], minluck, maxluck);
+                FloorPlan {
+                    layout: lvls[i],
+                    goodies: h.plan_map_with(&|index, seed| chest_seed_hook(i, index, seed)),
+                }
+            });
+            (minluck, maxluck, floors)
+        })
+        .collect()
+}
+
+/// generates the planned chests for the part of the plan within `luck`
+fn materialize_dungeon(
+    plan: &ProbabilityRange<[FloorPlan; 10]>,
+    settings: &GameSettings,
+    luck: (f64, f64),
 ) -> (
     [ProbabilityRange<u32>; 10],
     [ProbabilityRange<Vec<Goodie>>; 10],
 ) {
     let mut layouts_poss = [(); 10].map(|_| ProbabilityRange::<u32>::new());
     let mut loots_poss = [(); 10].map(|_| ProbabilityRange::<Vec<Goodie>>::new());
-    for (minluck, maxluck, lvls) in compute_volcano_layouts(settings) {
-        for (i, &x) in lvls.iter().enumerate() {
+    for (minluck, maxluck, floors) in plan {
+        let (minluck, maxluck) = (minluck.max(luck.0), maxluck.min(luck.1));
+        if minluck > maxluck {
+            continue;
+        }
+        for (i, floor) in floors.iter().enumerate() {
+            let x = floor.layout;
             if let Some(y) = layouts_poss[i].last_mut()
                 && y.2 == x
                 && y.1.next_up() == minluck
@@ -376,8 +661,12 @@ fn do_dungeon(
             } else {
                 layouts_poss[i].push((minluck, maxluck, x));
             }
-            let mut h = DungeonFloorState::new(settings, i as i32, x, minluck, maxluck);
-            let loot = h.load_map();
+            // no chests on the entrance/shop floors, the rng is used anyways
+            let loot = floor
+                .goodies
+                .iter()
+                .filter_map(|g| g.generate_at(settings, FloorLevel::ALL[i], minluck, maxluck))
+                .collect();
             fn handle_loot(
                 minluck: f64,
                 maxluck: f64,
@@ -400,7 +689,7 @@ fn do_dungeon(
                     let mut alt_loot = loot;
                     // we're always narrowing the [minluck, maxluck] range here, hence the
                     // .min() / .max() to make sure we keep ourselves in that range
-                    if chestluck >= minluck {
+                    if !luck::past_threshold(chestluck, minluck) {
                         alt_loot[ind] = Goodie::CommonChest(common.clone());
                         handle_loot(
                             minluck,
@@ -409,7 +698,7 @@ fn do_dungeon(
                             loots_poss,
                         );
                     }
-                    if chestluck < maxluck {
+                    if luck::past_threshold(chestluck, maxluck) {
                         alt_loot[ind] = Goodie::RareChest(rare.clone());
                         handle_loot(
                             chestluck.next_up().max(minluck),
@@ -445,117 +734,20 @@ fn display_luck(luck: f64) -> f64 {
 }
 
 fn is_mushroom_floor(layout: u32) -> bool {
-    layout >= 32 && layout <= 34
+    (32..=34).contains(&layout)
 }
 fn is_monster_floor(layout: u32) -> bool {
-    layout >= 35 && layout <= 37
+    (35..=37).contains(&layout)
 }
 
 #[wasm_bindgen]
 pub fn main_update(settings: GameSettings) {
     console_error_panic_hook::set_once();
-    let (layouts, loots) = do_dungeon(settings);
-
-    let total_seasons = (settings.days_played - 1) / 28;
-    let year = total_seasons / 4 + 1;
-    let season = total_seasons % 4;
-    let day = (settings.days_played - 1) % 28 + 1;
-    let day_name = format!(
-        "{} {day}, Y{year}",
-        ["spring", "summer", "fall", "winter"][season as usize],
-    );
-
-    fn format_layout(level: usize, layout: u32) -> String {
-        let displayname = if is_mushroom_floor(layout) {
-            format!("{} {}", format_icon("magma_cap"), layout)
-        } else if is_monster_floor(layout) {
-            format!("{} {}", format_icon("monster_floor"), layout)
-        } else {
-            layout.to_string()
-        };
-        format!(
-            "<button data-lvl=\"{}\" data-layout=\"{}\" class=\"layout-btn\">{}</button>",
-            level, layout, displayname
-        )
-    }
+    let day = forecast::DayForecast::generate(&settings);
 
-    let layouts_disp = String::from_iter(layouts.iter().enumerate().map(|(lvl, this_layouts)| {
-        let mut out = String::from("<td>");
-        if this_layouts.len() == 1 {
-            out += &format_layout(lvl, this_layouts[0].2);
-        } else {
-            let formatted: Vec<_> = this_layouts
-                .iter()
-                .map(|&(a, b, c)| {
-                    format!(
-                        "<span title=\"luck {:.4} to {:.4}\">{}</span>",
-                        display_luck(a),
-                        display_luck(b),
-                        format_layout(lvl, c)
-                    )
-                })
-                .collect();
-            out += &formatted.join(" / ");
-        }
-        out += "</td>";
-        out
-    }));
-    let mut layouts_full = String::from("<table><tr>");
-    for i in 0..10 {
-        write!(layouts_full, "<td>{}</td>", i).unwrap();
-    }
-    layouts_full += "</tr><tr>";
-    layouts_full += &layouts_disp;
-    layouts_full += "</tr></table>";
-
-    let mut goodies_out = String::new();
-
-    macro_rules! out {
-        ( $( $x:expr ),* ) => {
-            writeln!(goodies_out, $($x),*).unwrap()
-        };
-    }
-
-    for (i, floor_loot) in loots.into_iter().enumerate() {
-        if floor_loot.iter().all(|y| y.2.is_empty()) {
-            continue;
-        }
-
-        out!("<div><b>floor {}:</b><ul>", i);
-        for (minl, maxl, loot) in &floor_loot {
-            if floor_loot.len() > 1 {
-                out!(
-                    "<li>luck {:.4} to {:.4}:</li>",
-                    display_luck(*minl),
-                    display_luck(*maxl)
-                );
-            }
-            out!("<ul>");
-            if loot.is_empty() {
-                out!("<li>[nothing]</li>");
-            }
-            let num_dragon_teeth = loot
-                .iter()
-                .filter(|x| matches!(x, Goodie::DragonTooth))
-                .count();
-            if num_dragon_teeth > 1 {
-                out!(
-                    "<li>{} ({})</li>",
-                    Goodie::DragonTooth.to_html(),
-                    num_dragon_teeth
-                );
-            } else if num_dragon_teeth > 0 {
-                out!("<li>{}</li>", Goodie::DragonTooth.to_html());
-            }
-            for l in loot {
-                if *l != Goodie::DragonTooth {
-                    out!("<li>{}</li>", l.to_html());
-                }
-            }
-            out!("</ul>");
-        }
-        out!("</ul></div>");
-    }
+    let day_name = html::day_name(settings.days_played);
+    let layouts_full = html::render_layouts(&day.layouts);
+    let goodies_out = html::render_loot(&day);
     let doc = web_sys::window().unwrap().document().unwrap();
     doc.get_element_by_id("goodies")
         .unwrap()
@@ -579,7 +771,7 @@ pub fn render_map(
 ) -> String {
     // TODO: currently the map rendering does not depend on luck, so we can just use a dummy value
     // for it. might need to track it properly later tho
-    let mut floor = DungeonFloorState::new(settings, lvl, layout, 0., 0.);
+    let mut floor = DungeonFloorState::new(&settings, lvl, layout, 0., 0.);
     floor.load_map();
     let mut has_buttons = false;
     let tiles = floor.get_tiles();
@@ -629,3 +821,245 @@ pub fn render_map(
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn behavior_hash_covers_every_field() {
+        let base = GameSettings {
+            seed: 77,
+            days_played: 5,
+            ..Default::default()
+        };
+        assert_eq!(
+            base.behavior_hash(),
+            GameSettings { ..base }.behavior_hash()
+        );
+        let changed = [
+            GameSettings { seed: 78, ..base },
+            GameSettings {
+                legacy_rng: true,
+                ..base
+            },
+            GameSettings {
+                has_caldera: true,
+                ..base
+            },
+            base.clone().with_version(&GameVersion::V1_6_4).unwrap(),
+            GameSettings {
+                golden_coconut_in_common: true,
+                ..base
+            },
+            GameSettings {
+                golden_coconuts_in_rare: true,
+                ..base
+            },
+            GameSettings {
+                special_charm: true,
+                ..base
+            },
+            GameSettings {
+                days_played: 6,
+                ..base
+            },
+            GameSettings {
+                max_luck_lvl: 1,
+                ..base
+            },
+            GameSettings {
+                quantities: loot::QuantityModifiers::new(2, 1).unwrap(),
+                ..base
+            },
+            GameSettings {
+                quantities: loot::QuantityModifiers::new(1, 2).unwrap(),
+                ..base
+            },
+            GameSettings {
+                has_ostrich_incubator: true,
+                ..base
+            },
+        ];
+        let mut hashes: Vec<u64> = changed.iter().map(|s| s.behavior_hash()).collect();
+        hashes.push(base.behavior_hash());
+        let count = hashes.len();
+        hashes.sort();
+        hashes.dedup();
+        assert_eq!(hashes.len(), count);
+    }
+
+    #[test]
+    fn save_hash_ignores_the_day() {
+        let settings = GameSettings {
+            seed: 77,
+            ..Default::default()
+        };
+        let later = GameSettings {
+            days_played: 100,
+            ..settings
+        };
+        assert_eq!(settings.save_hash(), later.save_hash());
+        assert_ne!(settings.behavior_hash(), later.behavior_hash());
+        let other = later.clone().with_cracked_golden_coconut(true);
+        assert_ne!(other.save_hash(), later.save_hash());
+    }
+
+    /// A save's settings next to things that aren't Copy. GameSettings isn't Copy either, so this
+    /// only compiles because everything borrows them.
+    struct Save {
+        name: String,
+        settings: GameSettings,
+    }
+
+    #[test]
+    fn settings_without_copy() {
+        let save = Save {
+            name: "farm".to_string(),
+            settings: GameSettings {
+                seed: 4242,
+                days_played: 3,
+                ..Default::default()
+            },
+        };
+        let settings = &save.settings.clone();
+        let day = forecast::DayForecast::generate(settings);
+        assert_eq!(forecast::range(settings, 3, 1).unwrap()[0].loot, day.loot);
+        let level = loot::FloorLevel::try_new(1).unwrap();
+        let item = loot::ChestItem::generate(loot::Rarity::Common, 7, settings);
+        let goodie = Goodie::generate_at(7, settings, level, 1., 1.).unwrap();
+        assert_eq!(goodie, Goodie::CommonChest(item.clone()));
+        let query = search::Query::kind(item.kind());
+        let hits = search::search_with_checkpoints(&query, 1, 4, settings, 10, |_| {}).unwrap();
+        assert!(
+            hits.iter()
+                .all(|hit| query.matches(hit.goodie.as_chest().unwrap().1))
+        );
+        assert_eq!(save.name, "farm");
+
+        // the by-value versions from before, they need their own copy now
+        #[allow(deprecated)]
+        {
+            assert_eq!(
+                loot::ChestItem::generate_common(7, save.settings.clone()),
+                item
+            );
+            assert_eq!(
+                loot::ChestItem::generate_rare(7, save.settings.clone()),
+                loot::ChestItem::generate(loot::Rarity::Rare, 7, settings)
+            );
+            assert_eq!(
+                Goodie::generate(7, save.settings.clone(), 1, 1., 1.),
+                goodie
+            );
+        }
+    }
+
+    #[test]
+    fn future_versions_fail_with_their_name() {
+        let stored = json::parse(r#"{"seed": 5, "version": "1.7"}"#).unwrap();
+        let json::Value::Object(fields) = stored else {
+            panic!("not an object");
+        };
+        let Some((_, json::Value::String(version))) = fields.iter().find(|(k, _)| k == "version")
+        else {
+            panic!("no version");
+        };
+        let version = GameVersion::from(version.as_str());
+        assert_eq!(version, GameVersion::Unknown("1.7".to_string()));
+        // it's stored as it was
+        assert_eq!(version.to_string(), "1.7");
+        let err = GameSettings::default().with_version(&version).unwrap_err();
+        assert!(matches!(&err, Error::UnsupportedGameVersion(v) if v == "1.7"));
+        assert!(err.to_string().contains("\"1.7\""));
+        for (s, version) in [
+            ("1.6", GameVersion::V1_6),
+            ("1.6.3", GameVersion::V1_6),
+            ("1.6.4", GameVersion::V1_6_4),
+            (" 1.6.15 ", GameVersion::V1_6_4),
+            ("1.5.6", GameVersion::Unknown("1.5.6".to_string())),
+        ] {
+            assert_eq!(GameVersion::from(s), version, "{}", s);
+        }
+    }
+
+    #[test]
+    fn chest_tiles_always_spawn() {
+        let mut teeth = 0;
+        for (seed, days_played) in [(1, 1), (77, 5), (4242, 30), (31337, 100)] {
+            let settings = GameSettings {
+                seed,
+                days_played,
+                ..Default::default()
+            };
+            let luck = luck::luck_range(&settings);
+            let hook_calls = std::cell::Cell::new(0);
+            let plan = plan_dungeon(&settings, luck, &|_, _, seed| {
+                hook_calls.set(hook_calls.get() + 1);
+                seed
+            });
+            let (_, loots) = materialize_dungeon(&plan, &settings, luck);
+            let mut planned_chests = 0;
+            for (minluck, maxluck, floors) in &plan {
+                for (i, floor) in floors.iter().enumerate() {
+                    let chests = floor
+                        .goodies
+                        .iter()
+                        .filter(|g| matches!(g, PlannedGoodie::Chest(_)))
+                        .count();
+                    planned_chests += chests;
+                    teeth += floor.goodies.len() - chests;
+                    if !FloorLevel::ALL[i].has_chests() {
+                        continue;
+                    }
+                    // every chest tile is a chest in the forecast, at every luck of the range
+                    for (_, _, loot) in loots[i]
+                        .iter()
+                        .filter(|(lo, hi, _)| lo >= minluck && hi <= maxluck)
+                    {
+                        let spawned = loot
+                            .iter()
+                            .filter(|g| !matches!(g, Goodie::DragonTooth))
+                            .count();
+                        assert_eq!(spawned, chests, "seed {} floor {}", seed, i);
+                    }
+                }
+            }
+            assert_eq!(hook_calls.get(), planned_chests);
+        }
+        // the teeth are the goodies with a spawn roll, they're planned too but aren't chests
+        assert!(teeth > 0);
+    }
+
+    #[test]
+    fn layout_seed_past_u32() {
+        let settings = GameSettings {
+            seed: 12,
+            days_played: 600_000_000,
+            post_1_6_4: true,
+            ..Default::default()
+        };
+        // 6e9 for level 9, that wrapped around to 1705032704 in u32
+        let level_9 = [1705032706., 9. * 5152., 6.];
+        assert_eq!(
+            layout_seed(&settings, 9),
+            rng::stardew_seed_mix(false, &level_9)
+        );
+        assert_ne!(
+            layout_seed(&settings, 9),
+            rng::stardew_seed_mix(false, &[1705032704., 9. * 5152., 6.])
+        );
+        assert_eq!(layout_seed(&settings, 9), -614723790);
+        let legacy = GameSettings {
+            legacy_rng: true,
+            ..settings
+        };
+        assert_eq!(layout_seed(&legacy, 9), 1705032706 + 46368 + 6);
+        // below the overflow it's the plain product
+        let day_1000 = GameSettings {
+            days_played: 1000,
+            ..legacy
+        };
+        assert_eq!(layout_seed(&day_1000, 9), 10000 + 46368 + 6);
+    }
+}
@@ -0,0 +1,117 @@
+//! Capping how much output a renderer can produce, so a huge request fails instead of building a
+//! gigabyte string.
+
+use crate::{Error, Result};
+
+/// Passes writes through to `inner` until they'd add up to more than `limit` bytes. The write
+/// that goes over is dropped and fails, and so does everything after it, so the output is never
+/// silently cut short.
+pub struct LimitedWriter<W> {
+    inner: W,
+    limit: usize,
+    /// bytes written to inner, plus the write that went over
+    emitted: usize,
+}
+
+impl<W> LimitedWriter<W> {
+    pub fn new(inner: W, limit: usize) -> Self {
+        LimitedWriter {
+            inner,
+            limit,
+            emitted: 0,
+        }
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.emitted > self.limit
+    }
+
+    /// OutputTooLarge if a write went over the limit. Check this when the writer fails, the
+    /// fmt::Write side can't say why.
+    pub fn check(&self) -> Result<()> {
+        if self.exceeded() {
+            return Err(Error::OutputTooLarge {
+                limit: self.limit,
+                emitted: self.emitted,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// counts `len` more bytes, false if they don't fit
+    fn accept(&mut self, len: usize) -> bool {
+        if self.exceeded() {
+            return false;
+        }
+        self.emitted += len;
+        !self.exceeded()
+    }
+}
+
+impl<W: std::fmt::Write> std::fmt::Write for LimitedWriter<W> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        if !self.accept(s.len()) {
+            return Err(std::fmt::Error);
+        }
+        self.inner.write_str(s)
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.accept(buf.len()) {
+            return Err(std::io::Error::other(self.check().unwrap_err()));
+        }
+        self.inner.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write as _;
+    use std::io::Write as _;
+
+    #[test]
+    fn fmt_writes_stop_at_the_limit() {
+        let mut out = LimitedWriter::new(String::new(), 10);
+        assert!(out.write_str("12345").is_ok());
+        assert!(out.write_str("67890").is_ok());
+        assert!(out.check().is_ok());
+        // the write that goes over is dropped as a whole, and so is everything after it
+        assert!(out.write_str("x").is_err());
+        assert!(out.write_str("").is_err());
+        assert!(matches!(
+            out.check(),
+            Err(Error::OutputTooLarge {
+                limit: 10,
+                emitted: 11
+            })
+        ));
+        assert_eq!(out.into_inner(), "1234567890");
+        let mut out = LimitedWriter::new(String::new(), 4);
+        assert!(write!(out, "{}", 123456).is_err());
+        assert!(out.exceeded());
+        assert_eq!(out.into_inner(), "");
+    }
+
+    #[test]
+    fn io_writes_stop_at_the_limit() {
+        let mut out = LimitedWriter::new(vec![], 6);
+        out.write_all(b"abc").unwrap();
+        let err = out.write_all(b"defg").unwrap_err();
+        assert!(err.to_string().contains("6"), "{}", err);
+        assert!(out.write_all(b"h").is_err());
+        assert!(out.check().is_err());
+        assert_eq!(out.into_inner(), b"abc");
+    }
+}
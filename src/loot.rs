@@ -1,5 +1,15 @@
-use crate::{GameSettings, format_icon, rng};
-use std::fmt::Display;
+use crate::luck::{ThresholdSplit, past_threshold, threshold_split};
+use crate::partial::SettingsAssumption;
+use crate::render::{Locale, Render, RenderOptions};
+use crate::seeds::SeedSpec;
+use crate::weapons::WeaponKind;
+use crate::{Error, GameSettings, GameVersion, Result, rng};
+use std::borrow::Cow;
+use std::fmt::{Display, Write};
+use std::str::FromStr;
+
+mod tables;
+pub use tables::{LootSlot, LootTables, SlotGate, TableRoll};
 
 // type definitions
 
@@ -18,10 +28,40 @@ pub enum Enchant {
 
 /// List of (enchant, level). The levels are stored as whatever number is shown in the UI, not the
 /// "real" internal level number. (i.e. 25-75 for crit.power, and negative for weight)
-#[derive(Clone, Debug, PartialEq, PartialOrd)]
-pub struct Enchants(Vec<(Enchant, i32)>);
+///
+/// Generated enchants also keep the rolls from before the game clamped them, see `rolls`.
+/// Comparisons only look at the levels.
+#[derive(Clone, Debug, Default)]
+pub struct Enchants {
+    levels: Vec<(Enchant, i32)>,
+    /// same order as the levels, None when only the level is known (e.g. parsed from a url)
+    raw: Vec<Option<i32>>,
+}
+
+/// One enchant of an Enchants, with the roll the level came from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnchantRoll {
+    pub enchant: Enchant,
+    /// before clamping, and before the *25 of crit. power
+    pub raw: Option<i32>,
+    pub ui_level: i32,
+}
+
+/// The bounds enchant_item clamps the rolls to. Weight and slime enchants are never clamped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClampRules {
+    pub defense: (i32, i32),
+    pub attack: (i32, i32),
+    pub crit_chance: (i32, i32),
+    /// in steps of 25
+    pub crit_power: (i32, i32),
+    /// the game uses (1, 4 - weapon speed), None keeps the level as it is
+    pub speed: Option<(i32, i32)>,
+}
 
-/// Possible contents of one chest (common or rare)
+/// Possible contents of one chest (common or rare). Non exhaustive for items of future game
+/// versions, `kind`, `enchants` and `is_weapon` cover what matches are usually for.
+#[non_exhaustive]
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum ChestItem {
     CinderShards3,
@@ -47,7 +87,35 @@ pub enum ChestItem {
     OstrichEgg,
 }
 
-/// One row of the loot overview: dragon tooth or chest contents
+/// ChestItem without the enchantments
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChestItemKind {
+    CinderShards3,
+    GoldenCoconut,
+    TaroTuber,
+    PineappleSeeds,
+    ProtectionRing,
+    SoulSapperRing,
+    DwarfSword,
+    DwarfHammer,
+    DwarfDagger,
+
+    CinderShards10,
+    MermaidBoots,
+    DragonscaleBoots,
+    GoldenCoconuts,
+    PhoenixRing,
+    HotJavaRing,
+    DragontoothCutlass,
+    DragontoothClub,
+    DragontoothShiv,
+    DeluxePirateHat,
+    OstrichEgg,
+}
+
+/// One row of the loot overview: dragon tooth or chest contents. Non exhaustive, there'll be
+/// more kinds of goodies; `as_chest` and `chance` get at the chests without matching.
+#[non_exhaustive]
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Goodie {
     DragonTooth,
@@ -58,91 +126,204 @@ pub enum Goodie {
         common: ChestItem,
         rare: ChestItem,
     },
+    /// depends on settings the player doesn't know, see `partial`
+    Ambiguous(Vec<(SettingsAssumption, Goodie)>),
+}
+
+/// The rolls that decided what a chest gives, for explaining the outcome. Both tables are
+/// included since which one is used can depend on the luck.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decisions {
+    /// the chest is rare if this is below rare_threshold + the luck boost
+    pub rarity_roll: f64,
+    pub rare_threshold: f64,
+    pub common: TableRoll,
+    pub rare: TableRoll,
+}
+
+/// A chest item together with the number of rng calls it took to generate it, including the
+/// initial rare/normal roll and any golden coconut rerolls
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratedItem {
+    pub item: ChestItem,
+    pub rng_cost: u32,
 }
 
 // Display impls / html rendering stuff
 
+/// Where an enchantment's level goes
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TooltipStyle {
+    /// "+25 Crit. Power", what Display does
+    #[default]
+    Prefix,
+    /// "Crit. Power +25", like the weapon tooltip in game
+    GameTooltip,
+}
+
+impl TooltipStyle {
+    /// `name` is the enchantment's name, possibly already wrapped in a link. enchantments
+    /// without levels (the slime ones) only get the name.
+    pub fn write_enchant(
+        self,
+        out: &mut dyn Write,
+        name: &dyn Display,
+        lvl: i32,
+    ) -> std::fmt::Result {
+        match (self, lvl) {
+            (_, 0) => write!(out, "{}", name),
+            (TooltipStyle::Prefix, _) => write!(out, "{:+} {}", lvl, name),
+            (TooltipStyle::GameTooltip, _) => write!(out, "{} {:+}", name, lvl),
+        }
+    }
+}
+
 impl Display for Enchants {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.0.is_empty() {
+        self.write_styled(f, TooltipStyle::Prefix)
+    }
+}
+
+impl Enchants {
+    /// " (+5 Attack, Slime Slayer)", or nothing without enchantments
+    pub fn write_styled(&self, out: &mut dyn Write, style: TooltipStyle) -> std::fmt::Result {
+        self.write_localized(out, style, Locale::En)
+    }
+
+    /// like write_styled, with the names of `locale`
+    pub fn write_localized(
+        &self,
+        out: &mut dyn Write,
+        style: TooltipStyle,
+        locale: Locale,
+    ) -> std::fmt::Result {
+        if self.levels.is_empty() {
             return Ok(());
         }
-        write!(f, " (")?;
-        for (i, &(e, lvl)) in self.0.iter().enumerate() {
+        write!(out, " (")?;
+        for (i, &(e, lvl)) in self.levels.iter().enumerate() {
             if i != 0 {
-                write!(f, ", ")?;
+                write!(out, ", ")?;
             }
-            if lvl != 0 {
-                write!(f, "{:+} ", lvl)?;
+            style.write_enchant(out, &e.localized_name(locale), lvl)?;
+        }
+        write!(out, ")")
+    }
+}
+
+impl Enchant {
+    /// Number for storing the enchantment, like ChestItemKind::stable_id: never renumbered or
+    /// reused
+    pub const fn stable_id(self) -> u16 {
+        match self {
+            Enchant::Defense => 0,
+            Enchant::Weight => 1,
+            Enchant::SlimeGatherer => 2,
+            Enchant::SlimeSlayer => 3,
+            Enchant::CritPower => 4,
+            Enchant::CritChance => 5,
+            Enchant::Attack => 6,
+            Enchant::Speed => 7,
+        }
+    }
+
+    /// the enchantment with this stable_id, None for ids that aren't in use
+    pub const fn from_stable_id(id: u16) -> Option<Self> {
+        let mut i = 0;
+        while i < Self::ALL.len() {
+            if Self::ALL[i].stable_id() == id {
+                return Some(Self::ALL[i]);
             }
-            f.write_str(match e {
-                Enchant::Defense => "Defense",
-                Enchant::Weight => "Weight",
-                Enchant::SlimeGatherer => "Slime Gatherer",
-                Enchant::SlimeSlayer => "Slime Slayer",
-                Enchant::CritPower => "Crit. Power",
-                Enchant::CritChance => "Crit. Chance",
-                Enchant::Attack => "Attack",
-                Enchant::Speed => "Speed",
-            })?;
+            i += 1;
         }
-        write!(f, ")")
+        None
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Enchant::Defense => "Defense",
+            Enchant::Weight => "Weight",
+            Enchant::SlimeGatherer => "Slime Gatherer",
+            Enchant::SlimeSlayer => "Slime Slayer",
+            Enchant::CritPower => "Crit. Power",
+            Enchant::CritChance => "Crit. Chance",
+            Enchant::Attack => "Attack",
+            Enchant::Speed => "Speed",
+        }
+    }
+
+    /// the name in the game's tooltips in `locale`
+    pub fn localized_name(self, locale: Locale) -> &'static str {
+        match locale {
+            Locale::En => self.name(),
+            Locale::De => match self {
+                Enchant::Defense => "Verteidigung",
+                Enchant::Weight => "Gewicht",
+                Enchant::SlimeGatherer => "Schleimsammler",
+                Enchant::SlimeSlayer => "Schleimtöter",
+                Enchant::CritPower => "Krit. Stärke",
+                Enchant::CritChance => "Krit. Chance",
+                Enchant::Attack => "Angriff",
+                Enchant::Speed => "Geschwindigkeit",
+            },
+        }
+    }
+
+    /// the forge's innate enchantment section, it covers all of them
+    pub fn wiki_url(self) -> &'static str {
+        "https://stardewvalleywiki.com/Forge#Innate_Enchantments"
+    }
+}
+
+impl Enchants {
+    pub fn as_slice(&self) -> &[(Enchant, i32)] {
+        &self.levels
     }
 }
 
 impl Display for ChestItem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::CinderShards3 => write!(f, "Cinder Shard (3)"),
-            Self::GoldenCoconut => write!(f, "Golden Coconut"),
-            Self::TaroTuber => write!(f, "Taro Tuber (8)"),
-            Self::PineappleSeeds => write!(f, "Pineapple Seeds (5)"),
-            Self::ProtectionRing => write!(f, "Protection Ring"),
-            Self::SoulSapperRing => write!(f, "Soul Sapper Ring"),
-            Self::DwarfSword(e) => write!(f, "Dwarf Sword{}", e),
-            Self::DwarfHammer(e) => write!(f, "Dwarf Hammer{}", e),
-            Self::DwarfDagger(e) => write!(f, "Dwarf Dagger{}", e),
-
-            Self::CinderShards10 => write!(f, "Cinder Shard (10)"),
-            Self::MermaidBoots => write!(f, "Mermaid Boots"),
-            Self::DragonscaleBoots => write!(f, "Dragonscale Boots"),
-            Self::GoldenCoconuts => write!(f, "Golden Coconut (3)"),
-            Self::PhoenixRing => write!(f, "Phoenix Ring"),
-            Self::HotJavaRing => write!(f, "Hot Java Ring"),
-            Self::DragontoothCutlass(e) => write!(f, "Dragontooth Cutlass{}", e),
-            Self::DragontoothClub(e) => write!(f, "Dragontooth Club{}", e),
-            Self::DragontoothShiv(e) => write!(f, "Dragontooth Shiv{}", e),
-            Self::DeluxePirateHat => write!(f, "Deluxe Pirate Hat"),
-            Self::OstrichEgg => write!(f, "Ostrich Egg"),
+        self.write_styled(f, TooltipStyle::Prefix)
+    }
+}
+
+impl ChestItem {
+    /// like Display, with the enchantments in `style`
+    pub fn write_styled(&self, out: &mut dyn Write, style: TooltipStyle) -> std::fmt::Result {
+        let meta = self.kind().meta();
+        out.write_str(meta.display_en)?;
+        if meta.quantity > 1 {
+            write!(out, " ({})", meta.quantity)?;
+        }
+        match self.enchants() {
+            Some(e) => e.write_styled(out, style),
+            None => Ok(()),
         }
     }
+
+    /// stack size the chest gives, with the bonuses of `modifiers`
+    pub fn quantity(&self, modifiers: &QuantityModifiers) -> u32 {
+        modifiers.apply(self.kind())
+    }
+
+    pub fn to_styled_string(&self, style: TooltipStyle) -> String {
+        let mut out = String::new();
+        self.write_styled(&mut out, style).unwrap();
+        out
+    }
 }
 
 impl ChestItem {
-    fn get_icon(&self) -> &'static str {
-        match self {
-            Self::CinderShards3 => "cinder_shard",
-            Self::GoldenCoconut => "golden_coconut",
-            Self::TaroTuber => "taro_tuber",
-            Self::PineappleSeeds => "pineapple_seeds",
-            Self::ProtectionRing => "protection_ring",
-            Self::SoulSapperRing => "soul_sapper_ring",
-            Self::DwarfSword(_) => "dwarf_sword",
-            Self::DwarfHammer(_) => "dwarf_hammer",
-            Self::DwarfDagger(_) => "dwarf_dagger",
+    /// wiki page of the item, the same for every quantity
+    pub fn wiki_url(&self) -> String {
+        format!(
+            "https://stardewvalleywiki.com/{}",
+            self.kind().name().replace(' ', "_")
+        )
+    }
 
-            Self::CinderShards10 => "cinder_shard",
-            Self::MermaidBoots => "mermaid_boots",
-            Self::DragonscaleBoots => "dragonscale_boots",
-            Self::GoldenCoconuts => "golden_coconut",
-            Self::PhoenixRing => "phoenix_ring",
-            Self::HotJavaRing => "hot_java_ring",
-            Self::DragontoothCutlass(_) => "dragontooth_cutlass",
-            Self::DragontoothClub(_) => "dragontooth_club",
-            Self::DragontoothShiv(_) => "dragontooth_shiv",
-            Self::DeluxePirateHat => "deluxe_pirate_hat",
-            Self::OstrichEgg => "ostrich_egg",
-        }
+    pub fn get_icon(&self) -> &'static str {
+        self.kind().meta().icon_key
     }
 }
 
@@ -164,163 +345,2576 @@ impl Display for Goodie {
                     minluck, rare, common
                 )
             }
+            Goodie::Ambiguous(alts) => {
+                write!(f, "depends on settings:")?;
+                for (a, g) in alts {
+                    write!(f, " [{}] {}", a, g)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
 impl Goodie {
-    pub fn to_html(&self) -> String {
+    /// chance of this being a rare chest, assuming the luck is uniformly distributed between
+    /// min_luck and max_luck
+    pub fn rare_probability(&self, min_luck: f64, max_luck: f64) -> f64 {
         match self {
-            Goodie::DragonTooth => format!("{} Dragon Tooth", format_icon("dragon_tooth")),
-            Goodie::CommonChest(c) => {
-                format!(
-                    "{} {} {}",
-                    format_icon("common_chest"),
-                    format_icon(c.get_icon()),
-                    c
-                )
+            Goodie::RareChest(_) => 1.,
+            Goodie::ChanceChest { minluck, .. } if max_luck > min_luck => {
+                ((max_luck - minluck) / (max_luck - min_luck)).clamp(0., 1.)
             }
-            Goodie::RareChest(c) => {
-                format!(
-                    "{} {} {}",
-                    format_icon("rare_chest"),
-                    format_icon(c.get_icon()),
-                    c
-                )
+            Goodie::ChanceChest { minluck, .. } => past_threshold(*minluck, min_luck) as u8 as f64,
+            _ => 0.,
+        }
+    }
+
+    pub fn to_html(&self) -> String {
+        self.try_render_to_string(&RenderOptions::default())
+            .expect("the default options have no output limit")
+    }
+
+    /// the item of a CommonChest or RareChest, None for everything else
+    pub fn as_chest(&self) -> Option<(Rarity, &ChestItem)> {
+        match self {
+            Goodie::CommonChest(c) => Some((Rarity::Common, c)),
+            Goodie::RareChest(c) => Some((Rarity::Rare, c)),
+            _ => None,
+        }
+    }
+
+    /// the two items of a ChanceChest, None for everything else
+    pub fn chance(&self) -> Option<ChanceInfo<'_>> {
+        match self {
+            Goodie::ChanceChest {
+                minluck,
+                common,
+                rare,
+            } => Some(ChanceInfo {
+                minluck: *minluck,
+                common,
+                rare,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A ChanceChest's fields, from Goodie::chance
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChanceInfo<'a> {
+    /// the chest is rare for luckMult above this
+    pub minluck: f64,
+    pub common: &'a ChestItem,
+    pub rare: &'a ChestItem,
+}
+
+impl ChestItem {
+    pub fn kind(&self) -> ChestItemKind {
+        match self {
+            Self::CinderShards3 => ChestItemKind::CinderShards3,
+            Self::GoldenCoconut => ChestItemKind::GoldenCoconut,
+            Self::TaroTuber => ChestItemKind::TaroTuber,
+            Self::PineappleSeeds => ChestItemKind::PineappleSeeds,
+            Self::ProtectionRing => ChestItemKind::ProtectionRing,
+            Self::SoulSapperRing => ChestItemKind::SoulSapperRing,
+            Self::DwarfSword(_) => ChestItemKind::DwarfSword,
+            Self::DwarfHammer(_) => ChestItemKind::DwarfHammer,
+            Self::DwarfDagger(_) => ChestItemKind::DwarfDagger,
+
+            Self::CinderShards10 => ChestItemKind::CinderShards10,
+            Self::MermaidBoots => ChestItemKind::MermaidBoots,
+            Self::DragonscaleBoots => ChestItemKind::DragonscaleBoots,
+            Self::GoldenCoconuts => ChestItemKind::GoldenCoconuts,
+            Self::PhoenixRing => ChestItemKind::PhoenixRing,
+            Self::HotJavaRing => ChestItemKind::HotJavaRing,
+            Self::DragontoothCutlass(_) => ChestItemKind::DragontoothCutlass,
+            Self::DragontoothClub(_) => ChestItemKind::DragontoothClub,
+            Self::DragontoothShiv(_) => ChestItemKind::DragontoothShiv,
+            Self::DeluxePirateHat => ChestItemKind::DeluxePirateHat,
+            Self::OstrichEgg => ChestItemKind::OstrichEgg,
+        }
+    }
+}
+
+impl ChestItem {
+    /// the enchantments are dropped for anything that isn't a weapon
+    pub fn with_enchants(kind: ChestItemKind, enchants: Enchants) -> Self {
+        match kind {
+            ChestItemKind::CinderShards3 => Self::CinderShards3,
+            ChestItemKind::GoldenCoconut => Self::GoldenCoconut,
+            ChestItemKind::TaroTuber => Self::TaroTuber,
+            ChestItemKind::PineappleSeeds => Self::PineappleSeeds,
+            ChestItemKind::ProtectionRing => Self::ProtectionRing,
+            ChestItemKind::SoulSapperRing => Self::SoulSapperRing,
+            ChestItemKind::DwarfSword => Self::DwarfSword(enchants),
+            ChestItemKind::DwarfHammer => Self::DwarfHammer(enchants),
+            ChestItemKind::DwarfDagger => Self::DwarfDagger(enchants),
+
+            ChestItemKind::CinderShards10 => Self::CinderShards10,
+            ChestItemKind::MermaidBoots => Self::MermaidBoots,
+            ChestItemKind::DragonscaleBoots => Self::DragonscaleBoots,
+            ChestItemKind::GoldenCoconuts => Self::GoldenCoconuts,
+            ChestItemKind::PhoenixRing => Self::PhoenixRing,
+            ChestItemKind::HotJavaRing => Self::HotJavaRing,
+            ChestItemKind::DragontoothCutlass => Self::DragontoothCutlass(enchants),
+            ChestItemKind::DragontoothClub => Self::DragontoothClub(enchants),
+            ChestItemKind::DragontoothShiv => Self::DragontoothShiv(enchants),
+            ChestItemKind::DeluxePirateHat => Self::DeluxePirateHat,
+            ChestItemKind::OstrichEgg => Self::OstrichEgg,
+        }
+    }
+
+    /// a weapon that didn't pass the enchantment gate
+    pub fn is_unenchanted_weapon(&self) -> bool {
+        self.enchants().is_some_and(|e| e.as_slice().is_empty())
+    }
+
+    pub fn is_weapon(&self) -> bool {
+        self.enchants().is_some()
+    }
+
+    /// see ChestItemKind::is_unique
+    pub fn is_unique(&self) -> bool {
+        self.kind().is_unique()
+    }
+
+    /// None for anything that isn't a weapon
+    pub fn enchants(&self) -> Option<&Enchants> {
+        match self {
+            Self::DwarfSword(e)
+            | Self::DwarfHammer(e)
+            | Self::DwarfDagger(e)
+            | Self::DragontoothCutlass(e)
+            | Self::DragontoothClub(e)
+            | Self::DragontoothShiv(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Bonuses (from mods, say) that multiply what a chest gives of a stack. The item is the same,
+/// so generation ignores these, only the counts shown and valued change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuantityModifiers {
+    shards: u32,
+    coconuts: u32,
+}
+
+impl Default for QuantityModifiers {
+    fn default() -> Self {
+        QuantityModifiers {
+            shards: 1,
+            coconuts: 1,
+        }
+    }
+}
+
+impl QuantityModifiers {
+    /// InvalidQuantityMultiplier for multipliers outside of 1-10
+    pub fn new(shards: u32, coconuts: u32) -> Result<Self> {
+        for m in [shards, coconuts] {
+            if !(1..=10).contains(&m) {
+                return Err(Error::InvalidQuantityMultiplier(m));
             }
-            Goodie::ChanceChest { .. } => panic!("Turning chance_chest into html"),
         }
+        Ok(QuantityModifiers { shards, coconuts })
+    }
+
+    /// for both cinder shard stacks
+    pub fn shards(&self) -> u32 {
+        self.shards
+    }
+
+    /// for the single golden coconut and the stack of 3
+    pub fn coconuts(&self) -> u32 {
+        self.coconuts
+    }
+
+    /// what the count of `kind` is multiplied by, 1 for the items it doesn't cover
+    pub fn multiplier(&self, kind: ChestItemKind) -> u32 {
+        match kind {
+            ChestItemKind::CinderShards3 | ChestItemKind::CinderShards10 => self.shards,
+            ChestItemKind::GoldenCoconut | ChestItemKind::GoldenCoconuts => self.coconuts,
+            _ => 1,
+        }
+    }
+
+    /// how many of `kind` the chest gives with these
+    pub fn apply(&self, kind: ChestItemKind) -> u32 {
+        kind.quantity() * self.multiplier(kind)
     }
 }
 
-// actual generation logic
+/// Everything about an item that doesn't depend on its enchantments
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ItemMeta {
+    pub kind: ChestItemKind,
+    /// see ChestItemKind::stable_id
+    pub stable_id: u16,
+    /// English name as used on the wiki, without the quantity
+    pub display_en: &'static str,
+    /// file name in icons/, without the .png
+    pub icon_key: &'static str,
+    /// qualified item id, e.g. "(O)848"
+    pub game_id: &'static str,
+    /// stack size the chest gives
+    pub quantity: u32,
+    /// the chests only have it from 1.6.4 on. None do, meta leaves it false, kinds that get
+    /// added by a version would set it with `ItemMeta { since_1_6_4: true, ..meta(...) }`
+    pub since_1_6_4: bool,
+}
+
+const fn meta(
+    kind: ChestItemKind,
+    stable_id: u16,
+    display_en: &'static str,
+    icon_key: &'static str,
+    game_id: &'static str,
+    quantity: u32,
+) -> ItemMeta {
+    ItemMeta {
+        kind,
+        stable_id,
+        display_en,
+        icon_key,
+        game_id,
+        quantity,
+        since_1_6_4: false,
+    }
+}
 
-/// apply innate enchantments to a weapon
-fn enchant_item(rng: &mut rng::DotnetRng, weapon_lvl: i32, weapon_speed: i32) -> Enchants {
-    // based on StardewValley.Tools.MeleeWeapon.attemptAddRandomInnateEnchantment
-    let mut enchs = vec![];
-    if rng.next_f64() < 0.5 {
-        if rng.next_f64() < 0.125 && weapon_lvl <= 10 {
-            let lvl = (rng.next_range(weapon_lvl + 1) / 2 + 1).min(2).max(1);
-            enchs.push((Enchant::Defense, lvl));
-        } else if rng.next_f64() < 0.125 {
-            enchs.push((Enchant::Weight, -(1 + rng.next_range(5))));
-        } else if rng.next_f64() < 0.125 {
-            enchs.push((Enchant::SlimeGatherer, 0));
+/// indexed by ChestItemKind as usize, the one place all of this is written down
+pub const ITEM_META: [ItemMeta; 20] = {
+    use ChestItemKind::*;
+    [
+        meta(
+            CinderShards3,
+            0,
+            "Cinder Shard",
+            "cinder_shard",
+            "(O)848",
+            3,
+        ),
+        meta(
+            GoldenCoconut,
+            1,
+            "Golden Coconut",
+            "golden_coconut",
+            "(O)791",
+            1,
+        ),
+        meta(TaroTuber, 2, "Taro Tuber", "taro_tuber", "(O)831", 8),
+        meta(
+            PineappleSeeds,
+            3,
+            "Pineapple Seeds",
+            "pineapple_seeds",
+            "(O)833",
+            5,
+        ),
+        meta(
+            ProtectionRing,
+            4,
+            "Protection Ring",
+            "protection_ring",
+            "(O)861",
+            1,
+        ),
+        meta(
+            SoulSapperRing,
+            5,
+            "Soul Sapper Ring",
+            "soul_sapper_ring",
+            "(O)862",
+            1,
+        ),
+        meta(DwarfSword, 6, "Dwarf Sword", "dwarf_sword", "(W)54", 1),
+        meta(DwarfHammer, 7, "Dwarf Hammer", "dwarf_hammer", "(W)55", 1),
+        meta(DwarfDagger, 8, "Dwarf Dagger", "dwarf_dagger", "(W)56", 1),
+        meta(
+            CinderShards10,
+            9,
+            "Cinder Shard",
+            "cinder_shard",
+            "(O)848",
+            10,
+        ),
+        meta(
+            MermaidBoots,
+            10,
+            "Mermaid Boots",
+            "mermaid_boots",
+            "(B)854",
+            1,
+        ),
+        meta(
+            DragonscaleBoots,
+            11,
+            "Dragonscale Boots",
+            "dragonscale_boots",
+            "(B)855",
+            1,
+        ),
+        meta(
+            GoldenCoconuts,
+            12,
+            "Golden Coconut",
+            "golden_coconut",
+            "(O)791",
+            3,
+        ),
+        meta(PhoenixRing, 13, "Phoenix Ring", "phoenix_ring", "(O)863", 1),
+        meta(
+            HotJavaRing,
+            14,
+            "Hot Java Ring",
+            "hot_java_ring",
+            "(O)860",
+            1,
+        ),
+        meta(
+            DragontoothCutlass,
+            15,
+            "Dragontooth Cutlass",
+            "dragontooth_cutlass",
+            "(W)57",
+            1,
+        ),
+        meta(
+            DragontoothClub,
+            16,
+            "Dragontooth Club",
+            "dragontooth_club",
+            "(W)58",
+            1,
+        ),
+        meta(
+            DragontoothShiv,
+            17,
+            "Dragontooth Shiv",
+            "dragontooth_shiv",
+            "(W)59",
+            1,
+        ),
+        meta(
+            DeluxePirateHat,
+            18,
+            "Deluxe Pirate Hat",
+            "deluxe_pirate_hat",
+            "(H)76",
+            1,
+        ),
+        meta(OstrichEgg, 19, "Ostrich Egg", "ostrich_egg", "(O)289", 1),
+    ]
+};
+
+// every kind has its entry at its own index, and a stable id of its own
+const _: () = {
+    let mut i = 0;
+    while i < ITEM_META.len() {
+        assert!(ITEM_META[i].kind as usize == i);
+        let mut j = 0;
+        while j < i {
+            assert!(ITEM_META[i].stable_id != ITEM_META[j].stable_id);
+            j += 1;
         }
+        i += 1;
+    }
+};
 
-        let last_one = match rng.next_range(5) {
-            0 => (
-                Enchant::Attack,
-                (rng.next_range(weapon_lvl + 1) / 2 + 1).clamp(1, 5),
-            ),
-            1 => (
-                Enchant::CritChance,
-                (rng.next_range(weapon_lvl) / 3).clamp(1, 3),
-            ),
-            2 => (
-                Enchant::Speed,
-                (rng.next_range(weapon_lvl)
-                    .clamp(1, i32::max(1, 4 - weapon_speed))),
-            ),
-            3 => (Enchant::SlimeSlayer, 0),
-            4 => (
-                Enchant::CritPower,
-                (rng.next_range(weapon_lvl) / 3).clamp(1, 3) * 25,
-            ),
-            _ => unreachable!(),
-        };
-        enchs.push(last_one);
+// the stable ids and their round trips, the enum order doesn't matter for these
+const _: () = {
+    let mut i = 0;
+    while i < ITEM_META.len() {
+        let kind = ITEM_META[i].kind;
+        assert!(
+            matches!(ChestItemKind::from_stable_id(kind.stable_id()), Some(k) if k as usize == i)
+        );
+        i += 1;
+    }
+    let mut i = 0;
+    while i < Enchant::ALL.len() {
+        let e = Enchant::ALL[i];
+        assert!(
+            matches!(Enchant::from_stable_id(e.stable_id()), Some(x) if x as usize == e as usize)
+        );
+        i += 1;
+    }
+    assert!(ChestItemKind::from_stable_id(ITEM_META.len() as u16).is_none());
+    assert!(Enchant::from_stable_id(Enchant::ALL.len() as u16).is_none());
+};
+
+impl ChestItemKind {
+    pub fn meta(self) -> &'static ItemMeta {
+        &ITEM_META[self as usize]
+    }
+
+    /// Number for storing the kind, e.g. in telemetry. Unlike `as` casts it stays the same when
+    /// variants are added or reordered: ids are never renumbered or reused, new kinds get new
+    /// ones. They're in ITEM_META.
+    pub const fn stable_id(self) -> u16 {
+        ITEM_META[self as usize].stable_id
+    }
+
+    /// the kind with this stable_id, None for ids that aren't (or aren't yet) in use
+    pub const fn from_stable_id(id: u16) -> Option<Self> {
+        let mut i = 0;
+        while i < ITEM_META.len() {
+            if ITEM_META[i].stable_id == id {
+                return Some(ITEM_META[i].kind);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// English name as used on the wiki, without the quantity
+    pub fn name(self) -> &'static str {
+        self.meta().display_en
+    }
+
+    /// stack size the chest gives
+    pub fn quantity(self) -> u32 {
+        self.meta().quantity
+    }
+
+    /// qualified item id, e.g. "(W)57"
+    pub fn game_id(self) -> &'static str {
+        self.meta().game_id
+    }
+
+    /// The first of the modeled versions whose chests have it. Older versions aren't modeled, so
+    /// items from before 1.6 (all of them so far) are V1_6.
+    pub fn introduced_in(self) -> GameVersion {
+        if self.meta().since_1_6_4 {
+            GameVersion::V1_6_4
+        } else {
+            GameVersion::V1_6
+        }
+    }
+
+    /// whether the chests can have it in `version`, false for Unknown versions since
+    /// there's nothing known about them
+    pub fn available_in(self, version: &GameVersion) -> bool {
+        match version {
+            GameVersion::V1_6 => !self.meta().since_1_6_4,
+            GameVersion::V1_6_4 => true,
+            GameVersion::Unknown(_) => false,
+        }
+    }
+
+    /// Everything the chests can give with these settings, common and rare together, in
+    /// ChestItemKind order. LootTables::obtainable has them per table, and for other tables.
+    pub fn all_obtainable(settings: &GameSettings) -> Vec<ChestItemKind> {
+        let tables = LootTables::vanilla();
+        let mut out = tables.obtainable(Rarity::Common, settings);
+        out.extend(tables.obtainable(Rarity::Rare, settings));
+        out.sort();
+        out.dedup();
+        out
+    }
+}
+
+/// Data/Boots values of the boots the chests give
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BootStats {
+    pub defense: i32,
+    pub immunity: i32,
+}
+
+impl ChestItemKind {
+    pub fn boot_stats(self) -> Option<BootStats> {
+        match self {
+            ChestItemKind::MermaidBoots => Some(BootStats {
+                defense: 1,
+                immunity: 8,
+            }),
+            ChestItemKind::DragonscaleBoots => Some(BootStats {
+                defense: 7,
+                immunity: 0,
+            }),
+            _ => None,
+        }
     }
-    Enchants(enchs)
 }
 
 impl ChestItem {
-    pub fn generate_common(seed: i32, settings: GameSettings) -> Self {
-        let mut rng = rng::DotnetRng::new(seed);
-        rng.next(); // one roll used for rare/normal check
-        let ind = loop {
-            let ind = rng.next_range(7);
-            if ind == 1 && !settings.cracked_golden_coconut {
-                continue;
+    /// What wearing it does, for rings, boots and the hat. None for weapons (their enchantments are
+    /// already in the name) and resources.
+    pub fn effect_text(&self, locale: Locale) -> Option<Cow<'static, str>> {
+        if let Some(stats) = self.kind().boot_stats() {
+            let (defense, immunity) = match locale {
+                Locale::En => ("Defense", "Immunity"),
+                Locale::De => ("Verteidigung", "Immunität"),
+            };
+            let mut text = format!("+{} {}", stats.defense, defense);
+            if stats.immunity > 0 {
+                write!(text, ", +{} {}", stats.immunity, immunity).ok()?;
+            }
+            return Some(text.into());
+        }
+        let text = match (locale, self) {
+            (Locale::En, ChestItem::ProtectionRing) => "Longer invincibility after taking damage",
+            (Locale::En, ChestItem::SoulSapperRing) => {
+                "Restores a little energy for every monster slain"
+            }
+            (Locale::En, ChestItem::PhoenixRing) => {
+                "Once a day, revives you with half health when knocked out"
+            }
+            (Locale::En, ChestItem::HotJavaRing) => "Monsters more likely to drop coffee",
+            (Locale::En, ChestItem::DeluxePirateHat) => "Cosmetic only",
+            (Locale::De, ChestItem::ProtectionRing) => {
+                "Längere Unverwundbarkeit, nachdem du Schaden genommen hast"
+            }
+            (Locale::De, ChestItem::SoulSapperRing) => {
+                "Stellt für jedes besiegte Monster etwas Energie wieder her"
             }
-            break ind;
+            (Locale::De, ChestItem::PhoenixRing) => {
+                "Belebt dich einmal am Tag mit halber Gesundheit wieder, wenn du umkippst"
+            }
+            (Locale::De, ChestItem::HotJavaRing) => "Monster lassen eher Kaffee fallen",
+            (Locale::De, ChestItem::DeluxePirateHat) => "Nur kosmetisch",
+            _ => return None,
         };
-        match ind {
-            0 => Self::CinderShards3,
-            1 => Self::GoldenCoconut,
-            2 => Self::TaroTuber,
-            3 => Self::PineappleSeeds,
-            4 => Self::ProtectionRing,
-            5 => Self::SoulSapperRing,
-            6 => match rng.next_range(3) {
-                0 => Self::DwarfSword(enchant_item(&mut rng, 13, 4)),
-                1 => Self::DwarfHammer(enchant_item(&mut rng, 13, -8)),
-                2 => Self::DwarfDagger(enchant_item(&mut rng, 11, 3)),
-                _ => unreachable!(),
+        Some(text.into())
+    }
+}
+
+// raw rolls
+
+impl PartialEq for Enchants {
+    fn eq(&self, other: &Self) -> bool {
+        self.levels == other.levels
+    }
+}
+
+impl PartialOrd for Enchants {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.levels.partial_cmp(&other.levels)
+    }
+}
+
+impl ClampRules {
+    pub const VANILLA: ClampRules = ClampRules {
+        defense: (1, 2),
+        attack: (1, 5),
+        crit_chance: (1, 3),
+        crit_power: (1, 3),
+        speed: None,
+    };
+
+    /// UI level of a raw roll, None if these rules don't clamp the enchant
+    pub fn ui_level(&self, enchant: Enchant, raw: i32) -> Option<i32> {
+        let clamp = |(min, max): (i32, i32)| math_helper_clamp(raw, min, max);
+        match enchant {
+            Enchant::Defense => Some(clamp(self.defense)),
+            Enchant::Attack => Some(clamp(self.attack)),
+            Enchant::CritChance => Some(clamp(self.crit_chance)),
+            Enchant::CritPower => Some(clamp(self.crit_power) * 25),
+            Enchant::Speed => self.speed.map(clamp),
+            Enchant::Weight | Enchant::SlimeGatherer | Enchant::SlimeSlayer => None,
+        }
+    }
+}
+
+/// MathHelper.Clamp, which unlike i32::clamp doesn't panic when min > max: min wins, as it's
+/// checked last
+fn math_helper_clamp(value: i32, min: i32, max: i32) -> i32 {
+    let value = if value > max { max } else { value };
+    if value < min { min } else { value }
+}
+
+impl Default for ClampRules {
+    fn default() -> Self {
+        ClampRules::VANILLA
+    }
+}
+
+impl Enchants {
+    pub(crate) fn from_levels(levels: Vec<(Enchant, i32)>) -> Self {
+        let raw = vec![None; levels.len()];
+        Enchants { levels, raw }
+    }
+
+    pub fn rolls(&self) -> Vec<EnchantRoll> {
+        self.levels
+            .iter()
+            .zip(&self.raw)
+            .map(|(&(enchant, ui_level), &raw)| EnchantRoll {
+                enchant,
+                raw,
+                ui_level,
+            })
+            .collect()
+    }
+
+    /// The levels the raw rolls would give under `rules`. Enchants without a raw roll, or that
+    /// `rules` doesn't clamp, keep their level.
+    pub fn reclamp(&self, rules: &ClampRules) -> Enchants {
+        let levels = self
+            .rolls()
+            .iter()
+            .map(|r| {
+                let lvl = r.raw.and_then(|raw| rules.ui_level(r.enchant, raw));
+                (r.enchant, lvl.unwrap_or(r.ui_level))
+            })
+            .collect();
+        Enchants {
+            levels,
+            raw: self.raw.clone(),
+        }
+    }
+}
+
+// comparing rolls
+
+impl Enchant {
+    /// whether level `a` is as good as `b`. weight is shown negative so lower is better, slime
+    /// enchants only have level 0.
+    fn as_good(self, a: i32, b: i32) -> bool {
+        match self {
+            Enchant::Weight => a <= b,
+            _ => a >= b,
+        }
+    }
+}
+
+impl Enchants {
+    /// every enchant of `floor` is here, with a level that's as good
+    pub fn at_least(&self, floor: &Enchants) -> bool {
+        floor.levels.iter().all(|&(e, lvl)| {
+            self.levels
+                .iter()
+                .any(|&(e2, lvl2)| e2 == e && e.as_good(lvl2, lvl))
+        })
+    }
+
+    /// as good as `other` in everything, and better in something
+    pub fn dominates(&self, other: &Enchants) -> bool {
+        self.at_least(other) && !other.at_least(self)
+    }
+}
+
+/// One enchant that's on both sides of a diff with different levels
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EnchantChange {
+    pub enchant: Enchant,
+    pub from: i32,
+    pub to: i32,
+}
+
+impl EnchantChange {
+    pub fn is_better(&self) -> bool {
+        self.enchant.as_good(self.to, self.from)
+    }
+}
+
+/// What changes going from one set of enchants to another, see Enchants::diff
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EnchantDiff {
+    pub gained: Vec<(Enchant, i32)>,
+    pub lost: Vec<(Enchant, i32)>,
+    /// only enchants with levels, the slime ones are just gained or lost
+    pub changed: Vec<EnchantChange>,
+}
+
+impl EnchantDiff {
+    pub fn is_empty(&self) -> bool {
+        self.gained.is_empty() && self.lost.is_empty() && self.changed.is_empty()
+    }
+
+    /// nothing lost or worse, and something gained or better. the same as the new enchants
+    /// dominating the old ones.
+    pub fn is_strict_upgrade(&self) -> bool {
+        !self.is_empty() && self.lost.is_empty() && self.changed.iter().all(|c| c.is_better())
+    }
+}
+
+impl Enchants {
+    /// what changes from these enchants to `other`, in the order of the enchants
+    pub fn diff(&self, other: &Enchants) -> EnchantDiff {
+        let mut diff = EnchantDiff::default();
+        for &(e, from) in &self.levels {
+            match other.levels.iter().find(|(e2, _)| *e2 == e) {
+                None => diff.lost.push((e, from)),
+                Some(&(_, to)) if to != from => diff.changed.push(EnchantChange {
+                    enchant: e,
+                    from,
+                    to,
+                }),
+                Some(_) => {}
             }
-            _ => unreachable!(),
         }
+        diff.gained = other
+            .levels
+            .iter()
+            .filter(|(e, _)| !self.levels.iter().any(|(e2, _)| e2 == e))
+            .copied()
+            .collect();
+        diff
     }
+}
 
-    pub fn generate_rare(seed: i32, settings: GameSettings) -> Self {
-        let mut rng = rng::DotnetRng::new(seed);
-        rng.next(); // one roll used for rare/normal check
-        let ind = loop {
-            let ind = rng.next_range(9);
-            if ind == 3 && !settings.cracked_golden_coconut {
-                continue;
+impl Display for EnchantDiff {
+    /// e.g. "+3 Attack → +5 Attack, gains -4 Weight", empty without changes
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let style = TooltipStyle::Prefix;
+        let mut first = true;
+        let mut sep = |f: &mut std::fmt::Formatter<'_>| {
+            if !std::mem::take(&mut first) {
+                write!(f, ", ")?;
             }
-            break ind;
+            Ok(())
         };
-        match ind {
-            0 => Self::CinderShards10,
-            1 => Self::MermaidBoots,
-            2 => Self::DragonscaleBoots,
-            3 => Self::GoldenCoconuts,
-            4 => Self::PhoenixRing,
-            5 => Self::HotJavaRing,
-            6 => match rng.next_range(3) {
-                0 => Self::DragontoothCutlass(enchant_item(&mut rng, 13, 0)),
-                1 => Self::DragontoothClub(enchant_item(&mut rng, 14, -8)),
-                2 => Self::DragontoothShiv(enchant_item(&mut rng, 12, 0)),
-                _ => unreachable!(),
-            }
-            7 => Self::DeluxePirateHat,
-            8 => Self::OstrichEgg,
-            _ => unreachable!(),
+        for c in &self.changed {
+            sep(f)?;
+            style.write_enchant(f, &c.enchant.name(), c.from)?;
+            write!(f, " → ")?;
+            style.write_enchant(f, &c.enchant.name(), c.to)?;
+        }
+        for &(e, lvl) in &self.gained {
+            sep(f)?;
+            write!(f, "gains ")?;
+            style.write_enchant(f, &e.name(), lvl)?;
         }
+        for &(e, lvl) in &self.lost {
+            sep(f)?;
+            write!(f, "loses ")?;
+            style.write_enchant(f, &e.name(), lvl)?;
+        }
+        Ok(())
     }
 }
 
-impl Goodie {
-    pub fn generate(
-        chest_seed: i32,
+// compact encoding, for urls
+
+impl Enchant {
+    pub(crate) const ALL: [Enchant; 8] = [
+        Enchant::Defense,
+        Enchant::Weight,
+        Enchant::SlimeGatherer,
+        Enchant::SlimeSlayer,
+        Enchant::CritPower,
+        Enchant::CritChance,
+        Enchant::Attack,
+        Enchant::Speed,
+    ];
+
+    fn compact_code(self) -> &'static str {
+        match self {
+            Enchant::Defense => "d",
+            Enchant::Weight => "w",
+            Enchant::SlimeGatherer => "sg",
+            Enchant::SlimeSlayer => "ss",
+            Enchant::CritPower => "cp",
+            Enchant::CritChance => "cc",
+            Enchant::Attack => "a",
+            Enchant::Speed => "s",
+        }
+    }
+
+    /// whether `lvl` is a UI level enchant_item can produce for this enchant (on any weapon)
+    pub(crate) fn valid_level(self, lvl: i32) -> bool {
+        match self {
+            Enchant::Defense => (1..=2).contains(&lvl),
+            Enchant::Weight => (-5..=-1).contains(&lvl),
+            Enchant::SlimeGatherer | Enchant::SlimeSlayer => lvl == 0,
+            Enchant::CritPower => matches!(lvl, 25 | 50 | 75),
+            Enchant::CritChance => (1..=3).contains(&lvl),
+            Enchant::Attack => (1..=5).contains(&lvl),
+            // the upper bound is 4 - weapon speed, which is 12 for the slowest weapons
+            Enchant::Speed => (1..=12).contains(&lvl),
+        }
+    }
+}
+
+impl Enchants {
+    /// e.g. "a4.w-3". Enchants without a level (slime ones) are just the code.
+    pub fn to_compact_string(&self) -> String {
+        let parts: Vec<String> = self
+            .levels
+            .iter()
+            .map(|&(e, lvl)| {
+                if lvl == 0 {
+                    e.compact_code().to_string()
+                } else {
+                    format!("{}{}", e.compact_code(), lvl)
+                }
+            })
+            .collect();
+        parts.join(".")
+    }
+
+    pub fn from_compact_string(s: &str) -> Result<Self> {
+        let mut out = vec![];
+        if s.is_empty() {
+            return Ok(Enchants::from_levels(out));
+        }
+        for part in s.split('.') {
+            let split = part
+                .find(|c: char| c == '-' || c.is_ascii_digit())
+                .unwrap_or(part.len());
+            let (code, lvl) = part.split_at(split);
+            let Some(e) = Enchant::ALL.into_iter().find(|e| e.compact_code() == code) else {
+                return Err(Error::UnknownEnchantCode(code.to_string()));
+            };
+            let lvl = if lvl.is_empty() {
+                0
+            } else {
+                lvl.parse()
+                    .map_err(|_| Error::UnknownEnchantCode(part.to_string()))?
+            };
+            if !e.valid_level(lvl) {
+                return Err(Error::EnchantLevelOutOfRange(e, lvl));
+            }
+            out.push((e, lvl));
+        }
+        Ok(Enchants::from_levels(out))
+    }
+}
+
+impl ChestItemKind {
+    pub const ALL: [ChestItemKind; 20] = [
+        Self::CinderShards3,
+        Self::GoldenCoconut,
+        Self::TaroTuber,
+        Self::PineappleSeeds,
+        Self::ProtectionRing,
+        Self::SoulSapperRing,
+        Self::DwarfSword,
+        Self::DwarfHammer,
+        Self::DwarfDagger,
+        Self::CinderShards10,
+        Self::MermaidBoots,
+        Self::DragonscaleBoots,
+        Self::GoldenCoconuts,
+        Self::PhoenixRing,
+        Self::HotJavaRing,
+        Self::DragontoothCutlass,
+        Self::DragontoothClub,
+        Self::DragontoothShiv,
+        Self::DeluxePirateHat,
+        Self::OstrichEgg,
+    ];
+
+    fn compact_code(self) -> &'static str {
+        match self {
+            Self::CinderShards3 => "cinder3",
+            Self::GoldenCoconut => "coconut",
+            Self::TaroTuber => "taro",
+            Self::PineappleSeeds => "pineapple",
+            Self::ProtectionRing => "protection_ring",
+            Self::SoulSapperRing => "soul_sapper",
+            Self::DwarfSword => "dwarf_sword",
+            Self::DwarfHammer => "dwarf_hammer",
+            Self::DwarfDagger => "dwarf_dagger",
+
+            Self::CinderShards10 => "cinder10",
+            Self::MermaidBoots => "mermaid_boots",
+            Self::DragonscaleBoots => "dragonscale_boots",
+            Self::GoldenCoconuts => "coconut3",
+            Self::PhoenixRing => "phoenix_ring",
+            Self::HotJavaRing => "hot_java",
+            Self::DragontoothCutlass => "dt_cutlass",
+            Self::DragontoothClub => "dt_club",
+            Self::DragontoothShiv => "dt_shiv",
+            Self::DeluxePirateHat => "pirate_hat",
+            Self::OstrichEgg => "ostrich_egg",
+        }
+    }
+
+    pub fn to_compact_string(self) -> String {
+        self.compact_code().to_string()
+    }
+
+    pub fn from_compact_string(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|k| k.compact_code() == s)
+            .ok_or_else(|| Error::UnknownItemCode(s.to_string()))
+    }
+
+    /// Item from a name someone typed: the English name, the compact code or an alias, in any
+    /// case and with up to 2 typos. Failing that, the items with a name containing it. Gives
+    /// UnknownItemName with suggestions (closest first) if it's none or several of them.
+    pub fn fuzzy_parse(s: &str) -> Result<Self> {
+        let input = normalize_name(s);
+        let names = |kind: Self| {
+            [
+                kind.name().to_lowercase(),
+                normalize_name(kind.compact_code()),
+            ]
+            .into_iter()
+            .chain(
+                ITEM_ALIASES
+                    .iter()
+                    .filter(move |a| a.1 == kind)
+                    .map(|a| a.0.to_string()),
+            )
+        };
+        let mut close: Vec<(usize, Self)> = Self::ALL
+            .into_iter()
+            .filter_map(|kind| {
+                let dist = names(kind).map(|n| edit_distance(&input, &n)).min()?;
+                (dist <= 2).then_some((dist, kind))
+            })
+            .collect();
+        close.sort_by_key(|&(dist, _)| dist);
+        let suggestions: Vec<Self> = if close.is_empty() {
+            let words = |n: String| format!(" {} ", n).contains(&format!(" {} ", input));
+            Self::ALL
+                .into_iter()
+                .filter(|&kind| !input.is_empty() && names(kind).any(words))
+                .collect()
+        } else {
+            let best = close[0].0;
+            // a unique best match wins, even if something else is within 2
+            if close.iter().filter(|c| c.0 == best).count() == 1 {
+                return Ok(close[0].1);
+            }
+            close.into_iter().map(|c| c.1).collect()
+        };
+        match suggestions[..] {
+            [kind] => Ok(kind),
+            _ => Err(Error::UnknownItemName {
+                name: s.to_string(),
+                suggestions,
+            }),
+        }
+    }
+}
+
+impl FromStr for ChestItemKind {
+    type Err = Error;
+    /// see fuzzy_parse
+    fn from_str(s: &str) -> Result<Self> {
+        Self::fuzzy_parse(s)
+    }
+}
+
+/// other names people use for the items, normalized like normalize_name
+const ITEM_ALIASES: &[(&str, ChestItemKind)] = &[
+    ("egg", ChestItemKind::OstrichEgg),
+    ("pirate hat", ChestItemKind::DeluxePirateHat),
+    ("dt cutlass", ChestItemKind::DragontoothCutlass),
+    ("dt club", ChestItemKind::DragontoothClub),
+    ("dt shiv", ChestItemKind::DragontoothShiv),
+    ("java ring", ChestItemKind::HotJavaRing),
+    ("soul sapper", ChestItemKind::SoulSapperRing),
+];
+
+/// lowercase, with `_` and `-` as spaces and the whitespace collapsed
+fn normalize_name(s: &str) -> String {
+    s.to_lowercase()
+        .replace(['_', '-'], " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Damerau-Levenshtein distance (optimal string alignment), swapping two neighbours is one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = (a[i - 1] != b[j - 1]) as usize;
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+// actual generation logic
+
+/// The gates behind a weapon's innate enchantments, to see why it got the ones it did
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnchantGates {
+    /// the < 0.5 roll for having any enchantments, None when forced like at the forge
+    pub any: Option<f64>,
+    /// the < 0.125 rolls for defense, weight and slime gatherer. They're an else-if chain, so the
+    /// rolls stop at the first one that passes and the rest are None. Defense also needs item
+    /// level 10 or less, its roll is used up either way.
+    pub extra_rolls: [Option<f64>; 3],
+    /// the enchantment that got the extra slot, at most one can
+    pub extra: Option<Enchant>,
+}
+
+impl EnchantGates {
+    /// whether the weapon got enchantments at all, and so the always rolled main one
+    pub fn passed(&self) -> bool {
+        self.any.is_none_or(|roll| roll < gate_probability())
+    }
+}
+
+/// Chance of a chest weapon getting any innate enchantments, the first gate of the rolls. It's
+/// the same for every volcano weapon and game version.
+pub const fn gate_probability() -> f64 {
+    0.5
+}
+
+/// apply innate enchantments to a weapon. `force` skips the 50% chance of getting none, like the
+/// forge does.
+pub(crate) fn enchant_item(
+    rng: &mut rng::DotnetRng,
+    weapon_lvl: i32,
+    weapon_speed: i32,
+    force: bool,
+) -> Enchants {
+    enchant_item_traced(rng, weapon_lvl, weapon_speed, force).0
+}
+
+/// enchant_item, also giving the gates that passed
+pub(crate) fn enchant_item_traced(
+    rng: &mut rng::DotnetRng,
+    weapon_lvl: i32,
+    weapon_speed: i32,
+    force: bool,
+) -> (Enchants, EnchantGates) {
+    // based on StardewValley.Tools.MeleeWeapon.attemptAddRandomInnateEnchantment (1.6): after
+    // the 0.5 gate it's `if (defense) .. else if (weight) .. else if (slime gatherer)`, then the
+    // switch for the main enchantment, which has no gate of its own. So a weapon with any
+    // enchantments always has the main one, and at most one extra: two in total, without any cap
+    // needed to enforce it.
+    // fast weapons (speed 4 and up) can only get speed 1
+    let rules = ClampRules {
+        speed: Some((1, i32::max(1, 4i32.wrapping_sub(weapon_speed)))),
+        ..ClampRules::VANILLA
+    };
+    let mut gates = EnchantGates {
+        any: (!force).then(|| rng.next_f64()),
+        extra_rolls: [None; 3],
+        extra: None,
+    };
+    // (enchant, raw roll)
+    let mut rolls = vec![];
+    if gates.passed() {
+        let extras = [Enchant::Defense, Enchant::Weight, Enchant::SlimeGatherer];
+        for (i, enchant) in extras.into_iter().enumerate() {
+            let roll = rng.next_f64();
+            gates.extra_rolls[i] = Some(roll);
+            if roll < 0.125 && (enchant != Enchant::Defense || weapon_lvl <= 10) {
+                gates.extra = Some(enchant);
+                break;
+            }
+        }
+        match gates.extra {
+            Some(Enchant::Defense) => {
+                rolls.push((Enchant::Defense, rng.next_range(weapon_lvl + 1) / 2 + 1))
+            }
+            Some(Enchant::Weight) => rolls.push((Enchant::Weight, -(1 + rng.next_range(5)))),
+            Some(e) => rolls.push((e, 0)),
+            None => {}
+        }
+
+        // crit chance and power are `Next(level) / 3`, clamped to 1-3 by the rules, so 0 counts
+        // as 1: a level 13 weapon gets +1/+2/+3 6/13, 3/13 and 4/13 of the time
+        let last_one = match rng.next_range(5) {
+            0 => (Enchant::Attack, rng.next_range(weapon_lvl + 1) / 2 + 1),
+            1 => (Enchant::CritChance, rng.next_range(weapon_lvl) / 3),
+            2 => (Enchant::Speed, rng.next_range(weapon_lvl)),
+            3 => (Enchant::SlimeSlayer, 0),
+            4 => (Enchant::CritPower, rng.next_range(weapon_lvl) / 3),
+            _ => unreachable!(),
+        };
+        rolls.push(last_one);
+    }
+    let enchants = Enchants {
+        levels: rolls
+            .iter()
+            .map(|&(e, raw)| (e, rules.ui_level(e, raw).unwrap_or(raw)))
+            .collect(),
+        raw: rolls.iter().map(|&(_, raw)| Some(raw)).collect(),
+    };
+    (enchants, gates)
+}
+
+/// Every set of innate enchantments a weapon with these stats can get once it passed the gate
+/// (enchant_item with force), with its chance. The same branches as enchant_item_traced, with each
+/// roll taken as uniform.
+pub fn enchant_outcomes(weapon_lvl: i32, weapon_speed: i32) -> Vec<(Enchants, f64)> {
+    let rules = ClampRules {
+        speed: Some((1, i32::max(1, 4i32.wrapping_sub(weapon_speed)))),
+        ..ClampRules::VANILLA
+    };
+    // the values of next_range(max), each as likely
+    let range = |max: i32| 0..max.max(1);
+    let uniform = |enchant: Enchant, max: i32, level: &dyn Fn(i32) -> i32| {
+        let n = max.max(1) as f64;
+        range(max)
+            .map(|r| ((enchant, level(r)), 1. / n))
+            .collect::<Vec<_>>()
+    };
+
+    let mut extras = vec![];
+    // chance of getting to the next roll of the else-if chain
+    let mut reached = 1.;
+    for enchant in [Enchant::Defense, Enchant::Weight, Enchant::SlimeGatherer] {
+        if enchant == Enchant::Defense && weapon_lvl > 10 {
+            continue;
+        }
+        let levels = match enchant {
+            Enchant::Defense => uniform(enchant, weapon_lvl + 1, &|r| r / 2 + 1),
+            Enchant::Weight => uniform(enchant, 5, &|r| -(1 + r)),
+            _ => vec![((enchant, 0), 1.)],
+        };
+        extras.extend(
+            levels
+                .into_iter()
+                .map(|(e, p)| (Some(e), reached * 0.125 * p)),
+        );
+        reached *= 0.875;
+    }
+    extras.push((None, reached));
+
+    let main: Vec<_> = [
+        uniform(Enchant::Attack, weapon_lvl + 1, &|r| r / 2 + 1),
+        uniform(Enchant::CritChance, weapon_lvl, &|r| r / 3),
+        uniform(Enchant::Speed, weapon_lvl, &|r| r),
+        vec![((Enchant::SlimeSlayer, 0), 1.)],
+        uniform(Enchant::CritPower, weapon_lvl, &|r| r / 3),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|(e, p)| (e, p / 5.))
+    .collect();
+
+    let mut out: Vec<(Enchants, f64)> = vec![];
+    for &(extra, p_extra) in &extras {
+        for &(last_one, p_main) in &main {
+            let rolls: Vec<(Enchant, i32)> = extra.into_iter().chain([last_one]).collect();
+            let enchants = Enchants {
+                levels: rolls
+                    .iter()
+                    .map(|&(e, raw)| (e, rules.ui_level(e, raw).unwrap_or(raw)))
+                    .collect(),
+                raw: rolls.iter().map(|&(_, raw)| Some(raw)).collect(),
+            };
+            match out.iter_mut().find(|(e, _)| *e == enchants) {
+                Some((_, p)) => *p += p_extra * p_main,
+                None => out.push((enchants, p_extra * p_main)),
+            }
+        }
+    }
+    out
+}
+
+/// Which of the two chest tables to roll on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rarity {
+    Common,
+    Rare,
+}
+
+/// Something a chest table gives, see Rarity::outcomes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableOutcome {
+    pub kind: ChestItemKind,
+    /// whether a weapon has innate enchantments, None for the other items
+    pub enchanted: Option<bool>,
+    pub probability: f64,
+}
+
+impl Rarity {
+    /// The slots of this table that can actually be rolled with these settings, with the items
+    /// each of them gives
+    pub fn effective_table(self, settings: &GameSettings) -> Vec<(i32, &'static [ChestItemKind])> {
+        LootTables::vanilla().effective_table(self, settings)
+    }
+
+    /// What a chest of this rarity gives when the seed isn't known, with the chances. Weapons
+    /// are there twice: without innate enchantments (gate_probability of the time) and with.
+    pub fn outcomes(self, settings: &GameSettings) -> Vec<TableOutcome> {
+        let table = self.effective_table(settings);
+        let mut out: Vec<TableOutcome> = vec![];
+        for (_, items) in &table {
+            for &kind in *items {
+                let probability = 1. / table.len() as f64 / items.len() as f64;
+                let split = match WeaponKind::from_item(kind) {
+                    Some(_) => vec![
+                        (Some(false), 1. - gate_probability()),
+                        (Some(true), gate_probability()),
+                    ],
+                    None => vec![(None, 1.)],
+                };
+                for (enchanted, p) in split {
+                    match out
+                        .iter_mut()
+                        .find(|o| o.kind == kind && o.enchanted == enchanted)
+                    {
+                        Some(o) => o.probability += probability * p,
+                        None => out.push(TableOutcome {
+                            kind,
+                            enchanted,
+                            probability: probability * p,
+                        }),
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl ChestItem {
+    /// the item a chest of `rarity` gives with the vanilla tables
+    pub fn generate(rarity: Rarity, seed: i32, settings: &GameSettings) -> Self {
+        Self::generate_traced(rarity, seed, settings).item
+    }
+
+    /// like generate, with the rng calls it took. LootTables::generate for other tables, which
+    /// can fail with EmptyLootTable.
+    pub fn generate_traced(rarity: Rarity, seed: i32, settings: &GameSettings) -> GeneratedItem {
+        LootTables::vanilla()
+            .generate(rarity, seed, settings)
+            .expect("vanilla loot tables are never empty")
+    }
+
+    #[deprecated(
+        note = "use ChestItem::generate(Rarity::Common, ..), it takes the settings by reference"
+    )]
+    pub fn generate_common(seed: i32, settings: GameSettings) -> Self {
+        Self::generate(Rarity::Common, seed, &settings)
+    }
+
+    #[deprecated(note = "use ChestItem::generate_traced(Rarity::Common, ..)")]
+    pub fn generate_common_traced(seed: i32, settings: GameSettings) -> GeneratedItem {
+        Self::generate_traced(Rarity::Common, seed, &settings)
+    }
+
+    #[deprecated(
+        note = "use ChestItem::generate(Rarity::Rare, ..), it takes the settings by reference"
+    )]
+    pub fn generate_rare(seed: i32, settings: GameSettings) -> Self {
+        Self::generate(Rarity::Rare, seed, &settings)
+    }
+
+    #[deprecated(note = "use ChestItem::generate_traced(Rarity::Rare, ..)")]
+    pub fn generate_rare_traced(seed: i32, settings: GameSettings) -> GeneratedItem {
+        Self::generate_traced(Rarity::Rare, seed, &settings)
+    }
+
+    /// The rng calls a chest of `rarity` with `seed` takes to give this item, like
+    /// GeneratedItem::rng_cost. The item doesn't remember its rerolls, so this generates it again,
+    /// None if the chest gives something else.
+    pub fn rng_cost(&self, rarity: Rarity, seed: i32, settings: &GameSettings) -> Option<u32> {
+        let generated = Self::generate_traced(rarity, seed, settings);
+        (generated.item == *self).then_some(generated.rng_cost)
+    }
+}
+
+/// What Goodie::rarity_only found out about a chest
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RarityOutcome {
+    Common,
+    Rare,
+    /// rare above this luckMult, like Goodie::ChanceChest
+    Chance {
+        minluck: f64,
+    },
+}
+
+impl RarityOutcome {
+    /// The chest's contents, the same goodie Goodie::generate_at gives for it
+    pub fn materialize(self, chest_seed: i32, settings: &GameSettings) -> Goodie {
+        match self {
+            RarityOutcome::Common => {
+                Goodie::CommonChest(ChestItem::generate(Rarity::Common, chest_seed, settings))
+            }
+            RarityOutcome::Rare => {
+                Goodie::RareChest(ChestItem::generate(Rarity::Rare, chest_seed, settings))
+            }
+            RarityOutcome::Chance { minluck } => Goodie::ChanceChest {
+                minluck,
+                common: ChestItem::generate(Rarity::Common, chest_seed, settings),
+                rare: ChestItem::generate(Rarity::Rare, chest_seed, settings),
+            },
+        }
+    }
+}
+
+/// The roll that decides whether a chest is rare: the first value of the chest's random. The
+/// contents come from the same random right after (that's the value the tables skip), there's no
+/// second stream and no second rarity roll.
+fn rarity_roll(chest_seed: i32) -> f64 {
+    rng::DotnetRng::first_f64(chest_seed)
+}
+
+/// a chest is rare if its rarity roll is below this plus the luck boost
+fn rare_threshold(level: FloorLevel) -> f64 {
+    let rule = level.rule();
+    if rule.forced_rare {
+        return f64::INFINITY;
+    }
+    rule.rare_chance_base
+}
+
+/// What the chests of a level can be
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LevelRule {
+    pub has_chests: bool,
+    /// chance of a chest being rare without luck, the luck boost is added to it
+    pub rare_chance_base: f64,
+    /// every chest is rare, whatever the roll
+    pub forced_rare: bool,
+}
+
+const fn level_rule(has_chests: bool, rare_chance_base: f64) -> LevelRule {
+    LevelRule {
+        has_chests,
+        rare_chance_base,
+        forced_rare: false,
+    }
+}
+
+/// Indexed by level. As in VolcanoDungeon: the entrance and the shop have fixed maps without
+/// chests, the other floors roll `< 0.1 + luck boost` for a rare chest and the summit `< 0.5 +`.
+/// No level forces rare chests, and with the lowest luck (a boost of -0.05) every chest floor can
+/// still have them.
+pub const LEVEL_CHEST_RULES: [LevelRule; 10] = [
+    level_rule(false, 0.),
+    level_rule(true, 0.1),
+    level_rule(true, 0.1),
+    level_rule(true, 0.1),
+    level_rule(true, 0.1),
+    level_rule(false, 0.),
+    level_rule(true, 0.1),
+    level_rule(true, 0.1),
+    level_rule(true, 0.1),
+    level_rule(true, 0.5),
+];
+
+/// The luck multiplier above which the chest with `chest_seed` on `level` is rare, see
+/// luck::past_threshold. The game checks `roll < (0.1 or 0.5) + (luckMult - 1)`, this is that
+/// solved for luckMult so that one threshold per chest can be shown and split at. The two can
+/// round differently within an ulp of the threshold, the same way on every platform, and the
+/// luck ranges aren't exact to an ulp anyways (see luck_range).
+pub(crate) fn rare_luck(chest_seed: i32, level: FloorLevel) -> f64 {
+    rarity_roll(chest_seed) - rare_threshold(level) + 1.
+}
+
+/// A floor of the volcano, 0 (the entrance) to 9 (the summit)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FloorLevel(u8);
+
+impl FloorLevel {
+    pub const ENTRANCE: Self = FloorLevel(0);
+    pub const SHOP: Self = FloorLevel(5);
+    pub const SUMMIT: Self = FloorLevel(9);
+    pub const ALL: [FloorLevel; 10] = {
+        let mut all = [FloorLevel(0); 10];
+        let mut i = 0;
+        while i < 10 {
+            all[i] = FloorLevel(i as u8);
+            i += 1;
+        }
+        all
+    };
+
+    /// InvalidLevel outside of 0-9
+    pub fn try_new(level: u8) -> Result<Self> {
+        if level > 9 {
+            return Err(Error::InvalidLevel(level as i32));
+        }
+        Ok(FloorLevel(level))
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+
+    pub fn is_entrance(self) -> bool {
+        self == Self::ENTRANCE
+    }
+
+    /// the dwarf's floor
+    pub fn is_shop(self) -> bool {
+        self == Self::SHOP
+    }
+
+    pub fn is_summit(self) -> bool {
+        self == Self::SUMMIT
+    }
+
+    /// its entry in LEVEL_CHEST_RULES
+    pub fn rule(self) -> &'static LevelRule {
+        &LEVEL_CHEST_RULES[self.0 as usize]
+    }
+
+    /// the entrance and the shop have fixed layouts without any chests
+    pub fn has_chests(self) -> bool {
+        self.rule().has_chests
+    }
+
+    /// chance of a chest here being rare without luck, 0 without chests
+    pub fn rare_chance_base(self) -> f64 {
+        self.rule().rare_chance_base
+    }
+
+    /// whether a chest here can be rare for a player with luckMult up to `max_luck`
+    pub fn rare_possible(self, max_luck: f64) -> bool {
+        let rule = self.rule();
+        rule.has_chests && (rule.forced_rare || rule.rare_chance_base + max_luck - 1. > 0.)
+    }
+
+    /// NoChestOnFloor if it doesn't
+    pub fn check_has_chests(self) -> Result<()> {
+        if !self.has_chests() {
+            return Err(Error::NoChestOnFloor(self.into()));
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<i32> for FloorLevel {
+    type Error = Error;
+
+    fn try_from(level: i32) -> Result<Self> {
+        let level = u8::try_from(level).map_err(|_| Error::InvalidLevel(level))?;
+        FloorLevel::try_new(level)
+    }
+}
+
+impl From<FloorLevel> for i32 {
+    fn from(level: FloorLevel) -> Self {
+        level.0 as i32
+    }
+}
+
+impl From<FloorLevel> for u8 {
+    fn from(level: FloorLevel) -> Self {
+        level.0
+    }
+}
+
+impl Display for FloorLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[deprecated(note = "use FloorLevel::has_chests")]
+pub fn level_has_chests(level: i32) -> bool {
+    FloorLevel::try_from(level).is_ok_and(FloorLevel::has_chests)
+}
+
+impl Decisions {
+    pub fn for_chest_at(
+        chest_seed: i32,
+        settings: &GameSettings,
+        level: FloorLevel,
+    ) -> Result<Self> {
+        level.check_has_chests()?;
+        let tables = LootTables::vanilla();
+        Ok(Decisions {
+            rarity_roll: rarity_roll(chest_seed),
+            rare_threshold: rare_threshold(level),
+            common: tables
+                .generate_explained(Rarity::Common, chest_seed, settings)?
+                .1,
+            rare: tables
+                .generate_explained(Rarity::Rare, chest_seed, settings)?
+                .1,
+        })
+    }
+
+    #[deprecated(note = "use Decisions::for_chest_at, it takes a FloorLevel")]
+    pub fn for_chest(chest_seed: i32, settings: &GameSettings, level: i32) -> Result<Self> {
+        Self::for_chest_at(chest_seed, settings, level.try_into()?)
+    }
+
+    /// whether the chest is rare with this luck multiplier, the same comparison as generate
+    pub fn is_rare(&self, luck: f64) -> bool {
+        past_threshold(self.rare_luck(), luck)
+    }
+
+    /// the luck multiplier above which the chest would be rare
+    pub fn rare_luck(&self) -> f64 {
+        self.rarity_roll - self.rare_threshold + 1.
+    }
+}
+
+impl Goodie {
+    /// The chest with this seed on `level`. Components have to point at a chest on `level`, they
+    /// pick the layout with `min_luck`. The luck range is checked like in rarity_only, before
+    /// anything is generated.
+    pub fn generate_at(
+        chest_seed: impl Into<SeedSpec>,
+        settings: &GameSettings,
+        level: FloorLevel,
+        min_luck: f64,
+        max_luck: f64,
+    ) -> Result<Self> {
+        level.check_has_chests()?;
+        check_luck(min_luck, max_luck)?;
+        let chest_seed = match chest_seed.into() {
+            SeedSpec::Components {
+                level: other,
+                chest_index,
+                ..
+            } if other != level.into() => {
+                return Err(Error::NoSuchChest {
+                    level: level.into(),
+                    index: chest_index,
+                });
+            }
+            spec => spec.resolve(settings, min_luck)?,
+        };
+        Ok(Self::rarity_only(chest_seed, level, (min_luck, max_luck))?
+            .materialize(chest_seed, settings))
+    }
+
+    /// Only the rarity of the chest with this seed on `level`, for luckMult in `luck`. That's a
+    /// single roll, the contents can be generated later with RarityOutcome::materialize.
+    ///
+    /// The ends can be infinite for no bound, but InvalidLuck for NaN, min above max and ranges
+    /// that are empty at infinity. With min == max the chest is common or rare, never a
+    /// ChanceChest.
+    pub fn rarity_only(
+        chest_seed: i32,
+        level: FloorLevel,
+        (min_luck, max_luck): (f64, f64),
+    ) -> Result<RarityOutcome> {
+        level.check_has_chests()?;
+        check_luck(min_luck, max_luck)?;
+        Ok(rarity_split(chest_seed, level, (min_luck, max_luck)))
+    }
+
+    /// Like it was before the levels were checked: any level but the summit rolls like the other
+    /// floors, even the ones without chests, and the luck range isn't checked either.
+    #[deprecated(
+        note = "use Goodie::generate_at, it takes a FloorLevel and the settings by reference, and fails for levels without chests"
+    )]
+    pub fn generate(
+        chest_seed: i32,
         settings: GameSettings,
         level: i32,
         min_luck: f64,
         max_luck: f64,
     ) -> Self {
-        let mut chest_rng = rng::DotnetRng::new(chest_seed);
-        // roll < (0.1 or 0.5) + luckboost
-        // roll - (0.1 or 0.5) < luckboost
-        // roll - (0.1 or 0.5) < luckmult-1
-        // roll - (0.1 or 0.5) + 1 < luckmult
-        // (though that technically rounds different..)
-        let chest_roll = chest_rng.next_f64() - if level == 9 { 0.5 } else { 0.1 } + 1.;
-        if chest_roll < min_luck {
-            // only rare
-            Goodie::RareChest(ChestItem::generate_rare(chest_seed, settings))
-        } else if chest_roll >= max_luck {
-            // only common
-            Goodie::CommonChest(ChestItem::generate_common(chest_seed, settings))
-        } else {
-            // both possible
-            Goodie::ChanceChest {
-                minluck: chest_roll,
-                common: ChestItem::generate_common(chest_seed, settings),
-                rare: ChestItem::generate_rare(chest_seed, settings),
+        let level = match level {
+            9 => FloorLevel::SUMMIT,
+            _ => FloorLevel(1),
+        };
+        rarity_split(chest_seed, level, (min_luck, max_luck)).materialize(chest_seed, &settings)
+    }
+}
+
+fn rarity_split(chest_seed: i32, level: FloorLevel, luck: (f64, f64)) -> RarityOutcome {
+    let chest_roll = rare_luck(chest_seed, level);
+    match threshold_split(chest_roll, luck) {
+        ThresholdSplit::Past => RarityOutcome::Rare,
+        ThresholdSplit::NotPast => RarityOutcome::Common,
+        ThresholdSplit::Within => RarityOutcome::Chance {
+            minluck: chest_roll,
+        },
+    }
+}
+
+/// InvalidLuck for NaN, min above max and ranges that are empty at infinity
+fn check_luck(min_luck: f64, max_luck: f64) -> Result<()> {
+    let ordered = min_luck.partial_cmp(&max_luck).is_some_and(|o| o.is_le());
+    if !ordered || min_luck == f64::INFINITY || max_luck == f64::NEG_INFINITY {
+        return Err(Error::InvalidLuck {
+            min: min_luck,
+            max: max_luck,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn available_in_versions() {
+        for kind in ChestItemKind::ALL {
+            assert!(kind.available_in(&GameVersion::V1_6_4), "{:?}", kind);
+            assert_eq!(
+                kind.available_in(&GameVersion::V1_6),
+                kind.introduced_in() == GameVersion::V1_6,
+                "{:?}",
+                kind
+            );
+            assert!(!kind.available_in(&GameVersion::Unknown("1.7".to_string())));
+        }
+        // since 1.5
+        assert_eq!(
+            ChestItemKind::DeluxePirateHat.introduced_in(),
+            GameVersion::V1_6
+        );
+        assert!(ChestItemKind::DeluxePirateHat.available_in(&GameVersion::V1_6));
+    }
+
+    #[test]
+    fn fuzzy_typos() {
+        assert_eq!(
+            ChestItemKind::fuzzy_parse("dragontooth cutlas").unwrap(),
+            ChestItemKind::DragontoothCutlass
+        );
+        assert_eq!(
+            ChestItemKind::fuzzy_parse("pheonix ring").unwrap(),
+            ChestItemKind::PhoenixRing
+        );
+        assert_eq!(
+            "Dragon_Tooth-Shiv".parse::<ChestItemKind>().unwrap(),
+            ChestItemKind::DragontoothShiv
+        );
+    }
+
+    #[test]
+    fn fuzzy_aliases() {
+        assert_eq!(
+            ChestItemKind::fuzzy_parse("dt shiv").unwrap(),
+            ChestItemKind::DragontoothShiv
+        );
+        assert_eq!(
+            ChestItemKind::fuzzy_parse("Pirate Hat").unwrap(),
+            ChestItemKind::DeluxePirateHat
+        );
+        assert_eq!(
+            ChestItemKind::fuzzy_parse("egg").unwrap(),
+            ChestItemKind::OstrichEgg
+        );
+    }
+
+    #[test]
+    fn fuzzy_ambiguous() {
+        let Err(Error::UnknownItemName { name, suggestions }) = ChestItemKind::fuzzy_parse("ring")
+        else {
+            panic!("ring is ambiguous");
+        };
+        assert_eq!(name, "ring");
+        let mut rings: Vec<ChestItemKind> = ChestItemKind::ALL
+            .into_iter()
+            .filter(|k| k.name().ends_with("Ring"))
+            .collect();
+        rings.sort();
+        let mut suggestions = suggestions;
+        suggestions.sort();
+        assert_eq!(suggestions, rings);
+        assert_eq!(rings.len(), 4);
+        assert!(matches!(
+            ChestItemKind::fuzzy_parse("qwertyuiop"),
+            Err(Error::UnknownItemName { suggestions, .. }) if suggestions.is_empty()
+        ));
+    }
+
+    #[test]
+    fn generate_at_checks_luck() {
+        let settings = GameSettings::default();
+        let level = FloorLevel::try_new(1).unwrap();
+        let generate = |min: f64, max: f64| Goodie::generate_at(7, &settings, level, min, max);
+        for (min, max) in [
+            (f64::NAN, 1.),
+            (1., f64::NAN),
+            (1.1, 1.),
+            (f64::INFINITY, f64::INFINITY),
+            (f64::NEG_INFINITY, f64::NEG_INFINITY),
+        ] {
+            assert!(
+                matches!(generate(min, max), Err(Error::InvalidLuck { .. })),
+                "{} to {}",
+                min,
+                max
+            );
+        }
+        // before the seed is resolved, this chest doesn't exist
+        let missing = SeedSpec::Components {
+            game_id: 1,
+            days_played: 1,
+            level: 1,
+            chest_index: 99,
+        };
+        assert!(matches!(
+            Goodie::generate_at(missing, &settings, level, 1., f64::NAN),
+            Err(Error::InvalidLuck { .. })
+        ));
+
+        // no bound: the chest can be either
+        assert!(matches!(
+            generate(f64::NEG_INFINITY, f64::INFINITY),
+            Ok(Goodie::ChanceChest { .. })
+        ));
+        let rare_luck = Decisions::for_chest_at(7, &settings, level)
+            .unwrap()
+            .rare_luck();
+        assert!(matches!(
+            generate(rare_luck - 1., f64::INFINITY),
+            Ok(Goodie::ChanceChest { .. })
+        ));
+        for luck in [rare_luck - 0.5, rare_luck, rare_luck + 0.5] {
+            let goodie = generate(luck, luck).unwrap();
+            assert!(
+                matches!(goodie, Goodie::CommonChest(_) | Goodie::RareChest(_)),
+                "{:?}",
+                goodie
+            );
+        }
+        assert!(matches!(
+            generate(rare_luck + 0.5, rare_luck + 0.5),
+            Ok(Goodie::RareChest(_))
+        ));
+        assert!(matches!(
+            generate(rare_luck - 0.5, rare_luck - 0.5),
+            Ok(Goodie::CommonChest(_))
+        ));
+    }
+
+    #[test]
+    fn rng_costs() {
+        let settings = GameSettings {
+            golden_coconut_in_common: false,
+            ..Default::default()
+        };
+        // (seed, cost): a plain item, one after a coconut reroll, a weapon without and one with
+        // two enchantments
+        for (seed, cost) in [(0, 2), (5, 4), (14, 4), (4, 9)] {
+            let generated = ChestItem::generate_traced(Rarity::Common, seed, &settings);
+            assert_eq!(generated.rng_cost, cost, "seed {}", seed);
+            let item = generated.item;
+            assert_eq!(item.rng_cost(Rarity::Common, seed, &settings), Some(cost));
+            #[allow(deprecated)]
+            let old = ChestItem::generate_common_traced(seed, settings.clone());
+            assert_eq!(old.item, item);
+        }
+        assert_eq!(
+            ChestItem::generate(Rarity::Common, 5, &settings),
+            ChestItem::ProtectionRing
+        );
+        let two = ChestItem::generate(Rarity::Common, 4, &settings);
+        assert_eq!(two.enchants().unwrap().as_slice().len(), 2);
+        assert_eq!(
+            ChestItem::SoulSapperRing.rng_cost(Rarity::Common, 5, &settings),
+            None
+        );
+    }
+
+    #[test]
+    fn ostrich_egg_takes_no_quality_roll() {
+        let settings = GameSettings::default();
+        // the egg takes the same 2 rolls as a ring: the table slot and the item in it
+        for seed in [4, 14] {
+            assert_eq!(
+                ChestItem::OstrichEgg.rng_cost(Rarity::Rare, seed, &settings),
+                Some(2)
+            );
+        }
+        assert_eq!(
+            ChestItem::HotJavaRing.rng_cost(Rarity::Rare, 6, &settings),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn enchant_outcomes_match_the_rolls() {
+        for (level, speed) in [(13, 4), (13, -8), (11, 3), (10, 0), (14, -8)] {
+            let outcomes = enchant_outcomes(level, speed);
+            let total: f64 = outcomes.iter().map(|(_, p)| p).sum();
+            assert!((total - 1.).abs() < 1e-9);
+            let samples = 40000;
+            let mut counts = vec![0; outcomes.len()];
+            for i in 0..samples {
+                let mut rng = rng::DotnetRng::new(i * 7919 + 13);
+                let enchants = enchant_item(&mut rng, level, speed, true);
+                let i = outcomes.iter().position(|(e, _)| *e == enchants);
+                counts[i.expect("rolled enchants are in the outcomes")] += 1;
             }
+            let worst = outcomes
+                .iter()
+                .zip(&counts)
+                .map(|((_, p), &n)| (n as f64 / samples as f64 - p).abs())
+                .fold(0., f64::max);
+            assert!(
+                worst < 0.005,
+                "level {} speed {}: off by {}",
+                level,
+                speed,
+                worst
+            );
+        }
+    }
+
+    #[test]
+    fn crit_chance_levels() {
+        let crit_level = |enchants: &Enchants| {
+            enchants
+                .as_slice()
+                .iter()
+                .find(|(e, _)| *e == Enchant::CritChance)
+                .map(|&(_, lvl)| lvl)
+        };
+        let expected = [6. / 13., 3. / 13., 4. / 13.];
+        // of the weapons that get crit chance, `Next(13) / 3` clamped to 1-3
+        let mut exact = [0.; 3];
+        for (enchants, p) in enchant_outcomes(13, 0) {
+            if let Some(lvl) = crit_level(&enchants) {
+                exact[lvl as usize - 1] += p;
+            }
+        }
+        let total: f64 = exact.iter().sum();
+        for (p, want) in exact.iter().zip(expected) {
+            assert!((p / total - want).abs() < 1e-9);
+        }
+        let mut counts = [0; 3];
+        for seed in 0..60000 {
+            let mut rng = rng::DotnetRng::new(seed);
+            if let Some(lvl) = crit_level(&enchant_item(&mut rng, 13, 0, true)) {
+                counts[lvl as usize - 1] += 1;
+            }
+        }
+        let n: i32 = counts.iter().sum();
+        for (count, want) in counts.iter().zip(expected) {
+            assert!(
+                (*count as f64 / n as f64 - want).abs() < 0.01,
+                "{:?}",
+                counts
+            );
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_generate_takes_any_level() {
+        let settings = GameSettings::default();
+        for seed in 0..50 {
+            for floor in FloorLevel::ALL.into_iter().filter(|f| f.has_chests()) {
+                assert_eq!(
+                    Goodie::generate(seed, settings.clone(), floor.into(), 0.95, 1.1),
+                    Goodie::generate_at(seed, &settings, floor, 0.95, 1.1).unwrap()
+                );
+            }
+            // the entrance, the shop and levels that don't exist roll like level 1
+            let level_1 = Goodie::generate(seed, settings.clone(), 1, 0.95, 1.1);
+            for level in [0, 5, -3, 12] {
+                assert_eq!(
+                    Goodie::generate(seed, settings.clone(), level, 0.95, 1.1),
+                    level_1
+                );
+            }
+        }
+        assert!(matches!(
+            Goodie::generate_at(3, &settings, FloorLevel::SHOP, 0.95, 1.1),
+            Err(Error::NoChestOnFloor(5))
+        ));
+    }
+
+    #[test]
+    fn coconut_slots_are_gated_separately() {
+        let tables = LootTables::vanilla();
+        for common in [false, true] {
+            for rare in [false, true] {
+                let settings = GameSettings {
+                    golden_coconut_in_common: common,
+                    golden_coconuts_in_rare: rare,
+                    ..Default::default()
+                };
+                let obtainable = |rarity| tables.obtainable(rarity, &settings);
+                assert_eq!(
+                    obtainable(Rarity::Common).contains(&ChestItemKind::GoldenCoconut),
+                    common
+                );
+                assert_eq!(
+                    obtainable(Rarity::Rare).contains(&ChestItemKind::GoldenCoconuts),
+                    rare
+                );
+                // neither table has the other's coconut
+                assert!(!obtainable(Rarity::Common).contains(&ChestItemKind::GoldenCoconuts));
+                assert!(!obtainable(Rarity::Rare).contains(&ChestItemKind::GoldenCoconut));
+
+                // the rolls: a gated slot is rerolled, an open one comes up for some seed
+                let rolled = |rarity| -> Vec<ChestItemKind> {
+                    (0..500)
+                        .map(|seed| ChestItem::generate(rarity, seed, &settings).kind())
+                        .collect()
+                };
+                assert_eq!(
+                    rolled(Rarity::Common).contains(&ChestItemKind::GoldenCoconut),
+                    common
+                );
+                assert_eq!(
+                    rolled(Rarity::Rare).contains(&ChestItemKind::GoldenCoconuts),
+                    rare
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn cracked_golden_coconut_opens_both_slots() {
+        let cracked = GameSettings {
+            cracked_golden_coconut: true,
+            ..Default::default()
+        };
+        let both = GameSettings::default().with_cracked_golden_coconut(true);
+        assert!(cracked.golden_coconut_in_common() && cracked.golden_coconuts_in_rare());
+        assert_eq!(cracked.behavior_hash(), both.behavior_hash());
+        for rarity in [Rarity::Common, Rarity::Rare] {
+            for seed in 0..200 {
+                assert_eq!(
+                    ChestItem::generate(rarity, seed, &cracked),
+                    ChestItem::generate(rarity, seed, &both)
+                );
+            }
+        }
+        // and the builder clears it again
+        let closed = cracked.with_cracked_golden_coconut(false);
+        assert!(!closed.golden_coconut_in_common() && !closed.golden_coconuts_in_rare());
+    }
+
+    /// the stats of the volcano weapons, (level, speed)
+    const WEAPON_STATS: [(i32, i32); 6] = [(13, 4), (13, -8), (11, 3), (13, 0), (14, -8), (12, 0)];
+
+    #[test]
+    fn compact_strings_round_trip() {
+        let mut seeds = rng::DotnetRng::new(104);
+        for _ in 0..10_000 {
+            let seed = seeds.next();
+            for (level, speed) in WEAPON_STATS {
+                let enchants = enchant_item(&mut rng::DotnetRng::new(seed), level, speed, false);
+                let s = enchants.to_compact_string();
+                assert_eq!(
+                    Enchants::from_compact_string(&s).unwrap(),
+                    enchants,
+                    "{}",
+                    s
+                );
+            }
+        }
+        for kind in ChestItemKind::ALL {
+            let s = kind.to_compact_string();
+            assert_eq!(ChestItemKind::from_compact_string(&s).unwrap(), kind);
+        }
+        assert_eq!(
+            Enchants::from_compact_string("a4.w-3").unwrap().as_slice(),
+            [(Enchant::Attack, 4), (Enchant::Weight, -3)]
+        );
+        assert!(
+            Enchants::from_compact_string("")
+                .unwrap()
+                .as_slice()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn compact_strings_rejected() {
+        for s in ["x1", "a4.q", "ss.", "a4w-3", "A4"] {
+            assert!(
+                matches!(
+                    Enchants::from_compact_string(s),
+                    Err(Error::UnknownEnchantCode(_))
+                ),
+                "{}",
+                s
+            );
+        }
+        for (s, enchant, lvl) in [
+            ("a6", Enchant::Attack, 6),
+            // a missing level is 0
+            ("a", Enchant::Attack, 0),
+            ("w-6", Enchant::Weight, -6),
+            ("w3", Enchant::Weight, 3),
+            ("ss1", Enchant::SlimeSlayer, 1),
+            ("cp30", Enchant::CritPower, 30),
+            ("s0", Enchant::Speed, 0),
+        ] {
+            assert!(
+                matches!(
+                    Enchants::from_compact_string(s),
+                    Err(Error::EnchantLevelOutOfRange(e, l)) if e == enchant && l == lvl
+                ),
+                "{}",
+                s
+            );
+        }
+        assert!(matches!(
+            ChestItemKind::from_compact_string("dwarf_spoon"),
+            Err(Error::UnknownItemCode(_))
+        ));
+    }
+
+    #[test]
+    fn enchant_outcomes_agree_with_rolls() {
+        // 10k random seeds per weapon, against the branches enchant_outcomes enumerates
+        let samples = 10_000;
+        let mut seeds = rng::DotnetRng::new(172);
+        for (level, speed) in WEAPON_STATS {
+            let outcomes = enchant_outcomes(level, speed);
+            let mut counts = vec![0; outcomes.len()];
+            for _ in 0..samples {
+                let enchants =
+                    enchant_item(&mut rng::DotnetRng::new(seeds.next()), level, speed, true);
+                let i = outcomes.iter().position(|(e, _)| *e == enchants);
+                counts[i.unwrap_or_else(|| panic!("{} isn't an outcome", enchants))] += 1;
+            }
+            for ((enchants, p), n) in outcomes.iter().zip(counts) {
+                // 4 standard deviations
+                let margin = 4. * (p * (1. - p) / samples as f64).sqrt() + 1e-9;
+                let freq = n as f64 / samples as f64;
+                assert!(
+                    (freq - p).abs() <= margin,
+                    "level {} speed {}: {} rolled {} times, expected {}",
+                    level,
+                    speed,
+                    enchants,
+                    freq,
+                    p
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn wiki_urls() {
+        let url = |kind| ChestItem::with_enchants(kind, Enchants::default()).wiki_url();
+        assert_eq!(
+            url(ChestItemKind::DeluxePirateHat),
+            "https://stardewvalleywiki.com/Deluxe_Pirate_Hat"
+        );
+        assert_eq!(
+            url(ChestItemKind::DragontoothCutlass),
+            "https://stardewvalleywiki.com/Dragontooth_Cutlass"
+        );
+        assert_eq!(
+            url(ChestItemKind::CinderShards3),
+            "https://stardewvalleywiki.com/Cinder_Shard"
+        );
+        assert_eq!(
+            url(ChestItemKind::CinderShards10),
+            url(ChestItemKind::CinderShards3)
+        );
+        assert_eq!(
+            url(ChestItemKind::GoldenCoconuts),
+            url(ChestItemKind::GoldenCoconut)
+        );
+        for kind in ChestItemKind::ALL {
+            let url = url(kind);
+            let page = url.strip_prefix("https://stardewvalleywiki.com/").unwrap();
+            assert!(
+                page.chars().all(|c| c.is_ascii_alphabetic() || c == '_'),
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn at_least_is_enchant_aware() {
+        let e = |levels: &[(Enchant, i32)]| Enchants::from_levels(levels.to_vec());
+        let heavy = e(&[(Enchant::Weight, -2)]);
+        let light = e(&[(Enchant::Weight, -4), (Enchant::Attack, 3)]);
+        assert!(light.at_least(&heavy));
+        assert!(!heavy.at_least(&light));
+        assert!(light.dominates(&heavy));
+        assert!(e(&[(Enchant::Attack, 5)]).at_least(&e(&[(Enchant::Attack, 3)])));
+        assert!(!e(&[(Enchant::Attack, 2)]).at_least(&e(&[(Enchant::Attack, 3)])));
+        // slime enchants are there or not
+        let slime = e(&[(Enchant::SlimeGatherer, 0)]);
+        assert!(e(&[(Enchant::SlimeGatherer, 0), (Enchant::Speed, 1)]).at_least(&slime));
+        assert!(!e(&[(Enchant::SlimeSlayer, 0)]).at_least(&slime));
+        // everything is at least nothing, and nothing dominates itself
+        assert!(heavy.at_least(&Enchants::default()));
+        assert!(!light.dominates(&light));
+    }
+
+    #[test]
+    fn lifted_caps_keep_the_raw_rolls() {
+        let lifted = ClampRules {
+            defense: (1, 99),
+            attack: (1, 99),
+            crit_chance: (1, 99),
+            crit_power: (1, 99),
+            speed: None,
+        };
+        let (level, speed) = WEAPON_STATS[3];
+        // a roll above the cap, the dragontooth weapons are high level enough to get them
+        let enchants = (0..10_000)
+            .map(|seed| enchant_item(&mut rng::DotnetRng::new(seed), level, speed, true))
+            .find(|e| e.reclamp(&lifted) != *e)
+            .expect("no roll above the cap");
+        assert_eq!(enchants.reclamp(&ClampRules::VANILLA), enchants);
+        let over: Vec<EnchantRoll> = enchants
+            .rolls()
+            .into_iter()
+            .filter(|r| r.raw.and_then(|raw| lifted.ui_level(r.enchant, raw)) != Some(r.ui_level))
+            .collect();
+        assert!(!over.is_empty());
+        for roll in over {
+            let raw = roll.raw.unwrap();
+            let capped = ClampRules::VANILLA.ui_level(roll.enchant, raw).unwrap();
+            assert_eq!(roll.ui_level, capped);
+            assert!(lifted.ui_level(roll.enchant, raw).unwrap() > capped);
+        }
+        // the raw rolls are only extra, the text and equality use the levels
+        let levels = Enchants::from_levels(enchants.as_slice().to_vec());
+        assert_eq!(levels, enchants);
+        assert_eq!(levels.to_string(), enchants.to_string());
+        assert!(levels.rolls().iter().all(|r| r.raw.is_none()));
+    }
+
+    #[test]
+    fn at_most_one_extra_enchant() {
+        let (level, speed) = WEAPON_STATS[1];
+        let hammer =
+            |seed| enchant_item_traced(&mut rng::DotnetRng::new(seed), level, speed, false);
+        // weight from the else-if chain, then the main enchant
+        let (enchants, gates) = hammer(9);
+        assert_eq!(enchants.to_compact_string(), "w-3.cc1");
+        assert_eq!(gates.extra, Some(Enchant::Weight));
+        assert!(gates.extra_rolls[1].unwrap() < 0.125 && gates.extra_rolls[2].is_none());
+        // the defense roll passes but the hammer is above level 10, so it falls through to the
+        // others, which fail, and only the main enchant is left
+        let (enchants, gates) = hammer(11);
+        assert_eq!(enchants.to_compact_string(), "a2");
+        assert!(gates.extra_rolls[0].unwrap() < 0.125);
+        assert_eq!(gates.extra, None);
+        assert!(gates.extra_rolls.iter().all(|r| r.is_some()));
+        // no enchantments at all
+        let (enchants, gates) = hammer(0);
+        assert!(!gates.passed() && enchants.as_slice().is_empty());
+
+        for seed in 0..10_000 {
+            let (enchants, gates) = hammer(seed);
+            let n = enchants.as_slice().len();
+            if !gates.passed() {
+                assert_eq!(n, 0);
+                continue;
+            }
+            // the main enchant is always there, so never weight alone
+            assert_eq!(n, 1 + gates.extra.is_some() as usize, "{}", seed);
+            assert_ne!(enchants.as_slice()[n - 1].0, Enchant::Weight);
+        }
+    }
+
+    #[test]
+    fn one_rarity_roll() {
+        // seeds where the first value of the chest's random and the second one are on different
+        // sides of the 0.1 a floor chest needs at luck 1: reading the rarity from anything but
+        // the first value would flip them
+        let at_luck_1 = |seed| {
+            Goodie::generate_at(seed, &GameSettings::default(), FloorLevel(1), 1., 1.).unwrap()
+        };
+        for (seed, expected) in [
+            (14, Goodie::RareChest(ChestItem::OstrichEgg)),
+            (16, Goodie::RareChest(ChestItem::PhoenixRing)),
+            (11, Goodie::CommonChest(ChestItem::CinderShards3)),
+            (18, Goodie::CommonChest(ChestItem::CinderShards3)),
+        ] {
+            let mut chest_rng = rng::DotnetRng::new(seed);
+            let (first, second) = (chest_rng.next_f64(), chest_rng.next_f64());
+            assert_ne!(first < 0.1, second < 0.1);
+            assert_eq!(rarity_roll(seed), first);
+            assert_eq!(at_luck_1(seed), expected, "{}", seed);
+            let decisions = Decisions::for_chest_at(seed, &GameSettings::default(), FloorLevel(1));
+            assert_eq!(decisions.unwrap().rarity_roll, first);
+        }
+    }
+
+    #[test]
+    fn effect_text_of_the_equipment() {
+        for kind in ChestItemKind::ALL {
+            let item = ChestItem::with_enchants(kind, Enchants::default());
+            let text = item.effect_text(Locale::En);
+            if item.is_weapon() || !kind.is_unique() {
+                assert_eq!(text, None, "{:?}", kind);
+            } else {
+                assert!(text.is_some_and(|t| !t.is_empty()), "{:?}", kind);
+            }
+        }
+        let boots = ChestItem::DragonscaleBoots.effect_text(Locale::En).unwrap();
+        assert_eq!(boots, "+7 Defense");
+    }
+
+    #[test]
+    fn enchant_diffs() {
+        let e = |levels: &[(Enchant, i32)]| Enchants::from_levels(levels.to_vec());
+        let attack = e(&[(Enchant::Attack, 3)]);
+        let better = e(&[(Enchant::Attack, 5), (Enchant::Weight, -4)]);
+        let diff = attack.diff(&better);
+        assert_eq!(diff.to_string(), "+3 Attack → +5 Attack, gains -4 Weight");
+        assert!(diff.is_strict_upgrade() && better.dominates(&attack));
+        // a gain and a loss
+        let slime = e(&[(Enchant::SlimeSlayer, 0), (Enchant::Attack, 3)]);
+        let diff = slime.diff(&better);
+        assert_eq!(diff.gained, [(Enchant::Weight, -4)]);
+        assert_eq!(diff.lost, [(Enchant::SlimeSlayer, 0)]);
+        assert_eq!(
+            diff.to_string(),
+            "+3 Attack → +5 Attack, gains -4 Weight, loses Slime Slayer"
+        );
+        assert!(!diff.is_strict_upgrade() && !better.dominates(&slime));
+        // only the level, and a worse one
+        let diff = better.diff(&e(&[(Enchant::Attack, 5), (Enchant::Weight, -2)]));
+        assert!(diff.gained.is_empty() && diff.lost.is_empty());
+        assert_eq!(diff.to_string(), "-4 Weight → -2 Weight");
+        assert!(!diff.is_strict_upgrade());
+        // the same, in another order
+        let same = attack.diff(&e(&[(Enchant::Attack, 3)]));
+        assert!(same.is_empty() && same.to_string().is_empty());
+        assert!(!same.is_strict_upgrade());
+        let swapped = better.diff(&e(&[(Enchant::Weight, -4), (Enchant::Attack, 5)]));
+        assert!(swapped.is_empty());
+    }
+
+    #[test]
+    fn unenchanted_weapons_are_their_own_outcome() {
+        let settings = GameSettings::default();
+        let outcomes = Rarity::Rare.outcomes(&settings);
+        let total: f64 = outcomes.iter().map(|o| o.probability).sum();
+        assert!((total - 1.).abs() < 1e-9);
+        for kind in ChestItemKind::ALL {
+            let of_kind: Vec<_> = outcomes.iter().filter(|o| o.kind == kind).collect();
+            if WeaponKind::from_item(kind).is_none() {
+                assert!(of_kind.len() <= 1 && of_kind.iter().all(|o| o.enchanted.is_none()));
+                continue;
+            }
+            let p = |enchanted| {
+                of_kind
+                    .iter()
+                    .find(|o| o.enchanted == Some(enchanted))
+                    .map_or(0., |o| o.probability)
+            };
+            if p(false) + p(true) == 0. {
+                continue;
+            }
+            let empty = p(false) / (p(false) + p(true));
+            assert!(
+                (empty - (1. - gate_probability())).abs() < 1e-12,
+                "{:?}",
+                kind
+            );
+        }
+        // and the real rolls agree
+        let weapons: Vec<_> = (0..10_000)
+            .map(|seed| ChestItem::generate(Rarity::Rare, seed, &settings))
+            .filter(ChestItem::is_weapon)
+            .collect();
+        let empty = weapons.iter().filter(|c| c.is_unenchanted_weapon()).count();
+        let share = empty as f64 / weapons.len() as f64;
+        assert!(
+            (share - (1. - gate_probability())).abs() < 0.03,
+            "{}",
+            share
+        );
+    }
+
+    #[test]
+    fn rarity_and_contents_in_two_steps() {
+        let settings = GameSettings::default();
+        let (lo, hi) = crate::luck::luck_range(&settings);
+        let levels = FloorLevel::ALL
+            .into_iter()
+            .filter(|l| l.check_has_chests().is_ok());
+        let mut chances = 0;
+        for level in levels {
+            for seed in (0..3_000).map(|i| i * 7919 - 50_000) {
+                let decisions = Decisions::for_chest_at(seed, &settings, level).unwrap();
+                for luck in [(lo, hi), (lo, lo), (hi, hi), (1., 1.)] {
+                    let outcome = Goodie::rarity_only(seed, level, luck).unwrap();
+                    let expected = match (decisions.is_rare(luck.0), decisions.is_rare(luck.1)) {
+                        (true, _) => RarityOutcome::Rare,
+                        (false, false) => RarityOutcome::Common,
+                        (false, true) => RarityOutcome::Chance {
+                            minluck: decisions.rare_luck(),
+                        },
+                    };
+                    assert_eq!(outcome, expected, "{} {:?} {:?}", seed, level, luck);
+                    chances += matches!(outcome, RarityOutcome::Chance { .. }) as u32;
+                    let goodie = Goodie::generate_at(seed, &settings, level, luck.0, luck.1);
+                    assert_eq!(outcome.materialize(seed, &settings), goodie.unwrap());
+                }
+                let rare = ChestItem::generate(Rarity::Rare, seed, &settings);
+                assert_eq!(
+                    RarityOutcome::Rare.materialize(seed, &settings),
+                    Goodie::RareChest(rare)
+                );
+            }
+        }
+        assert!(chances > 0);
+    }
+
+    #[test]
+    fn pinned_stable_ids() {
+        use ChestItemKind::*;
+        let kinds = [
+            (CinderShards3, 0),
+            (GoldenCoconut, 1),
+            (TaroTuber, 2),
+            (PineappleSeeds, 3),
+            (ProtectionRing, 4),
+            (SoulSapperRing, 5),
+            (DwarfSword, 6),
+            (DwarfHammer, 7),
+            (DwarfDagger, 8),
+            (CinderShards10, 9),
+            (MermaidBoots, 10),
+            (DragonscaleBoots, 11),
+            (GoldenCoconuts, 12),
+            (PhoenixRing, 13),
+            (HotJavaRing, 14),
+            (DragontoothCutlass, 15),
+            (DragontoothClub, 16),
+            (DragontoothShiv, 17),
+            (DeluxePirateHat, 18),
+            (OstrichEgg, 19),
+        ];
+        assert_eq!(kinds.len(), ChestItemKind::ALL.len());
+        for (kind, id) in kinds {
+            assert_eq!(kind.stable_id(), id, "{:?}", kind);
+            assert_eq!(ChestItemKind::from_stable_id(id), Some(kind));
+        }
+        let enchants = [
+            (Enchant::Defense, 0),
+            (Enchant::Weight, 1),
+            (Enchant::SlimeGatherer, 2),
+            (Enchant::SlimeSlayer, 3),
+            (Enchant::CritPower, 4),
+            (Enchant::CritChance, 5),
+            (Enchant::Attack, 6),
+            (Enchant::Speed, 7),
+        ];
+        assert_eq!(enchants.len(), Enchant::ALL.len());
+        for (enchant, id) in enchants {
+            assert_eq!(enchant.stable_id(), id, "{:?}", enchant);
+            assert_eq!(Enchant::from_stable_id(id), Some(enchant));
+        }
+        for id in [20, 100, u16::MAX] {
+            assert_eq!(ChestItemKind::from_stable_id(id), None);
+        }
+        for id in [8, 100, u16::MAX] {
+            assert_eq!(Enchant::from_stable_id(id), None);
+        }
+    }
+
+    #[test]
+    fn level_rules_match_the_rolls() {
+        let settings = GameSettings::default();
+        for level in FloorLevel::ALL {
+            let rule = LEVEL_CHEST_RULES[level.get() as usize];
+            assert_eq!(level.rule(), &rule);
+            assert_eq!(level.has_chests(), !level.is_entrance() && !level.is_shop());
+            if !rule.has_chests {
+                assert!(matches!(
+                    Goodie::rarity_only(0, level, (1., 1.)),
+                    Err(Error::NoChestOnFloor(_))
+                ));
+                continue;
+            }
+            let base = if level.is_summit() { 0.5 } else { 0.1 };
+            assert_eq!(level.rare_chance_base(), base);
+            assert!(!rule.forced_rare);
+            let mut rare = 0;
+            for seed in 0..2_000 {
+                let roll = Decisions::for_chest_at(seed, &settings, level)
+                    .unwrap()
+                    .rarity_roll;
+                // without luck the roll against the base alone, with some the boost is added
+                for (luck, threshold) in [(1., base), (1.05, base + 0.05), (0.95, base - 0.05)] {
+                    let outcome = Goodie::rarity_only(seed, level, (luck, luck)).unwrap();
+                    let expected = match roll < threshold {
+                        true => RarityOutcome::Rare,
+                        false => RarityOutcome::Common,
+                    };
+                    // up to rounding right at the threshold, see rare_luck
+                    if (roll - threshold).abs() > 1e-12 {
+                        assert_eq!(outcome, expected, "{} {:?} {}", seed, level, luck);
+                    }
+                }
+                rare += (roll < base) as u32;
+            }
+            let share = rare as f64 / 2_000.;
+            assert!((share - base).abs() < 0.03, "{:?} {}", level, share);
+        }
+    }
+
+    #[test]
+    fn where_rare_chests_are_impossible() {
+        // even the lowest luck leaves every chest floor a chance
+        let lowest = crate::luck::luck_range(&GameSettings::default()).0;
+        for level in FloorLevel::ALL {
+            assert_eq!(
+                level.rare_possible(lowest),
+                level.has_chests(),
+                "{:?}",
+                level
+            );
+        }
+        // with 10% less luck than that only the summit's chests still can be
+        let possible: Vec<u8> = FloorLevel::ALL
+            .into_iter()
+            .filter(|l| l.rare_possible(0.9))
+            .map(FloorLevel::get)
+            .collect();
+        assert_eq!(possible, [9]);
+    }
+
+    #[test]
+    fn goodie_accessors() {
+        let club = ChestItem::DragontoothClub(Enchants::from_levels(vec![(Enchant::Attack, 2)]));
+        let ring = ChestItem::PhoenixRing;
+        let chance = Goodie::ChanceChest {
+            minluck: 1.02,
+            common: ChestItem::TaroTuber,
+            rare: club.clone(),
+        };
+        assert_eq!(Goodie::DragonTooth.as_chest(), None);
+        assert_eq!(
+            Goodie::CommonChest(ring.clone()).as_chest(),
+            Some((Rarity::Common, &ring))
+        );
+        assert_eq!(
+            Goodie::RareChest(club.clone()).as_chest(),
+            Some((Rarity::Rare, &club))
+        );
+        assert_eq!(chance.as_chest(), None);
+        let info = chance.chance().unwrap();
+        assert_eq!(info.minluck, 1.02);
+        assert_eq!((info.common, info.rare), (&ChestItem::TaroTuber, &club));
+        let ambiguous = Goodie::Ambiguous(vec![(
+            SettingsAssumption::default(),
+            Goodie::RareChest(ring.clone()),
+        )]);
+        for goodie in [
+            Goodie::DragonTooth,
+            Goodie::CommonChest(ring.clone()),
+            Goodie::RareChest(club.clone()),
+            ambiguous.clone(),
+        ] {
+            assert!(goodie.chance().is_none(), "{:?}", goodie);
+        }
+        assert_eq!(ambiguous.as_chest(), None);
+    }
+
+    #[test]
+    fn chest_item_accessors() {
+        let enchants = Enchants::from_levels(vec![(Enchant::Speed, 1)]);
+        for kind in ChestItemKind::ALL {
+            let item = ChestItem::with_enchants(kind, enchants.clone());
+            assert_eq!(item.kind(), kind);
+            let weapon = WeaponKind::from_item(kind).is_some();
+            assert_eq!(item.is_weapon(), weapon, "{:?}", kind);
+            assert_eq!(item.enchants(), weapon.then_some(&enchants), "{:?}", kind);
+            assert_eq!(item.is_unique(), kind.is_unique(), "{:?}", kind);
+            assert!(!item.is_unenchanted_weapon());
+            let plain = ChestItem::with_enchants(kind, Enchants::default());
+            assert_eq!(plain.is_unenchanted_weapon(), weapon);
+        }
+        let unique: Vec<ChestItemKind> = ChestItemKind::ALL
+            .into_iter()
+            .filter(|k| ChestItem::with_enchants(*k, Enchants::default()).is_unique())
+            .collect();
+        use ChestItemKind::*;
+        assert_eq!(
+            unique,
+            [
+                ProtectionRing,
+                SoulSapperRing,
+                DwarfSword,
+                DwarfHammer,
+                DwarfDagger,
+                MermaidBoots,
+                DragonscaleBoots,
+                PhoenixRing,
+                HotJavaRing,
+                DragontoothCutlass,
+                DragontoothClub,
+                DragontoothShiv,
+                DeluxePirateHat,
+            ]
+        );
+    }
+
+    #[test]
+    fn item_meta_is_complete() {
+        for kind in ChestItemKind::ALL {
+            let meta = kind.meta();
+            assert_eq!(meta.kind, kind);
+            let item = ChestItem::with_enchants(kind, Enchants::default());
+            assert_eq!(item.kind(), kind);
+            let display = match meta.quantity {
+                1 => meta.display_en.to_string(),
+                n => format!("{} ({})", meta.display_en, n),
+            };
+            assert_eq!(item.to_string(), display);
+            assert_eq!(kind.name(), meta.display_en);
+            assert_eq!(item.get_icon(), meta.icon_key);
+            assert_eq!(kind.game_id(), meta.game_id);
+            assert_eq!(kind.quantity(), meta.quantity);
+            assert_eq!(item.quantity(&QuantityModifiers::default()), meta.quantity);
+            let code = kind.to_compact_string();
+            assert_eq!(ChestItemKind::from_compact_string(&code).unwrap(), kind);
+            assert!(meta.game_id.starts_with('('), "{}", meta.game_id);
+        }
+        // only the quantities tell these apart
+        let game_ids: Vec<&str> = ChestItemKind::ALL.map(ChestItemKind::game_id).to_vec();
+        let shared = |id| game_ids.iter().filter(|&&g| g == id).count();
+        assert_eq!(shared("(O)848"), 2);
+        assert_eq!(shared("(O)791"), 2);
+        assert!(game_ids.iter().all(|&g| shared(g) <= 2));
+    }
+
+    #[test]
+    fn floor_levels_are_checked() {
+        for level in 0..=9u8 {
+            let floor = FloorLevel::try_new(level).unwrap();
+            assert_eq!(floor.get(), level);
+            assert_eq!(FloorLevel::try_from(level as i32).unwrap(), floor);
+            assert_eq!(i32::from(floor), level as i32);
+            assert_eq!(u8::from(floor), level);
+            assert_eq!(floor.is_entrance(), level == 0);
+            assert_eq!(floor.is_shop(), level == 5);
+            assert_eq!(floor.is_summit(), level == 9);
+        }
+        assert!(matches!(
+            FloorLevel::try_new(10),
+            Err(Error::InvalidLevel(10))
+        ));
+        for level in [-3, 10, 256, i32::MIN] {
+            assert!(matches!(
+                FloorLevel::try_from(level),
+                Err(Error::InvalidLevel(l)) if l == level
+            ));
+        }
+        assert_eq!(
+            FloorLevel::ALL.map(FloorLevel::get),
+            [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]
+        );
+    }
+
+    #[test]
+    fn no_chests_on_the_entrance_and_shop() {
+        let settings = GameSettings::default();
+        for seed in 0..50 {
+            for level in FloorLevel::ALL {
+                let goodie = Goodie::generate_at(seed, &settings, level, 0.95, 1.1);
+                if level.get() == 0 || level.get() == 5 {
+                    assert!(matches!(goodie, Err(Error::NoChestOnFloor(l)) if l == level.into()));
+                } else {
+                    assert!(goodie.is_ok(), "level {}", level.get());
+                }
+            }
+        }
+        // the forecast leaves them out
+        let day = crate::forecast::DayForecast::generate(&GameSettings {
+            days_played: 5,
+            ..settings
+        });
+        for level in [0, 5] {
+            assert!(day.loot[level].iter().all(|(_, _, loot)| loot.is_empty()));
         }
     }
 }
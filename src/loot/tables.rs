@@ -0,0 +1,574 @@
+//! The two chest tables as data, see tables.toml. Only the bit of toml that file needs is
+//! supported: `[[common]]` / `[[rare]]` headers, and one-line strings, integers and arrays.
+
+use super::{ChestItem, ChestItemKind, EnchantGates, GeneratedItem, Rarity, enchant_item_traced};
+use crate::weapons::WeaponKind;
+use crate::{Error, GameSettings, Result, rng};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+// way more than enough: a vanilla table rejects at most 1 of 7 slots
+const MAX_REROLLS: u32 = 1000;
+
+/// A setting a slot depends on. The game rerolls the slot if it's off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlotGate {
+    GoldenCoconut,
+    GoldenCoconuts,
+}
+
+impl SlotGate {
+    const ALL: [SlotGate; 2] = [SlotGate::GoldenCoconut, SlotGate::GoldenCoconuts];
+
+    fn code(self) -> &'static str {
+        match self {
+            SlotGate::GoldenCoconut => "golden_coconut",
+            SlotGate::GoldenCoconuts => "golden_coconuts",
+        }
+    }
+
+    pub fn allows(self, settings: &GameSettings) -> bool {
+        match self {
+            SlotGate::GoldenCoconut => settings.golden_coconut_in_common(),
+            SlotGate::GoldenCoconuts => settings.golden_coconuts_in_rare(),
+        }
+    }
+}
+
+/// One slot of a chest table
+#[derive(Clone, Debug, PartialEq)]
+pub struct LootSlot {
+    /// if there's more than one, the game picks one of them with another roll
+    pub items: Vec<ChestItemKind>,
+    /// (level, speed) for each of the items, to use instead of the ones in weapons.rs. empty for
+    /// the vanilla tables, and for anything that isn't a weapon.
+    pub weapons: Vec<(i32, i32)>,
+    pub requires: Option<SlotGate>,
+}
+
+impl LootSlot {
+    pub fn allowed(&self, settings: &GameSettings) -> bool {
+        self.requires.is_none_or(|gate| gate.allows(settings))
+    }
+
+    /// (level, speed) the item of this slot gets its enchantments with, None if it isn't a weapon
+    pub fn weapon_stats(&self, kind: ChestItemKind) -> Option<(i32, i32)> {
+        let params = WeaponKind::from_item(kind)?.params();
+        let i = self.items.iter().position(|&k| k == kind)?;
+        Some(
+            self.weapons
+                .get(i)
+                .copied()
+                .unwrap_or((params.level, params.speed)),
+        )
+    }
+}
+
+/// The rolls behind what a chest table gave
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableRoll {
+    pub slot: i32,
+    /// how often a disallowed slot came up first, i.e. golden coconuts before they're unlocked
+    pub rerolls: u32,
+    /// for weapons, the roll that decides whether there are any enchantments (< 0.5)
+    pub enchant_roll: Option<f64>,
+    /// for weapons, all of the enchantment gates
+    pub enchant_gates: Option<EnchantGates>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LootTables {
+    pub common: Vec<LootSlot>,
+    pub rare: Vec<LootSlot>,
+}
+
+impl LootTables {
+    /// the tables of the current game version, these are what the rest of the crate uses
+    pub fn vanilla() -> &'static LootTables {
+        static TABLES: OnceLock<LootTables> = OnceLock::new();
+        TABLES.get_or_init(|| {
+            include_str!("tables.toml")
+                .parse()
+                .expect("embedded loot tables are valid")
+        })
+    }
+
+    pub fn table(&self, rarity: Rarity) -> &[LootSlot] {
+        match rarity {
+            Rarity::Common => &self.common,
+            Rarity::Rare => &self.rare,
+        }
+    }
+
+    /// The slots of this table that can actually be rolled with these settings, with the items
+    /// each of them gives
+    pub fn effective_table(
+        &self,
+        rarity: Rarity,
+        settings: &GameSettings,
+    ) -> Vec<(i32, &[ChestItemKind])> {
+        self.table(rarity)
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.allowed(settings))
+            .map(|(i, slot)| (i as i32, &slot.items[..]))
+            .collect()
+    }
+
+    /// Every item the table can give with these settings, each once and in ChestItemKind order
+    pub fn obtainable(&self, rarity: Rarity, settings: &GameSettings) -> Vec<ChestItemKind> {
+        let mut out: Vec<ChestItemKind> = self
+            .effective_table(rarity, settings)
+            .into_iter()
+            .flat_map(|(_, items)| items.iter().copied())
+            .collect();
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    /// roll a slot like the game, rerolling disallowed ones. the game would loop forever on a
+    /// table where nothing is allowed, this gives up instead.
+    fn roll_slot(
+        &self,
+        rarity: Rarity,
+        rng: &mut rng::DotnetRng,
+        settings: &GameSettings,
+    ) -> Result<(i32, u32)> {
+        let table = self.table(rarity);
+        if !table.iter().any(|slot| slot.allowed(settings)) {
+            return Err(Error::EmptyLootTable(rarity));
+        }
+        for rerolls in 0..MAX_REROLLS {
+            let ind = rng.next_range(table.len() as i32);
+            if table[ind as usize].allowed(settings) {
+                return Ok((ind, rerolls));
+            }
+        }
+        Err(Error::EmptyLootTable(rarity))
+    }
+
+    /// the slot, its rerolls and the item, with `rng` right after the pick
+    fn roll_kind(
+        &self,
+        rarity: Rarity,
+        rng: &mut rng::DotnetRng,
+        settings: &GameSettings,
+    ) -> Result<(i32, u32, ChestItemKind)> {
+        // the first value is the chest's rarity roll, see Goodie::generate_at
+        rng.next();
+        let (ind, rerolls) = self.roll_slot(rarity, rng, settings)?;
+        let slot = &self.table(rarity)[ind as usize];
+        let pick = if slot.items.len() > 1 {
+            rng.next_range(slot.items.len() as i32) as usize
+        } else {
+            0
+        };
+        Ok((ind, rerolls, slot.items[pick]))
+    }
+
+    /// just the item of `generate`, without rolling the enchantments
+    pub fn generate_kind(
+        &self,
+        rarity: Rarity,
+        seed: i32,
+        settings: &GameSettings,
+    ) -> Result<ChestItemKind> {
+        let mut rng = rng::DotnetRng::new(seed);
+        Ok(self.roll_kind(rarity, &mut rng, settings)?.2)
+    }
+
+    /// contents of a chest with this seed, if it has this rarity
+    pub fn generate(
+        &self,
+        rarity: Rarity,
+        seed: i32,
+        settings: &GameSettings,
+    ) -> Result<GeneratedItem> {
+        Ok(self.generate_explained(rarity, seed, settings)?.0)
+    }
+
+    /// like generate, also giving the rolls that led to the item
+    pub fn generate_explained(
+        &self,
+        rarity: Rarity,
+        seed: i32,
+        settings: &GameSettings,
+    ) -> Result<(GeneratedItem, TableRoll)> {
+        let mut rng = rng::DotnetRng::new(seed);
+        let (ind, rerolls, kind) = self.roll_kind(rarity, &mut rng, settings)?;
+        // nothing but weapons rolls anything after the pick (no quality, not even for the ostrich
+        // egg). each chest has its own rng, so this couldn't shift other chests anyways.
+        let mut enchant_gates = None;
+        let enchants = self.table(rarity)[ind as usize]
+            .weapon_stats(kind)
+            .map(|(lvl, speed)| {
+                let (enchants, gates) = enchant_item_traced(&mut rng, lvl, speed, false);
+                enchant_gates = Some(gates);
+                enchants
+            })
+            .unwrap_or_default();
+        let item = GeneratedItem {
+            item: ChestItem::with_enchants(kind, enchants),
+            rng_cost: rng.calls(),
+        };
+        let roll = TableRoll {
+            slot: ind,
+            rerolls,
+            enchant_roll: enchant_gates.and_then(|g| g.any),
+            enchant_gates,
+        };
+        Ok((item, roll))
+    }
+}
+
+#[derive(Debug)]
+enum Value {
+    Str(String),
+    Int(i32),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn parse(s: &str) -> std::result::Result<(Value, &str), String> {
+        let s = s.trim_start();
+        if let Some(rest) = s.strip_prefix('"') {
+            let end = rest.find('"').ok_or("unterminated string")?;
+            Ok((Value::Str(rest[..end].to_string()), &rest[end + 1..]))
+        } else if let Some(mut rest) = s.strip_prefix('[') {
+            let mut items = vec![];
+            loop {
+                rest = rest.trim_start();
+                if let Some(rest) = rest.strip_prefix(']') {
+                    return Ok((Value::Array(items), rest));
+                }
+                let (item, after) = Value::parse(rest)?;
+                items.push(item);
+                rest = after.trim_start();
+                if let Some(after) = rest.strip_prefix(',') {
+                    rest = after;
+                } else if !rest.starts_with(']') {
+                    return Err("expected , or ]".to_string());
+                }
+            }
+        } else {
+            let end = s
+                .find(|c: char| c != '-' && !c.is_ascii_digit())
+                .unwrap_or(s.len());
+            let n = s[..end]
+                .parse()
+                .map_err(|_| format!("unexpected value {:?}", s))?;
+            Ok((Value::Int(n), &s[end..]))
+        }
+    }
+
+    fn as_str(&self) -> std::result::Result<&str, String> {
+        match self {
+            Value::Str(s) => Ok(s),
+            _ => Err(format!("expected a string, got {:?}", self)),
+        }
+    }
+
+    fn as_int(&self) -> std::result::Result<i32, String> {
+        match self {
+            Value::Int(n) => Ok(*n),
+            _ => Err(format!("expected an integer, got {:?}", self)),
+        }
+    }
+
+    fn as_array(&self) -> std::result::Result<&[Value], String> {
+        match self {
+            Value::Array(a) => Ok(a),
+            _ => Err(format!("expected an array, got {:?}", self)),
+        }
+    }
+}
+
+fn parse_key(slot: &mut LootSlot, key: &str, value: &Value) -> std::result::Result<(), String> {
+    match key {
+        "items" => {
+            slot.items = value
+                .as_array()?
+                .iter()
+                .map(|v| ChestItemKind::from_compact_string(v.as_str()?).map_err(|e| e.to_string()))
+                .collect::<std::result::Result<_, _>>()?;
+        }
+        "weapons" => {
+            slot.weapons = value
+                .as_array()?
+                .iter()
+                .map(|v| match v.as_array()? {
+                    [lvl, speed] => Ok((lvl.as_int()?, speed.as_int()?)),
+                    _ => Err("weapons are [level, speed]".to_string()),
+                })
+                .collect::<std::result::Result<_, _>>()?;
+        }
+        "requires" => {
+            let code = value.as_str()?;
+            let gate = SlotGate::ALL.into_iter().find(|g| g.code() == code);
+            slot.requires = Some(gate.ok_or_else(|| format!("unknown setting {:?}", code))?);
+        }
+        _ => return Err(format!("unknown key {:?}", key)),
+    }
+    Ok(())
+}
+
+impl FromStr for LootTables {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tables = LootTables {
+            common: vec![],
+            rare: vec![],
+        };
+        // the slot currently being read, with the line of its header
+        let mut current: Option<(Rarity, usize, LootSlot)> = None;
+        let mut finish = |current: Option<(Rarity, usize, LootSlot)>| -> Result<()> {
+            let Some((rarity, line, slot)) = current else {
+                return Ok(());
+            };
+            if slot.items.is_empty() {
+                return Err(Error::InvalidLootTable(
+                    line,
+                    "slot without items".to_string(),
+                ));
+            }
+            if !slot.weapons.is_empty() {
+                if slot.weapons.len() != slot.items.len() {
+                    return Err(Error::InvalidLootTable(
+                        line,
+                        "weapons need one [level, speed] per item".to_string(),
+                    ));
+                }
+                if let Some(kind) = slot
+                    .items
+                    .iter()
+                    .find(|&&k| WeaponKind::from_item(k).is_none())
+                {
+                    return Err(Error::InvalidLootTable(
+                        line,
+                        format!(
+                            "weapons for {}, which isn't a weapon",
+                            kind.to_compact_string()
+                        ),
+                    ));
+                }
+            }
+            match rarity {
+                Rarity::Common => tables.common.push(slot),
+                Rarity::Rare => tables.rare.push(slot),
+            }
+            Ok(())
+        };
+
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let err = |msg: String| Error::InvalidLootTable(line_no, msg);
+            // no '#' in any of the strings, so this is fine
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+                let rarity = match header.trim() {
+                    "common" => Rarity::Common,
+                    "rare" => Rarity::Rare,
+                    other => return Err(err(format!("unknown table {:?}", other))),
+                };
+                finish(current.take())?;
+                let slot = LootSlot {
+                    items: vec![],
+                    weapons: vec![],
+                    requires: None,
+                };
+                current = Some((rarity, line_no, slot));
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(err("expected [[table]] or key = value".to_string()));
+            };
+            let Some((_, _, slot)) = &mut current else {
+                return Err(err("key outside of a slot".to_string()));
+            };
+            let (value, rest) = Value::parse(value).map_err(err)?;
+            if !rest.trim().is_empty() {
+                return Err(err(format!("trailing {:?}", rest.trim())));
+            }
+            parse_key(slot, key.trim(), &value).map_err(err)?;
+        }
+        finish(current)?;
+        Ok(tables)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DWARF: &str =
+        "[[common]]\nitems = [\"dwarf_sword\", \"dwarf_hammer\", \"dwarf_dagger\"]\n";
+
+    #[test]
+    fn weapons_default_to_weapons_rs() {
+        let plain: LootTables = DWARF.parse().unwrap();
+        let stats = [
+            WeaponKind::DwarfSword,
+            WeaponKind::DwarfHammer,
+            WeaponKind::DwarfDagger,
+        ]
+        .map(|w| format!("[{}, {}]", w.params().level, w.params().speed))
+        .join(", ");
+        let explicit: LootTables = format!("{}weapons = [{}]\n", DWARF, stats).parse().unwrap();
+        assert!(plain.common[0].weapons.is_empty());
+        let settings = GameSettings::default();
+        for seed in 0..200 {
+            assert_eq!(
+                plain.generate(Rarity::Common, seed, &settings).unwrap(),
+                explicit.generate(Rarity::Common, seed, &settings).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn weapons_override_stats() {
+        let tables: LootTables = format!("{}weapons = [[1, -8], [2, 0], [3, 4]]\n", DWARF)
+            .parse()
+            .unwrap();
+        let slot = &tables.common[0];
+        assert_eq!(slot.weapons, vec![(1, -8), (2, 0), (3, 4)]);
+        assert_eq!(slot.weapon_stats(ChestItemKind::DwarfHammer), Some((2, 0)));
+        assert_eq!(slot.weapon_stats(ChestItemKind::TaroTuber), None);
+        // a low level changes what the rolls give for some seed
+        let settings = GameSettings::default();
+        let vanilla: LootTables = DWARF.parse().unwrap();
+        assert!((0..200).any(|seed| {
+            tables.generate(Rarity::Common, seed, &settings).unwrap()
+                != vanilla.generate(Rarity::Common, seed, &settings).unwrap()
+        }));
+    }
+
+    #[test]
+    fn weapons_are_validated() {
+        let err = |s: String| match s.parse::<LootTables>() {
+            Err(Error::InvalidLootTable(line, msg)) => (line, msg),
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(
+            err(format!("{}weapons = [[1, 2]]\n", DWARF)),
+            (1, "weapons need one [level, speed] per item".to_string())
+        );
+        assert_eq!(
+            err("[[common]]\nitems = [\"taro\"]\nweapons = [[1, 2]]\n".to_string()),
+            (1, "weapons for taro, which isn't a weapon".to_string())
+        );
+        assert_eq!(
+            err(format!("{}weapons = [[1, 2, 3], [1, 2], [1, 2]]\n", DWARF)).1,
+            "weapons are [level, speed]"
+        );
+        assert_eq!(
+            err(format!("{}weapons = [[\"1\", 2], [1, 2], [1, 2]]\n", DWARF)).1,
+            "expected an integer, got Str(\"1\")"
+        );
+    }
+
+    /// one line per seed like fixtures/chest_items.txt: the common and rare item and their rng
+    /// cost, without and then with the golden coconut cracked
+    fn chest_items(tables: &LootTables) -> String {
+        let item = |item: &ChestItem| match item.enchants() {
+            Some(e) => format!(
+                "{}+{}",
+                item.kind().to_compact_string(),
+                e.to_compact_string()
+            ),
+            None => item.kind().to_compact_string(),
+        };
+        let mut seeds: Vec<i32> = (0..1000).collect();
+        let mut rng = rng::DotnetRng::new(1);
+        seeds.extend((0..1000).map(|_| rng.next()));
+        let mut out = String::new();
+        for seed in seeds {
+            out += &seed.to_string();
+            for cracked in [false, true] {
+                let settings = GameSettings::default().with_cracked_golden_coconut(cracked);
+                for rarity in [Rarity::Common, Rarity::Rare] {
+                    let generated = tables.generate(rarity, seed, &settings).unwrap();
+                    out += &format!(" {} {}", item(&generated.item), generated.rng_cost);
+                }
+            }
+            out += "\n";
+        }
+        out
+    }
+
+    #[test]
+    fn tables_give_what_the_match_arms_did() {
+        // made with the hardcoded tables before tables.toml
+        let fixture = include_str!("../../fixtures/chest_items.txt");
+        let vanilla = chest_items(LootTables::vanilla());
+        for (i, (got, want)) in vanilla.lines().zip(fixture.lines()).enumerate() {
+            assert_eq!(got, want, "line {}", i + 1);
+        }
+        assert_eq!(vanilla.lines().count(), fixture.lines().count());
+        let parsed: LootTables = include_str!("tables.toml").parse().unwrap();
+        assert_eq!(&parsed, LootTables::vanilla());
+        // and what the rest of the crate generates goes by them
+        let settings = GameSettings::default();
+        for seed in 0..200 {
+            for rarity in [Rarity::Common, Rarity::Rare] {
+                assert_eq!(
+                    ChestItem::generate_traced(rarity, seed, &settings),
+                    LootTables::vanilla()
+                        .generate(rarity, seed, &settings)
+                        .unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn from_str_tables() {
+        let tables: LootTables = "[[common]]\nitems = [\"taro\"] # a comment\n\n[[rare]]\n\
+            items = [\"coconut\"]\nrequires = \"golden_coconut\"\n"
+            .parse()
+            .unwrap();
+        let settings = GameSettings::default();
+        for seed in 0..50 {
+            assert_eq!(
+                tables
+                    .generate_kind(Rarity::Common, seed, &settings)
+                    .unwrap(),
+                ChestItemKind::TaroTuber
+            );
+        }
+        assert!(matches!(
+            tables.generate(Rarity::Rare, 0, &settings),
+            Err(Error::EmptyLootTable(Rarity::Rare))
+        ));
+        let cracked = settings.clone().with_cracked_golden_coconut(true);
+        assert_eq!(
+            tables.obtainable(Rarity::Rare, &cracked),
+            [ChestItemKind::GoldenCoconut]
+        );
+
+        let err = |s: &str| match s.parse::<LootTables>() {
+            Err(Error::InvalidLootTable(line, msg)) => (line, msg),
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(err("[[epic]]").1, "unknown table \"epic\"");
+        assert_eq!(err("items = [\"taro\"]").1, "key outside of a slot");
+        assert_eq!(
+            err("[[common]]\n[[rare]]\nitems = [\"taro\"]"),
+            (1, "slot without items".to_string())
+        );
+        assert_eq!(
+            err("[[common]]\nitem = [\"taro\"]"),
+            (2, "unknown key \"item\"".to_string())
+        );
+        assert_eq!(err("[[common]]\nitems = [\"taro\"] x").1, "trailing \"x\"");
+        assert_eq!(err("[[common]]\nitems = [\"taro\"\n").1, "expected , or ]");
+        assert_eq!(
+            err("[[common]]\nitems = [\"taro\"]\nrequires = \"luck\"").1,
+            "unknown setting \"luck\""
+        );
+        assert_eq!(err("[[common]]\nitems = [\"tar\"]").0, 2);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::GameSettings;
+use crate::date::DayNumber;
+
+pub mod sources;
+
+/// (minluck, maxluck) as luckMult over all possible daily luck values and luck buffs
+pub(crate) fn luck_range(settings: &GameSettings) -> (f64, f64) {
+    // these values are *technically* not exact due to rounding (special charm especially)
+    // but we only show them with 4 significant digits anyways
+    let mut minluck = -0.1;
+    let mut base_maxluck = 0.1;
+    if settings.special_charm {
+        minluck += sources::SPECIAL_CHARM.daily_luck;
+        base_maxluck += sources::SPECIAL_CHARM.daily_luck;
+    }
+    (
+        1. + minluck / 2.,
+        1. + base_maxluck / 2. + sources::LUCK_PER_LEVEL * (settings.max_luck_lvl as f64),
+    )
+}
+
+/// luckMult of a player with a known daily luck (what the fortune teller says, the special charm
+/// is added on top) and `luck_lvl` levels of luck buffs.
+///
+/// The daily luck itself can't be predicted from the settings: the game rolls it from the day's
+/// random after calls that depend on the save's state (and steps taken, before 1.6).
+pub fn luck_multiplier(settings: &GameSettings, daily_luck: f64, luck_lvl: u32) -> f64 {
+    let mut daily_luck = daily_luck;
+    if settings.special_charm {
+        daily_luck += sources::SPECIAL_CHARM.daily_luck;
+    }
+    1. + daily_luck / 2. + sources::LUCK_PER_LEVEL * luck_lvl as f64
+}
+
+/// Whether luckMult `luck` is past `threshold`. Strictly: at the threshold itself it isn't, so
+/// a chest exactly at its rare_luck is common, like a luck range split at a chance chest ends its
+/// common part with the threshold and starts the rare part at its next_up.
+///
+/// Everything that depends on the luck (chest rarity, the special floors) compares through this
+/// or threshold_split, so they can't disagree on the edge. The thresholds and luck values only
+/// come out of `+`, `-`, `*` and `/` on f64 (and halving/doubling, which is exact). IEEE 754
+/// rounds those the same everywhere: x86_64 has no x87 extended precision and Rust never turns
+/// `a * b + c` into an fma by itself, so a seed classifies bit for bit the same on every
+/// platform. Don't use mul_add or the libm functions (exp, ln, powf, ...) for anything that
+/// ends up here, their rounding is up to the platform. audit::check_rarity_fixture checks this.
+pub fn past_threshold(threshold: f64, luck: f64) -> bool {
+    threshold < luck
+}
+
+/// Where a threshold is for a luck range, by past_threshold at its ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ThresholdSplit {
+    /// even the lowest luck is past it
+    Past,
+    /// not even the highest luck is
+    NotPast,
+    /// the range has luck on both sides of it
+    Within,
+}
+
+pub(crate) fn threshold_split(threshold: f64, (min_luck, max_luck): (f64, f64)) -> ThresholdSplit {
+    if past_threshold(threshold, min_luck) {
+        ThresholdSplit::Past
+    } else if !past_threshold(threshold, max_luck) {
+        ThresholdSplit::NotPast
+    } else {
+        ThresholdSplit::Within
+    }
+}
+
+/// What's known about a player's luckMult on a day
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LuckDistribution {
+    Fixed(f64),
+    /// uniform between (min, max)
+    Uniform(f64, f64),
+}
+
+impl LuckDistribution {
+    /// The daily luck as the game rolls it, roughly uniform over -0.1 to 0.1, with `luck_lvl`
+    /// levels of buffs on top
+    pub fn daily(settings: &GameSettings, luck_lvl: u32) -> Self {
+        LuckDistribution::Uniform(
+            luck_multiplier(settings, -0.1, luck_lvl),
+            luck_multiplier(settings, 0.1, luck_lvl),
+        )
+    }
+
+    /// the lowest luckMult it can be
+    pub fn min(&self) -> f64 {
+        match *self {
+            LuckDistribution::Fixed(luck) => luck,
+            LuckDistribution::Uniform(lo, _) => lo,
+        }
+    }
+
+    /// chance of the luckMult being between `min` and `max`
+    pub fn probability(&self, min: f64, max: f64) -> f64 {
+        match *self {
+            LuckDistribution::Fixed(luck) => (min <= luck && luck <= max) as u8 as f64,
+            LuckDistribution::Uniform(lo, hi) if hi > lo => {
+                ((max.min(hi) - min.max(lo)) / (hi - lo)).max(0.)
+            }
+            LuckDistribution::Uniform(lo, _) => (min <= lo && lo <= max) as u8 as f64,
+        }
+    }
+}
+
+/// How a player's luckMult comes about on a day, for mods that change how daily luck and buffs
+/// combine. Forecasts, plans and the probabilities take a `&dyn LuckModel`, so a boxed one can be
+/// kept around.
+///
+/// Luck values have to be finite, with min <= max, a distribution over an infinite range doesn't
+/// mean anything. That's only for models: chest generation takes infinite ends as no bound, and
+/// fails with InvalidLuck for NaN and inverted ranges. Plans check this in debug builds.
+pub trait LuckModel {
+    /// (min, max) luckMult (1 + the luck boost) the player can have on `day`
+    fn boost_range(&self, day: DayNumber) -> (f64, f64);
+
+    /// the exact luckMult on `day`, if it's known. finite like boost_range
+    fn resolve(&self, day: DayNumber) -> Option<f64>;
+
+    /// the luck on `day` to weigh outcomes with, uniform over boost_range unless it's resolved
+    fn distribution(&self, day: DayNumber) -> LuckDistribution {
+        match self.resolve(day) {
+            Some(luck) => LuckDistribution::Fixed(luck),
+            None => {
+                let (lo, hi) = self.boost_range(day);
+                LuckDistribution::Uniform(lo, hi)
+            }
+        }
+    }
+}
+
+/// The game's own luck: any daily luck, the special charm, and up to max_luck_lvl levels of
+/// buffs. Never resolved, the daily luck can't be predicted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VanillaLuck {
+    pub special_charm: bool,
+    pub max_luck_lvl: u32,
+}
+
+impl VanillaLuck {
+    pub fn new(settings: &GameSettings) -> Self {
+        VanillaLuck {
+            special_charm: settings.special_charm,
+            max_luck_lvl: settings.max_luck_lvl,
+        }
+    }
+}
+
+impl LuckModel for VanillaLuck {
+    fn boost_range(&self, _day: DayNumber) -> (f64, f64) {
+        luck_range(&GameSettings {
+            special_charm: self.special_charm,
+            max_luck_lvl: self.max_luck_lvl,
+            ..Default::default()
+        })
+    }
+
+    fn resolve(&self, _day: DayNumber) -> Option<f64> {
+        None
+    }
+}
+
+/// the same luck every day
+impl LuckModel for LuckDistribution {
+    fn boost_range(&self, _day: DayNumber) -> (f64, f64) {
+        match *self {
+            LuckDistribution::Fixed(luck) => (luck, luck),
+            LuckDistribution::Uniform(lo, hi) => (lo, hi.max(lo)),
+        }
+    }
+
+    fn resolve(&self, _day: DayNumber) -> Option<f64> {
+        match *self {
+            LuckDistribution::Fixed(luck) => Some(luck),
+            LuckDistribution::Uniform(lo, hi) if hi <= lo => Some(lo),
+            LuckDistribution::Uniform(..) => None,
+        }
+    }
+
+    fn distribution(&self, _day: DayNumber) -> LuckDistribution {
+        *self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn daily_luck_distribution() {
+        let settings = GameSettings::default();
+        let daily = LuckDistribution::daily(&settings, 0);
+        assert_eq!(daily, LuckDistribution::Uniform(0.95, 1.05));
+        assert_eq!(daily.min(), 0.95);
+        assert!((daily.probability(1., 1.05) - 0.5).abs() < 1e-12);
+        assert!((daily.probability(0.9, 0.975) - 0.25).abs() < 1e-12);
+        assert_eq!(daily.probability(1.05, 2.), 0.);
+        assert_eq!(daily.probability(0., 2.), 1.);
+        // buffs move the whole range, the charm too
+        let buffed = LuckDistribution::daily(&settings, 2);
+        assert_eq!(buffed.min(), luck_multiplier(&settings, -0.1, 2));
+        let charmed = GameSettings {
+            special_charm: true,
+            ..Default::default()
+        };
+        let LuckDistribution::Uniform(_, hi) = LuckDistribution::daily(&charmed, 0) else {
+            unreachable!();
+        };
+        assert_eq!(hi, luck_range(&charmed).1);
+        let fixed = LuckDistribution::Fixed(1.01);
+        assert_eq!(fixed.probability(1., 1.01), 1.);
+        assert_eq!(fixed.probability(1.02, 1.1), 0.);
+    }
+
+    #[test]
+    fn vanilla_luck_model() {
+        for (special_charm, max_luck_lvl) in [(false, 0), (true, 0), (false, 3), (true, 2)] {
+            let settings = GameSettings {
+                special_charm,
+                max_luck_lvl,
+                ..Default::default()
+            };
+            let model = VanillaLuck::new(&settings);
+            for day in [1, 50, 5000] {
+                assert_eq!(model.boost_range(DayNumber(day)), luck_range(&settings));
+                assert_eq!(model.resolve(DayNumber(day)), None);
+                let (lo, hi) = luck_range(&settings);
+                assert_eq!(
+                    model.distribution(DayNumber(day)),
+                    LuckDistribution::Uniform(lo, hi)
+                );
+            }
+        }
+        // a distribution is a model of the same luck every day
+        let fixed = LuckDistribution::Fixed(1.02);
+        assert_eq!(fixed.boost_range(DayNumber(9)), (1.02, 1.02));
+        assert_eq!(fixed.resolve(DayNumber(9)), Some(1.02));
+        let empty = LuckDistribution::Uniform(1.02, 1.);
+        assert_eq!(empty.boost_range(DayNumber(9)), (1.02, 1.02));
+        assert_eq!(empty.resolve(DayNumber(9)), Some(1.02));
+        assert_eq!(
+            LuckDistribution::Uniform(1., 1.02).resolve(DayNumber(9)),
+            None
+        );
+    }
+}
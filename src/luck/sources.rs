@@ -0,0 +1,197 @@
+//! Vanilla things that change luck. Buffs add luck levels, which count 0.035 each towards
+//! luckMult. The special charm adds to daily luck instead, which counts half.
+
+/// Only one buff per slot is active. Eating another food replaces the food buff (same for
+/// drinks), while both ring slots can hold a luck ring.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BuffSlot {
+    Food,
+    Drink,
+    Ring,
+    /// permanent, always active once obtained
+    Permanent,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LuckSource {
+    pub name: &'static str,
+    pub luck_levels: i32,
+    /// added to daily luck
+    pub daily_luck: f64,
+    /// real-time seconds, None for equipment and permanent effects
+    pub duration_secs: Option<u32>,
+    pub slot: BuffSlot,
+}
+
+/// luckMult from each level of luck buff
+pub const LUCK_PER_LEVEL: f64 = 0.035;
+/// there's two ring slots (combined rings aside)
+pub const RING_SLOTS: usize = 2;
+
+pub const LUCKY_LUNCH: LuckSource = LuckSource {
+    name: "Lucky Lunch",
+    luck_levels: 3,
+    daily_luck: 0.,
+    duration_secs: Some(671),
+    slot: BuffSlot::Food,
+};
+pub const SPICY_EEL: LuckSource = LuckSource {
+    name: "Spicy Eel",
+    luck_levels: 1,
+    daily_luck: 0.,
+    duration_secs: Some(420),
+    slot: BuffSlot::Food,
+};
+pub const MAGIC_ROCK_CANDY: LuckSource = LuckSource {
+    name: "Magic Rock Candy",
+    luck_levels: 5,
+    daily_luck: 0.,
+    duration_secs: Some(210),
+    slot: BuffSlot::Food,
+};
+pub const GINGER_ALE: LuckSource = LuckSource {
+    name: "Ginger Ale",
+    luck_levels: 1,
+    daily_luck: 0.,
+    duration_secs: Some(300),
+    slot: BuffSlot::Drink,
+};
+pub const LUCKY_RING: LuckSource = LuckSource {
+    name: "Lucky Ring",
+    luck_levels: 1,
+    daily_luck: 0.,
+    duration_secs: None,
+    slot: BuffSlot::Ring,
+};
+pub const SPECIAL_CHARM: LuckSource = LuckSource {
+    name: "Special Charm",
+    luck_levels: 0,
+    // the game stores this as a float
+    daily_luck: 0.025_f32 as f64,
+    duration_secs: None,
+    slot: BuffSlot::Permanent,
+};
+
+pub const ALL: [LuckSource; 6] = [
+    LUCKY_LUNCH,
+    SPICY_EEL,
+    MAGIC_ROCK_CANDY,
+    GINGER_ALE,
+    LUCKY_RING,
+    SPECIAL_CHARM,
+];
+
+impl LuckSource {
+    /// whether this adds to others of the same slot instead of replacing them
+    pub fn stacks(&self) -> bool {
+        self.slot == BuffSlot::Ring
+    }
+}
+
+/// The sources that are actually active when applying them in order: later food/drink buffs
+/// replace earlier ones, only two rings fit, and permanent effects only count once.
+pub fn active(sources: &[LuckSource]) -> Vec<LuckSource> {
+    let mut out: Vec<LuckSource> = vec![];
+    for &src in sources {
+        match src.slot {
+            BuffSlot::Food | BuffSlot::Drink => {
+                out.retain(|x| x.slot != src.slot);
+                out.push(src);
+            }
+            BuffSlot::Ring => {
+                if out.iter().filter(|x| x.slot == BuffSlot::Ring).count() < RING_SLOTS {
+                    out.push(src);
+                }
+            }
+            BuffSlot::Permanent => {
+                if !out.contains(&src) {
+                    out.push(src);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// total luck levels of the active sources
+pub fn luck_levels(sources: &[LuckSource]) -> i32 {
+    active(sources).iter().map(|x| x.luck_levels).sum()
+}
+
+/// how much the sources add to luckMult, after applying the stacking rules
+pub fn stack(sources: &[LuckSource]) -> f64 {
+    let active = active(sources);
+    let levels: i32 = active.iter().map(|x| x.luck_levels).sum();
+    let daily: f64 = active.iter().map(|x| x.daily_luck).sum();
+    daily / 2. + LUCK_PER_LEVEL * levels as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn food_buffs_replace_each_other() {
+        // the lunch is gone once the eel is eaten, whichever is better
+        assert_eq!(active(&[LUCKY_LUNCH, SPICY_EEL]), [SPICY_EEL]);
+        assert_eq!(luck_levels(&[MAGIC_ROCK_CANDY, LUCKY_LUNCH]), 3);
+        assert!(close(stack(&[LUCKY_LUNCH, SPICY_EEL]), LUCK_PER_LEVEL));
+        assert!(close(stack(&[LUCKY_LUNCH]), 3. * LUCK_PER_LEVEL));
+        // drinks have their own slot
+        assert_eq!(luck_levels(&[LUCKY_LUNCH, GINGER_ALE]), 4);
+        assert_eq!(luck_levels(&[GINGER_ALE, GINGER_ALE]), 1);
+    }
+
+    #[test]
+    fn two_luck_rings() {
+        assert_eq!(luck_levels(&[LUCKY_RING, LUCKY_RING]), 2);
+        assert!(close(stack(&[LUCKY_RING, LUCKY_RING]), 2. * LUCK_PER_LEVEL));
+        // no third slot
+        assert_eq!(luck_levels(&[LUCKY_RING; 3]), 2);
+        assert!(LUCKY_RING.stacks() && !LUCKY_LUNCH.stacks());
+    }
+
+    #[test]
+    fn special_charm_counts_once_as_daily_luck() {
+        let charm = stack(&[SPECIAL_CHARM]);
+        assert!(close(charm, SPECIAL_CHARM.daily_luck / 2.));
+        assert!(close(stack(&[SPECIAL_CHARM, SPECIAL_CHARM]), charm));
+        let everything = stack(&[
+            MAGIC_ROCK_CANDY,
+            GINGER_ALE,
+            LUCKY_RING,
+            LUCKY_RING,
+            SPECIAL_CHARM,
+        ]);
+        assert!(close(everything, charm + 8. * LUCK_PER_LEVEL));
+        assert_eq!(stack(&[]), 0.);
+    }
+
+    #[test]
+    fn luck_range_uses_the_same_rules() {
+        let settings = crate::GameSettings {
+            special_charm: true,
+            max_luck_lvl: 8,
+            ..Default::default()
+        };
+        let plain = crate::luck::luck_range(&crate::GameSettings {
+            max_luck_lvl: 0,
+            ..Default::default()
+        });
+        let (min, max) = crate::luck::luck_range(&settings);
+        let charm = stack(&[SPECIAL_CHARM]);
+        assert!(close(min, plain.0 + charm));
+        let best = stack(&[
+            MAGIC_ROCK_CANDY,
+            GINGER_ALE,
+            LUCKY_RING,
+            LUCKY_RING,
+            SPECIAL_CHARM,
+        ]);
+        assert!(close(max, plain.1 + best));
+    }
+}
@@ -0,0 +1,108 @@
+//! What changed in the generation between crate versions, for telling whether cached forecasts
+//! are still right. Every change to what the generation gives adds an entry to BEHAVIOR_CHANGES
+//! and moves GENERATION_FINGERPRINT on, in the same commit as the change.
+
+use crate::{GameSettings, GameVersion, fnv1a};
+
+/// The fingerprint of a generation revision, from its name so the table says which is which
+const fn revision(name: &str) -> u64 {
+    fnv1a(name.as_bytes())
+}
+
+/// Identifies what the generation gives: forecasts made with this fingerprint are what the crate
+/// would make now. Store it next to cached forecasts and check them with `affects`.
+pub const GENERATION_FINGERPRINT: u64 = revision("layout-seed-u64");
+
+/// One change of the generation's results
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BehaviorChange {
+    /// the first crate version with the change
+    pub crate_version: &'static str,
+    pub before: u64,
+    pub after: u64,
+    /// the versions whose forecasts changed
+    pub game_versions: &'static [GameVersion],
+    pub description: &'static str,
+}
+
+/// in order, each one's `before` is the previous one's `after`
+const BEHAVIOR_CHANGES: &[BehaviorChange] = &[BehaviorChange {
+    crate_version: "0.1.0",
+    before: revision("baseline"),
+    after: revision("layout-seed-u64"),
+    game_versions: &[GameVersion::V1_6, GameVersion::V1_6_4],
+    description: "the layout seed multiplies days_played by the level without overflowing, \
+        like the game does. Only days where that's over u32::MAX (past day 400 million or so) \
+        were wrong",
+}];
+
+// the fingerprints are all different and chained, and the last one is the current one
+const _: () = {
+    let mut i = 0;
+    while i < BEHAVIOR_CHANGES.len() {
+        let change = &BEHAVIOR_CHANGES[i];
+        assert!(i == 0 || change.before == BEHAVIOR_CHANGES[i - 1].after);
+        assert!(change.after != BEHAVIOR_CHANGES[0].before);
+        let mut j = 0;
+        while j < i {
+            assert!(change.after != BEHAVIOR_CHANGES[j].after);
+            j += 1;
+        }
+        i += 1;
+    }
+    assert!(BEHAVIOR_CHANGES[BEHAVIOR_CHANGES.len() - 1].after == GENERATION_FINGERPRINT);
+};
+
+/// every change so far, oldest first
+pub fn behavior_changes() -> &'static [BehaviorChange] {
+    BEHAVIOR_CHANGES
+}
+
+/// The changes since forecasts with `old_fingerprint` were made that affect the version of
+/// `settings`, oldest first. Empty if they're still right, and all of them for fingerprints that
+/// aren't in the table (e.g. from a newer crate).
+pub fn affects(settings: &GameSettings, old_fingerprint: u64) -> Vec<&'static BehaviorChange> {
+    let since = if old_fingerprint == GENERATION_FINGERPRINT {
+        BEHAVIOR_CHANGES.len()
+    } else {
+        BEHAVIOR_CHANGES
+            .iter()
+            .position(|c| c.before == old_fingerprint)
+            .unwrap_or(0)
+    };
+    let version = settings.version();
+    BEHAVIOR_CHANGES[since..]
+        .iter()
+        .filter(|c| c.game_versions.contains(&version))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprints_are_distinct() {
+        let changes = behavior_changes();
+        let mut fingerprints = vec![changes[0].before];
+        fingerprints.extend(changes.iter().map(|c| c.after));
+        let mut distinct = fingerprints.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), fingerprints.len());
+        for pair in changes.windows(2) {
+            assert_eq!(pair[0].after, pair[1].before);
+        }
+        assert_eq!(changes.last().unwrap().after, GENERATION_FINGERPRINT);
+    }
+
+    #[test]
+    fn affects_since_the_fingerprint() {
+        let settings = GameSettings::default();
+        assert!(affects(&settings, GENERATION_FINGERPRINT).is_empty());
+        let first = &behavior_changes()[0];
+        assert_eq!(affects(&settings, first.before), [first]);
+        // unknown fingerprints get everything
+        assert_eq!(affects(&settings, 1).len(), behavior_changes().len());
+    }
+}
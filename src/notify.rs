@@ -0,0 +1,128 @@
+//! What changed between two forecasts of the same days, e.g. after changing settings or updating
+//! the crate, for pinging people about new finds.
+
+use crate::date::{DayNumber, GameDate};
+use crate::display_luck;
+use crate::forecast::DayForecast;
+use crate::loot::Goodie;
+use crate::search::{Query, SearchHit, day_hits_by};
+use std::fmt::Display;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Notification {
+    /// in the current forecast, not in the previous one
+    Appeared(SearchHit),
+    /// in the previous forecast, not anymore in the current one
+    Disappeared(SearchHit),
+}
+
+impl Notification {
+    pub fn hit(&self) -> &SearchHit {
+        match self {
+            Notification::Appeared(hit) | Notification::Disappeared(hit) => hit,
+        }
+    }
+}
+
+impl Display for Notification {
+    /// e.g. "new: Dragontooth Club (+5 Attack) on spring 3, Y1, floor 7, luck 0.0240 to 0.1250"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hit = self.hit();
+        match self {
+            Notification::Appeared(_) => write!(f, "new: ")?,
+            Notification::Disappeared(_) => write!(f, "gone: ")?,
+        }
+        match &hit.goodie {
+            Goodie::CommonChest(c) | Goodie::RareChest(c) => write!(f, "{}", c)?,
+            g => write!(f, "{}", g)?,
+        }
+        write!(
+            f,
+            " on {}, floor {}",
+            GameDate::from(DayNumber(hit.days_played)),
+            hit.level
+        )?;
+        if !hit.guaranteed {
+            write!(
+                f,
+                ", luck {:.4} to {:.4}",
+                display_luck(hit.min_luck),
+                display_luck(hit.max_luck)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Chests matching `criteria` that appeared or disappeared from `previous` to `current`, by day.
+/// Only the days both forecasts have are compared, new days would be all news. A chest counts as
+/// the same if it's the same item on the same floor, even if the luck it needs changed.
+pub fn watch(
+    previous: &[DayForecast],
+    current: &[DayForecast],
+    criteria: &Query,
+) -> Vec<Notification> {
+    let mut out = vec![];
+    for day in current {
+        let Some(before) = previous.iter().find(|d| d.days_played == day.days_played) else {
+            continue;
+        };
+        let mut gone = day_hits_by(before, |c| criteria.matches(c));
+        for hit in day_hits_by(day, |c| criteria.matches(c)) {
+            match gone
+                .iter()
+                .position(|h| h.level == hit.level && h.goodie == hit.goodie)
+            {
+                Some(i) => {
+                    gone.remove(i);
+                }
+                None => out.push(Notification::Appeared(hit)),
+            }
+        }
+        out.extend(gone.into_iter().map(Notification::Disappeared));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameSettings;
+    use crate::loot::ChestItemKind;
+
+    fn forecast(cracked: bool) -> Vec<DayForecast> {
+        let settings = GameSettings {
+            seed: 77,
+            ..Default::default()
+        }
+        .with_cracked_golden_coconut(cracked);
+        crate::forecast::range(&settings, 1, 28).unwrap()
+    }
+
+    #[test]
+    fn coconuts_after_cracking_one() {
+        let (before, after) = (forecast(false), forecast(true));
+        let coconuts = Query {
+            kinds: vec![ChestItemKind::GoldenCoconut, ChestItemKind::GoldenCoconuts],
+            min_enchants: None,
+            warnings: vec![],
+        };
+        let hits: Vec<SearchHit> = after
+            .iter()
+            .flat_map(|day| day_hits_by(day, |c| coconuts.matches(c)))
+            .collect();
+        assert!(!hits.is_empty());
+        let appeared: Vec<Notification> =
+            hits.iter().cloned().map(Notification::Appeared).collect();
+        assert_eq!(watch(&before, &after, &coconuts), appeared);
+        let disappeared: Vec<Notification> =
+            hits.into_iter().map(Notification::Disappeared).collect();
+        assert_eq!(watch(&after, &before, &coconuts), disappeared);
+        assert!(watch(&after, &after, &coconuts).is_empty());
+        // only the days both have
+        assert!(watch(&before[..1], &after[1..], &coconuts).is_empty());
+
+        let text = appeared[0].to_string();
+        assert!(text.starts_with("new: Golden Coconut") && text.contains(", floor "));
+    }
+}
@@ -0,0 +1,363 @@
+//! Forecasts for players who don't know some of their settings. Every combination of the unknown
+//! flags is generated, and chests that differ between them become `Goodie::Ambiguous`.
+
+use crate::calendar;
+use crate::date::{DayNumber, MAX_DAY_RANGE};
+use crate::forecast::DayForecast;
+use crate::loot::Goodie;
+use crate::{GameSettings, ProbabilityRange, Result};
+use std::fmt::Display;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Flag {
+    Yes,
+    No,
+    #[default]
+    Unknown,
+}
+
+impl Flag {
+    fn values(self) -> &'static [bool] {
+        match self {
+            Flag::Yes => &[true],
+            Flag::No => &[false],
+            Flag::Unknown => &[false, true],
+        }
+    }
+}
+
+impl From<bool> for Flag {
+    fn from(b: bool) -> Self {
+        if b { Flag::Yes } else { Flag::No }
+    }
+}
+
+/// GameSettings where the flags that change the loot can be unknown. The luck related settings
+/// have to be known, they change the luck ranges.
+#[derive(Clone, Debug)]
+pub struct PartialSettings {
+    /// everything except the flags below
+    pub settings: GameSettings,
+    pub legacy_rng: Flag,
+    pub has_caldera: Flag,
+    pub post_1_6_4: Flag,
+    /// the save's flag, it opens both coconut slots
+    pub cracked_golden_coconut: Flag,
+}
+
+/// The values assumed for the unknown flags, None for the ones that are known
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SettingsAssumption {
+    pub legacy_rng: Option<bool>,
+    pub has_caldera: Option<bool>,
+    pub post_1_6_4: Option<bool>,
+    pub cracked_golden_coconut: Option<bool>,
+}
+
+impl Display for SettingsAssumption {
+    /// e.g. "legacy rng, coconut not cracked"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts = [
+            self.legacy_rng
+                .map(|x| if x { "legacy rng" } else { "hashed rng" }),
+            self.has_caldera
+                .map(|x| if x { "caldera" } else { "no caldera" }),
+            self.post_1_6_4
+                .map(|x| if x { "1.6.4+" } else { "before 1.6.4" }),
+            self.cracked_golden_coconut.map(|x| {
+                if x {
+                    "coconut cracked"
+                } else {
+                    "coconut not cracked"
+                }
+            }),
+        ];
+        let parts: Vec<&str> = parts.into_iter().flatten().collect();
+        if parts.is_empty() {
+            return write!(f, "as given");
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl PartialSettings {
+    /// all flags known, taken from `settings`
+    pub fn new(settings: GameSettings) -> Self {
+        PartialSettings {
+            legacy_rng: settings.legacy_rng.into(),
+            has_caldera: settings.has_caldera.into(),
+            post_1_6_4: settings.post_1_6_4.into(),
+            cracked_golden_coconut: (settings.golden_coconut_in_common()
+                || settings.golden_coconuts_in_rare())
+            .into(),
+            settings,
+        }
+    }
+
+    /// every combination of the unknown flags, at most 16
+    pub fn assumptions(&self) -> Vec<(SettingsAssumption, GameSettings)> {
+        let unknown = |flag: Flag, x: bool| (flag == Flag::Unknown).then_some(x);
+        let mut out = vec![];
+        for &legacy_rng in self.legacy_rng.values() {
+            for &has_caldera in self.has_caldera.values() {
+                for &post_1_6_4 in self.post_1_6_4.values() {
+                    for &cracked_golden_coconut in self.cracked_golden_coconut.values() {
+                        let assumption = SettingsAssumption {
+                            legacy_rng: unknown(self.legacy_rng, legacy_rng),
+                            has_caldera: unknown(self.has_caldera, has_caldera),
+                            post_1_6_4: unknown(self.post_1_6_4, post_1_6_4),
+                            cracked_golden_coconut: unknown(
+                                self.cracked_golden_coconut,
+                                cracked_golden_coconut,
+                            ),
+                        };
+                        let settings = GameSettings {
+                            legacy_rng,
+                            has_caldera,
+                            post_1_6_4,
+                            ..self.settings
+                        }
+                        .with_cracked_golden_coconut(cracked_golden_coconut);
+                        out.push((assumption, settings));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn days(&self) -> Vec<(SettingsAssumption, DayForecast)> {
+        self.assumptions()
+            .into_iter()
+            .map(|(a, settings)| (a, DayForecast::generate(&settings)))
+            .collect()
+    }
+
+    /// Forecast for settings.days_played. Layouts can't be Ambiguous like the loot, so a floor
+    /// only has layouts if all of the assumptions give the same ones there, the others are left
+    /// empty. `layouts` has all of them.
+    pub fn forecast(&self) -> DayForecast {
+        let days = self.days();
+        let loot = std::array::from_fn(|level| {
+            let floors: Vec<(SettingsAssumption, &ProbabilityRange<Vec<Goodie>>)> =
+                days.iter().map(|(a, day)| (*a, &day.loot[level])).collect();
+            merge_floor(&floors)
+        });
+        let layouts = std::array::from_fn(|level| {
+            let first = &days[0].1.layouts[level];
+            if days.iter().all(|(_, day)| day.layouts[level] == *first) {
+                first.clone()
+            } else {
+                vec![]
+            }
+        });
+        DayForecast {
+            days_played: self.settings.days_played,
+            settings: self.settings.clone(),
+            layouts,
+            loot,
+            calendar_events: calendar::events_on(DayNumber(self.settings.days_played)),
+        }
+    }
+
+    /// The different layouts of settings.days_played, each with the assumptions that give them.
+    /// Just one if the unknown flags don't change the layouts.
+    pub fn layouts(&self) -> Vec<(Vec<SettingsAssumption>, [ProbabilityRange<u32>; 10])> {
+        let mut out: Vec<(Vec<SettingsAssumption>, [ProbabilityRange<u32>; 10])> = vec![];
+        for (assumption, day) in self.days() {
+            match out.iter_mut().find(|(_, layouts)| *layouts == day.layouts) {
+                Some((assumptions, _)) => assumptions.push(assumption),
+                None => out.push((vec![assumption], day.layouts)),
+            }
+        }
+        out
+    }
+
+    /// Forecasts for `days` consecutive days, starting at `start_day`, fails like forecast::range
+    pub fn range(&self, start_day: impl Into<DayNumber>, days: u32) -> Result<Vec<DayForecast>> {
+        let days = start_day.into().checked_span(days, MAX_DAY_RANGE)?;
+        Ok(days
+            .map(|days_played| {
+                PartialSettings {
+                    settings: GameSettings {
+                        days_played,
+                        ..self.settings
+                    },
+                    ..*self
+                }
+                .forecast()
+            })
+            .collect())
+    }
+}
+
+/// the luck ranges of all floors are split at each other's boundaries, those all cover the same
+/// range since the luck settings are known
+fn merge_floor(
+    floors: &[(SettingsAssumption, &ProbabilityRange<Vec<Goodie>>)],
+) -> ProbabilityRange<Vec<Goodie>> {
+    let mut starts: Vec<f64> = floors
+        .iter()
+        .flat_map(|(_, floor)| floor.iter().map(|x| x.0))
+        .collect();
+    starts.sort_by(f64::total_cmp);
+    starts.dedup();
+    let mut out: ProbabilityRange<Vec<Goodie>> = vec![];
+    for (i, &start) in starts.iter().enumerate() {
+        let alternatives: Vec<(SettingsAssumption, &[Goodie])> = floors
+            .iter()
+            .filter_map(|(a, floor)| {
+                let (_, _, loot) = floor.iter().find(|x| x.0 <= start && start <= x.1)?;
+                Some((*a, &loot[..]))
+            })
+            .collect();
+        let Some(end) = starts.get(i + 1).map(|x| x.next_down()).or_else(|| {
+            floors
+                .iter()
+                .filter_map(|f| f.1.last())
+                .map(|x| x.1)
+                .reduce(f64::max)
+        }) else {
+            continue;
+        };
+        let loot = merge_loot(&alternatives);
+        if let Some(last) = out.last_mut()
+            && last.2 == loot
+        {
+            last.1 = end;
+        } else {
+            out.push((start, end, loot));
+        }
+    }
+    out
+}
+
+/// Pairs up the chests of the alternatives by their order on the floor. Assumptions that don't
+/// have anything at some position are left out of the Ambiguous there.
+fn merge_loot(alternatives: &[(SettingsAssumption, &[Goodie])]) -> Vec<Goodie> {
+    let Some((_, first)) = alternatives.first() else {
+        return vec![];
+    };
+    if alternatives.iter().all(|(_, loot)| loot == first) {
+        return first.to_vec();
+    }
+    let teeth: Vec<usize> = alternatives
+        .iter()
+        .map(|(_, loot)| loot.iter().filter(|g| **g == Goodie::DragonTooth).count())
+        .collect();
+    let chests: Vec<Vec<&Goodie>> = alternatives
+        .iter()
+        .map(|(_, loot)| loot.iter().filter(|g| **g != Goodie::DragonTooth).collect())
+        .collect();
+
+    let min_teeth = teeth.iter().copied().min().unwrap_or(0);
+    let max_teeth = teeth.iter().copied().max().unwrap_or(0);
+    let mut out = vec![Goodie::DragonTooth; min_teeth];
+    for i in min_teeth..max_teeth {
+        let alts = alternatives
+            .iter()
+            .zip(&teeth)
+            .filter(|(_, n)| **n > i)
+            .map(|((a, _), _)| (*a, Goodie::DragonTooth))
+            .collect();
+        out.push(Goodie::Ambiguous(alts));
+    }
+
+    let max_chests = chests.iter().map(|c| c.len()).max().unwrap_or(0);
+    for i in 0..max_chests {
+        let alts: Vec<(SettingsAssumption, Goodie)> = alternatives
+            .iter()
+            .zip(&chests)
+            .filter_map(|((a, _), c)| Some((*a, (*c.get(i)?).clone())))
+            .collect();
+        if alts.len() == alternatives.len() && alts.iter().all(|(_, g)| *g == alts[0].1) {
+            out.push(alts[0].1.clone());
+        } else {
+            out.push(Goodie::Ambiguous(alts));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_ambiguous(floor: &ProbabilityRange<Vec<Goodie>>) -> bool {
+        floor
+            .iter()
+            .any(|(_, _, loot)| loot.iter().any(|g| matches!(g, Goodie::Ambiguous(_))))
+    }
+
+    #[test]
+    fn ambiguous_only_where_it_matters() {
+        let mut ambiguous_floors = 0;
+        for days_played in 1..60 {
+            let settings = GameSettings {
+                seed: 555,
+                days_played,
+                ..Default::default()
+            };
+            let partial = PartialSettings {
+                cracked_golden_coconut: Flag::Unknown,
+                ..PartialSettings::new(settings.clone())
+            };
+            let forecast = partial.forecast();
+            let known = [false, true].map(|cracked_golden_coconut| {
+                DayForecast::generate(
+                    &settings
+                        .clone()
+                        .with_cracked_golden_coconut(cracked_golden_coconut),
+                )
+            });
+            // the coconut doesn't change the layouts
+            assert_eq!(partial.layouts().len(), 1);
+            assert_eq!(forecast.layouts, known[0].layouts);
+            for level in 0..10 {
+                if known[0].loot[level] == known[1].loot[level] {
+                    assert_eq!(forecast.loot[level], known[0].loot[level]);
+                } else {
+                    assert!(has_ambiguous(&forecast.loot[level]));
+                    ambiguous_floors += 1;
+                }
+            }
+        }
+        assert!(ambiguous_floors > 0);
+    }
+
+    #[test]
+    fn layouts_of_unknown_rng() {
+        let settings = GameSettings {
+            seed: 555,
+            days_played: 12,
+            ..Default::default()
+        };
+        let partial = PartialSettings {
+            legacy_rng: Flag::Unknown,
+            ..PartialSettings::new(settings.clone())
+        };
+        let layouts = partial.layouts();
+        assert_eq!(layouts.len(), 2);
+        for (assumptions, layouts) in &layouts {
+            let [assumption] = assumptions[..] else {
+                panic!("{:?}", assumptions);
+            };
+            let known = DayForecast::generate(&GameSettings {
+                legacy_rng: assumption.legacy_rng.unwrap(),
+                ..settings
+            });
+            assert_eq!(*layouts, known.layouts);
+        }
+        // floors where the two disagree have no layouts in the forecast
+        let forecast = partial.forecast();
+        for level in 0..10 {
+            let (a, b) = (&layouts[0].1[level], &layouts[1].1[level]);
+            if a == b {
+                assert_eq!(&forecast.layouts[level], a);
+            } else {
+                assert!(forecast.layouts[level].is_empty());
+            }
+        }
+        assert!(forecast.layouts.iter().any(|floor| floor.is_empty()));
+    }
+}
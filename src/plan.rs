@@ -0,0 +1,581 @@
+//! Forecasts in two steps. A plan has the floor layouts and chest seeds, which only depend on the
+//! seed, the day and the version flags. Materializing it generates the chests, so trying another
+//! luck range or the coconut flag only needs that second step.
+
+use crate::calendar;
+use crate::date::{DayNumber, MAX_DAY_RANGE};
+use crate::forecast::DayForecast;
+use crate::loot::{ChestItemKind, FloorLevel, Goodie};
+use crate::luck::sources::{self, LuckSource};
+use crate::luck::{LuckModel, VanillaLuck, luck_multiplier, luck_range};
+use crate::search::SEARCH_CAP_DAYS;
+use crate::stats::{DayScoreModel, Exclusivity, ValueModel};
+use crate::{Error, GameSettings, ProbabilityRange, Result, materialize_dungeon, plan_dungeon};
+
+/// What a set piece puts on a floor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlannedGoodie {
+    DragonTooth,
+    /// with the seed of the chest
+    Chest(i32),
+}
+
+impl PlannedGoodie {
+    /// None for chests on floors that never have chests
+    pub fn generate_at(
+        &self,
+        settings: &GameSettings,
+        level: FloorLevel,
+        min_luck: f64,
+        max_luck: f64,
+    ) -> Option<Goodie> {
+        match *self {
+            PlannedGoodie::DragonTooth => Some(Goodie::DragonTooth),
+            PlannedGoodie::Chest(seed) => {
+                Goodie::generate_at(seed, settings, level, min_luck, max_luck).ok()
+            }
+        }
+    }
+
+    #[deprecated(note = "use PlannedGoodie::generate_at, it takes a FloorLevel")]
+    pub fn generate(
+        &self,
+        settings: &GameSettings,
+        level: i32,
+        min_luck: f64,
+        max_luck: f64,
+    ) -> Option<Goodie> {
+        self.generate_at(settings, level.try_into().ok()?, min_luck, max_luck)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloorPlan {
+    pub layout: u32,
+    /// in the order the set pieces place them
+    pub goodies: Vec<PlannedGoodie>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DayPlan {
+    pub days_played: u32,
+    /// one entry per luck range with different layouts
+    pub floors: ProbabilityRange<[FloorPlan; 10]>,
+}
+
+/// Layouts and chest seeds of a range of days
+#[derive(Clone, Debug)]
+pub struct SeedPlan {
+    /// what the plan was made with. materializing can only narrow its luck range.
+    pub settings: GameSettings,
+    pub days: Vec<DayPlan>,
+}
+
+impl SeedPlan {
+    /// Plan for `days` consecutive days from `start_day`, over the whole luck range of `settings`.
+    /// Fails like forecast::range for too many days or day 0.
+    pub fn new(
+        settings: &GameSettings,
+        start_day: impl Into<DayNumber>,
+        days: u32,
+    ) -> Result<Self> {
+        Self::for_luck(settings, start_day, days, &VanillaLuck::new(settings))
+    }
+
+    /// Like new, but each day covers the luck `model` gives for it
+    pub fn for_luck(
+        settings: &GameSettings,
+        start_day: impl Into<DayNumber>,
+        days: u32,
+        model: &dyn LuckModel,
+    ) -> Result<Self> {
+        let days = start_day
+            .into()
+            .checked_span(days, MAX_DAY_RANGE)?
+            .map(|days_played| {
+                let luck = match model.resolve(DayNumber(days_played)) {
+                    Some(luck) => (luck, luck),
+                    None => model.boost_range(DayNumber(days_played)),
+                };
+                debug_assert!(
+                    luck.0.is_finite() && luck.1.is_finite() && luck.0 <= luck.1,
+                    "LuckModel gave {:?} for day {}",
+                    luck,
+                    days_played
+                );
+                DayPlan {
+                    days_played,
+                    floors: plan_dungeon(
+                        &GameSettings {
+                            days_played,
+                            ..*settings
+                        },
+                        luck,
+                        &|_, _, seed| seed,
+                    ),
+                }
+            })
+            .collect();
+        Ok(SeedPlan {
+            settings: settings.clone(),
+            days,
+        })
+    }
+
+    /// (min, max) luckMult the plan covers, over all of its days
+    pub fn luck_range(&self) -> (f64, f64) {
+        let ends = self
+            .days
+            .iter()
+            .filter_map(|day| Some((day.floors.first()?.0, day.floors.last()?.1)));
+        ends.reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+            .unwrap_or_else(|| luck_range(&self.settings))
+    }
+
+    /// Forecasts for the planned days, for luckMult within `luck` (cut to the plan's range). Only
+    /// the loot flags of `settings` are used, like cracked_golden_coconut: the seed and the flags
+    /// that change the layouts are the plan's.
+    pub fn materialize(&self, settings: &GameSettings, luck: (f64, f64)) -> Vec<DayForecast> {
+        self.days
+            .iter()
+            .map(|day| {
+                let settings = GameSettings {
+                    seed: self.settings.seed,
+                    legacy_rng: self.settings.legacy_rng,
+                    has_caldera: self.settings.has_caldera,
+                    post_1_6_4: self.settings.post_1_6_4,
+                    days_played: day.days_played,
+                    ..*settings
+                };
+                let (layouts, loot) = materialize_dungeon(&day.floors, &settings, luck);
+                DayForecast {
+                    days_played: day.days_played,
+                    settings,
+                    layouts,
+                    loot,
+                    calendar_events: calendar::events_on(DayNumber(day.days_played)),
+                }
+            })
+            .collect()
+    }
+}
+
+/// What a trip plan has to stick to
+#[derive(Clone, Debug)]
+pub struct TripConstraints {
+    /// at most this many trips start in any 7 days in a row
+    pub max_trips_per_week: u32,
+    /// longest stay, in days. 1 for single day trips.
+    pub max_days_per_trip: u32,
+    /// days_played that have to be in some trip. If they'd go over the weekly limit, the ones
+    /// with the lowest scoring trips are dropped.
+    pub required_days: Vec<u32>,
+    pub model: DayScoreModel,
+}
+
+impl Default for TripConstraints {
+    fn default() -> Self {
+        TripConstraints {
+            max_trips_per_week: 2,
+            max_days_per_trip: 1,
+            required_days: vec![],
+            model: DayScoreModel::default(),
+        }
+    }
+}
+
+/// Consecutive days of the forecast to go on
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trip {
+    /// days_played, in order
+    pub days: Vec<u32>,
+    /// sum of the days' DayForecast::score
+    pub score: f64,
+    /// sum of the days' ValueModel::expected_value
+    pub expected_value: f64,
+    /// chest exclusive items any luck can get on these days, in ChestItemKind order
+    pub exclusive_items: Vec<ChestItemKind>,
+    /// luckMult to have for getting the most out of every day of the trip, None if any luck
+    /// gets the same
+    pub luck_prep: Option<f64>,
+}
+
+/// lowest luckMult with the highest expected value of the day, None if that's the lowest luck
+fn luck_prep(day: &DayForecast, values: &ValueModel) -> Option<f64> {
+    let mut cuts: Vec<f64> = day
+        .loot
+        .iter()
+        .flatten()
+        .flat_map(|(minl, maxl, _)| [*minl, *maxl])
+        .collect();
+    cuts.sort_by(f64::total_cmp);
+    cuts.dedup();
+    // the middle of each piece, the ends can belong to the neighbors
+    let pieces: Vec<(f64, f64)> = cuts
+        .windows(2)
+        .map(|w| {
+            (
+                w[0],
+                values.expected_value(&day.at_luck((w[0] + w[1]) / 2.)),
+            )
+        })
+        .collect();
+    let best = pieces.iter().map(|p| p.1).max_by(f64::total_cmp)?;
+    let first = pieces.iter().position(|p| p.1 >= best - 1e-9)?;
+    (first > 0).then(|| pieces[first].0)
+}
+
+/// Picks trips from `forecast` greedily: first the best trip around each required day (the best
+/// of those first), then the best remaining ones, as long as they don't overlap a picked trip or
+/// go over the weekly limit (counted by first day). Ties go to the earlier, then the shorter trip,
+/// so the same forecast always gives the same plan. The trips come sorted by day.
+pub fn suggest_trips(forecast: &[DayForecast], constraints: &TripConstraints) -> Vec<Trip> {
+    let mut days: Vec<&DayForecast> = forecast.iter().collect();
+    days.sort_by_key(|d| d.days_played);
+    days.dedup_by_key(|d| d.days_played);
+    let scores: Vec<f64> = days.iter().map(|d| d.score(&constraints.model)).collect();
+
+    // (score, first index, length) of every run of consecutive days that's short enough
+    let mut candidates = vec![];
+    for start in 0..days.len() {
+        let mut score = 0.;
+        for len in 1..=constraints.max_days_per_trip as usize {
+            let end = start + len - 1;
+            if end >= days.len()
+                || days[end].days_played != days[start].days_played + len as u32 - 1
+            {
+                break;
+            }
+            score += scores[end];
+            candidates.push((score, start, len));
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let mut picked: Vec<(usize, usize)> = vec![];
+    let overlaps = |picked: &[(usize, usize)], start: usize, len: usize| {
+        picked
+            .iter()
+            .any(|&(s, l)| start < s + l && s < start + len)
+    };
+    let fits_week = |picked: &[(usize, usize)], start: usize| {
+        let day = days[start].days_played;
+        // every 7 day window with `day` in it
+        (day.saturating_sub(6)..=day).all(|from| {
+            let starts = picked
+                .iter()
+                .filter(|&&(s, _)| (from..from + 7).contains(&days[s].days_played))
+                .count();
+            starts < constraints.max_trips_per_week as usize
+        })
+    };
+    let mut required: Vec<usize> = constraints
+        .required_days
+        .iter()
+        .filter_map(|&day| days.iter().position(|d| d.days_played == day))
+        .collect();
+    let best_score = |i: usize| {
+        candidates
+            .iter()
+            .find(|&&(_, s, l)| s <= i && i < s + l)
+            .map_or(0., |c| c.0)
+    };
+    required.sort_by(|&a, &b| best_score(b).total_cmp(&best_score(a)).then(a.cmp(&b)));
+    for i in required {
+        if overlaps(&picked, i, 1) {
+            continue;
+        }
+        if let Some(&(_, s, l)) = candidates.iter().find(|&&(_, s, l)| {
+            s <= i && i < s + l && !overlaps(&picked, s, l) && fits_week(&picked, s)
+        }) {
+            picked.push((s, l));
+        }
+    }
+    for &(_, start, len) in &candidates {
+        if !overlaps(&picked, start, len) && fits_week(&picked, start) {
+            picked.push((start, len));
+        }
+    }
+    picked.sort();
+
+    picked
+        .into_iter()
+        .map(|(start, len)| {
+            let members = &days[start..start + len];
+            let mut exclusive_items: Vec<ChestItemKind> = members
+                .iter()
+                .flat_map(|d| d.loot.iter().flatten())
+                .flat_map(|(_, _, loot)| loot)
+                .flat_map(|g| match g {
+                    Goodie::CommonChest(c) | Goodie::RareChest(c) => vec![c.kind()],
+                    Goodie::ChanceChest { common, rare, .. } => vec![common.kind(), rare.kind()],
+                    _ => vec![],
+                })
+                .filter(|k| k.exclusivity() == Exclusivity::ChestExclusive)
+                .collect();
+            exclusive_items.sort();
+            exclusive_items.dedup();
+            Trip {
+                days: members.iter().map(|d| d.days_played).collect(),
+                score: scores[start..start + len].iter().sum(),
+                expected_value: members
+                    .iter()
+                    .map(|d| constraints.model.values.expected_value(d))
+                    .sum(),
+                exclusive_items,
+                luck_prep: members
+                    .iter()
+                    .filter_map(|d| luck_prep(d, &constraints.model.values))
+                    .max_by(f64::total_cmp),
+            }
+        })
+        .collect()
+}
+
+/// whether `target` is on the day for every luckMult in [lo, hi]
+fn always_there(day: &DayForecast, target: ChestItemKind, lo: f64, hi: f64) -> bool {
+    let mut ranges: Vec<(f64, f64)> = day
+        .loot
+        .iter()
+        .flatten()
+        .filter(|(_, _, loot)| {
+            loot.iter().any(|g| match g {
+                Goodie::CommonChest(c) | Goodie::RareChest(c) => c.kind() == target,
+                _ => false,
+            })
+        })
+        .map(|(minl, maxl, _)| (*minl, *maxl))
+        .collect();
+    ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut next = lo;
+    for (minl, maxl) in ranges {
+        if minl > next {
+            break;
+        }
+        if maxl >= next {
+            next = maxl.next_up();
+        }
+        if next > hi {
+            return true;
+        }
+    }
+    false
+}
+
+/// most sources guaranteed_by takes, it tries every combination of them
+pub const MAX_PREP_SOURCES: usize = 12;
+
+/// The first day from `from_day` on where `target` is certain with some of the luck sources in
+/// `max_prep`, whatever the daily luck: guaranteed at any luck, or from a chest that the prep
+/// makes rare often enough. Also gives the least prep that does it (the active sources, empty if
+/// none is needed). A special charm of the settings is always there, the one in `max_prep` means
+/// buying it. None within SEARCH_CAP_DAYS, TooManyLuckSources for more than MAX_PREP_SOURCES.
+pub fn guaranteed_by(
+    target: ChestItemKind,
+    from_day: impl Into<DayNumber>,
+    settings: &GameSettings,
+    max_prep: &[LuckSource],
+) -> Result<Option<(DayNumber, Vec<LuckSource>)>> {
+    if max_prep.len() > MAX_PREP_SOURCES {
+        return Err(Error::TooManyLuckSources {
+            count: max_prep.len(),
+            max: MAX_PREP_SOURCES,
+        });
+    }
+    let owned: &[LuckSource] = if settings.special_charm {
+        &[sources::SPECIAL_CHARM]
+    } else {
+        &[]
+    };
+    let boost =
+        |prep: &[LuckSource]| sources::stack(&[owned, prep].concat()) - sources::stack(owned);
+    // every prep that gives a different luck, the least first
+    let mut preps: Vec<(f64, Vec<LuckSource>)> = (0..1u32 << max_prep.len())
+        .map(|mask| {
+            let picked: Vec<LuckSource> = (0..max_prep.len())
+                .filter(|i| mask & 1 << i != 0)
+                .map(|i| max_prep[i])
+                .collect();
+            let active = sources::active(&picked);
+            (boost(&active), active)
+        })
+        .collect();
+    preps.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.len().cmp(&b.1.len())));
+    preps.dedup_by(|a, b| a.0 == b.0);
+    let max_boost = preps.last().map_or(0., |p| p.0);
+    let settings = GameSettings {
+        max_luck_lvl: settings
+            .max_luck_lvl
+            .max((max_boost / sources::LUCK_PER_LEVEL).ceil() as u32),
+        ..*settings
+    };
+    let (lo, hi) = (
+        luck_multiplier(&settings, -0.1, 0),
+        luck_multiplier(&settings, 0.1, 0),
+    );
+    // the same as hi + max_boost, but that can round differently
+    let top = luck_range(&settings).1;
+    Ok(from_day
+        .into()
+        .checked_span(SEARCH_CAP_DAYS, MAX_DAY_RANGE)?
+        .find_map(|days_played| {
+            let day = DayForecast::generate(&GameSettings {
+                days_played,
+                ..settings
+            });
+            preps
+                .iter()
+                .find(|(boost, _)| always_there(&day, target, lo + boost, (hi + boost).min(top)))
+                .map(|(_, prep)| (DayNumber(days_played), prep.clone()))
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed(seed: i32) -> GameSettings {
+        GameSettings {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn luck_only_changes_chance_chests() {
+        let settings = seed(3);
+        let plan = SeedPlan::new(&settings, 1, 56).unwrap();
+        let (lo, hi) = plan.luck_range();
+        let unlucky = plan.materialize(&settings, (lo, lo));
+        let lucky = plan.materialize(&settings, (hi, hi));
+        // the same plan gives the same forecasts again
+        let again = plan.materialize(&settings, (lo, lo));
+        assert_eq!(format!("{:?}", unlucky), format!("{:?}", again));
+        let mut resolved = 0;
+        for ((day, unlucky), lucky) in plan.days.iter().zip(&unlucky).zip(&lucky) {
+            // on days where the luck changes the layouts, there's more than the chests to differ
+            let [(_, _, floors)] = day.floors.as_slice() else {
+                continue;
+            };
+            let layouts = |f: &DayForecast| {
+                f.layouts
+                    .clone()
+                    .map(|l| l.into_iter().map(|(_, _, l)| l).collect::<Vec<_>>())
+            };
+            assert_eq!(layouts(unlucky), layouts(lucky));
+            for (i, floor) in floors.iter().enumerate() {
+                let [(_, _, unlucky)] = unlucky.loot[i].as_slice() else {
+                    panic!("one luck, one loot");
+                };
+                let [(_, _, lucky)] = lucky.loot[i].as_slice() else {
+                    panic!("one luck, one loot");
+                };
+                let both: Vec<Goodie> = floor
+                    .goodies
+                    .iter()
+                    .filter_map(|g| g.generate_at(&settings, FloorLevel::ALL[i], lo, hi))
+                    .collect();
+                assert_eq!(both.len(), unlucky.len());
+                assert_eq!(both.len(), lucky.len());
+                for ((both, unlucky), lucky) in both.iter().zip(unlucky).zip(lucky) {
+                    match both {
+                        Goodie::ChanceChest { common, rare, .. } => {
+                            assert_eq!(unlucky, &Goodie::CommonChest(common.clone()));
+                            assert_eq!(lucky, &Goodie::RareChest(rare.clone()));
+                            resolved += 1;
+                        }
+                        _ => {
+                            assert_eq!(unlucky, both);
+                            assert_eq!(lucky, both);
+                        }
+                    }
+                }
+            }
+        }
+        assert!(resolved > 0);
+    }
+
+    #[test]
+    fn guaranteed_with_prep() {
+        let target = ChestItemKind::DragonscaleBoots;
+        let (day, prep) = guaranteed_by(target, 1, &seed(2), &sources::ALL)
+            .unwrap()
+            .unwrap();
+        assert_eq!((day, prep), (DayNumber(2), vec![sources::MAGIC_ROCK_CANDY]));
+        // only a chance chest, so it's later without the candy
+        let (unprepared, prep) = guaranteed_by(target, 1, &seed(2), &[]).unwrap().unwrap();
+        assert!(unprepared > day);
+        assert!(prep.is_empty());
+    }
+
+    #[test]
+    fn guaranteed_without_prep() {
+        let target = ChestItemKind::MermaidBoots;
+        let without = guaranteed_by(target, 1, &seed(1), &[]).unwrap().unwrap();
+        assert_eq!(without, (DayNumber(7), vec![]));
+        // nothing to prepare if it's there at any luck anyways
+        let with = guaranteed_by(target, 1, &seed(1), &sources::ALL).unwrap();
+        assert_eq!(with, Some(without));
+    }
+
+    #[test]
+    fn guaranteed_by_bounds_the_sources() {
+        let many = [sources::LUCKY_RING; 40];
+        for n in [MAX_PREP_SOURCES + 1, 32, 40] {
+            assert!(matches!(
+                guaranteed_by(ChestItemKind::MermaidBoots, 1, &seed(1), &many[..n]),
+                Err(Error::TooManyLuckSources { count, max: MAX_PREP_SOURCES }) if count == n
+            ));
+        }
+        assert!(
+            guaranteed_by(
+                ChestItemKind::MermaidBoots,
+                1,
+                &seed(1),
+                &many[..MAX_PREP_SOURCES]
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn trips_stay_under_the_weekly_limit() {
+        let forecast = crate::forecast::range(&seed(3), DayNumber(1), 28).unwrap();
+        let model = DayScoreModel::default();
+        let score = |day: u32| forecast[day as usize - 1].score(&model);
+        let constraints = TripConstraints {
+            max_trips_per_week: 2,
+            max_days_per_trip: 2,
+            required_days: vec![1, 3, 5, 20],
+            model: model.clone(),
+        };
+        let trips = suggest_trips(&forecast, &constraints);
+        for from in 1..=22 {
+            let starts = trips
+                .iter()
+                .filter(|t| (from..from + 7).contains(&t.days[0]))
+                .count();
+            assert!(
+                starts <= 2,
+                "{} trips in the week from day {}",
+                starts,
+                from
+            );
+        }
+        for trip in &trips {
+            let sum: f64 = trip.days.iter().map(|&d| score(d)).sum();
+            assert!((trip.score - sum).abs() < 1e-9);
+        }
+        // 1, 3 and 5 can't all be in trips of their own, the one with the worst trips goes
+        let has = |day: u32| trips.iter().any(|t| t.days.contains(&day));
+        assert!(has(20));
+        let dropped: Vec<u32> = [1, 3, 5].into_iter().filter(|&d| !has(d)).collect();
+        assert_eq!(dropped, [1]);
+        let kept_worst = trips
+            .iter()
+            .filter(|t| t.days.contains(&3) || t.days.contains(&5))
+            .map(|t| t.score)
+            .fold(f64::INFINITY, f64::min);
+        assert!(score(1).max(score(1) + score(2)) <= kept_worst);
+    }
+}
@@ -0,0 +1,1105 @@
+use crate::analysis::{self, CollisionGroup};
+use crate::date::{DayNumber, GameDate};
+use crate::filter::GoodieFilter;
+use crate::forecast::{ChestId, DayForecast};
+use crate::limit::LimitedWriter;
+use crate::loot::{ChestItem, ChestItemKind, Decisions, Goodie, QuantityModifiers, TooltipStyle};
+use crate::stats::{DayScoreModel, EnchantWeights, Exclusivity, TierThresholds};
+use crate::{Result, display_luck};
+use std::fmt::Write;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Html,
+    Plain,
+    Markdown,
+    /// plain text with terminal colors
+    Ansi,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    /// the enchantments, what the items do and the hints, the item names stay English
+    De,
+}
+
+/// How icons are inserted into html output. Other formats don't show icons.
+pub trait IconRenderer {
+    fn icon(&self, out: &mut dyn Write, name: &str) -> std::fmt::Result;
+}
+
+/// `<img>` tags pointing to the icons/ directory next to the page
+pub struct ImgIcons;
+
+impl IconRenderer for ImgIcons {
+    fn icon(&self, out: &mut dyn Write, name: &str) -> std::fmt::Result {
+        write!(out, "<img src=\"icons/{}.png\" class=icon>", name)
+    }
+}
+
+/// How numbers are written. Every float in the rendered output goes through this, so the formats
+/// all show them the same way.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub decimal_sep: char,
+    pub percent_decimals: usize,
+    /// for luck and the rolls it's compared against
+    pub luck_decimals: usize,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat {
+            decimal_sep: '.',
+            percent_decimals: 1,
+            luck_decimals: 4,
+        }
+    }
+}
+
+impl NumberFormat {
+    pub fn number(&self, x: f64, decimals: usize) -> String {
+        let out = format!("{:.*}", decimals, x);
+        if self.decimal_sep == '.' {
+            return out;
+        }
+        out.replace('.', &self.decimal_sep.to_string())
+    }
+
+    pub fn luck(&self, x: f64) -> String {
+        self.number(x, self.luck_decimals)
+    }
+
+    /// `p` between 0 and 1
+    pub fn percent(&self, p: f64) -> String {
+        format!("{}%", self.number(p * 100., self.percent_decimals))
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct RenderOptions<'a> {
+    pub format: OutputFormat,
+    pub locale: Locale,
+    pub icons: &'a dyn IconRenderer,
+    /// only show these goodies
+    pub filter: GoodieFilter,
+    /// whether days without any (remaining) goodies still get a header
+    pub keep_empty_days: bool,
+    /// give html days an id like `day-17` (days_played), e.g. for export::value_sparkline
+    pub day_anchors: bool,
+    /// show the rolls behind every chest, as a tooltip in html and footnotes otherwise
+    pub explain: bool,
+    /// link item and enchantment names to the wiki, html only
+    pub link_items: bool,
+    /// mark items that only volcano chests give, and ostrich eggs without the incubator to hatch
+    /// them, html and markdown only
+    pub exclusive_badge: bool,
+    /// whether ostrich eggs can be hatched, forecasts use the one of their settings instead
+    pub ostrich_incubator: bool,
+    /// the day's festivals next to its name, html and markdown only
+    pub calendar_badges: bool,
+    /// where enchantment levels go, Prefix like Display by default
+    pub tooltip_style: TooltipStyle,
+    /// what rings, boots and the hat do, as subtext in html and markdown
+    pub show_effects: bool,
+    /// say "(no innate enchant)" after weapons without any, instead of nothing
+    pub label_unenchanted: bool,
+    /// for the counts of stacks, forecasts use the ones of their settings instead
+    pub quantities: QuantityModifiers,
+    /// with quantity bonuses, also show the count without them, like "Cinder Shard (6, base 3)"
+    pub show_base_quantity: bool,
+    /// give html weapons a class for their EnchantTier, like `tier-god-roll`
+    pub enchant_tiers: Option<(&'a EnchantWeights, TierThresholds)>,
+    pub number_format: NumberFormat,
+    /// show the day's score next to its name, as stars
+    pub day_score: Option<&'a DayScoreModel>,
+    /// from analysis::find_collisions, annotates the chests in them
+    pub collisions: Option<&'a [CollisionGroup]>,
+    /// end forecasts with a note on the loot they don't list, from analysis::known_unmodeled
+    pub unmodeled_footnote: bool,
+    /// fail instead of rendering more than this, see `Render::try_render_to_string`
+    pub max_output_bytes: Option<usize>,
+}
+
+impl Default for RenderOptions<'_> {
+    fn default() -> Self {
+        RenderOptions {
+            format: OutputFormat::Html,
+            locale: Locale::En,
+            icons: &ImgIcons,
+            filter: GoodieFilter::ALL,
+            keep_empty_days: true,
+            day_anchors: false,
+            explain: false,
+            link_items: false,
+            exclusive_badge: false,
+            ostrich_incubator: false,
+            calendar_badges: false,
+            tooltip_style: TooltipStyle::default(),
+            show_effects: false,
+            label_unenchanted: false,
+            quantities: QuantityModifiers::default(),
+            show_base_quantity: false,
+            enchant_tiers: None,
+            number_format: NumberFormat::default(),
+            day_score: None,
+            collisions: None,
+            unmodeled_footnote: false,
+            max_output_bytes: None,
+        }
+    }
+}
+
+impl RenderOptions<'_> {
+    pub fn with_format(format: OutputFormat) -> Self {
+        RenderOptions {
+            format,
+            ..Default::default()
+        }
+    }
+}
+
+pub trait Render {
+    fn render(&self, out: &mut dyn Write, opts: &RenderOptions) -> std::fmt::Result;
+
+    /// Panics if the output goes over opts.max_output_bytes. Without a limit it can't fail, but
+    /// try_render_to_string says so in the type.
+    #[deprecated(note = "use try_render_to_string, this panics when the output is too large")]
+    fn render_to_string(&self, opts: &RenderOptions) -> String {
+        self.try_render_to_string(opts).unwrap()
+    }
+
+    /// OutputTooLarge if the output goes over opts.max_output_bytes, without the partial output
+    fn try_render_to_string(&self, opts: &RenderOptions) -> Result<String> {
+        let Some(limit) = opts.max_output_bytes else {
+            let mut out = String::new();
+            self.render(&mut out, opts).unwrap();
+            return Ok(out);
+        };
+        let mut out = LimitedWriter::new(String::new(), limit);
+        let rendered = self.render(&mut out, opts);
+        out.check()?;
+        rendered.unwrap();
+        Ok(out.into_inner())
+    }
+}
+
+/// " (6)" after the name, or " (6, base 3)", nothing for single items
+fn quantity_text(c: &ChestItem, opts: &RenderOptions) -> String {
+    let (base, count) = (c.kind().quantity(), c.quantity(&opts.quantities));
+    match count {
+        1 => String::new(),
+        _ if opts.show_base_quantity && count != base => format!(" ({}, base {})", count, base),
+        _ => format!(" ({})", count),
+    }
+}
+
+/// like ChestItem::to_styled_string, with the count from the options
+fn styled_name(c: &ChestItem, opts: &RenderOptions) -> String {
+    let mut out = c.kind().name().to_string() + &quantity_text(c, opts);
+    if let Some(enchants) = c.enchants() {
+        enchants
+            .write_localized(&mut out, opts.tooltip_style, opts.locale)
+            .unwrap();
+    }
+    out
+}
+
+/// like the Display impl, with the names as wiki links
+fn linked_item(out: &mut dyn Write, c: &ChestItem, opts: &RenderOptions) -> std::fmt::Result {
+    let style = opts.tooltip_style;
+    write!(out, "<a href=\"{}\">{}</a>", c.wiki_url(), c.kind().name())?;
+    write!(out, "{}", quantity_text(c, opts))?;
+    let Some(enchants) = c.enchants().filter(|e| !e.as_slice().is_empty()) else {
+        return Ok(());
+    };
+    write!(out, " (")?;
+    for (i, &(e, lvl)) in enchants.as_slice().iter().enumerate() {
+        if i != 0 {
+            write!(out, ", ")?;
+        }
+        let link = format!(
+            "<a href=\"{}\">{}</a>",
+            e.wiki_url(),
+            e.localized_name(opts.locale)
+        );
+        style.write_enchant(out, &link, lvl)?;
+    }
+    write!(out, ")")
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_BOLD: &str = "\x1b[1m";
+const ANSI_RARE: &str = "\x1b[33m";
+const ANSI_TOOTH: &str = "\x1b[36m";
+
+impl Render for Goodie {
+    fn render(&self, out: &mut dyn Write, opts: &RenderOptions) -> std::fmt::Result {
+        let exclusive = |c: &ChestItem| {
+            opts.exclusive_badge && c.kind().exclusivity() == Exclusivity::ChestExclusive
+        };
+        let needs_incubator = |c: &ChestItem| {
+            opts.exclusive_badge && !opts.ostrich_incubator && c.kind() == ChestItemKind::OstrichEgg
+        };
+        let effect = |c: &ChestItem| {
+            if opts.show_effects {
+                c.effect_text(opts.locale)
+            } else {
+                None
+            }
+        };
+        let chest = |out: &mut dyn Write, rarity: &str, c: &ChestItem| {
+            let unenchanted = if opts.label_unenchanted && c.is_unenchanted_weapon() {
+                " (no innate enchant)"
+            } else {
+                ""
+            };
+            let name = styled_name(c, opts) + unenchanted;
+            match opts.format {
+                OutputFormat::Html => {
+                    opts.icons.icon(out, &format!("{}_chest", rarity))?;
+                    write!(out, " ")?;
+                    let tier = opts
+                        .enchant_tiers
+                        .and_then(|(weights, thresholds)| c.enchant_tier(weights, &thresholds));
+                    if let Some(tier) = tier {
+                        write!(out, "<span class={}>", tier.css_class())?;
+                    }
+                    opts.icons.icon(out, c.get_icon())?;
+                    write!(out, " ")?;
+                    if opts.link_items {
+                        linked_item(out, c, opts)?;
+                        write!(out, "{}", unenchanted)?;
+                    } else {
+                        write!(out, "{}", name)?;
+                    }
+                    if tier.is_some() {
+                        write!(out, "</span>")?;
+                    }
+                    if let Some(text) = effect(c) {
+                        write!(out, " <small class=effect>{}</small>", text)?;
+                    }
+                    if exclusive(c) {
+                        write!(out, " <span class=exclusive>chest exclusive</span>")?;
+                    }
+                    if needs_incubator(c) {
+                        write!(out, " <span class=incubator>needs an incubator</span>")?;
+                    }
+                    Ok(())
+                }
+                OutputFormat::Plain => write!(out, "[{}] {}", rarity, name),
+                OutputFormat::Markdown => {
+                    write!(out, "*{}:* {}", rarity, name)?;
+                    if let Some(text) = effect(c) {
+                        write!(out, " — _{}_", text)?;
+                    }
+                    if exclusive(c) {
+                        write!(out, " `chest exclusive`")?;
+                    }
+                    if needs_incubator(c) {
+                        write!(out, " `needs an incubator`")?;
+                    }
+                    Ok(())
+                }
+                OutputFormat::Ansi if rarity == "rare" => {
+                    write!(out, "{}[{}] {}{}", ANSI_RARE, rarity, name, ANSI_RESET)
+                }
+                OutputFormat::Ansi => write!(out, "[{}] {}", rarity, name),
+            }
+        };
+        match self {
+            Goodie::DragonTooth => match opts.format {
+                OutputFormat::Html => {
+                    opts.icons.icon(out, "dragon_tooth")?;
+                    write!(out, " Dragon Tooth")
+                }
+                OutputFormat::Ansi => write!(out, "{}Dragon Tooth{}", ANSI_TOOTH, ANSI_RESET),
+                OutputFormat::Plain | OutputFormat::Markdown => write!(out, "Dragon Tooth"),
+            },
+            Goodie::CommonChest(c) => chest(out, "common", c),
+            Goodie::RareChest(c) => chest(out, "rare", c),
+            Goodie::ChanceChest {
+                minluck,
+                common,
+                rare,
+            } => {
+                let luck = opts.number_format.luck(display_luck(*minluck));
+                write!(out, "luck > {}: ", luck)?;
+                chest(out, "rare", rare)?;
+                write!(out, ", else ")?;
+                chest(out, "common", common)
+            }
+            Goodie::Ambiguous(alts) => {
+                write!(out, "depends on settings: ")?;
+                for (i, (a, g)) in alts.iter().enumerate() {
+                    if i != 0 {
+                        write!(out, "; ")?;
+                    }
+                    write!(out, "{}: ", a)?;
+                    g.render(out, opts)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// list markup for the loot overview, per format
+struct FloorMarkup {
+    floor_start: &'static str,
+    floor_end: &'static str,
+    luck_start: &'static str,
+    luck_end: &'static str,
+    range_start: &'static str,
+    range_end: &'static str,
+    item_start: &'static str,
+    item_end: &'static str,
+}
+
+impl FloorMarkup {
+    fn get(format: OutputFormat, multiple_ranges: bool) -> Self {
+        match format {
+            OutputFormat::Html => FloorMarkup {
+                floor_start: "<div><b>",
+                floor_end: "</ul></div>\n",
+                luck_start: "<li>",
+                luck_end: "</li>\n",
+                range_start: "<ul>\n",
+                range_end: "</ul>\n",
+                item_start: "<li>",
+                item_end: "</li>\n",
+            },
+            OutputFormat::Plain | OutputFormat::Ansi => FloorMarkup {
+                floor_start: if format == OutputFormat::Ansi {
+                    ANSI_BOLD
+                } else {
+                    ""
+                },
+                floor_end: "",
+                luck_start: "  ",
+                luck_end: "\n",
+                range_start: "",
+                range_end: "",
+                item_start: if multiple_ranges { "    " } else { "  " },
+                item_end: "\n",
+            },
+            OutputFormat::Markdown => FloorMarkup {
+                floor_start: "**",
+                floor_end: "\n",
+                luck_start: "- ",
+                luck_end: "\n",
+                range_start: "",
+                range_end: "",
+                item_start: if multiple_ranges { "  - " } else { "- " },
+                item_end: "\n",
+            },
+        }
+    }
+
+    fn floor_title_end(format: OutputFormat) -> &'static str {
+        match format {
+            OutputFormat::Html => "</b><ul>\n",
+            OutputFormat::Plain => "\n",
+            OutputFormat::Ansi => "\x1b[0m\n",
+            OutputFormat::Markdown => "**\n\n",
+        }
+    }
+}
+
+/// e.g. "rarity roll 0.7312, needs < 0.1000 for rare. slot 6, enchant roll 0.4102, no extra
+/// enchant"
+fn explanation(
+    d: &Decisions,
+    goodie: &Goodie,
+    min_luck: f64,
+    max_luck: f64,
+    nf: &NumberFormat,
+) -> String {
+    let (luck, table) = match goodie {
+        Goodie::RareChest(_) => (min_luck, d.rare),
+        _ => (max_luck, d.common),
+    };
+    let mut out = format!(
+        "rarity roll {}, needs < {} for rare. slot {}",
+        nf.luck(d.rarity_roll),
+        nf.luck(d.rare_threshold + luck - 1.),
+        table.slot
+    );
+    if table.rerolls > 0 {
+        let s = if table.rerolls == 1 { "" } else { "s" };
+        write!(out, " after {} reroll{}", table.rerolls, s).unwrap();
+    }
+    if let Some(roll) = table.enchant_roll {
+        write!(out, ", enchant roll {}", nf.luck(roll)).unwrap();
+    }
+    if let Some(gates) = table.enchant_gates.filter(|g| g.passed()) {
+        match gates.extra {
+            Some(e) => write!(out, ", {} gate passed", e.name()).unwrap(),
+            None => write!(out, ", no extra enchant").unwrap(),
+        }
+    }
+    out
+}
+
+/// (goodie, explanation, collision note)
+type FloorItems<'a> = Vec<(&'a Goodie, Option<String>, Option<String>)>;
+
+impl Render for DayForecast {
+    /// loot overview of all floors, skipping floors that never have anything
+    fn render(&self, out: &mut dyn Write, opts: &RenderOptions) -> std::fmt::Result {
+        let opts = &RenderOptions {
+            quantities: self.settings.quantities,
+            ostrich_incubator: self.settings.has_ostrich_incubator,
+            ..*opts
+        };
+        // (number, text), numbered through the whole day
+        let mut footnotes: Vec<(usize, String)> = vec![];
+        let mut num_footnotes = 0;
+        for (i, floor_loot) in self.loot.iter().enumerate() {
+            let floor_loot: Vec<(f64, f64, FloorItems)> = floor_loot
+                .iter()
+                .map(|(minl, maxl, loot)| {
+                    let decisions = if opts.explain {
+                        self.chest_decisions(i, *minl)
+                    } else {
+                        vec![]
+                    };
+                    let loot = loot
+                        .iter()
+                        .filter(|g| **g != Goodie::DragonTooth)
+                        .zip(0..)
+                        .map(|(g, index)| {
+                            let d = decisions
+                                .get(index as usize)
+                                .filter(|_| !matches!(g, Goodie::Ambiguous(_)));
+                            let text =
+                                d.map(|d| explanation(d, g, *minl, *maxl, &opts.number_format));
+                            let id = ChestId {
+                                days_played: self.days_played,
+                                level: i as u8,
+                                index,
+                            };
+                            let note = opts
+                                .collisions
+                                .and_then(|groups| analysis::collision_for(groups, id, *minl))
+                                .and_then(|group| group.note_for(id));
+                            (g, text, note)
+                        })
+                        .chain(
+                            loot.iter()
+                                .filter(|g| **g == Goodie::DragonTooth)
+                                .map(|g| (g, None, None)),
+                        )
+                        .filter(|(g, _, _)| opts.filter.matches(g, *minl, *maxl))
+                        .collect();
+                    (*minl, *maxl, loot)
+                })
+                .collect();
+            if floor_loot.iter().all(|y| y.2.is_empty()) {
+                continue;
+            }
+            let m = FloorMarkup::get(opts.format, floor_loot.len() > 1);
+
+            write!(
+                out,
+                "{}floor {}:{}",
+                m.floor_start,
+                i,
+                FloorMarkup::floor_title_end(opts.format)
+            )?;
+            for (minl, maxl, loot) in &floor_loot {
+                if floor_loot.len() > 1 {
+                    write!(
+                        out,
+                        "{}luck {} to {}:{}",
+                        m.luck_start,
+                        opts.number_format.luck(display_luck(*minl)),
+                        opts.number_format.luck(display_luck(*maxl)),
+                        m.luck_end
+                    )?;
+                }
+                write!(out, "{}", m.range_start)?;
+                if loot.is_empty() {
+                    write!(out, "{}[nothing]{}", m.item_start, m.item_end)?;
+                }
+                let num_dragon_teeth = loot
+                    .iter()
+                    .filter(|x| matches!(x.0, Goodie::DragonTooth))
+                    .count();
+                if num_dragon_teeth > 0 {
+                    write!(out, "{}", m.item_start)?;
+                    Goodie::DragonTooth.render(out, opts)?;
+                    if num_dragon_teeth > 1 {
+                        write!(out, " ({})", num_dragon_teeth)?;
+                    }
+                    write!(out, "{}", m.item_end)?;
+                }
+                for (l, explanation, note) in loot {
+                    if **l == Goodie::DragonTooth {
+                        continue;
+                    }
+                    match (opts.format, explanation) {
+                        (OutputFormat::Html, Some(text)) => {
+                            write!(out, "<li title=\"{}\">", text.replace('<', "&lt;"))?
+                        }
+                        _ => write!(out, "{}", m.item_start)?,
+                    }
+                    l.render(out, opts)?;
+                    match (opts.format, note) {
+                        (OutputFormat::Html, Some(note)) => write!(out, " <i>({})</i>", note)?,
+                        (_, Some(note)) => write!(out, " ({})", note)?,
+                        _ => {}
+                    }
+                    if let Some(text) = explanation
+                        && opts.format != OutputFormat::Html
+                    {
+                        num_footnotes += 1;
+                        footnotes.push((num_footnotes, text.clone()));
+                        match opts.format {
+                            OutputFormat::Markdown => {
+                                write!(out, " [^{}-{}]", self.days_played, num_footnotes)?
+                            }
+                            _ => write!(out, " [{}]", num_footnotes)?,
+                        }
+                    }
+                    write!(out, "{}", m.item_end)?;
+                }
+                write!(out, "{}", m.range_end)?;
+            }
+            if opts.format == OutputFormat::Markdown && !footnotes.is_empty() {
+                writeln!(out)?;
+            }
+            for (n, text) in footnotes.drain(..) {
+                match opts.format {
+                    OutputFormat::Markdown => {
+                        writeln!(out, "[^{}-{}]: {}", self.days_played, n, text)?
+                    }
+                    _ => writeln!(out, "  [{}] {}", n, text)?,
+                }
+            }
+            write!(out, "{}", m.floor_end)?;
+        }
+        Ok(())
+    }
+}
+
+impl Render for [DayForecast] {
+    /// one section per day
+    fn render(&self, out: &mut dyn Write, opts: &RenderOptions) -> std::fmt::Result {
+        for day in self {
+            if !opts.keep_empty_days && day.iter_filtered(opts.filter).next().is_none() {
+                continue;
+            }
+            let mut name = GameDate::from(DayNumber(day.days_played)).to_string();
+            if let Some(model) = opts.day_score {
+                name = format!("{} {}", name, model.stars(day.score(model)));
+            }
+            if opts.calendar_badges {
+                for event in &day.calendar_events {
+                    match opts.format {
+                        OutputFormat::Html => {
+                            let class = if event.time_restricted {
+                                "festival restricted"
+                            } else {
+                                "festival"
+                            };
+                            write!(name, " <small class=\"{}\">{}</small>", class, event.name)?;
+                        }
+                        OutputFormat::Markdown => write!(name, " `{}`", event.name)?,
+                        OutputFormat::Plain | OutputFormat::Ansi => {}
+                    }
+                }
+            }
+            match opts.format {
+                OutputFormat::Html if opts.day_anchors => writeln!(
+                    out,
+                    "<section class=day id=day-{}><h3>{}</h3>",
+                    day.days_played, name
+                )?,
+                OutputFormat::Html => writeln!(out, "<section class=day><h3>{}</h3>", name)?,
+                OutputFormat::Plain => writeln!(out, "== {} ==", name)?,
+                OutputFormat::Ansi => writeln!(out, "{}== {} =={}", ANSI_BOLD, name, ANSI_RESET)?,
+                OutputFormat::Markdown => write!(out, "### {}\n\n", name)?,
+            }
+            day.render(out, opts)?;
+            match opts.format {
+                OutputFormat::Html => writeln!(out, "</section>")?,
+                _ => writeln!(out)?,
+            }
+        }
+        if opts.unmodeled_footnote {
+            render_unmodeled(out, opts.format)?;
+        }
+        Ok(())
+    }
+}
+
+/// the footnote for RenderOptions::unmodeled_footnote
+fn render_unmodeled(out: &mut dyn Write, format: OutputFormat) -> std::fmt::Result {
+    let intro = "Not listed";
+    match format {
+        OutputFormat::Html => {
+            write!(out, "<p class=unmodeled>{}:", intro)?;
+            for (i, s) in analysis::known_unmodeled().iter().enumerate() {
+                let sep = if i == 0 { " " } else { ", " };
+                write!(out, "{}<span title=\"{}\">{}</span>", sep, s.note, s.name)?;
+            }
+            writeln!(out, "</p>")
+        }
+        OutputFormat::Markdown => {
+            writeln!(out, "_{}:_\n", intro)?;
+            for s in analysis::known_unmodeled() {
+                writeln!(out, "- {} ({})", s.name, s.note)?;
+            }
+            Ok(())
+        }
+        OutputFormat::Plain | OutputFormat::Ansi => {
+            writeln!(out, "{}:", intro)?;
+            for s in analysis::known_unmodeled() {
+                writeln!(out, "  - {} ({})", s.name, s.note)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loot::{Enchant, Enchants};
+    use crate::stats::LuckCounting;
+    use crate::{Error, GameSettings};
+
+    /// rendered by the string building code before the Render trait (html), or by the first
+    /// version of it (the others)
+    const SNAPSHOTS: [(OutputFormat, &str); 4] = [
+        (
+            OutputFormat::Html,
+            include_str!("../fixtures/render/forecast.html"),
+        ),
+        (
+            OutputFormat::Plain,
+            include_str!("../fixtures/render/forecast.txt"),
+        ),
+        (
+            OutputFormat::Markdown,
+            include_str!("../fixtures/render/forecast.md"),
+        ),
+        (
+            OutputFormat::Ansi,
+            include_str!("../fixtures/render/forecast.ansi"),
+        ),
+    ];
+
+    #[test]
+    fn same_as_the_snapshots() {
+        let settings = GameSettings {
+            seed: 12,
+            ..Default::default()
+        };
+        let forecast = crate::forecast::range(&settings, 1, 28).unwrap();
+        for (format, expected) in SNAPSHOTS {
+            let opts = RenderOptions::with_format(format);
+            let mut streamed = String::new();
+            forecast.render(&mut streamed, &opts).unwrap();
+            for (i, (got, want)) in streamed.lines().zip(expected.lines()).enumerate() {
+                assert_eq!(got, want, "{:?} line {}", format, i + 1);
+            }
+            assert_eq!(streamed, expected, "{:?}", format);
+            assert_eq!(forecast.try_render_to_string(&opts).unwrap(), expected);
+        }
+        // the html wrappers
+        let html = SNAPSHOTS[0].1;
+        assert_eq!(crate::html::render_forecast(&forecast), html);
+        let days: String = forecast
+            .iter()
+            .map(|day| {
+                format!(
+                    "<section class=day><h3>{}</h3>\n{}</section>\n",
+                    crate::html::day_name(day.days_played),
+                    crate::html::render_loot(day)
+                )
+            })
+            .collect();
+        assert_eq!(days, html);
+    }
+
+    /// a forecast with `loot` at every luck
+    fn handmade_day(days_played: u32, loot: [Vec<Goodie>; 10]) -> DayForecast {
+        let settings = GameSettings {
+            days_played,
+            ..Default::default()
+        };
+        let (lo, hi) = crate::luck::luck_range(&settings);
+        DayForecast {
+            days_played,
+            settings,
+            layouts: std::array::from_fn(|_| vec![(lo, hi, 0)]),
+            loot: loot.map(|goodies| vec![(lo, hi, goodies)]),
+            calendar_events: vec![],
+        }
+    }
+
+    /// a day with a bit of everything, the chance chest is rare for 3/4 of the luck range
+    fn mixed_day() -> DayForecast {
+        let (lo, hi) = crate::luck::luck_range(&GameSettings::default());
+        let mut loot: [Vec<Goodie>; 10] = Default::default();
+        loot[2] = vec![
+            Goodie::DragonTooth,
+            Goodie::CommonChest(ChestItem::TaroTuber),
+            Goodie::RareChest(ChestItem::PhoenixRing),
+        ];
+        loot[5] = vec![Goodie::ChanceChest {
+            minluck: lo + (hi - lo) / 4.,
+            common: ChestItem::PineappleSeeds,
+            rare: ChestItem::MermaidBoots,
+        }];
+        loot[9] = vec![Goodie::RareChest(ChestItem::DeluxePirateHat)];
+        handmade_day(5, loot)
+    }
+
+    #[test]
+    fn rare_only() {
+        let day = mixed_day();
+        let filtered = |filter: GoodieFilter| {
+            let opts = RenderOptions {
+                filter,
+                ..RenderOptions::with_format(OutputFormat::Plain)
+            };
+            day.try_render_to_string(&opts).unwrap()
+        };
+        assert_eq!(
+            filtered(GoodieFilter::RARE),
+            "floor 2:\n  [rare] Phoenix Ring\nfloor 9:\n  [rare] Deluxe Pirate Hat\n"
+        );
+        let chance = filtered(GoodieFilter::RARE.chance_as_rare_above(0.7));
+        assert!(chance.contains("Mermaid Boots") && chance.contains("Pineapple Seeds"));
+        assert!(!filtered(GoodieFilter::RARE.chance_as_rare_above(0.8)).contains("Mermaid"));
+        let teeth = filtered(GoodieFilter::TEETH | GoodieFilter::COMMON);
+        assert_eq!(
+            teeth,
+            "floor 2:\n  Dragon Tooth\n  [common] Taro Tuber (8)\n"
+        );
+        // the same goodies as iter_filtered
+        let kinds: Vec<usize> = day
+            .iter_filtered(GoodieFilter::RARE)
+            .map(|(level, _, _, _)| level)
+            .collect();
+        assert_eq!(kinds, [2, 9]);
+        assert_eq!(filtered(GoodieFilter::NONE), "");
+    }
+
+    #[test]
+    fn empty_days() {
+        let mut commons: [Vec<Goodie>; 10] = Default::default();
+        commons[4] = vec![Goodie::CommonChest(ChestItem::TaroTuber)];
+        let forecast = [mixed_day(), handmade_day(6, commons)];
+        let render = |keep_empty_days| {
+            let opts = RenderOptions {
+                filter: GoodieFilter::RARE,
+                keep_empty_days,
+                ..RenderOptions::with_format(OutputFormat::Plain)
+            };
+            forecast.try_render_to_string(&opts).unwrap()
+        };
+        let kept = render(true);
+        assert!(kept.contains("== spring 5, Y1 ==") && kept.contains("== spring 6, Y1 ==\n\n"));
+        assert!(!kept.contains("Taro Tuber"));
+        let omitted = render(false);
+        assert!(omitted.contains("== spring 5, Y1 =="));
+        assert!(!omitted.contains("spring 6"));
+    }
+
+    #[test]
+    fn explained_chests() {
+        let day = DayForecast::generate(&GameSettings {
+            seed: 12,
+            days_played: 2,
+            ..Default::default()
+        });
+        let render = |format, explain| {
+            let opts = RenderOptions {
+                explain,
+                ..RenderOptions::with_format(format)
+            };
+            day.try_render_to_string(&opts).unwrap()
+        };
+        let chests = day
+            .records(&crate::forecast::DayState::default())
+            .iter()
+            .filter(|r| r.chest.is_some())
+            .count();
+        let html = render(OutputFormat::Html, true);
+        assert_eq!(html.matches("<li title=\"rarity roll ").count(), chests);
+        assert!(!render(OutputFormat::Html, false).contains("rarity roll"));
+        let plain = render(OutputFormat::Plain, true);
+        assert!(plain.contains(" [1]") && plain.contains("  [1] rarity roll "));
+        assert_eq!(plain.matches("needs < ").count(), chests);
+        assert!(!render(OutputFormat::Plain, false).contains("rarity roll"));
+    }
+
+    #[test]
+    fn output_limit() {
+        let day = DayForecast::generate(&GameSettings {
+            seed: 12,
+            days_played: 5,
+            ..Default::default()
+        });
+        let opts = RenderOptions::with_format(OutputFormat::Plain);
+        let full = day.try_render_to_string(&opts).unwrap();
+        #[allow(deprecated)]
+        let old = day.render_to_string(&opts);
+        assert_eq!(old, full);
+
+        let fits = RenderOptions {
+            max_output_bytes: Some(full.len()),
+            ..RenderOptions::with_format(OutputFormat::Plain)
+        };
+        assert_eq!(day.try_render_to_string(&fits).unwrap(), full);
+        let limited = RenderOptions {
+            max_output_bytes: Some(full.len() - 1),
+            ..RenderOptions::with_format(OutputFormat::Plain)
+        };
+        assert!(matches!(
+            day.try_render_to_string(&limited),
+            Err(Error::OutputTooLarge { limit, .. }) if limit == full.len() - 1
+        ));
+    }
+
+    fn localized(locale: Locale) -> RenderOptions<'static> {
+        RenderOptions {
+            locale,
+            tooltip_style: TooltipStyle::GameTooltip,
+            show_effects: true,
+            ..RenderOptions::with_format(OutputFormat::Plain)
+        }
+    }
+
+    #[test]
+    fn game_tooltips() {
+        let cutlass = ChestItem::DragontoothCutlass(Enchants::from_levels(vec![
+            (Enchant::Weight, -3),
+            (Enchant::CritPower, 50),
+        ]));
+        assert_eq!(
+            styled_name(&cutlass, &localized(Locale::En)),
+            "Dragontooth Cutlass (Weight -3, Crit. Power +50)"
+        );
+        assert_eq!(
+            styled_name(&cutlass, &localized(Locale::De)),
+            "Dragontooth Cutlass (Gewicht -3, Krit. Stärke +50)"
+        );
+        // Display stays as it was
+        assert_eq!(
+            cutlass.to_string(),
+            "Dragontooth Cutlass (-3 Weight, +50 Crit. Power)"
+        );
+    }
+
+    #[test]
+    fn every_enchant_in_both_locales() {
+        let expected = [
+            (Enchant::Defense, 2, "Defense +2", "Verteidigung +2"),
+            (Enchant::Weight, -1, "Weight -1", "Gewicht -1"),
+            (
+                Enchant::SlimeGatherer,
+                0,
+                "Slime Gatherer",
+                "Schleimsammler",
+            ),
+            (Enchant::SlimeSlayer, 0, "Slime Slayer", "Schleimtöter"),
+            (
+                Enchant::CritPower,
+                25,
+                "Crit. Power +25",
+                "Krit. Stärke +25",
+            ),
+            (Enchant::CritChance, 3, "Crit. Chance +3", "Krit. Chance +3"),
+            (Enchant::Attack, 5, "Attack +5", "Angriff +5"),
+            (Enchant::Speed, 4, "Speed +4", "Geschwindigkeit +4"),
+        ];
+        assert_eq!(expected.len(), Enchant::ALL.len());
+        for (enchant, lvl, en, de) in expected {
+            let sword = ChestItem::DwarfSword(Enchants::from_levels(vec![(enchant, lvl)]));
+            for (locale, text) in [(Locale::En, en), (Locale::De, de)] {
+                let goodie = Goodie::CommonChest(sword.clone());
+                let out = goodie.try_render_to_string(&localized(locale)).unwrap();
+                assert!(
+                    out.contains(&format!("Dwarf Sword ({})", text)),
+                    "{:?}: {}",
+                    locale,
+                    out
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn effects_in_both_locales() {
+        let effect = |item: ChestItem, locale| item.effect_text(locale).unwrap();
+        assert_eq!(
+            effect(ChestItem::MermaidBoots, Locale::En),
+            "+1 Defense, +8 Immunity"
+        );
+        assert_eq!(
+            effect(ChestItem::MermaidBoots, Locale::De),
+            "+1 Verteidigung, +8 Immunität"
+        );
+        assert_eq!(
+            effect(ChestItem::DeluxePirateHat, Locale::De),
+            "Nur kosmetisch"
+        );
+        // every item with an effect has it in both
+        for kind in ChestItemKind::ALL {
+            let item = ChestItem::with_enchants(kind, Enchants::default());
+            assert_eq!(
+                item.effect_text(Locale::En).is_some(),
+                item.effect_text(Locale::De).is_some(),
+                "{:?}",
+                kind
+            );
+        }
+    }
+
+    #[test]
+    fn linked_items() {
+        let cutlass = ChestItem::DragontoothCutlass(Enchants::from_levels(vec![
+            (Enchant::Attack, 3),
+            (Enchant::Speed, 2),
+        ]));
+        let mut loot: [Vec<Goodie>; 10] = Default::default();
+        loot[1] = vec![
+            Goodie::RareChest(cutlass),
+            Goodie::CommonChest(ChestItem::CinderShards3),
+        ];
+        let day = handmade_day(3, loot);
+        let render = |link_items| {
+            let opts = RenderOptions {
+                link_items,
+                ..RenderOptions::with_format(OutputFormat::Html)
+            };
+            day.try_render_to_string(&opts).unwrap()
+        };
+        let html = render(true);
+        assert!(html.contains(
+            "<a href=\"https://stardewvalleywiki.com/Dragontooth_Cutlass\">Dragontooth Cutlass</a>"
+        ));
+        assert!(html.contains("<a href=\"https://stardewvalleywiki.com/Cinder_Shard\">"));
+        let forge = "<a href=\"https://stardewvalleywiki.com/Forge#Innate_Enchantments\">";
+        assert_eq!(html.matches(forge).count(), 2);
+        assert!(!render(false).contains("<a href"));
+    }
+
+    #[test]
+    fn exclusive_badges() {
+        let day = mixed_day();
+        for format in [OutputFormat::Html, OutputFormat::Markdown] {
+            let render = |exclusive_badge| {
+                let opts = RenderOptions {
+                    exclusive_badge,
+                    ..RenderOptions::with_format(format)
+                };
+                day.try_render_to_string(&opts).unwrap()
+            };
+            let with = render(true);
+            // the hat and the boots of the chance chest, not the phoenix ring or the taro
+            assert_eq!(with.matches("chest exclusive").count(), 2, "{:?}", format);
+            let hat = with
+                .lines()
+                .find(|l| l.contains("Deluxe Pirate Hat"))
+                .unwrap();
+            assert!(hat.contains("chest exclusive"));
+            assert!(!render(false).contains("chest exclusive"), "{:?}", format);
+        }
+        // the boots only count for the luck that gets the rare chest
+        let fractional = crate::stats::exclusive_items(&day, LuckCounting::Fractional);
+        assert!((fractional - 1.75).abs() < 1e-9, "{}", fractional);
+    }
+
+    #[test]
+    fn number_formats() {
+        let chance = Goodie::ChanceChest {
+            // 0.0123456 as displayed luck
+            minluck: 1. + 0.0123456 / 2.,
+            common: ChestItem::TaroTuber,
+            rare: ChestItem::PhoenixRing,
+        };
+        let german = NumberFormat {
+            decimal_sep: ',',
+            percent_decimals: 0,
+            luck_decimals: 2,
+        };
+        let render = |number_format| {
+            let opts = RenderOptions {
+                number_format,
+                ..RenderOptions::with_format(OutputFormat::Plain)
+            };
+            chance.try_render_to_string(&opts).unwrap()
+        };
+        assert!(render(NumberFormat::default()).starts_with("luck > 0.0123: "));
+        assert!(render(german).starts_with("luck > 0,01: "));
+        assert_eq!(NumberFormat::default().percent(0.125), "12.5%");
+        assert_eq!(german.percent(0.125), "12%");
+        assert_eq!(german.number(-1.25, 1), "-1,2");
+        // the luck ranges of a day go through the same helper
+        let day = mixed_day();
+        let opts = RenderOptions {
+            number_format: german,
+            ..RenderOptions::with_format(OutputFormat::Html)
+        };
+        let html = day.try_render_to_string(&opts).unwrap();
+        assert!(html.contains("luck > "));
+        assert!(!html.contains("luck > 0.") && !html.contains("luck > -0."));
+    }
+
+    #[test]
+    fn effects_as_subtext() {
+        let day = mixed_day();
+        let render = |format, show_effects| {
+            let opts = RenderOptions {
+                show_effects,
+                ..RenderOptions::with_format(format)
+            };
+            day.try_render_to_string(&opts).unwrap()
+        };
+        let ring = ChestItem::PhoenixRing.effect_text(Locale::En).unwrap();
+        let html = render(OutputFormat::Html, true);
+        assert!(html.contains(&format!("<small class=effect>{}</small>", ring)));
+        let markdown = render(OutputFormat::Markdown, true);
+        assert!(markdown.contains(&format!(" — _{}_", ring)));
+        for format in [OutputFormat::Html, OutputFormat::Markdown] {
+            assert!(!render(format, false).contains(ring.as_ref()));
+        }
+        // the taro tuber has none
+        let taro = html.lines().find(|l| l.contains("Taro Tuber")).unwrap();
+        assert!(!taro.contains("effect"));
+    }
+
+    #[test]
+    fn unenchanted_label() {
+        let mut loot: [Vec<Goodie>; 10] = Default::default();
+        loot[4] = vec![
+            Goodie::RareChest(ChestItem::DwarfHammer(Enchants::default())),
+            Goodie::RareChest(ChestItem::DragontoothClub(Enchants::from_levels(vec![(
+                Enchant::Attack,
+                2,
+            )]))),
+        ];
+        let day = handmade_day(7, loot);
+        let render = |label_unenchanted| {
+            let opts = RenderOptions {
+                label_unenchanted,
+                ..RenderOptions::with_format(OutputFormat::Html)
+            };
+            day.try_render_to_string(&opts).unwrap()
+        };
+        let html = render(true);
+        assert_eq!(html.matches("(no innate enchant)").count(), 1);
+        let hammer = html.lines().find(|l| l.contains("Dwarf Hammer")).unwrap();
+        assert!(hammer.contains("(no innate enchant)"));
+        assert!(!render(false).contains("no innate enchant"));
+    }
+}
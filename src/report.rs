@@ -0,0 +1,198 @@
+//! Everything needed to reproduce a forecast someone got, as one token they can paste into a bug
+//! report.
+//!
+//! A token is base64 of: a version byte, the settings (the same bytes as in snapshots), the first
+//! day and number of days (u32 each, little endian), and optionally the observation that didn't
+//! match (presence byte, days_played u32, level u8, then the goodie as in snapshots). Newer
+//! versions may only add to the end, so a version 1 reader can tell a newer token apart from a
+//! broken one.
+
+use crate::cache::{Decoder, encode_goodie, encode_settings};
+use crate::date::DayNumber;
+use crate::detect::Observation;
+use crate::forecast::{self, DayForecast};
+use crate::partial::{Flag, PartialSettings, SettingsAssumption};
+use crate::{Error, GameSettings, Result, base64};
+
+// 2: the quantity multipliers in the settings
+const VERSION: u8 = 2;
+
+#[derive(Clone, Debug)]
+pub struct ReportInput {
+    /// with the game id as the seed
+    pub settings: GameSettings,
+    pub start_day: u32,
+    pub days: u32,
+    /// what the player found instead of the forecast
+    pub observation: Option<Observation>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReportOutput {
+    pub forecast: Vec<DayForecast>,
+    /// whether the reported settings can give the observation, None without one
+    pub observation_matches: Option<bool>,
+    /// If it doesn't match: the values of the loot changing flags that would give it. Empty
+    /// means the settings aren't the problem, e.g. a wrong day or game id.
+    pub hypotheses: Vec<SettingsAssumption>,
+}
+
+/// Fails only for an observation goodie the format can't hold, see TooLargeToEncode
+pub fn encode(input: &ReportInput) -> Result<String> {
+    let mut out = vec![VERSION];
+    encode_settings(&mut out, &input.settings);
+    out.extend_from_slice(&input.start_day.to_le_bytes());
+    out.extend_from_slice(&input.days.to_le_bytes());
+    if let Some(obs) = &input.observation {
+        out.push(1);
+        out.extend_from_slice(&obs.days_played.to_le_bytes());
+        out.push(obs.level as u8);
+        encode_goodie(&mut out, &obs.goodie)?;
+    }
+    Ok(base64::encode(&out))
+}
+
+pub fn decode(token: &str) -> Result<ReportInput> {
+    let invalid = |msg: &str| Error::InvalidReport(msg.to_string());
+    let data = base64::decode(token).ok_or_else(|| invalid("not base64"))?;
+    let (&version, rest) = data.split_first().ok_or_else(|| invalid("empty token"))?;
+    if version != VERSION {
+        return Err(Error::UnsupportedReportVersion(version));
+    }
+    let mut d = Decoder(rest);
+    let decoded = (|| {
+        let settings = d.settings()?;
+        let start_day = d.u32()?;
+        let days = d.u32()?;
+        let mut observation = None;
+        if !d.0.is_empty() {
+            if d.u8()? != 1 {
+                return Err(invalid("bad observation marker"));
+            }
+            observation = Some(Observation {
+                days_played: d.u32()?,
+                level: d.u8()? as i32,
+                goodie: d.goodie()?,
+            });
+        }
+        Ok(ReportInput {
+            settings,
+            start_day,
+            days,
+            observation,
+        })
+    })()
+    .map_err(|e| match e {
+        Error::CorruptSnapshot(msg) => Error::InvalidReport(msg),
+        e => e,
+    })?;
+    if !d.0.is_empty() {
+        return Err(invalid("trailing bytes"));
+    }
+    Ok(decoded)
+}
+
+/// Reruns the forecast of the report. Fails if it's for more than forecast::DEFAULT_MAX_DAYS.
+pub fn reproduce(input: &ReportInput) -> Result<ReportOutput> {
+    DayNumber(input.start_day).checked_span(input.days, forecast::DEFAULT_MAX_DAYS)?;
+    let forecast = forecast::range(&input.settings, input.start_day, input.days)?;
+    let observation_matches = input
+        .observation
+        .as_ref()
+        .map(|obs| obs.matches(&input.settings));
+    let hypotheses = match (&input.observation, observation_matches) {
+        (Some(obs), Some(false)) => {
+            let unknown = PartialSettings {
+                settings: input.settings.clone(),
+                legacy_rng: Flag::Unknown,
+                has_caldera: Flag::Unknown,
+                post_1_6_4: Flag::Unknown,
+                cracked_golden_coconut: Flag::Unknown,
+            };
+            unknown
+                .assumptions()
+                .into_iter()
+                .filter(|(_, settings)| obs.matches(settings))
+                .map(|(assumption, _)| assumption)
+                .collect()
+        }
+        _ => vec![],
+    };
+    Ok(ReportOutput {
+        forecast,
+        observation_matches,
+        hypotheses,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loot::{ChestItem, ChestItemKind, Enchants, Goodie};
+
+    fn input() -> ReportInput {
+        ReportInput {
+            settings: GameSettings {
+                seed: 987654,
+                post_1_6_4: true,
+                days_played: 40,
+                ..Default::default()
+            },
+            start_day: 38,
+            days: 5,
+            observation: Some(Observation {
+                days_played: 40,
+                level: 3,
+                goodie: Goodie::RareChest(ChestItem::with_enchants(
+                    ChestItemKind::DragontoothShiv,
+                    Enchants::from_compact_string("a2").unwrap(),
+                )),
+            }),
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let input = input();
+        for observation in [input.observation.clone(), None] {
+            let input = ReportInput {
+                observation,
+                ..input.clone()
+            };
+            let decoded = decode(&encode(&input).unwrap()).unwrap();
+            assert_eq!(
+                decoded.settings.behavior_hash(),
+                input.settings.behavior_hash()
+            );
+            assert_eq!(decoded.start_day, input.start_day);
+            assert_eq!(decoded.days, input.days);
+            assert_eq!(decoded.observation, input.observation);
+        }
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut data = base64::decode(&encode(&input()).unwrap()).unwrap();
+        data[0] ^= 1;
+        assert!(matches!(
+            decode(&base64::encode(&data)),
+            Err(Error::UnsupportedReportVersion(3))
+        ));
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let mut data = base64::decode(&encode(&input()).unwrap()).unwrap();
+        // replace the observed goodie with ambiguous ones in ambiguous ones
+        let goodie_start = data.len() - 5;
+        data.truncate(goodie_start);
+        for _ in 0..100_000 {
+            data.extend_from_slice(&[4, 1, 0, 0, 0, 0, 0]);
+        }
+        data.push(0);
+        assert!(matches!(
+            decode(&base64::encode(&data)),
+            Err(Error::InvalidReport(_))
+        ));
+    }
+}
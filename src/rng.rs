@@ -1,13 +1,19 @@
 use std::hash::Hasher;
 
+/// `1.0 / int.MaxValue` from Random.Sample(). C# folds this to the same double, and rust doesn't
+/// fuse or reorder float ops on its own, so next_f64 matches the game bit for bit.
+const SAMPLE_SCALE: f64 = 1.0 / i32::MAX as f64;
+
+#[derive(Clone)]
 pub struct DotnetRng {
     state: [i32; 56],
     inext: usize,
     inextp: usize,
+    calls: u32,
 }
 
 impl DotnetRng {
-    pub fn new(seed: i32) -> Self {
+    pub const fn new(seed: i32) -> Self {
         // Reference:
         // https://github.com/dotnet/runtime/blob/a45853c4751b5f532cdb38e3db5d4324b5ca878a/src/libraries/System.Private.CoreLib/src/System/Random.Net5CompatImpl.cs#L258
         let mut state = [0_i32; 56];
@@ -15,7 +21,9 @@ impl DotnetRng {
         state[55] = mj;
         let mut mk = 1_i32;
         let mut ii = 0;
-        for _i in 1..55 {
+        // while loops since this is const
+        let mut i = 1;
+        while i < 55 {
             ii = (ii + 21) % 55;
             state[ii] = mk;
             mk = mj - mk;
@@ -23,24 +31,32 @@ impl DotnetRng {
                 mk += i32::MAX;
             }
             mj = state[ii];
+            i += 1;
         }
-        for _k in 1..5 {
-            for i in 1..56 {
+        let mut k = 1;
+        while k < 5 {
+            let mut i = 1;
+            while i < 56 {
                 let n = (i + 30) % 55;
                 state[i] = state[i].wrapping_sub(state[1 + n]);
                 if state[i] < 0 {
                     state[i] += i32::MAX;
                 };
+                i += 1;
             }
+            k += 1;
         }
         DotnetRng {
             state,
             // this should be a 31 instead lmao
             inextp: 21,
             inext: 0,
+            calls: 0,
         }
     }
-    pub fn next(&mut self) -> i32 {
+    /// Random.Next(): in [0, i32::MAX), never i32::MAX itself
+    pub const fn next(&mut self) -> i32 {
+        self.calls += 1;
         self.inext = (self.inext % 55) + 1;
         self.inextp = (self.inextp % 55) + 1;
         let mut result = self.state[self.inext].wrapping_sub(self.state[self.inextp]);
@@ -56,14 +72,92 @@ impl DotnetRng {
         self.state[self.inext] = result;
         result
     }
+    /// number of values taken from this rng so far (every next_f64/next_range is one next call)
+    pub fn calls(&self) -> u32 {
+        self.calls
+    }
     pub fn next_f64(&mut self) -> f64 {
-        self.next() as f64 * (1.0 / i32::MAX as f64)
+        self.next() as f64 * SAMPLE_SCALE
+    }
+
+    /// `DotnetRng::new(seed).next_f64()`, for rolls that only need the first value. Faster, it
+    /// doesn't keep the rng around and skips what that value doesn't depend on.
+    pub const fn first_f64(seed: i32) -> f64 {
+        first_next(seed) as f64 * SAMPLE_SCALE
+    }
+    /// the same as `(next_f64() * max) as i32`, written out so it can be const
+    pub const fn next_range(&mut self, max: i32) -> i32 {
+        (self.next() as f64 * SAMPLE_SCALE * max as f64) as i32
+    }
+}
+
+/// `DotnetRng::new(seed).next()`. The seeding is the same, but the loops of the passes are
+/// split where `(i + 30) % 55` wraps, and the last pass only does the two entries the first value
+/// is made of (1 and 22, both read entries after them that the pass didn't get to yet).
+const fn first_next(seed: i32) -> i32 {
+    const fn sub(a: i32, b: i32) -> i32 {
+        let x = a.wrapping_sub(b);
+        if x < 0 { x + i32::MAX } else { x }
+    }
+    let mut state = [0_i32; 56];
+    let mut mj = 161803398 - seed.saturating_abs();
+    state[55] = mj;
+    let mut mk = 1_i32;
+    let mut ii = 0;
+    let mut i = 1;
+    while i < 55 {
+        ii = (ii + 21) % 55;
+        state[ii] = mk;
+        mk = mj - mk;
+        if mk < 0 {
+            mk += i32::MAX;
+        }
+        mj = state[ii];
+        i += 1;
+    }
+    let mut k = 1;
+    while k < 4 {
+        let mut i = 1;
+        while i < 25 {
+            state[i] = sub(state[i], state[i + 31]);
+            i += 1;
+        }
+        while i < 56 {
+            state[i] = sub(state[i], state[i - 24]);
+            i += 1;
+        }
+        k += 1;
+    }
+    let mut result = sub(state[1], state[32]).wrapping_sub(sub(state[22], state[53]));
+    if result == i32::MAX {
+        result -= 1;
     }
-    pub fn next_range(&mut self, max: i32) -> i32 {
-        (self.next_f64() * max as f64) as i32
+    if result < 0 {
+        result += i32::MAX;
     }
+    result
 }
 
+// first outputs of System.Random (the Net5Compat one the game uses), checked at compile time
+const _: () = {
+    const fn check(seed: i32, expected: [i32; 3], range_100: i32) {
+        let mut rng = DotnetRng::new(seed);
+        let mut i = 0;
+        while i < 3 {
+            assert!(rng.next() == expected[i]);
+            i += 1;
+        }
+        assert!(rng.next_range(100) == range_100);
+        assert!(first_next(seed) == expected[0]);
+    }
+    check(0, [1559595546, 1755192844, 1649316166], 55);
+    check(1, [534011718, 237820880, 1002897798], 77);
+    check(12345, [143337951, 150666398, 1663795458], 51);
+    check(-7, [822959691, 1871007331, 1419354884], 5);
+    // saturating_abs, not the overflowing one
+    check(i32::MIN, [1559595546, 1755192844, 1649316172], 55);
+};
+
 fn stardew_hashcode(data: &[u8]) -> i32 {
     let mut hasher = twox_hash::XxHash32::with_seed(0);
     hasher.write(data);
@@ -86,3 +180,82 @@ pub fn stardew_seed_mix(legacy_rng: bool, values: &[f64]) -> i32 {
         stardew_hashcode(&bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_f64_bits() {
+        // the outputs checked above, scaled like Sample() does
+        let vectors = [
+            (
+                0,
+                [0x3fe73d6286ae7ac5, 0x3fea278783344f0f, 0x3fe893a451b12749],
+            ),
+            (
+                1,
+                [0x3fcfd45f463fa8bf, 0x3fbc59b7a038b36f, 0x3fdde380c33bc702],
+            ),
+            (
+                12345,
+                [0x3fb11653be222ca7, 0x3fb1f5f93c23ebf2, 0x3fe8cae040b195c1],
+            ),
+            (
+                -7,
+                [0x3fd886af25b10d5e, 0x3febe15398f7c2a7, 0x3fe52668c12a4cd2],
+            ),
+        ];
+        for (seed, bits) in vectors {
+            let mut rng = DotnetRng::new(seed);
+            for b in bits {
+                assert_eq!(rng.next_f64().to_bits(), b, "seed {}", seed);
+            }
+            assert_eq!(DotnetRng::first_f64(seed).to_bits(), bits[0]);
+        }
+        // the ends of what next gives
+        let scale = |n: i32| (n as f64 * SAMPLE_SCALE).to_bits();
+        assert_eq!(scale(1), 0x3e00000000200000);
+        assert_eq!(scale(i32::MAX - 1), 0x3fefffffffc00000);
+    }
+
+    #[test]
+    fn next_is_never_max() {
+        let mut rng = DotnetRng::new(0);
+        // the entries next subtracts
+        rng.state[1] = i32::MAX;
+        rng.state[22] = 0;
+        assert_eq!(rng.next(), i32::MAX - 1);
+        assert!(rng.next_f64() < 1.);
+        let mut rng = DotnetRng::new(42);
+        assert!((0..100_000).all(|_| rng.next() < i32::MAX));
+    }
+
+    #[test]
+    fn const_and_runtime_agree() {
+        // evaluated by the compiler
+        const SEQUENCE: [i32; 4] = {
+            let mut rng = DotnetRng::new(987654);
+            [
+                rng.next(),
+                rng.next(),
+                rng.next_range(10),
+                rng.next_range(1000),
+            ]
+        };
+        let mut rng = DotnetRng::new(std::hint::black_box(987654));
+        let runtime = [
+            rng.next(),
+            rng.next(),
+            rng.next_range(10),
+            rng.next_range(1000),
+        ];
+        assert_eq!(runtime, SEQUENCE);
+        for seed in (-50_000..50_000).step_by(7) {
+            let mut rng = DotnetRng::new(seed);
+            assert_eq!(first_next(seed), rng.next(), "{}", seed);
+            let mut rng = DotnetRng::new(seed);
+            assert_eq!(DotnetRng::first_f64(seed), rng.next_f64(), "{}", seed);
+        }
+    }
+}
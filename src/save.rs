@@ -0,0 +1,203 @@
+//! Reading what a player already has from their save file (the one named like the save folder,
+//! without an extension). Only 1.6 saves, older ones store the items differently.
+//!
+//! The wallet only holds special items like the special charm, none of them come from the
+//! chests. The ostrich incubator isn't a chest item either, that's
+//! GameSettings::has_ostrich_incubator.
+
+use crate::loot::ChestItemKind;
+use crate::xml::{self, Element};
+use crate::{Error, Result};
+use std::collections::HashSet;
+
+/// the items a player already has
+pub type Owned = HashSet<ChestItemKind>;
+
+/// The kinds the scan looks for, the ones a player only needs one of. Weapons aren't in it: one
+/// with other enchantments is still worth having.
+pub const TRACKED: [ChestItemKind; 8] = [
+    ChestItemKind::ProtectionRing,
+    ChestItemKind::SoulSapperRing,
+    ChestItemKind::PhoenixRing,
+    ChestItemKind::HotJavaRing,
+    ChestItemKind::MermaidBoots,
+    ChestItemKind::DragonscaleBoots,
+    ChestItemKind::DeluxePirateHat,
+    ChestItemKind::OstrichEgg,
+];
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// also the inventories and equipment of the other players, not only the host's
+    pub farmhands: bool,
+}
+
+/// The TRACKED items in a save: in the inventory and the equipment slots (rings combined into
+/// one count too) of the host, and of the farmhands with `options.farmhands`, in chests, dressers
+/// and fridges anywhere (also inside buildings), and in the junimo chests. Items lost on death
+/// don't count until they're bought back.
+pub fn owned_items(save: &str, options: ScanOptions) -> Result<Owned> {
+    let root = xml::parse(save).map_err(Error::InvalidSave)?;
+    if root.name != "SaveGame" {
+        return Err(Error::InvalidSave(format!(
+            "root element is {}, not SaveGame",
+            root.name
+        )));
+    }
+    let mut owned = Owned::new();
+    let player = root
+        .child("player")
+        .ok_or_else(|| Error::InvalidSave("no player".to_string()))?;
+    scan_farmer(player, &mut owned);
+    if options.farmhands {
+        for farmhand in root.children_named("farmhands").flat_map(|f| &f.children) {
+            scan_farmer(farmhand, &mut owned);
+        }
+    }
+    for location in root.children_named("locations").flat_map(|l| &l.children) {
+        scan_location(location, &mut owned);
+    }
+    // the junimo chests in the world are empty, they share this one
+    let global = root.children_named("globalInventories");
+    for entry in global.flat_map(|g| g.children_named("item")) {
+        let id = entry.child("key").and_then(|k| k.child_text("string"));
+        if id == Some("JunimoChests") {
+            scan(entry, &mut owned);
+        }
+    }
+    Ok(owned)
+}
+
+fn scan_farmer(farmer: &Element, owned: &mut Owned) {
+    for slot in ["items", "hat", "boots", "leftRing", "rightRing"] {
+        for el in farmer.children_named(slot) {
+            scan(el, owned);
+        }
+    }
+}
+
+fn scan_location(location: &Element, owned: &mut Owned) {
+    // objects has the chests, furniture the dressers
+    for part in ["objects", "furniture", "fridge"] {
+        for el in location.children_named(part) {
+            scan(el, owned);
+        }
+    }
+    let buildings = location
+        .children_named("buildings")
+        .flat_map(|b| &b.children);
+    for indoors in buildings.flat_map(|b| b.children_named("indoors")) {
+        scan_location(indoors, owned);
+    }
+}
+
+/// `el` and everything in it, containers can be nested (a chest in a chest, rings in a combined
+/// ring)
+fn scan(el: &Element, owned: &mut Owned) {
+    if let Some(kind) = item_kind(el) {
+        owned.insert(kind);
+    }
+    for child in &el.children {
+        scan(child, owned);
+    }
+}
+
+/// the tracked kind of an item element, None for anything else
+fn item_kind(el: &Element) -> Option<ChestItemKind> {
+    let id = el.child_text("itemId")?;
+    // elements of a declared type have no xsi:type, the equipment slots
+    let class = el.attr("xsi:type").unwrap_or(match el.name.as_str() {
+        "hat" => "Hat",
+        "boots" => "Boots",
+        "leftRing" | "rightRing" | "Ring" => "Ring",
+        _ => "Object",
+    });
+    let prefix = match class {
+        "Ring" | "CombinedRing" => "(O)",
+        "Boots" => "(B)",
+        "Hat" => "(H)",
+        "Object" | "ColoredObject" if el.child_text("bigCraftable") == Some("true") => "(BC)",
+        "Object" | "ColoredObject" => "(O)",
+        _ => return None,
+    };
+    let qualified = if id.starts_with('(') {
+        id.to_string()
+    } else {
+        format!("{}{}", prefix, id)
+    };
+    TRACKED.into_iter().find(|k| k.game_id() == qualified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../fixtures/save.xml");
+
+    #[test]
+    fn fixture_save() {
+        let owned = owned_items(FIXTURE, ScanOptions::default()).unwrap();
+        let expected = [
+            // equipped, the soul sapper ring in a combined ring
+            ChestItemKind::PhoenixRing,
+            ChestItemKind::SoulSapperRing,
+            ChestItemKind::MermaidBoots,
+            // in the dresser
+            ChestItemKind::DeluxePirateHat,
+            // in the island fridge
+            ChestItemKind::OstrichEgg,
+            // in the junimo chests
+            ChestItemKind::HotJavaRing,
+        ];
+        assert_eq!(owned, Owned::from(expected));
+        // the dragonscale boots were lost on death, the protection ring is the farmhand's
+        let with_farmhands = owned_items(FIXTURE, ScanOptions { farmhands: true }).unwrap();
+        assert_eq!(
+            with_farmhands,
+            &owned | &Owned::from([ChestItemKind::ProtectionRing])
+        );
+    }
+
+    #[test]
+    fn qualified_ids() {
+        let save = |item: &str| {
+            format!(
+                "<SaveGame><player><items>{}</items></player></SaveGame>",
+                item
+            )
+        };
+        let owned = |item: &str| owned_items(&save(item), ScanOptions::default()).unwrap();
+        assert_eq!(
+            owned("<Item xsi:type=\"Boots\"><itemId>(B)855</itemId></Item>"),
+            Owned::from([ChestItemKind::DragonscaleBoots])
+        );
+        // the same number as some other type is another item
+        assert!(owned("<Item xsi:type=\"Hat\"><itemId>289</itemId></Item>").is_empty());
+        assert!(
+            owned("<Item><itemId>289</itemId><bigCraftable>true</bigCraftable></Item>").is_empty()
+        );
+        assert_eq!(
+            owned("<Item><itemId>289</itemId></Item>"),
+            Owned::from([ChestItemKind::OstrichEgg])
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        for s in [
+            "",
+            "<Farmer></Farmer>",
+            "<SaveGame></SaveGame>",
+            "<SaveGame>",
+        ] {
+            assert!(
+                matches!(
+                    owned_items(s, ScanOptions::default()),
+                    Err(Error::InvalidSave(_))
+                ),
+                "{}",
+                s
+            );
+        }
+    }
+}
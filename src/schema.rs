@@ -0,0 +1,243 @@
+//! JSON Schemas of the json this crate writes, for generating types in other languages. Enabled
+//! with the `schema` feature.
+//!
+//! The json is written by hand (see json.rs), so the schemas are too: they're the files in
+//! schema/ at the crate root, and these are those files. Changing the format means changing them.
+
+#[cfg(test)]
+use crate::json::Value;
+use std::collections::BTreeMap;
+
+/// export::to_predictor_json and the server's /forecast
+pub const PREDICTOR: &str = include_str!("../schema/predictor.schema.json");
+/// the server's /search
+pub const SEARCH: &str = include_str!("../schema/search.schema.json");
+/// the body of the server's error responses
+pub const ERROR: &str = include_str!("../schema/error.schema.json");
+
+/// every schema by name, the name is also the file name without `.schema.json`
+pub fn export_all() -> BTreeMap<&'static str, &'static str> {
+    BTreeMap::from([
+        ("error", ERROR),
+        ("predictor", PREDICTOR),
+        ("search", SEARCH),
+    ])
+}
+
+/// Checks `value` against `schema`, with just the keywords the schemas here use. Panics on any
+/// other keyword, so a schema change can't be skipped by the tests without noticing.
+#[cfg(test)]
+pub(crate) fn validate(schema: &Value, value: &Value) -> std::result::Result<(), String> {
+    validate_at(schema, schema, value, "")
+}
+
+#[cfg(test)]
+fn validate_at(
+    root: &Value,
+    schema: &Value,
+    value: &Value,
+    path: &str,
+) -> std::result::Result<(), String> {
+    let Value::Object(keywords) = schema else {
+        panic!("schema at {} isn't an object", path);
+    };
+    let fail = |msg: String| Err(format!("{}: {}", path, msg));
+    let number = |v: &Value| match v {
+        Value::Number(n) => *n,
+        other => panic!("expected a number in the schema, got {:?}", other),
+    };
+    for (keyword, arg) in keywords {
+        match (keyword.as_str(), arg) {
+            ("$schema" | "$id" | "$defs" | "title" | "description", _) => {}
+            ("$ref", Value::String(target)) => {
+                let name = target.strip_prefix("#/$defs/").expect("local refs only");
+                validate_at(
+                    root,
+                    field(root, "$defs").and_then(|d| field(d, name)).unwrap(),
+                    value,
+                    path,
+                )?;
+            }
+            ("type", types) => {
+                let types = match types {
+                    Value::Array(types) => types.iter().collect(),
+                    single => vec![single],
+                };
+                let matches = |t: &Value| match (t, value) {
+                    (Value::String(t), _) => match (t.as_str(), value) {
+                        ("object", Value::Object(_))
+                        | ("array", Value::Array(_))
+                        | ("string", Value::String(_))
+                        | ("number", Value::Number(_))
+                        | ("null", Value::Null)
+                        | ("boolean", Value::Bool(_)) => true,
+                        ("integer", Value::Number(n)) => n.fract() == 0.,
+                        _ => false,
+                    },
+                    _ => panic!("bad type {:?}", t),
+                };
+                if !types.into_iter().any(matches) {
+                    return fail(format!("{:?} isn't a {:?}", value, arg));
+                }
+            }
+            ("enum", Value::Array(options)) => {
+                if !options.contains(value) {
+                    return fail(format!("{:?} isn't one of {:?}", value, options));
+                }
+            }
+            ("minimum", min) => {
+                if let Value::Number(n) = value
+                    && *n < number(min)
+                {
+                    return fail(format!("{} is below the minimum", n));
+                }
+            }
+            ("maximum", max) => {
+                if let Value::Number(n) = value
+                    && *n > number(max)
+                {
+                    return fail(format!("{} is above the maximum", n));
+                }
+            }
+            ("required", Value::Array(keys)) => {
+                for key in keys {
+                    let Value::String(key) = key else {
+                        panic!("bad required key {:?}", key)
+                    };
+                    if matches!(value, Value::Object(_)) && field(value, key).is_none() {
+                        return fail(format!("missing {:?}", key));
+                    }
+                }
+            }
+            ("properties", Value::Object(properties)) => {
+                for (key, property) in properties {
+                    if let Some(v) = field(value, key) {
+                        validate_at(root, property, v, &format!("{}/{}", path, key))?;
+                    }
+                }
+            }
+            ("additionalProperties", extra) => {
+                let Value::Object(fields) = value else {
+                    continue;
+                };
+                let known = field(schema, "properties");
+                for (key, v) in fields {
+                    if known.is_some_and(|p| field(p, key).is_some()) {
+                        continue;
+                    }
+                    match extra {
+                        Value::Bool(false) => return fail(format!("unexpected key {:?}", key)),
+                        Value::Bool(true) => {}
+                        extra => validate_at(root, extra, v, &format!("{}/{}", path, key))?,
+                    }
+                }
+            }
+            ("propertyNames", names) => {
+                let Value::Object(fields) = value else {
+                    continue;
+                };
+                let pattern = field(names, "pattern");
+                for (key, _) in fields {
+                    // the only patterns there are
+                    let ok = match pattern {
+                        Some(Value::String(p)) if p == "^[0-9]+$" => {
+                            !key.is_empty() && key.bytes().all(|b| b.is_ascii_digit())
+                        }
+                        Some(Value::String(p)) if p == "^[0-9]$" => {
+                            key.len() == 1 && key.bytes().all(|b| b.is_ascii_digit())
+                        }
+                        other => panic!("unsupported property names {:?}", other),
+                    };
+                    if !ok {
+                        return fail(format!("bad key {:?}", key));
+                    }
+                }
+            }
+            ("items", items) => {
+                if let Value::Array(values) = value {
+                    let skip = match field(schema, "prefixItems") {
+                        Some(Value::Array(prefix)) => prefix.len(),
+                        _ => 0,
+                    };
+                    for (i, v) in values.iter().enumerate().skip(skip) {
+                        validate_at(root, items, v, &format!("{}/{}", path, i))?;
+                    }
+                }
+            }
+            ("prefixItems", Value::Array(prefix)) => {
+                if let Value::Array(values) = value {
+                    for (i, (s, v)) in prefix.iter().zip(values).enumerate() {
+                        validate_at(root, s, v, &format!("{}/{}", path, i))?;
+                    }
+                }
+            }
+            ("minItems", min) => {
+                if let Value::Array(values) = value
+                    && (values.len() as f64) < number(min)
+                {
+                    return fail(format!("{} items, fewer than the minimum", values.len()));
+                }
+            }
+            ("maxItems", max) => {
+                if let Value::Array(values) = value
+                    && (values.len() as f64) > number(max)
+                {
+                    return fail(format!("{} items, more than the maximum", values.len()));
+                }
+            }
+            (other, _) => panic!("unsupported schema keyword {:?}", other),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn field<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameSettings, export, forecast, json};
+
+    fn schema(name: &str) -> Value {
+        json::parse(export_all()[name]).unwrap()
+    }
+
+    #[test]
+    fn forecasts_match_the_schema() {
+        let settings = GameSettings {
+            seed: 31337,
+            has_caldera: true,
+            max_luck_lvl: 3,
+            ..Default::default()
+        };
+        let forecast = forecast::range(&settings, 1, 28).unwrap();
+        let written = json::parse(&export::to_predictor_json(&forecast)).unwrap();
+        validate(&schema("predictor"), &written).unwrap();
+    }
+
+    #[test]
+    fn validation_fails() {
+        let predictor = schema("predictor");
+        let check = |s: &str| validate(&predictor, &json::parse(s).unwrap());
+        assert!(check(r#"{"days": {}}"#).is_ok());
+        assert!(check(r#"{"days": {"x": {"date": "", "floors": {}}}}"#).is_err());
+        assert!(check(r#"{"days": {"1": {"date": "", "floors": {"10": []}}}}"#).is_err());
+        assert!(check(r#"{"days": {"1": {"floors": {}}}}"#).is_err());
+        let item = r#"{"days": {"1": {"date": "", "floors": {"1": [{"luck": [1, null], "items": [
+            {"name": "Taro Root", "quantity": QTY, "chest": "common", "enchantments": []}]}]}}}}"#;
+        assert!(check(&item.replace("QTY", "3")).is_ok());
+        assert!(check(&item.replace("QTY", "0")).is_err());
+        assert!(check(&item.replace("QTY", "1.5")).is_err());
+        assert!(check(&item.replace("QTY", "3").replace("\"common\"", "\"epic\"")).is_err());
+        assert!(check(&item.replace("QTY", "3").replace("[1, null]", "[1]")).is_err());
+        let error = schema("error");
+        assert!(validate(&error, &json::parse(r#"{"error": "no"}"#).unwrap()).is_ok());
+        assert!(validate(&error, &json::parse(r#"{"error": 1}"#).unwrap()).is_err());
+    }
+}
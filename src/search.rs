@@ -0,0 +1,908 @@
+//! Finding the days with specific items
+
+use crate::cache::{Decoder, encode_goodie};
+#[cfg(feature = "compression")]
+use crate::compress::{CompressionLevel, compress};
+use crate::compress::{decompress, is_compressed};
+use crate::date::{DayNumber, MAX_DAY_RANGE};
+use crate::forecast::{DayForecast, chest_forecast};
+use crate::loot::{ChestItem, ChestItemKind, Enchants, FloorLevel, Goodie};
+use crate::seeds::SeedSpec;
+use crate::stats::ValueModel;
+use crate::{Error, GameSettings, GameVersion, Result, fnv1a};
+use std::fmt::Display;
+
+/// give up after this many days (10 years)
+pub const SEARCH_CAP_DAYS: u32 = 10 * 112;
+
+/// One chest with the searched item
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub days_played: u32,
+    pub level: u8,
+    /// lowest and highest luck that get this chest, the whole floor's range if it's guaranteed
+    pub min_luck: f64,
+    pub max_luck: f64,
+    /// CommonChest or RareChest with the item
+    pub goodie: Goodie,
+    /// false if only some luck gets it
+    pub guaranteed: bool,
+}
+
+/// Something about a parsed query that won't find anything, it's still searched
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryWarning {
+    /// the chests don't have the kind in the settings' version
+    UnavailableKind {
+        kind: ChestItemKind,
+        version: GameVersion,
+    },
+}
+
+impl Display for QueryWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryWarning::UnavailableKind { kind, version } => write!(
+                f,
+                "{} isn't in the chests in {}, only from {} on",
+                kind.name(),
+                version,
+                kind.introduced_in()
+            ),
+        }
+    }
+}
+
+/// Items to look for: any of `kinds`, with at least `min_enchants` if it's set
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    pub kinds: Vec<ChestItemKind>,
+    pub min_enchants: Option<Enchants>,
+    /// from Query::parse, they don't change what's searched
+    pub warnings: Vec<QueryWarning>,
+}
+
+impl Query {
+    pub fn kind(kind: ChestItemKind) -> Self {
+        Query {
+            kinds: vec![kind],
+            min_enchants: None,
+            warnings: vec![],
+        }
+    }
+
+    /// A query someone typed: item names (see ChestItemKind::fuzzy_parse) separated by commas,
+    /// then optionally `+` and the least enchants in compact form, e.g. "dt cutlass, dwarf sword
+    /// + a4". Kinds the settings' version doesn't have are kept, with a warning.
+    pub fn parse(s: &str, settings: &GameSettings) -> Result<Self> {
+        Self::parse_for(s, &settings.version())
+    }
+
+    fn parse_for(s: &str, version: &GameVersion) -> Result<Self> {
+        let (names, enchants) = match s.split_once('+') {
+            Some((names, enchants)) => (names, Some(enchants.trim())),
+            None => (s, None),
+        };
+        let mut kinds = vec![];
+        for name in names.split(',') {
+            let kind = ChestItemKind::fuzzy_parse(name)?;
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+        let warnings = kinds
+            .iter()
+            .filter(|kind| !kind.available_in(version))
+            .map(|&kind| QueryWarning::UnavailableKind {
+                kind,
+                version: version.clone(),
+            })
+            .collect();
+        Ok(Query {
+            kinds,
+            min_enchants: enchants.map(Enchants::from_compact_string).transpose()?,
+            warnings,
+        })
+    }
+
+    pub fn with_enchants(self, min_enchants: Enchants) -> Self {
+        Query {
+            min_enchants: Some(min_enchants),
+            ..self
+        }
+    }
+
+    /// Stable hash for telling queries apart, see Checkpoint. The order of `kinds` counts.
+    pub fn hash(&self) -> u64 {
+        let mut bytes: Vec<u8> = self.kinds.iter().map(|&k| k as u8).collect();
+        if let Some(e) = &self.min_enchants {
+            bytes.push(0xff);
+            bytes.extend_from_slice(e.to_compact_string().as_bytes());
+        }
+        fnv1a(&bytes)
+    }
+
+    /// checks the kind first, it's cheaper
+    pub fn matches(&self, item: &ChestItem) -> bool {
+        self.kinds.contains(&item.kind())
+            && self
+                .min_enchants
+                .as_ref()
+                .is_none_or(|floor| item.enchants().is_some_and(|e| e.at_least(floor)))
+    }
+}
+
+/// Everything else on the day of a hit, for "on the way you also get ..."
+#[derive(Clone, Debug, PartialEq)]
+pub struct TripContext {
+    /// the luck it's for, one that gets the hit
+    pub luck: f64,
+    /// every goodie of the day at that luck by floor, the hit included
+    pub goodies: Vec<(u8, Goodie)>,
+    /// index of the hit in `goodies`, None if the settings don't give it (not the ones the
+    /// search used)
+    pub hit_index: Option<usize>,
+    /// ValueModel::expected_value of the day at that luck
+    pub value: f64,
+}
+
+impl SearchHit {
+    /// Regenerates the hit's day (with settings.seed) for what else the trip gives. Searches
+    /// don't do this themselves, it's a whole day forecast per hit.
+    pub fn expand_context(&self, settings: &GameSettings, model: &ValueModel) -> TripContext {
+        let day = DayForecast::generate(&GameSettings {
+            days_played: self.days_played,
+            ..*settings
+        });
+        // inside the first luck range with the hit, its ends can belong to the neighbors
+        let luck = day.loot[self.level as usize]
+            .iter()
+            .find(|(minl, _, _)| *minl == self.min_luck)
+            .map_or(self.min_luck, |(minl, maxl, _)| (minl + maxl) / 2.);
+        let day = day.at_luck(luck);
+        let goodies: Vec<(u8, Goodie)> = day
+            .loot
+            .iter()
+            .enumerate()
+            .flat_map(|(level, floor)| {
+                floor.iter().flat_map(move |(_, _, loot)| {
+                    loot.iter().map(move |g| (level as u8, g.clone()))
+                })
+            })
+            .collect();
+        let hit_index = goodies
+            .iter()
+            .position(|(level, g)| *level == self.level && *g == self.goodie);
+        TripContext {
+            luck,
+            value: model.expected_value(&day),
+            goodies,
+            hit_index,
+        }
+    }
+}
+
+fn chest_matches(goodie: &Goodie, pred: &impl Fn(&ChestItem) -> bool) -> bool {
+    match goodie {
+        Goodie::CommonChest(c) | Goodie::RareChest(c) => pred(c),
+        _ => false,
+    }
+}
+
+/// every chest with `kind` on the day, by floor
+pub fn day_hits(day: &DayForecast, kind: ChestItemKind) -> Vec<SearchHit> {
+    day_hits_by(day, |c| c.kind() == kind)
+}
+
+/// every chest whose item passes `pred`, by floor
+pub fn day_hits_by(day: &DayForecast, pred: impl Fn(&ChestItem) -> bool) -> Vec<SearchHit> {
+    let mut out = vec![];
+    for (level, floor) in day.loot.iter().enumerate() {
+        let matched: Vec<Vec<bool>> = floor
+            .iter()
+            .map(|(_, _, loot)| loot.iter().map(|g| chest_matches(g, &pred)).collect())
+            .collect();
+        out.extend(floor_hits(day.days_played, level, floor, &matched));
+    }
+    out
+}
+
+/// the hits of one floor, `matched` says which goodies of each luck range are hits
+fn floor_hits(
+    days_played: u32,
+    level: usize,
+    floor: &[(f64, f64, Vec<Goodie>)],
+    matched: &[Vec<bool>],
+) -> Vec<SearchHit> {
+    let counts: Vec<usize> = matched
+        .iter()
+        .map(|m| m.iter().filter(|&&m| m).count())
+        .collect();
+    let (Some(&guaranteed), Some(&most)) = (counts.iter().min(), counts.iter().max()) else {
+        return vec![];
+    };
+    // the i-th chest with the item is there in every luck range with more than i of them
+    (0..most)
+        .map(|i| {
+            let ranges: Vec<_> = floor
+                .iter()
+                .zip(matched)
+                .zip(&counts)
+                .filter(|(_, n)| **n > i)
+                .map(|(range, _)| range)
+                .collect();
+            let ((_, _, loot), first_matched) = ranges[0];
+            let goodie = loot
+                .iter()
+                .zip(first_matched)
+                .filter(|(_, m)| **m)
+                .nth(i)
+                .unwrap()
+                .0;
+            SearchHit {
+                days_played,
+                level: level as u8,
+                min_luck: ranges[0].0.0,
+                max_luck: ranges[ranges.len() - 1].0.1,
+                goodie: goodie.clone(),
+                guaranteed: i < guaranteed,
+            }
+        })
+        .collect()
+}
+
+/// The chest with this seed on `level` if it can have an item of the query, a hit per luck range
+/// where it does (see forecast::chest_forecast), guaranteed if that's every range. The day is
+/// the components' or the settings' one.
+pub fn chest_hits(
+    query: &Query,
+    chest_seed: impl Into<SeedSpec>,
+    settings: &GameSettings,
+    level: FloorLevel,
+) -> Result<Vec<SearchHit>> {
+    let chest_seed = chest_seed.into();
+    let days_played = match chest_seed {
+        SeedSpec::Raw(_) => settings.days_played,
+        SeedSpec::Components { days_played, .. } => days_played,
+    };
+    let ranges = chest_forecast(chest_seed, settings, level)?;
+    let guaranteed = ranges
+        .iter()
+        .all(|(_, _, goodie)| chest_matches(goodie, &|c: &ChestItem| query.matches(c)));
+    Ok(ranges
+        .into_iter()
+        .filter(|(_, _, goodie)| chest_matches(goodie, &|c: &ChestItem| query.matches(c)))
+        .map(|(min_luck, max_luck, goodie)| SearchHit {
+            days_played,
+            level: level.into(),
+            min_luck,
+            max_luck,
+            goodie,
+            guaranteed,
+        })
+        .collect())
+}
+
+fn weapon(hit: &SearchHit) -> Option<(ChestItemKind, &Enchants)> {
+    let (_, c) = hit.goodie.as_chest()?;
+    Some((c.kind(), c.enchants()?))
+}
+
+/// Drops the weapon hits that have another hit of the same weapon with dominating enchantments,
+/// keeping the order. Hits that aren't weapons are kept.
+pub fn pareto_front(hits: &[SearchHit]) -> Vec<SearchHit> {
+    hits.iter()
+        .filter(|hit| {
+            let Some((kind, enchants)) = weapon(hit) else {
+                return true;
+            };
+            !hits
+                .iter()
+                .filter_map(weapon)
+                .any(|(k, e)| k == kind && e.dominates(enchants))
+        })
+        .cloned()
+        .collect()
+}
+
+/// The next `n` chests with `kind` from `from_day` on, including the ones that need luck. Fails
+/// if there aren't that many within SEARCH_CAP_DAYS, or for day 0.
+pub fn next_occurrences(
+    game_seed: i32,
+    from_day: impl Into<DayNumber>,
+    kind: ChestItemKind,
+    n: usize,
+    settings: &GameSettings,
+) -> Result<Vec<SearchHit>> {
+    let mut out = vec![];
+    for days_played in from_day
+        .into()
+        .checked_span(SEARCH_CAP_DAYS, MAX_DAY_RANGE)?
+    {
+        if out.len() >= n {
+            break;
+        }
+        let day = DayForecast::generate(&GameSettings {
+            seed: game_seed,
+            days_played,
+            ..*settings
+        });
+        out.extend(day_hits(&day, kind));
+    }
+    if out.len() < n {
+        return Err(Error::SearchCapReached(out.len()));
+    }
+    out.truncate(n);
+    Ok(out)
+}
+
+/// The earliest chest matching any of `targets` in the `days` days from `start_day`, with
+/// settings.seed. Chests on the same day go by floor, hits that need luck count too. The days are
+/// checked like forecast::range does.
+pub fn first_of(
+    targets: &[Query],
+    start_day: impl Into<DayNumber>,
+    days: u32,
+    settings: &GameSettings,
+) -> Result<Option<SearchHit>> {
+    let mut days = start_day.into().checked_span(days, MAX_DAY_RANGE)?;
+    Ok(days.find_map(|days_played| {
+        let day = DayForecast::generate(&GameSettings {
+            days_played,
+            ..*settings
+        });
+        day_hits_by(&day, |c| targets.iter().any(|q| q.matches(c)))
+            .into_iter()
+            .next()
+    }))
+}
+
+/// The earliest chest for each of `targets`, in the same order. Every day is generated once for
+/// all of them, and the search stops when everything has been found.
+pub fn all_firsts(
+    targets: &[Query],
+    start_day: impl Into<DayNumber>,
+    days: u32,
+    settings: &GameSettings,
+) -> Result<Vec<Option<SearchHit>>> {
+    all_firsts_with(targets, start_day, days, settings, |_| {})
+}
+
+/// all_firsts, calling `on_day` with every day it generates. Each chest is checked against the
+/// targets that haven't been found yet once, Query::matches looks at the kind before the
+/// enchantments.
+pub fn all_firsts_with(
+    targets: &[Query],
+    start_day: impl Into<DayNumber>,
+    days: u32,
+    settings: &GameSettings,
+    mut on_day: impl FnMut(&DayForecast),
+) -> Result<Vec<Option<SearchHit>>> {
+    let mut out: Vec<Option<SearchHit>> = vec![None; targets.len()];
+    for days_played in start_day.into().checked_span(days, MAX_DAY_RANGE)? {
+        if out.iter().all(Option::is_some) {
+            break;
+        }
+        let day = DayForecast::generate(&GameSettings {
+            days_played,
+            ..*settings
+        });
+        on_day(&day);
+        for (level, floor) in day.loot.iter().enumerate() {
+            // per luck range and goodie, the targets it's a hit for
+            let matched: Vec<Vec<Vec<bool>>> = floor
+                .iter()
+                .map(|(_, _, loot)| {
+                    loot.iter()
+                        .map(|g| {
+                            targets
+                                .iter()
+                                .zip(&out)
+                                .map(|(query, found)| {
+                                    found.is_none()
+                                        && chest_matches(g, &|c: &ChestItem| query.matches(c))
+                                })
+                                .collect()
+                        })
+                        .collect()
+                })
+                .collect();
+            for (t, found) in out.iter_mut().enumerate() {
+                if found.is_some() {
+                    continue;
+                }
+                let target_matched: Vec<Vec<bool>> = matched
+                    .iter()
+                    .map(|range| range.iter().map(|m| m[t]).collect())
+                    .collect();
+                *found = floor_hits(days_played, level, floor, &target_matched)
+                    .into_iter()
+                    .next();
+            }
+        }
+    }
+    Ok(out)
+}
+
+const CHECKPOINT_MAGIC: &[u8; 4] = b"SDVC";
+const CHECKPOINT_VERSION: u8 = 1;
+
+/// Where a long search got to, so it can be picked up again with `resume` if the process dies
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    /// Query::hash of what's searched for
+    pub query_hash: u64,
+    /// GameSettings::save_hash
+    pub settings_hash: u64,
+    /// the first day that hasn't been scanned yet
+    pub next_day: u32,
+    /// where the search stops, exclusive
+    pub end_day: u32,
+    /// everything found before next_day
+    pub hits: Vec<SearchHit>,
+}
+
+impl Checkpoint {
+    pub fn is_done(&self) -> bool {
+        self.next_day >= self.end_day
+    }
+
+    /// `SDVC`, version byte, the hashes (u64), next_day and end_day (u32), the number of hits
+    /// (u32) and per hit: days_played (u32), level (u8), min and max luck (f64), guaranteed (u8)
+    /// and the goodie as in snapshots. All little endian.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = CHECKPOINT_MAGIC.to_vec();
+        out.push(CHECKPOINT_VERSION);
+        out.extend_from_slice(&self.query_hash.to_le_bytes());
+        out.extend_from_slice(&self.settings_hash.to_le_bytes());
+        out.extend_from_slice(&self.next_day.to_le_bytes());
+        out.extend_from_slice(&self.end_day.to_le_bytes());
+        let hits = u32::try_from(self.hits.len())
+            .map_err(|_| Error::TooLargeToEncode(format!("{} hits", self.hits.len())))?;
+        out.extend_from_slice(&hits.to_le_bytes());
+        for hit in &self.hits {
+            out.extend_from_slice(&hit.days_played.to_le_bytes());
+            out.push(hit.level);
+            out.extend_from_slice(&hit.min_luck.to_le_bytes());
+            out.extend_from_slice(&hit.max_luck.to_le_bytes());
+            out.push(hit.guaranteed as u8);
+            encode_goodie(&mut out, &hit.goodie)?;
+        }
+        Ok(out)
+    }
+
+    /// to_bytes as a zstd frame, from_bytes tells them apart
+    #[cfg(feature = "compression")]
+    pub fn to_bytes_compressed(&self, level: CompressionLevel) -> Result<Vec<u8>> {
+        Ok(compress(&self.to_bytes()?, level))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let decompressed;
+        let data = if is_compressed(data) {
+            decompressed = decompress(data, 0)?;
+            &decompressed
+        } else {
+            data
+        };
+        let corrupt = |msg: &str| Error::CorruptSnapshot(msg.to_string());
+        let rest = data
+            .strip_prefix(CHECKPOINT_MAGIC)
+            .ok_or_else(|| corrupt("not a checkpoint file"))?;
+        let mut d = Decoder(rest);
+        if d.u8()? != CHECKPOINT_VERSION {
+            return Err(corrupt("unsupported checkpoint version"));
+        }
+        let query_hash = d.u64()?;
+        let settings_hash = d.u64()?;
+        let next_day = d.u32()?;
+        let end_day = d.u32()?;
+        let hits = (0..d.u32()?)
+            .map(|_| {
+                Ok(SearchHit {
+                    days_played: d.u32()?,
+                    level: d.u8()?,
+                    min_luck: d.f64()?,
+                    max_luck: d.f64()?,
+                    guaranteed: d.u8()? != 0,
+                    goodie: d.goodie()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        if !d.0.is_empty() {
+            return Err(corrupt("trailing bytes after the hits"));
+        }
+        Ok(Checkpoint {
+            query_hash,
+            settings_hash,
+            next_day,
+            end_day,
+            hits,
+        })
+    }
+}
+
+/// Every chest matching `query` in the `days` days from `start_day`, with settings.seed. After
+/// every `every` days and at the end, `on_checkpoint` gets the progress so far, e.g. to write it
+/// to a file for `resume`. The days are checked like forecast::range does.
+pub fn search_with_checkpoints(
+    query: &Query,
+    start_day: impl Into<DayNumber>,
+    days: u32,
+    settings: &GameSettings,
+    every: u32,
+    on_checkpoint: impl FnMut(&Checkpoint),
+) -> Result<Vec<SearchHit>> {
+    let days = start_day.into().checked_span(days, MAX_DAY_RANGE)?;
+    let checkpoint = Checkpoint {
+        query_hash: query.hash(),
+        settings_hash: settings.save_hash(),
+        next_day: days.start,
+        end_day: days.end,
+        hits: vec![],
+    };
+    Ok(scan(checkpoint, query, settings, every, on_checkpoint))
+}
+
+/// Continues the search a checkpoint is from, giving all of its hits like an uninterrupted
+/// search would. Refuses checkpoints for another query or other settings.
+pub fn resume(
+    checkpoint: Checkpoint,
+    query: &Query,
+    settings: &GameSettings,
+    every: u32,
+    on_checkpoint: impl FnMut(&Checkpoint),
+) -> Result<Vec<SearchHit>> {
+    if checkpoint.query_hash != query.hash() {
+        return Err(Error::CheckpointQueryMismatch);
+    }
+    if checkpoint.settings_hash != settings.save_hash() {
+        return Err(Error::CheckpointSettingsMismatch);
+    }
+    Ok(scan(checkpoint, query, settings, every, on_checkpoint))
+}
+
+fn scan(
+    mut checkpoint: Checkpoint,
+    query: &Query,
+    settings: &GameSettings,
+    every: u32,
+    mut on_checkpoint: impl FnMut(&Checkpoint),
+) -> Vec<SearchHit> {
+    let every = every.max(1);
+    while !checkpoint.is_done() {
+        let chunk_end = checkpoint
+            .end_day
+            .min(checkpoint.next_day.saturating_add(every));
+        for days_played in checkpoint.next_day..chunk_end {
+            let day = DayForecast::generate(&GameSettings {
+                days_played,
+                ..*settings
+            });
+            checkpoint
+                .hits
+                .extend(day_hits_by(&day, |c| query.matches(c)));
+        }
+        checkpoint.next_day = chunk_end;
+        on_checkpoint(&checkpoint);
+    }
+    checkpoint.hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loot::Enchant;
+    use crate::luck::luck_range;
+
+    fn query() -> Query {
+        Query {
+            kinds: vec![ChestItemKind::DragontoothShiv, ChestItemKind::GoldenCoconut],
+            min_enchants: None,
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn next_occurrences_in_order() {
+        let settings = GameSettings::default();
+        let kind = ChestItemKind::OstrichEgg;
+        let hits = next_occurrences(5, 10, kind, 3, &settings).unwrap();
+        assert_eq!(hits.len(), 3);
+        // the same as every hit of the days up to the third one
+        let all: Vec<SearchHit> = (10..=hits[2].days_played)
+            .flat_map(|days_played| {
+                let day = DayForecast::generate(&GameSettings {
+                    seed: 5,
+                    days_played,
+                    ..settings
+                });
+                day_hits(&day, kind)
+            })
+            .collect();
+        assert_eq!(all[..3], hits[..]);
+        for hit in &hits {
+            let day = DayForecast::generate(&GameSettings {
+                seed: 5,
+                days_played: hit.days_played,
+                ..settings
+            });
+            let floor = &day.loot[hit.level as usize];
+            let there = |loot: &Vec<Goodie>| loot.contains(&hit.goodie);
+            assert_eq!(hit.guaranteed, floor.iter().all(|(_, _, loot)| there(loot)));
+            for (min_luck, max_luck, loot) in floor {
+                if *min_luck >= hit.min_luck && *max_luck <= hit.max_luck {
+                    assert!(there(loot), "{:?}", hit);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn next_occurrences_stop_at_the_cap() {
+        // no coconuts in the chests before the first one is cracked
+        let settings = GameSettings::default().with_cracked_golden_coconut(false);
+        let result = next_occurrences(5, 1, ChestItemKind::GoldenCoconuts, 1, &settings);
+        assert!(matches!(result, Err(Error::SearchCapReached(0))));
+        assert!(matches!(
+            next_occurrences(5, 0, ChestItemKind::OstrichEgg, 1, &settings),
+            Err(Error::DayOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn trip_context_is_the_day() {
+        let settings = GameSettings {
+            seed: 8,
+            ..Default::default()
+        };
+        let model = ValueModel::default();
+        let hits = next_occurrences(8, 1, ChestItemKind::PhoenixRing, 3, &settings).unwrap();
+        for hit in &hits {
+            let context = hit.expand_context(&settings, &model);
+            let day = DayForecast::generate(&GameSettings {
+                days_played: hit.days_played,
+                ..settings
+            })
+            .at_luck(context.luck);
+            assert!(hit.min_luck <= context.luck && context.luck <= hit.max_luck);
+            let goodies: Vec<(u8, Goodie)> = (0..10)
+                .flat_map(|level| {
+                    day.loot[level].iter().flat_map(move |(_, _, loot)| {
+                        loot.iter().map(move |g| (level as u8, g.clone()))
+                    })
+                })
+                .collect();
+            assert_eq!(context.goodies, goodies);
+            assert!(context.goodies.windows(2).all(|w| w[0].0 <= w[1].0));
+            let i = context.hit_index.unwrap();
+            assert_eq!(context.goodies[i], (hit.level, hit.goodie.clone()));
+            let copies = context
+                .goodies
+                .iter()
+                .filter(|(level, g)| *level == hit.level && *g == hit.goodie)
+                .count();
+            assert_eq!(copies, 1);
+            assert_eq!(context.value, model.expected_value(&day));
+        }
+    }
+
+    #[test]
+    fn parse_warns_about_unavailable_kinds() {
+        let settings = GameSettings::default();
+        for version in [GameVersion::V1_6, GameVersion::V1_6_4] {
+            let settings = settings.clone().with_version(&version).unwrap();
+            let query = Query::parse("dt cutlass, dwarf sword + a4", &settings).unwrap();
+            assert_eq!(query.kinds.len(), 2);
+            assert!(query.warnings.is_empty());
+        }
+
+        // nothing is known about unknown versions, so every kind warns and it's still parsed
+        let version = GameVersion::Unknown("1.5".to_string());
+        let query = Query::parse_for("dt cutlass, dwarf sword + a4", &version).unwrap();
+        assert_eq!(
+            query.kinds,
+            [ChestItemKind::DragontoothCutlass, ChestItemKind::DwarfSword]
+        );
+        assert!(query.min_enchants.is_some());
+        assert_eq!(
+            query.warnings,
+            query
+                .kinds
+                .iter()
+                .map(|&kind| QueryWarning::UnavailableKind {
+                    kind,
+                    version: version.clone()
+                })
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            query.warnings[0].to_string(),
+            "Dragontooth Cutlass isn't in the chests in 1.5, only from 1.6 on"
+        );
+        assert!(Query::parse_for("no such item", &version).is_err());
+    }
+
+    fn checkpoints(settings: &GameSettings) -> (Vec<SearchHit>, Vec<Checkpoint>) {
+        let mut checkpoints = vec![];
+        let hits = search_with_checkpoints(&query(), 1, 60, settings, 20, |c| {
+            checkpoints.push(c.clone())
+        })
+        .unwrap();
+        (hits, checkpoints)
+    }
+
+    #[test]
+    fn checkpoint_roundtrip() {
+        let settings = GameSettings {
+            seed: 4242,
+            ..Default::default()
+        };
+        let (hits, checkpoints) = checkpoints(&settings);
+        assert_eq!(checkpoints.len(), 3);
+        assert!(!hits.is_empty());
+        for checkpoint in &checkpoints {
+            let bytes = checkpoint.to_bytes().unwrap();
+            assert_eq!(&Checkpoint::from_bytes(&bytes).unwrap(), checkpoint);
+        }
+        let resumed = resume(checkpoints[0].clone(), &query(), &settings, 20, |_| {}).unwrap();
+        assert_eq!(resumed, hits);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_checkpoint_roundtrip() {
+        let (_, checkpoints) = checkpoints(&GameSettings::default());
+        let checkpoint = checkpoints.last().unwrap();
+        let bytes = checkpoint
+            .to_bytes_compressed(CompressionLevel::default())
+            .unwrap();
+        assert_eq!(&Checkpoint::from_bytes(&bytes).unwrap(), checkpoint);
+    }
+
+    #[test]
+    fn checkpoint_rejects_deep_nesting() {
+        let checkpoint = Checkpoint {
+            query_hash: 1,
+            settings_hash: 2,
+            next_day: 3,
+            end_day: 4,
+            hits: vec![],
+        };
+        let mut bytes = checkpoint.to_bytes().unwrap();
+        // one hit, its goodie nested way past the limit
+        let hits_at = bytes.len() - 4;
+        bytes[hits_at] = 1;
+        bytes.extend_from_slice(&[0; 4 + 1 + 8 + 8 + 1]);
+        for _ in 0..100_000 {
+            bytes.extend_from_slice(&[4, 1, 0, 0, 0, 0, 0]);
+        }
+        bytes.push(0);
+        assert!(matches!(
+            Checkpoint::from_bytes(&bytes),
+            Err(Error::CorruptSnapshot(_))
+        ));
+    }
+
+    #[test]
+    fn pareto_front_keeps_incomparable_rolls() {
+        let hit = |item| SearchHit {
+            days_played: 1,
+            level: 3,
+            min_luck: -0.1,
+            max_luck: 0.1,
+            goodie: Goodie::RareChest(item),
+            guaranteed: true,
+        };
+        let club = |levels: &[(Enchant, i32)]| {
+            hit(ChestItem::DragontoothClub(Enchants::from_levels(
+                levels.to_vec(),
+            )))
+        };
+        let fast = club(&[(Enchant::Speed, 3)]);
+        let strong = club(&[(Enchant::Attack, 4)]);
+        let worse = club(&[(Enchant::Attack, 2)]);
+        // a worse dwarf sword is another weapon, the ring isn't one
+        let sword = hit(ChestItem::DwarfSword(Enchants::from_levels(vec![(
+            Enchant::Attack,
+            1,
+        )])));
+        let ring = hit(ChestItem::PhoenixRing);
+        let hits = [
+            fast.clone(),
+            worse,
+            strong.clone(),
+            sword.clone(),
+            ring.clone(),
+        ];
+        assert_eq!(pareto_front(&hits), [fast, strong, sword, ring]);
+    }
+
+    #[test]
+    fn chest_hits_by_components() {
+        let settings = GameSettings {
+            seed: 4242,
+            ..Default::default()
+        };
+        let level = FloorLevel::try_new(2).unwrap();
+        let index = usize::from(u8::from(level));
+        let (days_played, chest_seed) = (1..30)
+            .find_map(|days_played| {
+                let day = DayForecast::generate(&GameSettings {
+                    days_played,
+                    ..settings
+                });
+                let seeds = day.chest_seeds(index, luck_range(&settings).0);
+                (day.layouts[index].len() == 1 && !seeds.is_empty())
+                    .then(|| (days_played, seeds[0]))
+            })
+            .unwrap();
+        let spec = SeedSpec::Components {
+            game_id: 4242,
+            days_played,
+            level: level.into(),
+            chest_index: 0,
+        };
+        let settings = GameSettings {
+            days_played,
+            ..settings
+        };
+        assert_eq!(spec.resolve(&settings, 1.).unwrap(), chest_seed);
+        assert_eq!(
+            chest_forecast(spec, &settings, level).unwrap(),
+            chest_forecast(chest_seed, &settings, level).unwrap()
+        );
+        let kind = ChestItem::generate(crate::loot::Rarity::Common, chest_seed, &settings).kind();
+        let hits = chest_hits(&Query::kind(kind), spec, &settings, level).unwrap();
+        assert!(!hits.is_empty());
+        assert_eq!(
+            hits,
+            chest_hits(&Query::kind(kind), chest_seed, &settings, level).unwrap()
+        );
+        // the day's forecast has the same chest
+        let day = DayForecast::generate(&settings);
+        let day_hits = day_hits(&day, kind);
+        for hit in &hits {
+            assert!(
+                day_hits
+                    .iter()
+                    .any(|h| h.level == hit.level && h.goodie == hit.goodie)
+            );
+        }
+    }
+
+    #[test]
+    fn all_firsts_in_one_pass() {
+        let settings = GameSettings {
+            seed: 4242,
+            ..Default::default()
+        };
+        let targets = [
+            Query::kind(ChestItemKind::DragontoothShiv),
+            Query::parse("dt cutlass, dt club + s2", &settings).unwrap(),
+            Query::parse("dwarf sword, dt club", &settings).unwrap(),
+            Query::kind(ChestItemKind::DragonscaleBoots),
+        ];
+        let mut generated = vec![];
+        let firsts = all_firsts_with(&targets, 1, 112, &settings, |day| {
+            generated.push(day.days_played)
+        })
+        .unwrap();
+        for (target, first) in targets.iter().zip(&firsts) {
+            assert_eq!(
+                first,
+                &first_of(std::slice::from_ref(target), 1, 112, &settings).unwrap()
+            );
+        }
+        assert!(firsts.iter().all(Option::is_some));
+        // every day up to the last first hit, once
+        let last = firsts
+            .iter()
+            .flatten()
+            .map(|h| h.days_played)
+            .max()
+            .unwrap();
+        assert_eq!(generated, (1..=last).collect::<Vec<_>>());
+        assert_eq!(firsts, all_firsts(&targets, 1, 112, &settings).unwrap());
+    }
+}
@@ -0,0 +1,151 @@
+//! Chest seeds either as the final value (e.g. from other tools) or as where the chest is, so
+//! both can be passed wherever a chest seed is taken.
+
+use crate::loot::FloorLevel;
+use crate::{DungeonFloorState, Error, GameSettings, Result, compute_volcano_layouts};
+use std::cell::Cell;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SeedSpec {
+    /// the seed as the chest gets it, used as is
+    Raw(i32),
+    /// the `chest_index`th chest on `level`, counting from 0 like ChestId
+    Components {
+        game_id: u64,
+        days_played: u32,
+        level: i32,
+        chest_index: i32,
+    },
+}
+
+impl From<i32> for SeedSpec {
+    fn from(seed: i32) -> Self {
+        SeedSpec::Raw(seed)
+    }
+}
+
+impl SeedSpec {
+    /// The chest seed. For Components this lays out the floor with the settings (so legacy_rng
+    /// and the version are respected) and `luck`, which picks the layout when that depends on the
+    /// luck (outside of the settings' luck range, the first layout is used). The game id and day
+    /// replace the ones in the settings. Raw seeds ignore all of it.
+    pub fn resolve(&self, settings: &GameSettings, luck: f64) -> Result<i32> {
+        let (game_id, days_played, level, chest_index) = match *self {
+            SeedSpec::Raw(seed) => return Ok(seed),
+            SeedSpec::Components {
+                game_id,
+                days_played,
+                level,
+                chest_index,
+            } => (game_id, days_played, level, chest_index),
+        };
+        FloorLevel::try_from(level)?.check_has_chests()?;
+        let settings = GameSettings {
+            seed: GameSettings::for_game_id(game_id)?.seed,
+            days_played,
+            ..*settings
+        };
+        let layouts = compute_volcano_layouts(&settings);
+        let not_found = Error::NoSuchChest {
+            level,
+            index: chest_index,
+        };
+        let Some((_, _, layout)) = layouts
+            .iter()
+            .find(|(minl, maxl, _)| (*minl..=*maxl).contains(&luck))
+            .or(layouts.first())
+        else {
+            return Err(not_found);
+        };
+        let mut floor =
+            DungeonFloorState::new(&settings, level, layout[level as usize], luck, luck);
+        floor.load_map_tiles();
+        let seed = Cell::new(None);
+        floor.plan_set_pieces(&|index, s| {
+            if index as i32 == chest_index {
+                seed.set(Some(s));
+            }
+            s
+        });
+        seed.get().ok_or(not_found)
+    }
+
+    /// the level the components point at
+    pub fn level(&self) -> Option<i32> {
+        match *self {
+            SeedSpec::Raw(_) => None,
+            SeedSpec::Components { level, .. } => Some(level),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chest(level: i32, chest_index: i32) -> SeedSpec {
+        SeedSpec::Components {
+            game_id: 4242,
+            days_played: 20,
+            level,
+            chest_index,
+        }
+    }
+
+    #[test]
+    fn raw_seeds_are_used_as_is() {
+        let settings = GameSettings::default();
+        for seed in [0, -5, i32::MAX] {
+            assert_eq!(SeedSpec::from(seed).resolve(&settings, 1.).unwrap(), seed);
+            assert_eq!(SeedSpec::Raw(seed).level(), None);
+        }
+        assert_eq!(chest(3, 0).level(), Some(3));
+    }
+
+    #[test]
+    fn components_that_dont_exist() {
+        let settings = GameSettings::default();
+        assert!(matches!(
+            chest(5, 0).resolve(&settings, 1.),
+            Err(Error::NoChestOnFloor(5))
+        ));
+        assert!(matches!(
+            chest(12, 0).resolve(&settings, 1.),
+            Err(Error::InvalidLevel(12))
+        ));
+        assert!(matches!(
+            chest(3, 99).resolve(&settings, 1.),
+            Err(Error::NoSuchChest {
+                level: 3,
+                index: 99
+            })
+        ));
+    }
+
+    #[test]
+    fn components_follow_the_rng_mode() {
+        // the first chest of a floor that has one for both modes
+        let resolve = |legacy_rng| {
+            let settings = GameSettings {
+                legacy_rng,
+                ..Default::default()
+            };
+            (1..10)
+                .filter_map(|level| chest(level, 0).resolve(&settings, 1.).ok())
+                .collect::<Vec<i32>>()
+        };
+        let (hashed, legacy) = (resolve(false), resolve(true));
+        assert!(!hashed.is_empty() && !legacy.is_empty());
+        assert_ne!(hashed, legacy);
+        // the game id and the day replace the settings' ones
+        let other = GameSettings {
+            seed: 1,
+            days_played: 3,
+            ..Default::default()
+        };
+        let same: Vec<i32> = (1..10)
+            .filter_map(|level| chest(level, 0).resolve(&other, 1.).ok())
+            .collect();
+        assert_eq!(same, hashed);
+    }
+}
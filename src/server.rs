@@ -0,0 +1,400 @@
+//! Tiny blocking http server for self-hosting the predictor, enabled with the `server` feature.
+//!
+//! - `GET /forecast?seed=&day=&days=` gives the json of [`export::to_predictor_json`]
+//! - `GET /search?q=&seed=&day=&days=` lists every goodie whose item name contains `q`. A `q`
+//!   that's in no item name is read as a misspelled one, or a 400 with suggestions.
+//!
+//! `day` takes anything [`DayNumber`] parses, missing parameters come from the defaults. Both also
+//! take `version=` (e.g. 1.6.8), unknown versions are a 400. Errors are `{"error": message}`, the
+//! json schemas of all of these are in schema/. Responses are canonical json, see
+//! [`export::to_canonical_json`].
+
+use crate::cache::ChestCache;
+use crate::date::DayNumber;
+use crate::filter::GoodieFilter;
+use crate::forecast::DayForecast;
+use crate::loot::{ChestItemKind, Goodie};
+use crate::{GameSettings, display_luck, export, html, json};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+/// so a single request can't keep the server busy for minutes
+const MAX_DAYS: u32 = 28 * 4 * 10;
+/// days kept from earlier requests, enough for the largest request
+const CACHED_DAYS: usize = MAX_DAYS as usize;
+/// most of a request that's read, request line and headers together. longer requests get cut off
+/// there, so the request line has to fit.
+const MAX_HEADER: u64 = 8 * 1024;
+/// longest `q` that's searched, way longer than any item name. the typo matching is quadratic in
+/// it, so longer ones are turned down before getting there.
+const MAX_QUERY: usize = 100;
+/// connections are handled one at a time, so one that doesn't send (or read) can't be waited
+/// on forever
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    fn ok(body: String) -> Self {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, msg: &str) -> Self {
+        Response {
+            status,
+            body: format!("{{\"error\":{}}}", json::quote(msg)),
+        }
+    }
+}
+
+/// Serves forever on `addr`
+pub fn serve(addr: impl ToSocketAddrs, defaults: GameSettings) -> io::Result<()> {
+    serve_listener(TcpListener::bind(addr)?, defaults)
+}
+
+/// Serves forever on an already bound listener, e.g. one on port 0
+pub fn serve_listener(listener: TcpListener, defaults: GameSettings) -> io::Result<()> {
+    let mut cache = ChestCache::new(CACHED_DAYS);
+    for stream in listener.incoming() {
+        // a broken connection shouldn't take the server down
+        let Ok(stream) = stream else { continue };
+        let _ = handle_connection(stream, &defaults, &mut cache);
+    }
+    Ok(())
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    defaults: &GameSettings,
+    cache: &mut ChestCache,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new((&stream).take(MAX_HEADER));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // skip the headers, nothing in them matters
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let response = match request_line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["GET", target, _] => {
+            panic::catch_unwind(AssertUnwindSafe(|| route(target, defaults, cache)))
+                .unwrap_or_else(|_| Response::error(500, "internal error"))
+        }
+        [_, _, _] => Response::error(405, "only GET is supported"),
+        _ => Response::error(400, "malformed request"),
+    };
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+fn route(target: &str, defaults: &GameSettings, cache: &mut ChestCache) -> Response {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let params: Vec<(String, String)> = query
+        .split('&')
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let (k, v) = x.split_once('=').unwrap_or((x, ""));
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    };
+    let result = match path {
+        "/forecast" => forecast_params(&param, defaults).and_then(|(settings, day, days)| {
+            let forecast = cache
+                .range(&settings, day, days)
+                .map_err(|e| e.to_string())?;
+            Ok(export::to_predictor_json(&forecast))
+        }),
+        "/search" => match param("q") {
+            Some(q) if q.len() > MAX_QUERY => {
+                Err(format!("search query longer than {} bytes", MAX_QUERY))
+            }
+            Some(q) if !q.trim().is_empty() => search_query(q).and_then(|q| {
+                let (settings, day, days) = forecast_params(&param, defaults)?;
+                let forecast = cache
+                    .range(&settings, day, days)
+                    .map_err(|e| e.to_string())?;
+                Ok(search(&q, &forecast))
+            }),
+            _ => Err("missing search query q".to_string()),
+        },
+        _ => return Response::error(404, "not found"),
+    };
+    // canonical, so the same request always gets the same bytes
+    match result.and_then(|body| export::to_canonical_json(&body).map_err(|e| e.to_string())) {
+        Ok(body) => Response::ok(body),
+        Err(msg) => Response::error(400, &msg),
+    }
+}
+
+fn forecast_params<'a>(
+    param: &impl Fn(&str) -> Option<&'a str>,
+    defaults: &GameSettings,
+) -> Result<(GameSettings, DayNumber, u32), String> {
+    let mut settings = defaults.clone();
+    if let Some(seed) = param("seed") {
+        settings.seed = seed
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid seed: {}", seed))?;
+    }
+    if let Some(version) = param("version") {
+        settings = settings
+            .with_version(&version.into())
+            .map_err(|e| e.to_string())?;
+    }
+    let day = match param("day") {
+        Some(day) => day.parse::<DayNumber>().map_err(|e| e.to_string())?,
+        None => DayNumber(defaults.days_played.max(1)),
+    };
+    let days = match param("days") {
+        Some(days) => days
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid number of days: {}", days))?,
+        None => 1,
+    };
+    if !(1..=MAX_DAYS).contains(&days) {
+        return Err(format!("days must be between 1 and {}", MAX_DAYS));
+    }
+    day.checked_span(days, MAX_DAYS)
+        .map_err(|e| e.to_string())?;
+    Ok((settings, day, days))
+}
+
+/// `q` if it's part of some item name, otherwise the item it's a typo of
+fn search_query(q: &str) -> Result<String, String> {
+    let lower = q.trim().to_lowercase();
+    let known = ChestItemKind::ALL
+        .iter()
+        .map(|k| k.name())
+        .chain(["Dragon Tooth"])
+        .any(|name| name.to_lowercase().contains(&lower));
+    if known {
+        return Ok(q.to_string());
+    }
+    ChestItemKind::fuzzy_parse(q)
+        .map(|kind| kind.name().to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// `{"results": [{"days_played", "date", "level", "luck": [min, max], "chest", "name"}]}`
+fn search(q: &str, forecast: &[DayForecast]) -> String {
+    let q = q.trim().to_lowercase();
+    let mut results = Vec::new();
+    for day in forecast {
+        for (level, minl, maxl, goodie) in day.iter_filtered(GoodieFilter::ALL) {
+            let mut items = vec![];
+            search_names(goodie, &mut items);
+            for (chest, name) in items {
+                if !name.to_lowercase().contains(&q) {
+                    continue;
+                }
+                let mut out = String::new();
+                write!(
+                    out,
+                    "{{\"days_played\":{},\"date\":{},\"level\":{},\"luck\":[{},{}],\"chest\":{},\"name\":{}}}",
+                    day.days_played,
+                    json::quote(&html::day_name(day.days_played)),
+                    level,
+                    json::number(display_luck(minl)),
+                    json::number(display_luck(maxl)),
+                    chest.map(json::quote).unwrap_or_else(|| "null".to_string()),
+                    json::quote(name)
+                )
+                .unwrap();
+                results.push(out);
+            }
+        }
+    }
+    format!("{{\"results\":[{}]}}", results.join(","))
+}
+
+/// (chest rarity, item name) of everything the goodie could be
+fn search_names(goodie: &Goodie, out: &mut Vec<(Option<&'static str>, &'static str)>) {
+    match goodie {
+        Goodie::DragonTooth => out.push((None, "Dragon Tooth")),
+        Goodie::CommonChest(c) => out.push((Some("common"), c.kind().name())),
+        Goodie::RareChest(c) => out.push((Some("rare"), c.kind().name())),
+        Goodie::ChanceChest { common, rare, .. } => {
+            out.push((Some("common"), common.kind().name()));
+            out.push((Some("rare"), rare.kind().name()));
+        }
+        Goodie::Ambiguous(alts) => {
+            for (_, g) in alts {
+                search_names(g, out);
+            }
+        }
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = bytes.clone().take(2).collect();
+                match std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                {
+                    Some(x) if hex.len() == 2 => {
+                        out.push(x);
+                        bytes.nth(1);
+                    }
+                    _ => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::Value;
+    use std::net::SocketAddr;
+    use std::sync::OnceLock;
+
+    /// one server on an ephemeral port for all the tests, it runs until the tests are done
+    fn server() -> SocketAddr {
+        static ADDR: OnceLock<SocketAddr> = OnceLock::new();
+        *ADDR.get_or_init(|| {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            let defaults = GameSettings {
+                seed: 12345,
+                ..Default::default()
+            };
+            std::thread::spawn(move || serve_listener(listener, defaults));
+            addr
+        })
+    }
+
+    fn get(target: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(server()).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, json::parse(body).unwrap())
+    }
+
+    fn field<'a>(value: &'a Value, key: &str) -> &'a Value {
+        match value {
+            Value::Object(fields) => &fields.iter().find(|(k, _)| k == key).unwrap().1,
+            _ => panic!("not an object: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn forecast() {
+        let (status, body) = get("/forecast?seed=999&day=Summer%205&days=2");
+        assert_eq!(status, 200);
+        let Value::Object(days) = field(&body, "days") else {
+            panic!("{:?}", body);
+        };
+        let days_played: Vec<&str> = days.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(days_played, ["33", "34"]);
+        assert_eq!(
+            field(&days[0].1, "date"),
+            &Value::String(html::day_name(33))
+        );
+        // the same bytes from the cache the second time
+        assert_eq!(
+            get("/forecast?seed=999&day=Summer%205&days=2"),
+            (status, body)
+        );
+    }
+
+    #[test]
+    fn search() {
+        let (status, body) = get("/search?q=shiv&days=112");
+        assert_eq!(status, 200);
+        let Value::Array(results) = field(&body, "results") else {
+            panic!("{:?}", body);
+        };
+        assert!(!results.is_empty());
+        for result in results {
+            assert_eq!(
+                field(result, "name"),
+                &Value::String("Dragontooth Shiv".to_string())
+            );
+        }
+        // misspelled
+        let (status, typo) = get("/search?q=dragontooth%20shif&days=112");
+        assert_eq!((status, typo), (200, body));
+    }
+
+    #[test]
+    fn errors() {
+        let long = format!("/search?q={}", "a".repeat(MAX_QUERY + 1));
+        for (target, status) in [
+            ("/forecast?days=0", 400),
+            ("/forecast?seed=x", 400),
+            ("/forecast?version=2.0", 400),
+            ("/search?q=", 400),
+            (long.as_str(), 400),
+            ("/search?q=zzzzzzzz", 400),
+            ("/nothing", 404),
+        ] {
+            let (got, body) = get(target);
+            assert_eq!(got, status, "{}", target);
+            assert!(matches!(field(&body, "error"), Value::String(_)));
+        }
+        let (_, body) = get("/forecast?version=1.7");
+        assert!(matches!(field(&body, "error"), Value::String(e) if e.contains("\"1.7\"")));
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn responses_match_the_schemas() {
+        use crate::schema;
+        for (target, name) in [
+            ("/forecast?seed=999&day=Summer%205&days=3", "predictor"),
+            ("/search?q=shiv&days=112", "search"),
+            ("/forecast?days=0", "error"),
+            ("/nothing", "error"),
+        ] {
+            let schema = json::parse(schema::export_all()[name]).unwrap();
+            let (_, body) = get(target);
+            schema::validate(&schema, &body).unwrap_or_else(|e| panic!("{}: {}", target, e));
+        }
+    }
+}
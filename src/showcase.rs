@@ -0,0 +1,178 @@
+//! Finding a seed whose first days show a bit of everything, for screenshots. The seeds come from
+//! a sequence seeded by the caller, so the same meta seed always finds the same one.
+
+use crate::forecast::{self, DayForecast, DayState};
+use crate::loot::{ChestItem, Goodie};
+use crate::{Error, GameSettings, Result, rng, stats};
+use std::collections::BTreeMap;
+
+/// seeds find_representative_seed tries before giving up
+pub const MAX_SHOWCASE_SEEDS: usize = 10_000;
+
+/// What the days have to have, at least
+#[derive(Clone, Debug)]
+pub struct ShowcaseCriteria {
+    /// the seed is ignored, days_played is the first day
+    pub settings: GameSettings,
+    pub days: u32,
+    /// see stats::rare_uniques
+    pub rare_uniques: u32,
+    /// chests with a weapon that has an innate enchant, for some luck
+    pub enchanted_weapons: u32,
+    /// chests that are common or rare depending on the luck
+    pub chance_chests: u32,
+}
+
+impl Default for ShowcaseCriteria {
+    /// one of each in the first week
+    fn default() -> Self {
+        ShowcaseCriteria {
+            settings: GameSettings {
+                days_played: 1,
+                ..Default::default()
+            },
+            days: 7,
+            rare_uniques: 1,
+            enchanted_weapons: 1,
+            chance_chests: 1,
+        }
+    }
+}
+
+/// How much of each thing the criteria look at some days have
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShowcaseCounts {
+    pub rare_uniques: u32,
+    pub enchanted_weapons: u32,
+    pub chance_chests: u32,
+}
+
+/// adds to (has an enchanted weapon, can be common, can be rare) of a chest
+fn chest_kinds(goodie: &Goodie, seen: &mut (bool, bool, bool)) {
+    let enchanted = |c: &ChestItem| c.enchants().is_some_and(|e| !e.as_slice().is_empty());
+    match goodie {
+        Goodie::DragonTooth => {}
+        Goodie::CommonChest(c) => {
+            seen.0 |= enchanted(c);
+            seen.1 = true;
+        }
+        Goodie::RareChest(c) => {
+            seen.0 |= enchanted(c);
+            seen.2 = true;
+        }
+        Goodie::ChanceChest { common, rare, .. } => {
+            seen.0 |= enchanted(common) || enchanted(rare);
+            (seen.1, seen.2) = (true, true);
+        }
+        Goodie::Ambiguous(alts) => alts.iter().for_each(|(_, g)| chest_kinds(g, seen)),
+    }
+}
+
+impl ShowcaseCounts {
+    pub fn of(forecast: &[DayForecast]) -> Self {
+        let mut counts = ShowcaseCounts {
+            rare_uniques: forecast.iter().map(stats::rare_uniques).sum(),
+            ..Default::default()
+        };
+        for day in forecast {
+            // a chest is the same one in every luck range it's in
+            let mut chests = BTreeMap::new();
+            for record in day.records(&DayState::default()) {
+                let Some(id) = record.chest else {
+                    continue;
+                };
+                let seen = chests
+                    .entry((id.level, id.index))
+                    .or_insert((false, false, false));
+                chest_kinds(&record.goodie, seen);
+            }
+            for (enchanted, common, rare) in chests.into_values() {
+                counts.enchanted_weapons += enchanted as u32;
+                counts.chance_chests += (common && rare) as u32;
+            }
+        }
+        counts
+    }
+}
+
+impl ShowcaseCriteria {
+    pub fn matches(&self, forecast: &[DayForecast]) -> bool {
+        let counts = ShowcaseCounts::of(forecast);
+        counts.rare_uniques >= self.rare_uniques
+            && counts.enchanted_weapons >= self.enchanted_weapons
+            && counts.chance_chests >= self.chance_chests
+    }
+
+    /// the criteria's days for `seed`
+    pub fn forecast(&self, seed: i32) -> Result<Vec<DayForecast>> {
+        let settings = GameSettings {
+            seed,
+            ..self.settings
+        };
+        forecast::range(&settings, settings.days_played.max(1), self.days)
+    }
+}
+
+/// The first seed of the sequence from `search_meta_seed` whose days meet the criteria.
+/// NoShowcaseSeed if none of the first MAX_SHOWCASE_SEEDS does.
+pub fn find_representative_seed(criteria: &ShowcaseCriteria, search_meta_seed: i32) -> Result<i32> {
+    let mut seeds = rng::DotnetRng::new(search_meta_seed);
+    for _ in 0..MAX_SHOWCASE_SEEDS {
+        let seed = seeds.next();
+        if criteria.matches(&criteria.forecast(seed)?) {
+            return Ok(seed);
+        }
+    }
+    Err(Error::NoShowcaseSeed(MAX_SHOWCASE_SEEDS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_matching_seed_of_the_sequence() {
+        let criteria = ShowcaseCriteria::default();
+        let seed = find_representative_seed(&criteria, 42).unwrap();
+        let counts = ShowcaseCounts::of(&criteria.forecast(seed).unwrap());
+        assert!(counts.rare_uniques >= 1, "{:?}", counts);
+        assert!(counts.enchanted_weapons >= 1, "{:?}", counts);
+        assert!(counts.chance_chests >= 1, "{:?}", counts);
+        assert_eq!(find_representative_seed(&criteria, 42).unwrap(), seed);
+        // the ones before it in the sequence don't match
+        let mut seeds = rng::DotnetRng::new(42);
+        let before: Vec<i32> = std::iter::from_fn(|| Some(seeds.next()))
+            .take_while(|&s| s != seed)
+            .collect();
+        assert!(before.len() < MAX_SHOWCASE_SEEDS);
+        for s in before {
+            assert!(!criteria.matches(&criteria.forecast(s).unwrap()), "{}", s);
+        }
+    }
+
+    #[test]
+    fn stricter_criteria() {
+        let criteria = ShowcaseCriteria {
+            rare_uniques: 3,
+            enchanted_weapons: 2,
+            chance_chests: 4,
+            ..Default::default()
+        };
+        let seed = find_representative_seed(&criteria, 7).unwrap();
+        let forecast = criteria.forecast(seed).unwrap();
+        assert_eq!(forecast.len(), 7);
+        assert!(forecast.iter().all(|day| day.settings.seed == seed));
+        assert!(criteria.matches(&forecast));
+        // the counts add up day by day
+        let by_day =
+            forecast
+                .chunks(1)
+                .map(ShowcaseCounts::of)
+                .fold(ShowcaseCounts::default(), |a, b| ShowcaseCounts {
+                    rare_uniques: a.rare_uniques + b.rare_uniques,
+                    enchanted_weapons: a.enchanted_weapons + b.enchanted_weapons,
+                    chance_chests: a.chance_chests + b.chance_chests,
+                });
+        assert_eq!(by_day, ShowcaseCounts::of(&forecast));
+    }
+}
@@ -0,0 +1,275 @@
+//! Packing the icons into one sprite sheet, with the `image` feature. build_sheet writes the sheet
+//! and a stylesheet with a class per icon, SpriteIconRenderer renders icons as those classes.
+//!
+//! The icons are placed on a grid with cells the size of the largest icon, in icon_keys order.
+
+use crate::loot::ChestItemKind;
+use crate::render::IconRenderer;
+use crate::{Error, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Where build_sheet put each icon
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpriteMap {
+    /// size of a grid cell in pixels, (width, height)
+    pub cell: (u32, u32),
+    pub columns: u32,
+    pub rows: u32,
+    /// the pixel offset of each icon's cell in the sheet
+    pub positions: BTreeMap<&'static str, (u32, u32)>,
+}
+
+impl SpriteMap {
+    /// size of the whole sheet in pixels, (width, height)
+    pub fn size(&self) -> (u32, u32) {
+        (self.cell.0 * self.columns, self.cell.1 * self.rows)
+    }
+
+    /// The stylesheet for the sheet at `sheet_url`: `.icon` sets the sheet and the size, then a
+    /// `.icon-<key>` class per icon with its position.
+    pub fn css(&self, sheet_url: &str) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            ".icon {{ display: inline-block; width: {}px; height: {}px; background-image: url({}); }}",
+            self.cell.0,
+            self.cell.1,
+            css_string(sheet_url)
+        )
+        .unwrap();
+        let offset = |px: u32| match px {
+            0 => "0".to_string(),
+            px => format!("-{}px", px),
+        };
+        for (key, &(x, y)) in &self.positions {
+            writeln!(
+                out,
+                ".icon-{} {{ background-position: {} {}; }}",
+                key,
+                offset(x),
+                offset(y)
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+/// `s` as a quoted css string: quotes and backslashes get a backslash, control characters are hex
+/// escapes (with the space that ends them)
+fn css_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if c.is_control() => write!(out, "\\{:x} ", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// All the icon names the renderers use: the chests, the dragon tooth and the get_icon of every
+/// chest item, without duplicates
+pub fn icon_keys() -> Vec<&'static str> {
+    let mut keys = vec!["common_chest", "rare_chest", "dragon_tooth"];
+    for kind in ChestItemKind::ALL {
+        let key = kind.meta().icon_key;
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+/// an icon decoded to 8 bit rgba
+struct Icon {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+fn decode(path: &Path, key: &str) -> Result<Icon> {
+    let invalid = |e: png::DecodingError| Error::InvalidIcon(key.to_string(), e.to_string());
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(invalid)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(invalid)?;
+    buf.truncate(info.buffer_size());
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf
+            .chunks(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        // expanded by the transformations
+        png::ColorType::Indexed => {
+            return Err(Error::InvalidIcon(
+                key.to_string(),
+                "indexed colors".to_string(),
+            ));
+        }
+    };
+    Ok(Icon {
+        width: info.width,
+        height: info.height,
+        rgba,
+    })
+}
+
+/// Packs `<key>.png` from `icon_dir` for every key of icon_keys into the sheet `out_png`, and
+/// writes the stylesheet for it to `out_css`. The stylesheet refers to the sheet by its file name,
+/// so they should be served from the same directory. MissingIcons with all the missing ones.
+pub fn build_sheet(icon_dir: &Path, out_png: &Path, out_css: &Path) -> Result<SpriteMap> {
+    let keys = icon_keys();
+    let missing: Vec<String> = keys
+        .iter()
+        .filter(|key| !icon_dir.join(format!("{}.png", key)).is_file())
+        .map(|key| key.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(Error::MissingIcons(missing));
+    }
+    let icons = keys
+        .iter()
+        .map(|key| decode(&icon_dir.join(format!("{}.png", key)), key))
+        .collect::<Result<Vec<_>>>()?;
+
+    let cell = (
+        icons.iter().map(|i| i.width).max().unwrap_or(0),
+        icons.iter().map(|i| i.height).max().unwrap_or(0),
+    );
+    let columns = (keys.len() as f64).sqrt().ceil() as u32;
+    let rows = (keys.len() as u32).div_ceil(columns);
+    let mut map = SpriteMap {
+        cell,
+        columns,
+        rows,
+        positions: BTreeMap::new(),
+    };
+    let (width, height) = map.size();
+    let mut sheet = vec![0u8; width as usize * height as usize * 4];
+    for (i, (key, icon)) in keys.iter().zip(&icons).enumerate() {
+        let (x, y) = (i as u32 % columns * cell.0, i as u32 / columns * cell.1);
+        map.positions.insert(*key, (x, y));
+        let row_len = icon.width as usize * 4;
+        for (row, pixels) in icon.rgba.chunks(row_len).enumerate() {
+            let start = ((y as usize + row) * width as usize + x as usize) * 4;
+            sheet[start..start + row_len].copy_from_slice(pixels);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(out_png)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let encoding = |e: png::EncodingError| Error::Io(std::io::Error::other(e));
+    let mut writer = encoder.write_header().map_err(encoding)?;
+    writer.write_image_data(&sheet).map_err(encoding)?;
+    writer.finish().map_err(encoding)?;
+
+    let sheet_url = out_png
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    std::fs::write(out_css, map.css(&sheet_url))?;
+    Ok(map)
+}
+
+/// `<span>` tags with the classes of the stylesheet from build_sheet
+pub struct SpriteIconRenderer;
+
+impl IconRenderer for SpriteIconRenderer {
+    fn icon(&self, out: &mut dyn std::fmt::Write, name: &str) -> std::fmt::Result {
+        write!(out, "<span class=\"icon icon-{}\"></span>", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// a directory in the temp dir that's removed again when the test is done
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let name = format!("sdv-volcano-{}-{}", std::process::id(), name);
+            let dir = std::env::temp_dir().join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn write_png(path: &Path, width: u32, height: u32) {
+        let mut encoder = png::Encoder::new(File::create(path).unwrap(), width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer
+            .write_image_data(&vec![200; (width * height * 3) as usize])
+            .unwrap();
+    }
+
+    #[test]
+    fn sheet_of_placeholders() {
+        let dir = TempDir::new("sprites");
+        let keys = icon_keys();
+        // different sizes, the cells are the largest
+        for (i, key) in keys.iter().enumerate() {
+            write_png(&dir.0.join(format!("{}.png", key)), 16, 10 + i as u32 % 7);
+        }
+        let (png_path, css_path) = (dir.0.join("sheet.png"), dir.0.join("sheet.css"));
+        let map = build_sheet(&dir.0, &png_path, &css_path).unwrap();
+        assert_eq!(map.cell, (16, 16));
+        assert!(map.columns * map.rows >= keys.len() as u32);
+        assert!((map.columns - 1) * map.rows < keys.len() as u32);
+
+        let css = std::fs::read_to_string(&css_path).unwrap();
+        assert!(css.contains("url(\"sheet.png\")"));
+        for key in &keys {
+            let rule = format!(".icon-{} {{", key);
+            assert_eq!(css.matches(&rule).count(), 1, "{}", key);
+        }
+        assert_eq!(css.lines().count(), keys.len() + 1);
+
+        let decoder = png::Decoder::new(File::open(&png_path).unwrap());
+        let info = decoder.read_info().unwrap().info().clone();
+        assert_eq!((info.width, info.height), map.size());
+        assert_eq!(map.size(), (16 * map.columns, 16 * map.rows));
+
+        std::fs::remove_file(dir.0.join("rare_chest.png")).unwrap();
+        assert!(matches!(
+            build_sheet(&dir.0, &png_path, &css_path),
+            Err(Error::MissingIcons(missing)) if missing == ["rare_chest"]
+        ));
+    }
+
+    #[test]
+    fn css_urls_are_escaped() {
+        assert_eq!(css_string("icons/sheet.png"), "\"icons/sheet.png\"");
+        assert_eq!(css_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(css_string("a\nb"), "\"a\\a b\"");
+        assert_eq!(css_string("ü"), "\"ü\"");
+    }
+}
@@ -0,0 +1,1235 @@
+use crate::date::{DayNumber, GameDate, Season};
+use crate::forecast::{self, DayForecast};
+use crate::loot::{
+    ChestItem, ChestItemKind, Enchant, Enchants, Goodie, QuantityModifiers, Rarity, TableOutcome,
+    enchant_outcomes,
+};
+use crate::luck::{LuckDistribution, LuckModel, luck_range, past_threshold};
+use crate::weapons::WeaponKind;
+use crate::{GameSettings, Result, rng};
+
+/// How much each item is worth to the player. The default numbers are a rough desirability
+/// score (resources low, rare equipment high), not gold values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueModel {
+    /// indexed by ChestItemKind as usize. the ostrich egg's is with an incubator to hatch it
+    pub items: [f64; 20],
+    /// an ostrich egg without the incubator, when all it's good for is selling
+    pub ostrich_egg_sold: f64,
+    pub dragon_tooth: f64,
+    /// added to weapons, times their enchant score
+    pub enchant_factor: f64,
+    pub enchant_weights: EnchantWeights,
+}
+
+/// Per-level worth of each innate enchant, for ranking weapon rolls
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnchantWeights {
+    /// indexed by Enchant as usize. the slime enchants have no level and count once.
+    pub per_level: [f64; 8],
+}
+
+impl Default for EnchantWeights {
+    fn default() -> Self {
+        let mut per_level = [0.; 8];
+        per_level[Enchant::Defense as usize] = 1.;
+        // weight levels are negative
+        per_level[Enchant::Weight as usize] = -0.5;
+        per_level[Enchant::SlimeGatherer as usize] = 0.5;
+        per_level[Enchant::SlimeSlayer as usize] = 1.;
+        per_level[Enchant::CritPower as usize] = 1. / 25.;
+        per_level[Enchant::CritChance as usize] = 1.;
+        per_level[Enchant::Attack as usize] = 1.;
+        per_level[Enchant::Speed as usize] = 0.5;
+        EnchantWeights { per_level }
+    }
+}
+
+impl Default for ValueModel {
+    fn default() -> Self {
+        use ChestItemKind::*;
+        let mut items = [0.; 20];
+        for kind in ChestItemKind::ALL {
+            items[kind as usize] = match kind {
+                CinderShards3 => 1.,
+                CinderShards10 => 3.,
+                TaroTuber | PineappleSeeds => 1.,
+                GoldenCoconut => 2.,
+                GoldenCoconuts => 5.,
+                ProtectionRing | SoulSapperRing => 3.,
+                DwarfSword | DwarfHammer | DwarfDagger => 4.,
+                OstrichEgg => 5.,
+                MermaidBoots | HotJavaRing => 8.,
+                DragonscaleBoots | PhoenixRing | DeluxePirateHat => 10.,
+                DragontoothCutlass | DragontoothClub | DragontoothShiv => 10.,
+            };
+        }
+        ValueModel {
+            items,
+            ostrich_egg_sold: 1.5,
+            dragon_tooth: 1.,
+            enchant_factor: 1.,
+            enchant_weights: EnchantWeights::default(),
+        }
+    }
+}
+
+impl Enchants {
+    pub fn score(&self, weights: &EnchantWeights) -> f64 {
+        self.as_slice()
+            .iter()
+            .map(|&(e, lvl)| {
+                let w = weights.per_level[e as usize];
+                if lvl == 0 { w } else { w * lvl as f64 }
+            })
+            .sum()
+    }
+}
+
+/// Rough quality of a weapon's enchants, from its score
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EnchantTier {
+    Junk,
+    Decent,
+    Great,
+    GodRoll,
+}
+
+impl EnchantTier {
+    /// html class of weapons in this tier
+    pub fn css_class(self) -> &'static str {
+        match self {
+            EnchantTier::Junk => "tier-junk",
+            EnchantTier::Decent => "tier-decent",
+            EnchantTier::Great => "tier-great",
+            EnchantTier::GodRoll => "tier-god-roll",
+        }
+    }
+}
+
+/// Lowest score of each tier above junk
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TierThresholds {
+    pub decent: f64,
+    pub great: f64,
+    pub god_roll: f64,
+}
+
+impl Default for TierThresholds {
+    /// with the default weights: a +2 attack is decent, +4 great, and +5 attack with +2 defense
+    /// (the best double roll) a god roll
+    fn default() -> Self {
+        TierThresholds {
+            decent: 2.,
+            great: 4.,
+            god_roll: 7.,
+        }
+    }
+}
+
+impl TierThresholds {
+    pub fn tier(&self, score: f64) -> EnchantTier {
+        if score >= self.god_roll {
+            EnchantTier::GodRoll
+        } else if score >= self.great {
+            EnchantTier::Great
+        } else if score >= self.decent {
+            EnchantTier::Decent
+        } else {
+            EnchantTier::Junk
+        }
+    }
+}
+
+impl ChestItem {
+    /// None for items that aren't weapons
+    pub fn enchant_tier(
+        &self,
+        weights: &EnchantWeights,
+        thresholds: &TierThresholds,
+    ) -> Option<EnchantTier> {
+        self.enchants().map(|e| thresholds.tier(e.score(weights)))
+    }
+}
+
+impl ChestItemKind {
+    /// one-of-a-kind equipment, as opposed to resources you'd want many of
+    pub fn is_unique(self) -> bool {
+        use ChestItemKind::*;
+        !matches!(
+            self,
+            CinderShards3
+                | CinderShards10
+                | GoldenCoconut
+                | GoldenCoconuts
+                | TaroTuber
+                | PineappleSeeds
+                | OstrichEgg
+        )
+    }
+
+    pub fn is_dragontooth_weapon(self) -> bool {
+        use ChestItemKind::*;
+        matches!(self, DragontoothCutlass | DragontoothClub | DragontoothShiv)
+    }
+
+    pub fn exclusivity(self) -> Exclusivity {
+        use ChestItemKind::*;
+        match self {
+            DwarfSword | DwarfHammer | DwarfDagger | MermaidBoots | DragonscaleBoots
+            | DragontoothCutlass | DragontoothClub | DragontoothShiv | DeluxePirateHat => {
+                Exclusivity::ChestExclusive
+            }
+            // the rings also drop from volcano monsters, the seeds are sold by the island trader
+            CinderShards3 | CinderShards10 | GoldenCoconut | GoldenCoconuts | TaroTuber
+            | PineappleSeeds | ProtectionRing | SoulSapperRing | PhoenixRing | HotJavaRing => {
+                Exclusivity::IslandObtainable
+            }
+            OstrichEgg => Exclusivity::CommonElsewhere,
+        }
+    }
+}
+
+/// Where else an item can be gotten
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Exclusivity {
+    /// only from volcano chests
+    ChestExclusive,
+    /// also somewhere else on ginger island
+    IslandObtainable,
+    /// also outside of the island
+    CommonElsewhere,
+}
+
+impl ValueModel {
+    pub fn item_value(&self, item: &ChestItem) -> f64 {
+        self.item_value_with(item, &QuantityModifiers::default())
+    }
+
+    /// like item_value, with the stack's worth scaled by the bonuses of `modifiers`. This and
+    /// item_value count ostrich eggs as hatchable, item_value_for goes by the settings.
+    pub fn item_value_with(&self, item: &ChestItem, modifiers: &QuantityModifiers) -> f64 {
+        self.value_of(item, modifiers, true)
+    }
+
+    /// worth of the item to a player with `settings`: their quantity bonuses, and ostrich eggs
+    /// only count as hatchable with the incubator
+    pub fn item_value_for(&self, item: &ChestItem, settings: &GameSettings) -> f64 {
+        self.value_of(item, &settings.quantities, settings.has_ostrich_incubator)
+    }
+
+    fn value_of(&self, item: &ChestItem, modifiers: &QuantityModifiers, incubator: bool) -> f64 {
+        let enchants = item
+            .enchants()
+            .map_or(0., |e| e.score(&self.enchant_weights));
+        let base = match item.kind() {
+            ChestItemKind::OstrichEgg if !incubator => self.ostrich_egg_sold,
+            kind => self.items[kind as usize],
+        };
+        base * modifiers.multiplier(item.kind()) as f64 + self.enchant_factor * enchants
+    }
+
+    /// Worth of something a chest table gives to a player with `settings`, see Rarity::outcomes.
+    /// Weapons without innate enchantments are worth their base value, enchanted ones the
+    /// average score of what they can roll on top.
+    pub fn outcome_value(&self, outcome: &TableOutcome, settings: &GameSettings) -> f64 {
+        let base = self.item_value_for(
+            &ChestItem::with_enchants(outcome.kind, Enchants::default()),
+            settings,
+        );
+        let weapon = WeaponKind::from_item(outcome.kind).map(|w| w.params());
+        match (outcome.enchanted, weapon) {
+            (Some(true), Some(weapon)) => {
+                let score: f64 = enchant_outcomes(weapon.level, weapon.speed)
+                    .iter()
+                    .map(|(enchants, p)| p * enchants.score(&self.enchant_weights))
+                    .sum();
+                base + self.enchant_factor * score
+            }
+            _ => base,
+        }
+    }
+
+    /// expected worth of a chest of `rarity` whose seed isn't known, by its outcomes
+    pub fn table_value(&self, rarity: Rarity, settings: &GameSettings) -> f64 {
+        rarity
+            .outcomes(settings)
+            .iter()
+            .map(|o| o.probability * self.outcome_value(o, settings))
+            .sum()
+    }
+
+    /// worth of a goodie for a player whose luck is in [min_luck, max_luck]
+    pub fn goodie_value(&self, goodie: &Goodie, min_luck: f64, max_luck: f64) -> f64 {
+        self.goodie_value_with(goodie, min_luck, max_luck, &QuantityModifiers::default())
+    }
+
+    /// like goodie_value, with the quantity bonuses of `modifiers`
+    pub fn goodie_value_with(
+        &self,
+        goodie: &Goodie,
+        min_luck: f64,
+        max_luck: f64,
+        modifiers: &QuantityModifiers,
+    ) -> f64 {
+        self.goodie_value_by(goodie, min_luck, max_luck, &|c| {
+            self.item_value_with(c, modifiers)
+        })
+    }
+
+    /// like goodie_value, for a player with `settings` (see item_value_for)
+    pub fn goodie_value_for(
+        &self,
+        goodie: &Goodie,
+        min_luck: f64,
+        max_luck: f64,
+        settings: &GameSettings,
+    ) -> f64 {
+        self.goodie_value_by(goodie, min_luck, max_luck, &|c| {
+            self.item_value_for(c, settings)
+        })
+    }
+
+    fn goodie_value_by(
+        &self,
+        goodie: &Goodie,
+        min_luck: f64,
+        max_luck: f64,
+        item_value: &dyn Fn(&ChestItem) -> f64,
+    ) -> f64 {
+        match goodie {
+            Goodie::DragonTooth => self.dragon_tooth,
+            Goodie::CommonChest(c) | Goodie::RareChest(c) => item_value(c),
+            Goodie::ChanceChest { common, rare, .. } => {
+                let p = goodie.rare_probability(min_luck, max_luck);
+                p * item_value(rare) + (1. - p) * item_value(common)
+            }
+            // the assumptions are equally likely, as far as we know
+            Goodie::Ambiguous(alts) => {
+                let total: f64 = alts
+                    .iter()
+                    .map(|(_, g)| self.goodie_value_by(g, min_luck, max_luck, item_value))
+                    .sum();
+                total / alts.len().max(1) as f64
+            }
+        }
+    }
+
+    /// expected worth of a day, assuming luck is uniformly distributed over the forecast's luck
+    /// ranges. Items are worth what they are with the day's settings, see item_value_for.
+    pub fn expected_value(&self, day: &DayForecast) -> f64 {
+        day.loot
+            .iter()
+            .map(|floor| {
+                let Some(total) = luck_width(floor) else {
+                    return 0.;
+                };
+                floor
+                    .iter()
+                    .map(|(minl, maxl, loot)| {
+                        let weight = if total > 0. {
+                            (maxl - minl) / total
+                        } else {
+                            1.
+                        };
+                        let value: f64 = loot
+                            .iter()
+                            .map(|g| self.goodie_value_for(g, *minl, *maxl, &day.settings))
+                            .sum();
+                        weight * value
+                    })
+                    .sum::<f64>()
+            })
+            .sum()
+    }
+}
+
+/// Weights for DayForecast::score, the one number answer to "is the volcano worth it today"
+#[derive(Clone, Debug, PartialEq)]
+pub struct DayScoreModel {
+    pub values: ValueModel,
+    /// per point of expected value
+    pub value_weight: f64,
+    /// per chest exclusive item (fractional counting)
+    pub exclusive_weight: f64,
+    /// per tier of the best weapon above junk
+    pub tier_weight: f64,
+    pub tier_thresholds: TierThresholds,
+    /// per point of value the luckiest player gets over the unluckiest. negative, a day that
+    /// depends on luck is worse when you don't know yours.
+    pub luck_weight: f64,
+    /// the score that gets 5 stars
+    pub max_score: f64,
+}
+
+impl Default for DayScoreModel {
+    fn default() -> Self {
+        DayScoreModel {
+            values: ValueModel::default(),
+            value_weight: 0.25,
+            exclusive_weight: 1.,
+            tier_weight: 1.,
+            tier_thresholds: TierThresholds::default(),
+            luck_weight: -0.1,
+            max_score: 10.,
+        }
+    }
+}
+
+impl DayScoreModel {
+    /// `score` as 0 to 5 stars, e.g. "★★★☆☆"
+    pub fn stars(&self, score: f64) -> String {
+        let n = (score / self.max_score * 5.).round().clamp(0., 5.) as usize;
+        format!("{}{}", "★".repeat(n), "☆".repeat(5 - n))
+    }
+}
+
+fn chest_items(goodie: &Goodie) -> Vec<&ChestItem> {
+    match goodie {
+        Goodie::DragonTooth => vec![],
+        Goodie::CommonChest(c) | Goodie::RareChest(c) => vec![c],
+        Goodie::ChanceChest { common, rare, .. } => vec![common, rare],
+        Goodie::Ambiguous(alts) => alts.iter().flat_map(|(_, g)| chest_items(g)).collect(),
+    }
+}
+
+impl DayForecast {
+    /// How good the day is, higher is better. Only comparable between scores of the same model.
+    pub fn score(&self, model: &DayScoreModel) -> f64 {
+        let weights = &model.values.enchant_weights;
+        let best_tier = self
+            .loot
+            .iter()
+            .flatten()
+            .flat_map(|(_, _, loot)| loot.iter().flat_map(chest_items))
+            .filter_map(|c| c.enchant_tier(weights, &model.tier_thresholds))
+            .max()
+            .map_or(0., |t| t as u8 as f64);
+        let (min_luck, max_luck) = luck_range(&self.settings);
+        let luck_sensitivity = model.values.expected_value(&self.at_luck(max_luck))
+            - model.values.expected_value(&self.at_luck(min_luck));
+        model.value_weight * model.values.expected_value(self)
+            + model.exclusive_weight * exclusive_items(self, LuckCounting::Fractional)
+            + model.tier_weight * best_tier
+            + model.luck_weight * luck_sensitivity.abs()
+    }
+}
+
+fn luck_width<T>(floor: &[(f64, f64, T)]) -> Option<f64> {
+    Some(floor.last()?.1 - floor.first()?.0)
+}
+
+/// Rare chest unique items per floor for the luckiest player (maximum over the luck ranges),
+/// summed over all floors
+pub fn rare_uniques(day: &DayForecast) -> u32 {
+    day.loot
+        .iter()
+        .map(|floor| {
+            floor
+                .iter()
+                .map(|(_, _, loot)| {
+                    loot.iter()
+                        .filter(|g| {
+                            g.as_chest()
+                                .is_some_and(|(r, c)| r == Rarity::Rare && c.is_unique())
+                        })
+                        .count() as u32
+                })
+                .max()
+                .unwrap_or(0)
+        })
+        .sum()
+}
+
+/// How items that only some luck ranges get are counted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LuckCounting {
+    /// weighted by the share of the luck range that gets them, like expected_value
+    #[default]
+    Fractional,
+    /// counted if any luck gets them, like rare_uniques
+    Optimistic,
+}
+
+fn kind_count(
+    goodie: &Goodie,
+    kind: ChestItemKind,
+    min_luck: f64,
+    max_luck: f64,
+    counting: LuckCounting,
+) -> f64 {
+    match goodie {
+        Goodie::DragonTooth => 0.,
+        Goodie::CommonChest(c) | Goodie::RareChest(c) => (c.kind() == kind) as u8 as f64,
+        Goodie::ChanceChest { common, rare, .. } => {
+            let p = goodie.rare_probability(min_luck, max_luck);
+            match counting {
+                LuckCounting::Fractional => {
+                    p * (rare.kind() == kind) as u8 as f64
+                        + (1. - p) * (common.kind() == kind) as u8 as f64
+                }
+                LuckCounting::Optimistic => {
+                    (rare.kind() == kind || common.kind() == kind) as u8 as f64
+                }
+            }
+        }
+        Goodie::Ambiguous(alts) => {
+            let counts = alts
+                .iter()
+                .map(|(_, g)| kind_count(g, kind, min_luck, max_luck, counting));
+            match counting {
+                LuckCounting::Fractional => counts.sum::<f64>() / alts.len().max(1) as f64,
+                LuckCounting::Optimistic => counts.fold(0., f64::max),
+            }
+        }
+    }
+}
+
+/// How often each of `kinds` comes out of a chest on one day, in the same order
+pub fn item_counts(day: &DayForecast, kinds: &[ChestItemKind], counting: LuckCounting) -> Vec<f64> {
+    let mut out = vec![0.; kinds.len()];
+    for floor in &day.loot {
+        let Some(total) = luck_width(floor) else {
+            continue;
+        };
+        let mut floor_counts = vec![0.; kinds.len()];
+        for (minl, maxl, loot) in floor {
+            let weight = if total > 0. {
+                (maxl - minl) / total
+            } else {
+                1.
+            };
+            for (k, &kind) in kinds.iter().enumerate() {
+                let count: f64 = loot
+                    .iter()
+                    .map(|g| kind_count(g, kind, *minl, *maxl, counting))
+                    .sum();
+                match counting {
+                    LuckCounting::Fractional => floor_counts[k] += weight * count,
+                    LuckCounting::Optimistic => floor_counts[k] = f64::max(floor_counts[k], count),
+                }
+            }
+        }
+        for (o, c) in out.iter_mut().zip(floor_counts) {
+            *o += c;
+        }
+    }
+    out
+}
+
+/// Like item_counts, but how many of each item that is: times the stack size, with the quantity
+/// bonuses of the day's settings
+pub fn item_amounts(
+    day: &DayForecast,
+    kinds: &[ChestItemKind],
+    counting: LuckCounting,
+) -> Vec<f64> {
+    item_counts(day, kinds, counting)
+        .into_iter()
+        .zip(kinds)
+        .map(|(count, &kind)| count * day.settings.quantities.apply(kind) as f64)
+        .collect()
+}
+
+/// Chest exclusive items on the day, over all floors
+pub fn exclusive_items(day: &DayForecast, counting: LuckCounting) -> f64 {
+    let kinds: Vec<ChestItemKind> = ChestItemKind::ALL
+        .into_iter()
+        .filter(|k| k.exclusivity() == Exclusivity::ChestExclusive)
+        .collect();
+    item_counts(day, &kinds, counting).iter().sum()
+}
+
+/// whether anyone with exactly this luckMult finds `target` in a chest on the day
+fn found_at_luck(day: &DayForecast, target: ChestItemKind, luck: f64) -> bool {
+    day.loot.iter().any(|floor| {
+        let range = floor
+            .iter()
+            .find(|(minl, maxl, _)| *minl <= luck && luck <= *maxl);
+        range.is_some_and(|(_, _, loot)| {
+            loot.iter().any(|g| match g {
+                Goodie::CommonChest(c) | Goodie::RareChest(c) => c.kind() == target,
+                Goodie::ChanceChest {
+                    minluck,
+                    common,
+                    rare,
+                } => {
+                    let c = if past_threshold(*minluck, luck) {
+                        rare
+                    } else {
+                        common
+                    };
+                    c.kind() == target
+                }
+                // the settings are known here, so these don't come up
+                Goodie::DragonTooth | Goodie::Ambiguous(_) => false,
+            })
+        })
+    })
+}
+
+/// Chance of finding `target` in at least one chest of the day. The chests of a day aren't
+/// independent, they all get the same luck. But once the luck is known the whole day is, so this
+/// adds up the luck ranges in which the item is found.
+pub fn day_probability(day: &DayForecast, target: ChestItemKind, luck: &dyn LuckModel) -> f64 {
+    let luck = luck.distribution(DayNumber(day.days_played));
+    let (lo, hi) = match luck {
+        LuckDistribution::Uniform(lo, hi) if hi > lo => (lo, hi),
+        LuckDistribution::Uniform(luck, _) | LuckDistribution::Fixed(luck) => {
+            return found_at_luck(day, target, luck) as u8 as f64;
+        }
+    };
+    // nothing changes between these
+    let mut cuts = vec![lo, hi];
+    for (minl, maxl, loot) in day.loot.iter().flatten() {
+        cuts.extend([*minl, *maxl]);
+        cuts.extend(loot.iter().filter_map(|g| match g {
+            Goodie::ChanceChest { minluck, .. } => Some(*minluck),
+            _ => None,
+        }));
+    }
+    cuts.retain(|x| (lo..=hi).contains(x));
+    cuts.sort_by(f64::total_cmp);
+    cuts.dedup();
+    cuts.windows(2)
+        .filter(|w| found_at_luck(day, target, (w[0] + w[1]) / 2.))
+        .map(|w| luck.probability(w[0], w[1]))
+        .sum()
+}
+
+/// Chance of finding `target` at least once in the `days` days from `start_day`, going every day.
+/// The days are treated as independent: each gets its own daily luck from `luck`, and one day's
+/// loot doesn't change another's (which it doesn't, the seeds only depend on the day).
+pub fn prob_at_least_one(
+    game_seed: i32,
+    start_day: impl Into<DayNumber>,
+    days: u32,
+    target: ChestItemKind,
+    settings: &GameSettings,
+    luck: &dyn LuckModel,
+) -> Result<f64> {
+    let settings = GameSettings {
+        seed: game_seed,
+        ..*settings
+    };
+    let none: f64 = forecast::range_with_luck(&settings, start_day, days, luck)?
+        .iter()
+        .map(|day| 1. - day_probability(day, target, luck))
+        .product();
+    Ok(1. - none)
+}
+
+/// Counts of some items per season of one year, e.g. for a heatmap
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeasonalCounts {
+    pub year: u32,
+    kinds: Vec<ChestItemKind>,
+    /// per kind, indexed by season
+    counts: Vec<[f64; 4]>,
+}
+
+impl SeasonalCounts {
+    pub fn generate(
+        game_seed: i32,
+        year: u32,
+        settings: &GameSettings,
+        kinds: &[ChestItemKind],
+        counting: LuckCounting,
+    ) -> Result<Self> {
+        let start = DayNumber::from(GameDate::new(year, Season::Spring, 1)?);
+        let settings = GameSettings {
+            seed: game_seed,
+            ..*settings
+        };
+        let mut counts = vec![[0.; 4]; kinds.len()];
+        for day in forecast::range(&settings, start, 4 * 28)? {
+            let season = GameDate::from(DayNumber(day.days_played)).season as usize;
+            for (c, n) in counts.iter_mut().zip(item_counts(&day, kinds, counting)) {
+                c[season] += n;
+            }
+        }
+        Ok(SeasonalCounts {
+            year,
+            kinds: kinds.to_vec(),
+            counts,
+        })
+    }
+
+    pub fn kinds(&self) -> &[ChestItemKind] {
+        &self.kinds
+    }
+
+    /// 0 for kinds that weren't counted
+    pub fn get(&self, kind: ChestItemKind, season: Season) -> f64 {
+        self.kinds
+            .iter()
+            .zip(&self.counts)
+            .find(|(k, _)| **k == kind)
+            .map_or(0., |(_, c)| c[season as usize])
+    }
+
+    /// all counted kinds together
+    pub fn season_total(&self, season: Season) -> f64 {
+        self.counts.iter().map(|c| c[season as usize]).sum()
+    }
+
+    pub fn year_total(&self, kind: ChestItemKind) -> f64 {
+        Season::ALL.into_iter().map(|s| self.get(kind, s)).sum()
+    }
+}
+
+/// How often something happened in a sample
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Frequency {
+    pub count: u32,
+    pub total: u32,
+}
+
+impl Frequency {
+    /// 0 for an empty sample
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            return 0.;
+        }
+        self.count as f64 / self.total as f64
+    }
+
+    /// Wilson score interval for the real probability, `z` = 1.96 for 95%
+    pub fn wilson_interval(&self, z: f64) -> (f64, f64) {
+        if self.total == 0 {
+            return (0., 1.);
+        }
+        let n = self.total as f64;
+        let p = self.fraction();
+        let z2 = z * z;
+        let center = (p + z2 / (2. * n)) / (1. + z2 / n);
+        let half = z / (1. + z2 / n) * (p * (1. - p) / n + z2 / (4. * n * n)).sqrt();
+        ((center - half).max(0.), (center + half).min(1.))
+    }
+}
+
+/// Chest items counted over many random days, see long_run_frequencies
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrequencyTable {
+    /// days that were sampled
+    pub days: u32,
+    /// indexed by ChestItemKind as usize
+    common: [u32; ChestItemKind::ALL.len()],
+    rare: [u32; ChestItemKind::ALL.len()],
+    common_chests: u32,
+    rare_chests: u32,
+}
+
+impl FrequencyTable {
+    /// share of the common chests that gave `kind`
+    pub fn common(&self, kind: ChestItemKind) -> Frequency {
+        Frequency {
+            count: self.common[kind as usize],
+            total: self.common_chests,
+        }
+    }
+
+    /// share of the rare chests that gave `kind`
+    pub fn rare(&self, kind: ChestItemKind) -> Frequency {
+        Frequency {
+            count: self.rare[kind as usize],
+            total: self.rare_chests,
+        }
+    }
+
+    /// share of all chests that gave `kind`
+    pub fn any(&self, kind: ChestItemKind) -> Frequency {
+        Frequency {
+            count: self.common[kind as usize] + self.rare[kind as usize],
+            total: self.common_chests + self.rare_chests,
+        }
+    }
+
+    /// share of the chests that were rare
+    pub fn rare_chests(&self) -> Frequency {
+        Frequency {
+            count: self.rare_chests,
+            total: self.common_chests + self.rare_chests,
+        }
+    }
+}
+
+/// Counts what the chests on `levels` give over `sample_days` random days of random saves, each
+/// with its own luck from `luck`, clamped to the settings' luck range. The saves and days (within
+/// the first 10 years) come from `meta_seed`, so the same arguments give the same table. Unlike
+/// the loot tables, this includes the rarity roll, coconut rerolls and the layouts.
+pub fn long_run_frequencies(
+    settings: &GameSettings,
+    levels: &[i32],
+    sample_days: u32,
+    meta_seed: i32,
+    luck: &dyn LuckModel,
+) -> FrequencyTable {
+    let mut table = FrequencyTable {
+        days: sample_days,
+        common: [0; ChestItemKind::ALL.len()],
+        rare: [0; ChestItemKind::ALL.len()],
+        common_chests: 0,
+        rare_chests: 0,
+    };
+    let mut rng = rng::DotnetRng::new(meta_seed);
+    for _ in 0..sample_days {
+        let settings = GameSettings {
+            seed: rng.next(),
+            days_played: rng.next_range(10 * 112) as u32 + 1,
+            ..*settings
+        };
+        let luck = match luck.distribution(DayNumber(settings.days_played)) {
+            LuckDistribution::Fixed(luck) => luck,
+            LuckDistribution::Uniform(lo, hi) => lo + rng.next_f64() * (hi - lo),
+        };
+        let (min_luck, max_luck) = luck_range(&settings);
+        let fixed = LuckDistribution::Fixed(luck.clamp(min_luck, max_luck));
+        let day = forecast::range_with_luck(&settings, settings.days_played, 1, &fixed)
+            .expect("one day in the first 10 years")
+            .remove(0);
+        for &level in levels {
+            let Some(floor) = day.loot.get(level as usize) else {
+                continue;
+            };
+            // at one luck there are no chance chests
+            for goodie in floor.iter().flat_map(|(_, _, loot)| loot) {
+                let (rare, item) = match goodie {
+                    Goodie::CommonChest(c) => (false, c),
+                    Goodie::RareChest(c) => (true, c),
+                    _ => continue,
+                };
+                if rare {
+                    table.rare[item.kind() as usize] += 1;
+                    table.rare_chests += 1;
+                } else {
+                    table.common[item.kind() as usize] += 1;
+                    table.common_chests += 1;
+                }
+            }
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{OutputFormat, Render, RenderOptions};
+
+    fn settings() -> GameSettings {
+        GameSettings {
+            seed: 31337,
+            has_caldera: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn seasonal_counts_agree_with_the_days() {
+        // only in rare chests, so rare_uniques counts the same items
+        let kinds = [
+            ChestItemKind::MermaidBoots,
+            ChestItemKind::DragonscaleBoots,
+            ChestItemKind::PhoenixRing,
+            ChestItemKind::HotJavaRing,
+            ChestItemKind::DragontoothCutlass,
+            ChestItemKind::DragontoothClub,
+            ChestItemKind::DragontoothShiv,
+            ChestItemKind::DeluxePirateHat,
+        ];
+        let settings = settings();
+        for counting in [LuckCounting::Fractional, LuckCounting::Optimistic] {
+            let counts = SeasonalCounts::generate(7, 2, &settings, &kinds, counting).unwrap();
+            assert_eq!(counts.kinds(), kinds);
+            // summer of year 2, one day at a time
+            let start = DayNumber::from(GameDate::new(2, Season::Summer, 1).unwrap()).0;
+            let days: Vec<DayForecast> = (start..start + 28)
+                .map(|days_played| {
+                    DayForecast::generate(&GameSettings {
+                        seed: 7,
+                        days_played,
+                        ..settings.clone()
+                    })
+                })
+                .collect();
+            for (k, &kind) in kinds.iter().enumerate() {
+                let slow: f64 = days
+                    .iter()
+                    .map(|d| item_counts(d, &kinds, counting)[k])
+                    .sum();
+                let fast = counts.get(kind, Season::Summer);
+                assert!((fast - slow).abs() < 1e-9, "{:?} {:?}", kind, counting);
+            }
+            let uniques: u32 = days.iter().map(rare_uniques).sum();
+            let total = counts.season_total(Season::Summer);
+            match counting {
+                LuckCounting::Optimistic => assert!(total >= uniques as f64),
+                LuckCounting::Fractional => assert!(total <= uniques as f64),
+            }
+            assert!(total > 0.);
+            let year: f64 = Season::ALL
+                .into_iter()
+                .map(|s| counts.season_total(s))
+                .sum();
+            let by_kind: f64 = kinds.iter().map(|&k| counts.year_total(k)).sum();
+            assert!((year - by_kind).abs() < 1e-9);
+        }
+        assert_eq!(
+            SeasonalCounts::generate(7, 2, &settings, &kinds, LuckCounting::Fractional)
+                .unwrap()
+                .get(ChestItemKind::TaroTuber, Season::Fall),
+            0.
+        );
+    }
+
+    #[test]
+    fn shard_multiplier() {
+        let doubled = GameSettings {
+            quantities: QuantityModifiers::new(2, 1).unwrap(),
+            ..settings()
+        };
+        let kinds = [
+            ChestItemKind::CinderShards3,
+            ChestItemKind::CinderShards10,
+            ChestItemKind::TaroTuber,
+        ];
+        let plain_days = forecast::range(&settings(), 1, 28).unwrap();
+        let doubled_days = forecast::range(&doubled, 1, 28).unwrap();
+        let model = ValueModel::default();
+        let mut shards = 0.;
+        for (plain, doubled) in plain_days.iter().zip(&doubled_days) {
+            // the same items, only the counts change
+            assert_eq!(plain.loot, doubled.loot);
+            let counts = item_counts(plain, &kinds, LuckCounting::Fractional);
+            assert_eq!(
+                counts,
+                item_counts(doubled, &kinds, LuckCounting::Fractional)
+            );
+            let plain_amounts = item_amounts(plain, &kinds, LuckCounting::Fractional);
+            let amounts = item_amounts(doubled, &kinds, LuckCounting::Fractional);
+            let taro = ChestItemKind::TaroTuber.quantity() as f64;
+            assert_eq!(
+                plain_amounts,
+                [counts[0] * 3., counts[1] * 10., counts[2] * taro]
+            );
+            assert_eq!(amounts, [counts[0] * 6., counts[1] * 20., counts[2] * taro]);
+            shards += counts[0] + counts[1];
+            if counts[0] + counts[1] > 0. {
+                assert!(model.expected_value(doubled) > model.expected_value(plain));
+            } else {
+                assert_eq!(model.expected_value(doubled), model.expected_value(plain));
+            }
+        }
+        assert!(shards > 0.);
+
+        let item = ChestItem::with_enchants(ChestItemKind::CinderShards3, Default::default());
+        let modifiers = doubled.quantities;
+        assert_eq!(
+            model.item_value_with(&item, &modifiers),
+            2. * model.item_value(&item)
+        );
+        let opts = RenderOptions {
+            format: OutputFormat::Plain,
+            quantities: modifiers,
+            ..Default::default()
+        };
+        let goodie = Goodie::CommonChest(item);
+        assert!(
+            goodie
+                .try_render_to_string(&opts)
+                .unwrap()
+                .contains("Cinder Shard (6)")
+        );
+        let with_base = RenderOptions {
+            show_base_quantity: true,
+            ..opts
+        };
+        assert!(
+            goodie
+                .try_render_to_string(&with_base)
+                .unwrap()
+                .contains("Cinder Shard (6, base 3)")
+        );
+    }
+
+    #[test]
+    fn ostrich_incubator() {
+        let settings = settings();
+        let day = forecast::range(&settings, 1, 112)
+            .unwrap()
+            .into_iter()
+            .find(|day| {
+                item_counts(day, &[ChestItemKind::OstrichEgg], LuckCounting::Optimistic)[0] > 0.
+            })
+            .expect("an ostrich egg in the first year");
+        let hatching = DayForecast::generate(&GameSettings {
+            has_ostrich_incubator: true,
+            days_played: day.days_played,
+            ..settings
+        });
+        assert_eq!(day.loot, hatching.loot);
+        let model = ValueModel::default();
+        assert!(model.expected_value(&hatching) > model.expected_value(&day));
+
+        let opts = RenderOptions {
+            exclusive_badge: true,
+            ..Default::default()
+        };
+        assert!(
+            day.try_render_to_string(&opts)
+                .unwrap()
+                .contains("needs an incubator")
+        );
+        assert!(
+            !hatching
+                .try_render_to_string(&opts)
+                .unwrap()
+                .contains("needs an incubator")
+        );
+    }
+
+    #[test]
+    fn table_value_by_outcomes() {
+        let model = ValueModel::default();
+        let settings = settings();
+        for rarity in [Rarity::Common, Rarity::Rare] {
+            let outcomes = rarity.outcomes(&settings);
+            for o in outcomes.iter().filter(|o| o.enchanted.is_some()) {
+                let base = model.item_value(&ChestItem::with_enchants(o.kind, Enchants::default()));
+                match o.enchanted {
+                    Some(false) => assert_eq!(model.outcome_value(o, &settings), base),
+                    _ => assert!(model.outcome_value(o, &settings) > base),
+                }
+            }
+            let samples = 40000;
+            let sampled: f64 = (0..samples)
+                .map(|i| {
+                    let item = ChestItem::generate(rarity, i * 7919 + 13, &settings);
+                    model.item_value_for(&item, &settings)
+                })
+                .sum::<f64>()
+                / samples as f64;
+            let value = model.table_value(rarity, &settings);
+            assert!(
+                (sampled - value).abs() < 0.02 * value,
+                "{:?}: {} sampled, {} by the outcomes",
+                rarity,
+                sampled,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn long_run_frequencies_clamp_the_luck() {
+        let settings = settings();
+        let (lo, hi) = luck_range(&settings);
+        let sample =
+            |luck: LuckDistribution| long_run_frequencies(&settings, &[1, 5, 9], 40, 7, &luck);
+        let lucky = sample(LuckDistribution::Fixed(hi));
+        assert_eq!(sample(LuckDistribution::Fixed(hi + 1.)), lucky);
+        assert_eq!(
+            sample(LuckDistribution::Fixed(lo - 1.)),
+            sample(LuckDistribution::Fixed(lo))
+        );
+        // sampling takes a roll, so these are other days than the fixed ones
+        assert_eq!(
+            sample(LuckDistribution::Uniform(hi + 1., hi + 2.)),
+            sample(LuckDistribution::Uniform(hi, hi))
+        );
+        let common: u32 = ChestItemKind::ALL
+            .iter()
+            .map(|&k| lucky.common(k).count)
+            .sum();
+        let rare: u32 = ChestItemKind::ALL
+            .iter()
+            .map(|&k| lucky.rare(k).count)
+            .sum();
+        assert_eq!(
+            common,
+            lucky.rare_chests().total - lucky.rare_chests().count
+        );
+        assert_eq!(rare, lucky.rare_chests().count);
+        assert!(rare > 0 && common > 0);
+    }
+
+    #[test]
+    fn exclusivity_of_every_kind() {
+        use ChestItemKind::*;
+        use Exclusivity::*;
+        let expected = [
+            (CinderShards3, IslandObtainable),
+            (GoldenCoconut, IslandObtainable),
+            (TaroTuber, IslandObtainable),
+            (PineappleSeeds, IslandObtainable),
+            (ProtectionRing, IslandObtainable),
+            (SoulSapperRing, IslandObtainable),
+            (DwarfSword, ChestExclusive),
+            (DwarfHammer, ChestExclusive),
+            (DwarfDagger, ChestExclusive),
+            (CinderShards10, IslandObtainable),
+            (MermaidBoots, ChestExclusive),
+            (DragonscaleBoots, ChestExclusive),
+            (GoldenCoconuts, IslandObtainable),
+            (PhoenixRing, IslandObtainable),
+            (HotJavaRing, IslandObtainable),
+            (DragontoothCutlass, ChestExclusive),
+            (DragontoothClub, ChestExclusive),
+            (DragontoothShiv, ChestExclusive),
+            (DeluxePirateHat, ChestExclusive),
+            (OstrichEgg, CommonElsewhere),
+        ];
+        assert_eq!(expected.map(|(k, _)| k), ChestItemKind::ALL);
+        for (kind, exclusivity) in expected {
+            assert_eq!(kind.exclusivity(), exclusivity, "{:?}", kind);
+        }
+    }
+
+    /// a forecast with `loot` at every luck
+    fn handmade_day(days_played: u32, loot: [Vec<Goodie>; 10]) -> DayForecast {
+        let (lo, hi) = luck_range(&settings());
+        DayForecast {
+            days_played,
+            settings: settings(),
+            layouts: std::array::from_fn(|_| vec![(lo, hi, 0)]),
+            loot: loot.map(|goodies| vec![(lo, hi, goodies)]),
+            calendar_events: vec![],
+        }
+    }
+
+    #[test]
+    fn enchant_tiers() {
+        let weights = EnchantWeights::default();
+        let thresholds = TierThresholds::default();
+        let shiv = |levels: &[(Enchant, i32)]| {
+            ChestItem::DragontoothShiv(Enchants::from_levels(levels.to_vec()))
+        };
+        let items = [
+            (shiv(&[]), EnchantTier::Junk),
+            (shiv(&[(Enchant::Attack, 3)]), EnchantTier::Decent),
+            (
+                shiv(&[(Enchant::Attack, 5), (Enchant::Defense, 2)]),
+                EnchantTier::GodRoll,
+            ),
+        ];
+        assert_eq!(
+            ChestItem::PhoenixRing.enchant_tier(&weights, &thresholds),
+            None
+        );
+        let mut loot: [Vec<Goodie>; 10] = Default::default();
+        for (item, tier) in &items {
+            assert_eq!(item.enchant_tier(&weights, &thresholds), Some(*tier));
+            loot[3].push(Goodie::RareChest(item.clone()));
+        }
+        loot[3].push(Goodie::RareChest(ChestItem::PhoenixRing));
+        let day = handmade_day(1, loot);
+        let opts = RenderOptions {
+            enchant_tiers: Some((&weights, thresholds)),
+            ..RenderOptions::with_format(OutputFormat::Html)
+        };
+        let html = day.try_render_to_string(&opts).unwrap();
+        let lines: Vec<&str> = html.lines().filter(|l| l.contains("<li")).collect();
+        assert_eq!(lines.len(), 4);
+        for (line, (_, tier)) in lines.iter().zip(&items) {
+            assert!(line.contains(&format!("<span class={}>", tier.css_class())));
+        }
+        assert!(!lines[3].contains("class=tier-"));
+        let untiered = day
+            .try_render_to_string(&RenderOptions::with_format(OutputFormat::Html))
+            .unwrap();
+        assert!(!untiered.contains("tier-"));
+    }
+
+    #[test]
+    fn ranking_days() {
+        let mut shards: [Vec<Goodie>; 10] = Default::default();
+        shards[1] = vec![Goodie::CommonChest(ChestItem::CinderShards10); 4];
+        let mut hat: [Vec<Goodie>; 10] = Default::default();
+        hat[2] = vec![Goodie::RareChest(ChestItem::DeluxePirateHat)];
+        let mut club: [Vec<Goodie>; 10] = Default::default();
+        club[3] = vec![Goodie::RareChest(ChestItem::DragontoothClub(
+            Enchants::from_levels(vec![(Enchant::Attack, 5), (Enchant::Defense, 2)]),
+        ))];
+        let days = [
+            handmade_day(1, shards),
+            handmade_day(2, hat),
+            handmade_day(3, club),
+        ];
+        let ranked = |model: &DayScoreModel| -> Vec<u32> {
+            forecast::best_days(&days, 3, model)
+                .iter()
+                .map(|d| d.days_played)
+                .collect()
+        };
+        let model = DayScoreModel::default();
+        // the god roll, then the exclusive hat over more shards
+        assert_eq!(ranked(&model), [3, 2, 1]);
+        assert_eq!(model.stars(days[2].score(&model)), "★★★★☆");
+        assert_eq!(model.stars(-1.), "☆☆☆☆☆");
+        assert_eq!(model.stars(100.), "★★★★★");
+        let no_exclusives = DayScoreModel {
+            exclusive_weight: 0.,
+            ..DayScoreModel::default()
+        };
+        assert_eq!(ranked(&no_exclusives), [3, 1, 2]);
+        let opts = RenderOptions {
+            day_score: Some(&model),
+            ..RenderOptions::with_format(OutputFormat::Plain)
+        };
+        let text = days.try_render_to_string(&opts).unwrap();
+        assert!(text.contains("★★★★☆"));
+        assert!(
+            !days
+                .try_render_to_string(&RenderOptions::default())
+                .unwrap()
+                .contains('★')
+        );
+        assert_eq!(
+            forecast::best_days(&days, 1, &model)
+                .iter()
+                .map(|d| d.days_played)
+                .collect::<Vec<_>>(),
+            [3]
+        );
+    }
+
+    #[test]
+    fn at_least_one_like_simulating_it() {
+        let settings = settings();
+        let luck = LuckDistribution::daily(&settings, 0);
+        let (lo, hi) = luck.boost_range(DayNumber(1));
+        let mut rng = rng::DotnetRng::new(149);
+        for (kind, start) in [
+            (ChestItemKind::PhoenixRing, 29),
+            (ChestItemKind::DeluxePirateHat, 1),
+        ] {
+            let p = prob_at_least_one(settings.seed, start, 28, kind, &settings, &luck).unwrap();
+            // only the days with the item at some luck can change the outcome
+            let days: Vec<DayForecast> = forecast::range_with_luck(&settings, start, 28, &luck)
+                .unwrap()
+                .into_iter()
+                .filter(|day| item_counts(day, &[kind], LuckCounting::Optimistic)[0] > 0.)
+                .collect();
+            let found_per_day = |day: &DayForecast, luck: f64| {
+                item_counts(&day.at_luck(luck), &[kind], LuckCounting::Fractional)[0] > 0.
+            };
+            let trials = 40_000;
+            let hits = (0..trials)
+                .filter(|_| {
+                    // every day gets its own daily luck, shared by all of its chests
+                    days.iter()
+                        .any(|day| found_per_day(day, lo + (hi - lo) * rng.next_f64()))
+                })
+                .count();
+            let simulated = hits as f64 / trials as f64;
+            assert!(
+                (p - simulated).abs() < 0.01,
+                "{:?}: {} vs {}",
+                kind,
+                p,
+                simulated
+            );
+        }
+    }
+}
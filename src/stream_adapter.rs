@@ -0,0 +1,111 @@
+//! Forecasts generated on their own thread and sent through a bounded channel, for async servers
+//! that can't block their executor on generation. The channel's capacity is the back-pressure:
+//! when the consumer is slow, generation waits for it. Needs threads, so not for wasm.
+//!
+//! For an async consumer, receive on a blocking task (or poll `try_recv`), the crate doesn't
+//! depend on any runtime.
+
+use crate::date::{DayNumber, MAX_DAY_RANGE};
+use crate::forecast::{self, DayForecast};
+use crate::{GameSettings, Result};
+use std::sync::mpsc::SyncSender;
+use std::thread::{self, JoinHandle};
+
+/// Runs `iter` on a new thread and sends its items in chunks of `chunk` (at least 1), the last
+/// one can be shorter. With `sender` from `mpsc::sync_channel(capacity)`, at most `capacity`
+/// chunks wait in the channel and one more is being filled. Dropping the receiver stops the
+/// thread at its next send, without finishing the iterator.
+pub fn blocking_iter_to_channel<I>(
+    iter: I,
+    chunk: usize,
+    sender: SyncSender<Vec<I::Item>>,
+) -> JoinHandle<()>
+where
+    I: Iterator + Send + 'static,
+    I::Item: Send + 'static,
+{
+    let chunk = chunk.max(1);
+    thread::spawn(move || {
+        let mut iter = iter.fuse();
+        loop {
+            let items: Vec<I::Item> = iter.by_ref().take(chunk).collect();
+            if items.is_empty() || sender.send(items).is_err() {
+                return;
+            }
+        }
+    })
+}
+
+/// The days of forecast::range one at a time, generated as they're asked for. Fails right away
+/// for the ranges forecast::range doesn't take.
+pub fn days(
+    settings: GameSettings,
+    start_day: impl Into<DayNumber>,
+    days: u32,
+) -> Result<impl Iterator<Item = DayForecast> + Send + 'static> {
+    let span = start_day.into().checked_span(days, MAX_DAY_RANGE)?;
+    Ok(span
+        .flat_map(move |day| forecast::range(&settings, day, 1).expect("days in a checked span")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    /// 0.. counting how many items were taken from it
+    fn counted(taken: &Arc<AtomicUsize>) -> impl Iterator<Item = usize> + Send + 'static {
+        let taken = Arc::clone(taken);
+        (0..).inspect(move |_| {
+            taken.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+
+    #[test]
+    fn dropping_the_receiver_stops_the_producer() {
+        let taken = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = mpsc::sync_channel(2);
+        let producer = blocking_iter_to_channel(counted(&taken), 10, sender);
+        assert_eq!(receiver.recv().unwrap(), (0..10).collect::<Vec<_>>());
+        drop(receiver);
+        // the iterator never ends, so this only returns because the send failed
+        producer.join().unwrap();
+        // the one received, 2 in the channel, and the one that couldn't be sent
+        assert!(taken.load(Ordering::SeqCst) <= 40);
+    }
+
+    #[test]
+    fn slow_consumer_gets_everything_in_order() {
+        let (capacity, chunk) = (3, 7);
+        let taken = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let producer = blocking_iter_to_channel(counted(&taken).take(500), chunk, sender);
+        let mut received = vec![];
+        while let Ok(items) = receiver.recv() {
+            assert!(!items.is_empty() && items.len() <= chunk);
+            received.extend(items);
+            thread::sleep(Duration::from_millis(1));
+            // waiting in the channel, being filled and blocked in send
+            assert!(taken.load(Ordering::SeqCst) <= received.len() + (capacity + 2) * chunk);
+        }
+        producer.join().unwrap();
+        assert_eq!(received, (0..500).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn days_match_the_range() {
+        let settings = GameSettings::default();
+        let expected = forecast::range(&settings, 30, 5).unwrap();
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let producer = blocking_iter_to_channel(days(settings.clone(), 30, 5).unwrap(), 2, sender);
+        let received: Vec<DayForecast> = receiver.into_iter().flatten().collect();
+        producer.join().unwrap();
+        // DayForecast has no PartialEq
+        assert_eq!(format!("{:?}", received), format!("{:?}", expected));
+        assert!(days(settings.clone(), 0, 5).is_err());
+        assert!(days(settings, 1, u32::MAX).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! Stats of the weapons volcano chests can give. Level and speed go into the innate enchantment
+//! rolls, the damage is just for showing.
+//!
+//! Only these six are in the table, the other melee weapons would need their levels from the
+//! game data. Any other weapon can still be rolled with forge::reroll_sequence_for_stats, and the
+//! rolls are fuzzed over levels 1 to 20 and speeds -8 to 8, plus speeds that would overflow.
+
+use crate::loot::ChestItemKind;
+
+/// The weapons that can get innate enchantments from volcano chests
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WeaponKind {
+    DwarfSword,
+    DwarfHammer,
+    DwarfDagger,
+    DragontoothCutlass,
+    DragontoothClub,
+    DragontoothShiv,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WeaponParams {
+    pub kind: WeaponKind,
+    pub level: i32,
+    pub speed: i32,
+    pub base_damage_min: i32,
+    pub base_damage_max: i32,
+}
+
+const fn weapon(
+    kind: WeaponKind,
+    level: i32,
+    speed: i32,
+    base_damage_min: i32,
+    base_damage_max: i32,
+) -> WeaponParams {
+    WeaponParams {
+        kind,
+        level,
+        speed,
+        base_damage_min,
+        base_damage_max,
+    }
+}
+
+/// indexed by WeaponKind as usize
+pub const VOLCANO_WEAPONS: [WeaponParams; 6] = [
+    weapon(WeaponKind::DwarfSword, 13, 4, 65, 75),
+    weapon(WeaponKind::DwarfHammer, 13, -8, 75, 85),
+    weapon(WeaponKind::DwarfDagger, 11, 3, 32, 38),
+    weapon(WeaponKind::DragontoothCutlass, 13, 0, 75, 90),
+    weapon(WeaponKind::DragontoothClub, 14, -8, 80, 100),
+    weapon(WeaponKind::DragontoothShiv, 12, 0, 40, 50),
+];
+
+impl WeaponKind {
+    pub const fn params(self) -> &'static WeaponParams {
+        &VOLCANO_WEAPONS[self as usize]
+    }
+
+    /// None for items that aren't weapons
+    pub fn from_item(kind: ChestItemKind) -> Option<Self> {
+        match kind {
+            ChestItemKind::DwarfSword => Some(WeaponKind::DwarfSword),
+            ChestItemKind::DwarfHammer => Some(WeaponKind::DwarfHammer),
+            ChestItemKind::DwarfDagger => Some(WeaponKind::DwarfDagger),
+            ChestItemKind::DragontoothCutlass => Some(WeaponKind::DragontoothCutlass),
+            ChestItemKind::DragontoothClub => Some(WeaponKind::DragontoothClub),
+            ChestItemKind::DragontoothShiv => Some(WeaponKind::DragontoothShiv),
+            _ => None,
+        }
+    }
+}
+
+impl From<WeaponKind> for ChestItemKind {
+    fn from(weapon: WeaponKind) -> Self {
+        match weapon {
+            WeaponKind::DwarfSword => ChestItemKind::DwarfSword,
+            WeaponKind::DwarfHammer => ChestItemKind::DwarfHammer,
+            WeaponKind::DwarfDagger => ChestItemKind::DwarfDagger,
+            WeaponKind::DragontoothCutlass => ChestItemKind::DragontoothCutlass,
+            WeaponKind::DragontoothClub => ChestItemKind::DragontoothClub,
+            WeaponKind::DragontoothShiv => ChestItemKind::DragontoothShiv,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loot::{Enchant, Enchants, enchant_item_traced};
+    use crate::rng::DotnetRng;
+
+    fn enchant(seed: i32, level: i32, speed: i32) -> Enchants {
+        enchant_item_traced(&mut DotnetRng::new(seed), level, speed, true).0
+    }
+
+    #[test]
+    fn table_is_indexed_by_kind() {
+        for (i, params) in VOLCANO_WEAPONS.iter().enumerate() {
+            assert_eq!(params.kind as usize, i);
+            assert_eq!(params.kind.params(), params);
+            assert_eq!(WeaponKind::from_item(params.kind.into()), Some(params.kind));
+        }
+    }
+
+    #[test]
+    fn fuzz_weapon_stats() {
+        let table = VOLCANO_WEAPONS.iter().map(|w| (w.level, w.speed));
+        let synthetic = (1..=20).flat_map(|level| (-8..=8).map(move |speed| (level, speed)));
+        for (level, speed) in table.chain(synthetic) {
+            for seed in 0..200 {
+                for force in [false, true] {
+                    let (enchants, _) =
+                        enchant_item_traced(&mut DotnetRng::new(seed), level, speed, force);
+                    let enchants = enchants.as_slice();
+                    assert!(enchants.len() <= 2);
+                    assert!(!force || !enchants.is_empty());
+                    for &(enchant, lvl) in enchants {
+                        assert!(enchant.valid_level(lvl), "{:?} {}", enchant, lvl);
+                        match enchant {
+                            Enchant::Speed => assert!(lvl <= i32::max(1, 4 - speed)),
+                            Enchant::Defense => assert!(level <= 10),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        // the clamp bounds would be inverted here, and 4 - speed overflows
+        for speed in [4, 100, i32::MAX, i32::MIN] {
+            for seed in 0..200 {
+                for &(enchant, lvl) in enchant(seed, 13, speed).as_slice() {
+                    if enchant == Enchant::Speed {
+                        assert_eq!(lvl, 1);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn boundary_weapons() {
+        let sword = WeaponKind::DwarfSword.params();
+        assert_eq!((sword.level, sword.speed), (13, 4));
+        // speed 4 and up only ever gets speed 1, the slowest weapons more with the same roll
+        assert_eq!(
+            enchant(0, sword.level, sword.speed).to_compact_string(),
+            "s1"
+        );
+        assert_eq!(enchant(0, 13, 8).to_compact_string(), "s1");
+        assert_eq!(enchant(0, 13, -8).to_compact_string(), "s2");
+        // the defense roll passes, but only counts up to level 10. above that the weight and
+        // slime gatherer rolls come next, and the main enchantment is rolled after them
+        assert_eq!(enchant(14, 10, 0).to_compact_string(), "d2.s4");
+        assert_eq!(enchant(14, 11, 0).to_compact_string(), "s4");
+        assert_eq!(enchant(16, 10, 0).to_compact_string(), "d2.cp75");
+        assert_eq!(enchant(16, 11, 0).to_compact_string(), "cp25");
+    }
+}
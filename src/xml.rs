@@ -0,0 +1,253 @@
+//! Just enough xml reading for save files: elements, attributes and text. No namespaces (prefixes
+//! stay in the names, like `xsi:type`), no DTDs, comments and processing instructions are skipped.
+
+/// An element with everything in it. `text` is all of its text joined, without the children's.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+impl Element {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// the first child called `name`
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// the text of the first child called `name`, trimmed
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|c| c.text.trim())
+    }
+}
+
+/// how deep elements can nest, so that parsing can't run out of stack. saves don't get near it.
+const MAX_DEPTH: usize = 256;
+
+struct Parser<'a> {
+    rest: &'a str,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn excerpt(&self) -> String {
+        self.rest.chars().take(20).collect()
+    }
+
+    /// until after `end`
+    fn skip_past(&mut self, end: &str) -> Result<(), String> {
+        let i = self
+            .rest
+            .find(end)
+            .ok_or_else(|| format!("missing {}", end))?;
+        self.rest = &self.rest[i + end.len()..];
+        Ok(())
+    }
+
+    /// comments, `<?...?>` and `<!DOCTYPE ...>` before or between elements
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            self.rest = self.rest.trim_start();
+            if self.rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest.starts_with("<!") && !self.rest.starts_with("<![CDATA[") {
+                self.skip_past(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '='))
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return Err(format!("expected a name at {:?}", self.excerpt()));
+        }
+        let name = self.rest[..end].to_string();
+        self.rest = &self.rest[end..];
+        Ok(name)
+    }
+
+    /// at the `<`
+    fn element(&mut self) -> Result<Element, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(format!("nested more than {} deep", MAX_DEPTH));
+        }
+        self.rest = &self.rest[1..];
+        let mut el = Element {
+            name: self.name()?,
+            ..Default::default()
+        };
+        loop {
+            self.rest = self.rest.trim_start();
+            if let Some(rest) = self.rest.strip_prefix("/>") {
+                self.rest = rest;
+                return Ok(el);
+            }
+            if let Some(rest) = self.rest.strip_prefix('>') {
+                self.rest = rest;
+                break;
+            }
+            let key = self.name()?;
+            self.rest = self.rest.trim_start();
+            let Some(rest) = self.rest.strip_prefix('=') else {
+                return Err(format!("expected = after {}", key));
+            };
+            let rest = rest.trim_start();
+            let quote = rest
+                .chars()
+                .next()
+                .filter(|c| matches!(c, '"' | '\''))
+                .ok_or_else(|| format!("unquoted value of {}", key))?;
+            let end = rest[1..]
+                .find(quote)
+                .ok_or_else(|| format!("unterminated value of {}", key))?;
+            el.attrs.push((key, unescape(&rest[1..end + 1])?));
+            self.rest = &rest[end + 2..];
+        }
+        self.depth += 1;
+        loop {
+            let end = self
+                .rest
+                .find('<')
+                .ok_or_else(|| format!("unclosed {}", el.name))?;
+            el.text += &unescape(&self.rest[..end])?;
+            self.rest = &self.rest[end..];
+            if let Some(rest) = self.rest.strip_prefix("</") {
+                self.rest = rest;
+                let name = self.name()?;
+                if name != el.name {
+                    return Err(format!("{} closed by {}", el.name, name));
+                }
+                self.rest = self.rest.trim_start();
+                let Some(rest) = self.rest.strip_prefix('>') else {
+                    return Err(format!("expected > after </{}", name));
+                };
+                self.rest = rest;
+                self.depth -= 1;
+                return Ok(el);
+            }
+            if let Some(rest) = self.rest.strip_prefix("<![CDATA[") {
+                let end = rest.find("]]>").ok_or("unterminated CDATA")?;
+                el.text += &rest[..end];
+                self.rest = &rest[end + 3..];
+                continue;
+            }
+            if self.rest.starts_with("<!--") || self.rest.starts_with("<?") {
+                self.skip_misc()?;
+                continue;
+            }
+            el.children.push(self.element()?);
+        }
+    }
+}
+
+/// `&amp;` and the others, and character references
+fn unescape(s: &str) -> Result<String, String> {
+    if !s.contains('&') {
+        return Ok(s.to_string());
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out += &rest[..i];
+        let end = rest[i..]
+            .find(';')
+            .ok_or_else(|| "unterminated entity".to_string())?;
+        let entity = &rest[i + 1..i + end];
+        out.push(match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|d| d.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+                    .ok_or_else(|| format!("unknown entity &{};", entity))?
+            }
+        });
+        rest = &rest[i + end + 1..];
+    }
+    out += rest;
+    Ok(out)
+}
+
+/// the root element
+pub fn parse(s: &str) -> Result<Element, String> {
+    let mut parser = Parser {
+        // the byte order mark the game writes
+        rest: s.trim_start_matches('\u{feff}'),
+        depth: 0,
+    };
+    parser.skip_misc()?;
+    if !parser.rest.starts_with('<') {
+        return Err(format!("expected an element at {:?}", parser.excerpt()));
+    }
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if !parser.rest.is_empty() {
+        return Err(format!("trailing {:?}", parser.excerpt()));
+    }
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elements_and_attributes() {
+        let root = parse(
+            "\u{feff}<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!-- a save -->\n\
+             <a x=\"1\" y='&lt;2&gt;'><b>one &amp; <![CDATA[<two>]]></b><c/> <b>&#51;&#x34;</b></a>",
+        )
+        .unwrap();
+        assert_eq!(root.name, "a");
+        assert_eq!(root.attr("x"), Some("1"));
+        assert_eq!(root.attr("y"), Some("<2>"));
+        assert_eq!(root.child_text("b"), Some("one & <two>"));
+        assert_eq!(root.children_named("b").count(), 2);
+        assert_eq!(root.children[2].text, "34");
+        assert!(root.child("c").unwrap().children.is_empty());
+    }
+
+    #[test]
+    fn rejects_broken_xml() {
+        for s in [
+            "",
+            "text",
+            "<a>",
+            "<a></b>",
+            "<a x=1/>",
+            "<a>&nope;</a>",
+            "<a/><b/>",
+            "<a x=\"1/>",
+        ] {
+            assert!(parse(s).is_err(), "{}", s);
+        }
+        let nested = |n: usize| "<a>".repeat(n) + &"</a>".repeat(n);
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+    }
+}
@@ -0,0 +1,16 @@
+// the same code path as `cargo run --example forecast`, through the public api only
+
+#[allow(dead_code)]
+#[path = "../examples/forecast.rs"]
+mod forecast_example;
+
+#[test]
+fn forecast_example_output() {
+    let mut out = String::new();
+    forecast_example::write_forecast(&mut out).unwrap();
+    let expected = include_str!("../fixtures/forecast_example.txt");
+    for (i, (got, want)) in out.lines().zip(expected.lines()).enumerate() {
+        assert_eq!(got, want, "line {}", i + 1);
+    }
+    assert_eq!(out, expected);
+}